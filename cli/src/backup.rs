@@ -0,0 +1,238 @@
+//! Backup and restore of settings via `pbpctrl settings export|import`.
+//!
+//! Settings are stored as a flat table mapping setting names to values, in
+//! the same representation as presets and daemon rules, either as TOML or,
+//! for files ending in `.json`, as JSON:
+//!
+//! ```toml
+//! current-ancr-state = "active"
+//! volume-asymmetry = 10
+//! current-user-eq = [0.0, 1.5, 0.0, -1.0, 0.0]
+//! ```
+//!
+//! Values are validated when reading the file, before connecting to the
+//! device, so that invalid files do not result in partially restored
+//! settings.
+
+use std::path::Path;
+
+use anyhow::{Context, Result};
+
+use dbus::arg::{ArgType, RefArg};
+
+use maestro::service::settings::{Danger, SettingId, SettingValue};
+
+use serde_json::Value;
+
+use crate::daemon::{rules, value};
+
+
+/// Format of a backup file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Toml,
+    Json,
+}
+
+impl Format {
+    /// Format of the given file, based on its extension.
+    pub fn of(path: &Path) -> Self {
+        match path.extension() {
+            Some(ext) if ext.eq_ignore_ascii_case("json") => Format::Json,
+            _ => Format::Toml,
+        }
+    }
+}
+
+
+/// Whether the setting is included in backups. Settings used internally by
+/// the device or companion app are left out.
+pub fn is_backed_up(id: SettingId) -> bool {
+    id.info().danger != Danger::Dangerous
+}
+
+pub fn to_string(values: &[SettingValue], format: Format) -> Result<String> {
+    match format {
+        Format::Toml => {
+            let mut doc = toml_edit::DocumentMut::new();
+
+            for value in values {
+                doc.insert(value.id().as_str(), toml_edit::value(to_toml(&*value::to_variant(value).0)?));
+            }
+
+            Ok(doc.to_string())
+        },
+        Format::Json => {
+            let mut map = serde_json::Map::new();
+
+            for value in values {
+                map.insert(value.id().as_str().to_owned(), to_json(&*value::to_variant(value).0)?);
+            }
+
+            Ok(serde_json::to_string_pretty(&Value::Object(map))? + "\n")
+        },
+    }
+}
+
+pub fn from_str(text: &str, format: Format) -> Result<Vec<SettingValue>> {
+    let doc: toml_edit::DocumentMut = match format {
+        Format::Toml => text.parse()?,
+        Format::Json => {
+            let value: Value = serde_json::from_str(text)?;
+            let object = value.as_object()
+                .ok_or_else(|| anyhow::anyhow!("expected an object mapping settings to values"))?;
+
+            let mut doc = toml_edit::DocumentMut::new();
+            for (key, value) in object {
+                let value = from_json(value)
+                    .with_context(|| format!("invalid value for setting '{key}'"))?;
+
+                doc.insert(key, toml_edit::value(value));
+            }
+
+            doc
+        },
+    };
+
+    let mut values = Vec::new();
+    for (key, item) in doc.iter() {
+        let id = rules::parse_setting_id(key)?;
+
+        if !is_backed_up(id) {
+            anyhow::bail!("refusing to restore internal setting '{id}'");
+        }
+
+        values.push(rules::parse_setting_value(id, item)?);
+    }
+
+    Ok(values)
+}
+
+pub fn read(path: &Path) -> Result<Vec<SettingValue>> {
+    let text = std::fs::read_to_string(path)
+        .with_context(|| format!("failed to read '{}'", path.display()))?;
+
+    from_str(&text, Format::of(path))
+        .with_context(|| format!("invalid settings file '{}'", path.display()))
+}
+
+pub fn write(path: &Path, values: &[SettingValue]) -> Result<()> {
+    let text = to_string(values, Format::of(path))?;
+
+    std::fs::write(path, text)
+        .with_context(|| format!("failed to write '{}'", path.display()))
+}
+
+fn to_toml(value: &dyn RefArg) -> Result<toml_edit::Value> {
+    let value = match value.arg_type() {
+        ArgType::Boolean => toml_edit::Value::from(value.as_u64() != Some(0)),
+        ArgType::Int32 => toml_edit::Value::from(value.as_i64().unwrap_or_default()),
+        ArgType::Double => toml_edit::Value::from(value.as_f64().unwrap_or_default()),
+        ArgType::String => toml_edit::Value::from(value.as_str().unwrap_or_default()),
+        ArgType::Struct => {
+            let items = value.as_iter()
+                .ok_or_else(|| anyhow::anyhow!("invalid struct value"))?
+                .map(to_toml)
+                .collect::<Result<toml_edit::Array>>()?;
+
+            toml_edit::Value::Array(items)
+        },
+        ty => anyhow::bail!("unsupported value type {ty:?}"),
+    };
+
+    Ok(value)
+}
+
+fn to_json(value: &dyn RefArg) -> Result<Value> {
+    let value = match value.arg_type() {
+        ArgType::Boolean => Value::from(value.as_u64() != Some(0)),
+        ArgType::Int32 => Value::from(value.as_i64().unwrap_or_default()),
+        ArgType::Double => Value::from(value.as_f64().unwrap_or_default()),
+        ArgType::String => Value::from(value.as_str().unwrap_or_default()),
+        ArgType::Struct => {
+            let items = value.as_iter()
+                .ok_or_else(|| anyhow::anyhow!("invalid struct value"))?
+                .map(to_json)
+                .collect::<Result<Vec<_>>>()?;
+
+            Value::Array(items)
+        },
+        ty => anyhow::bail!("unsupported value type {ty:?}"),
+    };
+
+    Ok(value)
+}
+
+fn from_json(value: &Value) -> Result<toml_edit::Value> {
+    let value = match value {
+        Value::Bool(x) => toml_edit::Value::from(*x),
+        Value::Number(x) => match x.as_i64() {
+            Some(x) => toml_edit::Value::from(x),
+            None => toml_edit::Value::from(x.as_f64().unwrap_or_default()),
+        },
+        Value::String(x) => toml_edit::Value::from(x.as_str()),
+        Value::Array(x) => {
+            let items = x.iter()
+                .map(from_json)
+                .collect::<Result<toml_edit::Array>>()?;
+
+            toml_edit::Value::Array(items)
+        },
+        _ => anyhow::bail!("unsupported value type"),
+    };
+
+    Ok(value)
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use maestro::service::settings::{
+        AncState, EqBands, GestureControl, RegularActionTarget, VolumeAsymmetry,
+    };
+
+    fn values() -> Vec<SettingValue> {
+        vec![
+            SettingValue::CurrentAncrState(AncState::Aware),
+            SettingValue::VolumeAsymmetry(VolumeAsymmetry::from_normalized(10)),
+            SettingValue::CurrentUserEq(EqBands::new(0.0, 1.5, 0.0, -1.0, 0.0)),
+            SettingValue::GestureControl(GestureControl {
+                left: RegularActionTarget::AncControl,
+                right: RegularActionTarget::AssistantQuery,
+            }),
+            SettingValue::SumToMono(true),
+        ]
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        let sorted = |mut values: Vec<SettingValue>| {
+            values.sort_by_key(|v| v.id().as_str());
+            values
+        };
+
+        // JSON objects are not ordered
+        for format in [Format::Toml, Format::Json] {
+            let text = to_string(&values(), format).unwrap();
+            assert_eq!(sorted(from_str(&text, format).unwrap()), sorted(values()), "{text}");
+        }
+
+        let text = to_string(&values()[..2], Format::Toml).unwrap();
+        assert_eq!(text, "current-ancr-state = \"aware\"\nvolume-asymmetry = 10\n");
+    }
+
+    #[test]
+    fn test_invalid() {
+        assert!(from_str("volume-asymmetry = 101", Format::Toml).is_err());
+        assert!(from_str("current-user-eq = [0.0, 1.5, 0.0, -1.0, 20.0]", Format::Toml).is_err());
+        assert!(from_str("current-ancr-state = \"loud\"", Format::Toml).is_err());
+        assert!(from_str("oobe-mode = true", Format::Toml).is_err());
+        assert!(from_str("[1, 2]", Format::Json).is_err());
+        assert!(from_str("{\"sum-to-mono\": null}", Format::Json).is_err());
+
+        assert_eq!(Format::of(Path::new("buds.JSON")), Format::Json);
+        assert_eq!(Format::of(Path::new("buds.toml")), Format::Toml);
+    }
+}
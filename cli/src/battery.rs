@@ -0,0 +1,201 @@
+//! Battery drain reports.
+//!
+//! Over a monitoring session started via `pbpctrl battery-report`, battery
+//! levels are recorded via [`maestro::service::battery::Tracker`]. At the end
+//! of the session, the resulting report is printed and appended, including
+//! the recorded curves, to `$XDG_DATA_HOME/pbpctrl/battery.jsonl`.
+
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{Instant, SystemTime};
+
+use anyhow::Result;
+
+use serde_json::{json, Value};
+
+use maestro::protocol::types::BatteryInfo;
+use maestro::service::battery::{Component, Curve, Rate, Report, Tracker};
+
+use crate::daemon::dosimeter;
+
+
+/// File storing reports of past sessions.
+#[derive(Debug, Clone)]
+pub struct Store {
+    path: PathBuf,
+}
+
+impl Store {
+    /// Default location of the report file, i.e.,
+    /// `$XDG_DATA_HOME/pbpctrl/battery.jsonl`.
+    pub fn default_path() -> Option<PathBuf> {
+        let base = std::env::var_os("XDG_DATA_HOME")
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".local").join("share")))?;
+
+        Some(base.join("pbpctrl").join("battery.jsonl"))
+    }
+
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn append(&self, entry: &Value) -> Result<()> {
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+
+        let mut line = serde_json::to_vec(entry)?;
+        line.push(b'\n');
+
+        file.write_all(&line)?;
+        Ok(())
+    }
+}
+
+
+/// A monitoring session.
+pub struct Session {
+    start: Instant,
+    time: SystemTime,
+    tracker: Tracker,
+}
+
+impl Session {
+    pub fn new() -> Self {
+        Self {
+            start: Instant::now(),
+            time: SystemTime::now(),
+            tracker: Tracker::new(),
+        }
+    }
+
+    pub fn record(&mut self, info: &BatteryInfo) {
+        self.tracker.record(self.start.elapsed(), info);
+    }
+
+    /// Print the report of the session and store it.
+    pub fn finish(&self) -> Result<()> {
+        let report = self.tracker.report();
+
+        if report.components.is_empty() {
+            println!("no battery levels recorded");
+            return Ok(());
+        }
+
+        print(&report);
+
+        if let Some(path) = Store::default_path() {
+            let store = Store::new(path);
+            store.append(&to_json(self.time, &self.tracker, &report))?;
+
+            println!();
+            println!("report saved to '{}'", store.path().display());
+        }
+
+        Ok(())
+    }
+}
+
+
+fn print(report: &Report) {
+    let rate = |rate: &Rate| match rate.duration.as_secs() {
+        0 => "n/a".to_owned(),
+        secs => format!("{:.1} %/h over {}m", rate.per_hour(), secs / 60),
+    };
+
+    for summary in &report.components {
+        println!("{}:", summary.component.name());
+        println!("  level:     {}% -> {}%", summary.first.level, summary.last.level);
+        println!("  discharge: {}", rate(&summary.discharge));
+        println!("  charge:    {}", rate(&summary.charge));
+    }
+
+    println!();
+    if report.anomalies.is_empty() {
+        println!("no anomalies detected");
+    }
+
+    for anomaly in &report.anomalies {
+        println!("warning: {anomaly}");
+    }
+}
+
+fn to_json(time: SystemTime, tracker: &Tracker, report: &Report) -> Value {
+    let rate = |rate: &Rate| json!({ "change": rate.change, "duration": rate.duration.as_secs() });
+
+    let curve = |curve: &Curve| -> Vec<Value> {
+        curve.samples().iter()
+            .map(|s| json!([s.time.as_secs(), s.level, s.charging]))
+            .collect()
+    };
+
+    let components: serde_json::Map<_, _> = report.components.iter()
+        .map(|s| {
+            let value = json!({
+                "discharge": rate(&s.discharge),
+                "charge": rate(&s.charge),
+                "samples": curve(tracker.curve(s.component)),
+                "end": tracker.curve(s.component).end().as_secs(),
+            });
+
+            (key(s.component).to_owned(), value)
+        })
+        .collect();
+
+    let anomalies: Vec<_> = report.anomalies.iter()
+        .map(|a| Value::String(a.to_string()))
+        .collect();
+
+    json!({
+        "time": dosimeter::timestamp(time),
+        "components": components,
+        "anomalies": anomalies,
+    })
+}
+
+fn key(component: Component) -> &'static str {
+    match component {
+        Component::Case => "case",
+        Component::Left => "left",
+        Component::Right => "right",
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::time::Duration;
+
+    use maestro::protocol::types::DeviceBatteryInfo;
+
+    #[test]
+    fn test_to_json() {
+        let battery = |level| Some(DeviceBatteryInfo { level, state: 1 });
+
+        let mut tracker = Tracker::new();
+        tracker.record(Duration::ZERO, &BatteryInfo { case: None, left: battery(90), right: None });
+        tracker.record(Duration::from_secs(600), &BatteryInfo { case: None, left: battery(88), right: None });
+
+        let report = tracker.report();
+        let value = to_json(SystemTime::UNIX_EPOCH, &tracker, &report);
+
+        assert_eq!(value["time"], 0);
+        assert_eq!(value["components"]["left"]["discharge"], json!({ "change": 2, "duration": 600 }));
+        assert_eq!(value["components"]["left"]["samples"], json!([[0, 90, false], [600, 88, false]]));
+        assert!(value["components"].get("right").is_none());
+    }
+}
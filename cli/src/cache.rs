@@ -0,0 +1,116 @@
+//! Cache of the last successful connection.
+//!
+//! Stores the adapter, device address, and Maestro channel used for the last
+//! successful connection, which are tried first on the next invocation before
+//! falling back to full device discovery and channel resolution.
+//!
+//! The cache is stored in `$XDG_CACHE_HOME/pbpctrl/connection.json`.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+
+use serde_json::{json, Value};
+
+use crate::transport::Address;
+
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Connection {
+    pub adapter: String,
+    pub address: Address,
+    pub channel: u32,
+}
+
+impl Connection {
+    /// Default location of the cache file, i.e.,
+    /// `$XDG_CACHE_HOME/pbpctrl/connection.json`.
+    pub fn default_path() -> Option<PathBuf> {
+        let base = std::env::var_os("XDG_CACHE_HOME")
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))?;
+
+        Some(base.join("pbpctrl").join("connection.json"))
+    }
+
+    /// Load the cached connection, if any. Invalid cache files are ignored.
+    pub fn load() -> Option<Self> {
+        Self::load_from(&Self::default_path()?)
+    }
+
+    pub fn load_from(path: &Path) -> Option<Self> {
+        let data = std::fs::read(path).ok()?;
+        let value: Value = serde_json::from_slice(&data).ok()?;
+
+        let connection = Self::from_json(&value);
+        if connection.is_none() {
+            tracing::debug!(path=%path.display(), "ignoring invalid connection cache");
+        }
+
+        connection
+    }
+
+    /// Store the connection to the cache. Errors are logged, but otherwise
+    /// ignored.
+    pub fn store(&self) {
+        let Some(path) = Self::default_path() else {
+            return;
+        };
+
+        if let Err(err) = self.store_to(&path) {
+            tracing::debug!(error=?err, path=%path.display(), "failed to write connection cache");
+        }
+    }
+
+    pub fn store_to(&self, path: &Path) -> Result<()> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+
+        std::fs::write(path, serde_json::to_vec(&self.to_json())?)?;
+        Ok(())
+    }
+
+    fn to_json(&self) -> Value {
+        json!({
+            "adapter": self.adapter,
+            "address": self.address.to_string(),
+            "channel": self.channel,
+        })
+    }
+
+    fn from_json(value: &Value) -> Option<Self> {
+        Some(Self {
+            adapter: value.get("adapter")?.as_str()?.to_owned(),
+            address: value.get("address")?.as_str()?.parse().ok()?,
+            channel: value.get("channel")?.as_u64()?.try_into().ok()?,
+        })
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_store_load() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("pbpctrl").join("connection.json");
+
+        assert_eq!(Connection::load_from(&path), None);
+
+        let connection = Connection {
+            adapter: "hci0".to_owned(),
+            address: "01:23:45:67:89:AB".parse().unwrap(),
+            channel: 3,
+        };
+
+        connection.store_to(&path).unwrap();
+        assert_eq!(Connection::load_from(&path), Some(connection));
+
+        std::fs::write(&path, b"{\"adapter\": \"hci0\"}").unwrap();
+        assert_eq!(Connection::load_from(&path), None);
+    }
+}
@@ -0,0 +1,236 @@
+//! Chaining of multiple settings in a single `set` command, e.g.
+//! `pbpctrl set anc aware mono true balance 10`.
+//!
+//! Clap does not support chaining subcommands, so the arguments of `set` are
+//! split into one group per setting before parsing. The first group contains
+//! the full command line up to and including the first setting and is parsed
+//! as usual, each further group contains only a single setting and its
+//! values. Options not defined by the setting they follow, e.g. `--force`,
+//! `--read-only`, or `--device`, are moved to the first group, so that they
+//! are handled exactly as if only a single setting had been given.
+
+use std::ffi::OsString;
+
+use clap::{CommandFactory, FromArgMatches, Parser, Subcommand};
+
+use crate::cli::{Args, Command, SetSetting};
+
+
+/// Parse the command line, exiting with a usage error if it is invalid.
+pub fn parse(argv: Vec<OsString>) -> Args {
+    try_parse(argv).unwrap_or_else(|err| err.exit())
+}
+
+pub fn try_parse(argv: Vec<OsString>) -> Result<Args, clap::Error> {
+    let (first, rest) = split(&argv);
+    let mut args = Args::try_parse_from(first)?;
+
+    if let Command::Set { more, .. } = &mut args.command {
+        for group in rest {
+            more.push(parse_setting(group)?);
+        }
+    }
+
+    Ok(args)
+}
+
+/// Parse a single setting and its values, e.g. `["mono", "true"]`.
+fn parse_setting(group: Vec<OsString>) -> Result<SetSetting, clap::Error> {
+    let cmd = SetSetting::augment_subcommands(clap::Command::new("set"))
+        .bin_name("pbpctrl set")
+        .no_binary_name(true)
+        .subcommand_required(true);
+
+    let mut matches = cmd.try_get_matches_from(group)?;
+    SetSetting::from_arg_matches_mut(&mut matches)
+}
+
+/// Split the arguments of a `set` command at each setting name following
+/// all values of the previous setting. Returns the command line of the first
+/// group and the arguments of all further ones. Other commands are returned
+/// as-is.
+fn split(argv: &[OsString]) -> (Vec<OsString>, Vec<Vec<OsString>>) {
+    let mut cmd = Args::command();
+    cmd.build();
+
+    // find the subcommand
+    let mut args = argv.iter().enumerate().skip(1);
+    let position = loop {
+        let Some((i, arg)) = args.next() else { return (argv.to_vec(), Vec::new()) };
+        let Some(arg) = arg.to_str() else { return (argv.to_vec(), Vec::new()) };
+
+        if arg == "--" || !is_option(arg) {
+            break i;
+        }
+
+        if takes_value(find_arg(&cmd, arg), arg) {
+            args.next();
+        }
+    };
+
+    let Some(set) = cmd.find_subcommand("set").filter(|_| argv[position] == "set") else {
+        return (argv.to_vec(), Vec::new());
+    };
+
+    let mut first = argv[..=position].to_vec();
+    let mut groups: Vec<Vec<OsString>> = Vec::new();
+    let mut setting: Option<&clap::Command> = None;
+    let mut values = 0;
+    let mut escaped = false;
+
+    let mut args = argv[position + 1..].iter();
+    while let Some(arg) = args.next() {
+        let Some(s) = arg.to_str() else {
+            groups.last_mut().unwrap_or(&mut first).push(arg.clone());
+            continue;
+        };
+
+        // values of the current setting may start with a dash, e.g.
+        // 'balance -- -10 mono true'
+        if s == "--" && !escaped {
+            groups.last_mut().unwrap_or(&mut first).push(arg.clone());
+            escaped = true;
+            continue;
+        }
+
+        if is_option(s) && !escaped {
+            let own = setting.and_then(|cmd| find_arg(cmd, s)).filter(|a| !a.is_global_set());
+            let arg_def = own.or_else(|| find_arg(set, s)).or_else(|| find_arg(&cmd, s));
+
+            let target = match groups.last_mut() {
+                Some(group) if own.is_some() => group,
+                _ => &mut first,
+            };
+
+            target.push(arg.clone());
+
+            if takes_value(arg_def, s) && let Some(value) = args.next() {
+                target.push(value.clone());
+            }
+
+            continue;
+        }
+
+        match setting {
+            Some(current) if values < current.get_positionals().count() => values += 1,
+            Some(_) if let Some(next) = set.find_subcommand(s) => {
+                groups.push(Vec::new());
+                setting = Some(next);
+                values = 0;
+                escaped = false;
+            },
+            Some(_) => {},
+            None => setting = set.find_subcommand(s),
+        }
+
+        groups.last_mut().unwrap_or(&mut first).push(arg.clone());
+    }
+
+    (first, groups)
+}
+
+fn is_option(arg: &str) -> bool {
+    arg.starts_with('-') && arg != "-"
+}
+
+fn find_arg<'a>(cmd: &'a clap::Command, option: &str) -> Option<&'a clap::Arg> {
+    match option.strip_prefix("--") {
+        Some(long) => {
+            let long = long.split_once('=').map_or(long, |(long, _)| long);
+            cmd.get_arguments().find(|a| a.get_long() == Some(long))
+        },
+        None => {
+            let short = option.chars().nth(1)?;
+            cmd.get_arguments().find(|a| a.get_short() == Some(short))
+        },
+    }
+}
+
+/// Whether the given option takes its value as separate argument.
+fn takes_value(arg: Option<&clap::Arg>, option: &str) -> bool {
+    let inline = option.contains('=') || (!option.starts_with("--") && option.len() > 2);
+
+    !inline && arg
+        .and_then(|a| a.get_num_args())
+        .is_some_and(|n| n.min_values() > 0)
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::cli::{DeviceArg, OutputFormat};
+
+    fn parse(line: &str) -> Args {
+        try_parse(line.split(' ').map(Into::into).collect()).unwrap()
+    }
+
+    fn settings(args: &Args) -> Vec<String> {
+        let Command::Set { setting, more, .. } = &args.command else {
+            panic!("expected set command");
+        };
+
+        setting.iter().chain(more).map(|s| format!("{s:?}")).collect()
+    }
+
+    #[test]
+    fn test_split() {
+        let args = parse("pbpctrl set anc aware mono true balance 10");
+        assert_eq!(settings(&args), [
+            "Anc { value: Aware, duration: None }",
+            "Mono { value: true }",
+            "Balance { value: 10 }",
+        ]);
+
+        let args = parse("pbpctrl set eq -- -1 2 3 4 5 balance -- -10 audio --left 50 mono false");
+        assert_eq!(settings(&args), [
+            "Eq { low_bass: -1.0, bass: 2.0, mid: 3.0, treble: 4.0, upper_treble: 5.0 }",
+            "Balance { value: -10 }",
+            "Audio { left: 50, right: 100 }",
+            "Mono { value: false }",
+        ]);
+
+        let args = parse("pbpctrl set --from-file settings.toml");
+        assert!(matches!(args.command, Command::Set { from_file: Some(_), setting: None, .. }));
+
+        let args = parse("pbpctrl show battery");
+        assert!(matches!(args.command, Command::Show { .. }));
+    }
+
+    #[test]
+    fn test_options() {
+        // before, between, and after the settings
+        for line in [
+            "pbpctrl --read-only -d 24:29:34:AC:9F:D1 set --force mono true anc off",
+            "pbpctrl set --read-only --device=24:29:34:AC:9F:D1 --force mono true anc off",
+            "pbpctrl set mono true --read-only -d 24:29:34:AC:9F:D1 anc off --force",
+            "pbpctrl set mono true anc off --read-only --device 24:29:34:AC:9F:D1 --force",
+        ] {
+            let args = parse(line);
+
+            assert!(args.read_only, "{line}");
+            assert_eq!(args.device, Some(DeviceArg::Address("24:29:34:AC:9F:D1".parse().unwrap())), "{line}");
+            assert!(matches!(args.command, Command::Set { force: true, .. }), "{line}");
+            assert_eq!(settings(&args), ["Mono { value: true }", "Anc { value: Off, duration: None }"], "{line}");
+        }
+
+        let args = parse("pbpctrl set mono true anc off -vv --output json");
+        assert_eq!(args.verbose, 2);
+        assert_eq!(args.output, Some(OutputFormat::Json));
+
+        let args = parse("pbpctrl set mono true anc aware --for 10m");
+        assert_eq!(settings(&args)[1], "Anc { value: Aware, duration: Some(600s) }");
+    }
+
+    #[test]
+    fn test_invalid() {
+        let parse = |line: &str| try_parse(line.split(' ').map(Into::into).collect());
+
+        assert!(parse("pbpctrl set mono true bogus").is_err());
+        assert!(parse("pbpctrl set mono true anc").is_err());
+        assert!(parse("pbpctrl set mono true anc loud").is_err());
+        assert!(parse("pbpctrl set mono true balance -10").is_err());
+        assert!(parse("pbpctrl set mono true --bogus").is_err());
+    }
+}
@@ -8,9 +8,92 @@ use maestro::service::settings;
 #[derive(Debug, Parser)]
 #[command(author, version, about, long_about = None)]
 pub struct Args {
-    /// Device to use (search for compatible device if unspecified)
-    #[arg(short, long, global=true)]
-    pub device: Option<Address>,
+    /// Device to use, by address or by name (search for compatible device if
+    /// unspecified)
+    ///
+    /// Names refer to devices defined in ~/.config/pbpctrl/config.toml,
+    /// which can also set defaults for this and other global options.
+    #[arg(short, long, global=true, value_parser=parse_device)]
+    pub device: Option<DeviceArg>,
+
+    /// Bluetooth adapter to use, e.g. hci1 (default adapter if unspecified)
+    #[arg(long, global=true)]
+    pub adapter: Option<String>,
+
+    /// Increase log verbosity (-v for debug, -vv for trace output, -vvv to
+    /// additionally log individual frames)
+    #[arg(short, long, global=true, action=clap::ArgAction::Count)]
+    pub verbose: u8,
+
+    /// Write log output to the given file instead of the terminal
+    ///
+    /// Appends to the file if it exists.
+    #[arg(long, global=true, value_name="FILE")]
+    pub log_file: Option<std::path::PathBuf>,
+
+    /// Connect to the device directly, even if a daemon is running
+    #[arg(long, global=true)]
+    pub no_daemon: bool,
+
+    /// Explain RPC errors in terms of the failed operation
+    ///
+    /// Only applies to direct connections, i.e., not when forwarding
+    /// commands to the daemon.
+    #[arg(long, global=true)]
+    pub explain: bool,
+
+    /// How to establish the RFCOMM connection to the device
+    #[arg(long, global=true, value_enum, default_value_t=ConnectMode::Profile)]
+    pub connect_mode: ConnectMode,
+
+    /// Reject commands writing settings
+    ///
+    /// Also applies to daemons started with this flag, which then reject
+    /// writes requested via D-Bus, the control socket, or rules. Can be
+    /// enabled for the daemon via `read-only = true` in its configuration
+    /// file.
+    #[arg(long, global=true)]
+    pub read_only: bool,
+
+    /// Keep the connection open in the background for the given number of
+    /// seconds after the command has completed
+    ///
+    /// Starts a daemon exiting once idle for the given duration. Commands
+    /// issued in the meantime are forwarded to it, so that scripts running
+    /// several commands in a row only connect once. Has no effect if a
+    /// daemon is already running.
+    #[arg(long, global=true, value_name="SECONDS")]
+    pub keep_alive: Option<u64>,
+
+    /// Report how long each phase of the command took
+    ///
+    /// Covers device discovery, connecting, resolving the channel, and each
+    /// RPC, printed to stderr as table or JSON once the command has
+    /// completed.
+    #[arg(long, global=true, value_name="FORMAT", num_args=0..=1, default_missing_value="table")]
+    pub timings: Option<TimingsFormat>,
+
+    /// Give up on an unresponsive device after this duration (e.g. 10s, 1m)
+    ///
+    /// Applies separately to connecting to the Maestro profile, resolving
+    /// the channel, and each RPC, defaulting to 10 seconds. Only applies to
+    /// direct connections, i.e., not when forwarding commands to the daemon.
+    #[arg(long, global=true, value_parser=parse_timeout)]
+    pub timeout: Option<std::time::Duration>,
+
+    /// Output format of the show, get, monitor, devices, doctor, and dosimeter summary commands
+    ///
+    /// Defaults to human-readable text. The `status` and `export` commands
+    /// select their format via their own `--format` option.
+    #[arg(long, global=true, value_enum, value_name="FORMAT")]
+    pub output: Option<OutputFormat>,
+
+    /// Record raw device communication to the given file
+    ///
+    /// Only applies to direct connections, i.e., not when forwarding
+    /// commands to the daemon.
+    #[arg(long, global=true, value_name="FILE")]
+    pub capture: Option<std::path::PathBuf>,
 
     #[command(subcommand)]
     pub command: Command
@@ -20,40 +103,330 @@ pub struct Args {
 pub enum Command {
     /// Show device information
     Show {
+        /// Only show information for the given component
+        #[arg(long, global=true, value_enum)]
+        component: Option<Component>,
+
         #[command(subcommand)]
         command: ShowCommand
     },
 
     /// Read settings value
     Get {
+        /// Print a description of the setting instead of reading its value
+        #[arg(long)]
+        describe: bool,
+
         #[command(subcommand)]
         setting: GetSetting
     },
 
     /// Write settings value
+    ///
+    /// Multiple settings can be written at once by listing them one after
+    /// the other, e.g. `set anc aware mono true balance 10`, or by reading
+    /// them from a file in the format of 'settings export' via --from-file.
+    /// All values are validated before any setting is written.
+    #[command(arg_required_else_help=true)]
     Set {
+        /// Write even if the buds run different firmware versions and the
+        /// setting is known to desync them in this case
+        #[arg(long, global=true)]
+        force: bool,
+
+        /// Read settings to write from the given file
+        #[arg(long, value_name="FILE")]
+        from_file: Option<std::path::PathBuf>,
+
+        #[command(subcommand)]
+        setting: Option<SetSetting>,
+
+        /// Further settings listed after the first one.
+        #[arg(skip)]
+        more: Vec<SetSetting>,
+    },
+
+    /// Pair a new device and set it up for use
+    ///
+    /// Put the buds into pairing mode (open the case and hold the button on
+    /// its back) before running this. Gives up if no device has been found
+    /// within `--timeout`, which defaults to 60 seconds for this command.
+    Pair,
+
+    /// Diagnose problems connecting to the device
+    ///
+    /// Checks each stage of connecting, from the Bluetooth adapter to the
+    /// resolved Maestro channel, stopping at the first one that fails with a
+    /// hint on what to check.
+    Doctor,
+
+    /// List paired compatible devices
+    ///
+    /// Prints address, name, model, and connection state of each device,
+    /// e.g. to select one via `--device` if multiple buds are paired.
+    Devices,
+
+    /// Ring the buds to locate them
+    ///
+    /// Rings the right bud first, then both, repeating with increasing
+    /// duration until a bud is touched or Enter is pressed. Do not use this
+    /// while wearing them. If a daemon is running and neither --side nor
+    /// --duration is given, ringing continues in the background.
+    Find {
+        /// Stop ringing
+        #[arg(long, conflicts_with_all=["side", "duration"])]
+        stop: bool,
+
+        /// Ring only the given bud(s)
+        #[arg(long, value_enum)]
+        side: Option<RingSide>,
+
+        /// Ring once for this duration instead of escalating (e.g. 30s)
+        #[arg(long, value_parser=parse_timeout)]
+        duration: Option<std::time::Duration>,
+    },
+
+    /// Apply a preset defined in the configuration file
+    ///
+    /// Writes the settings of the preset in the order they are listed in
+    /// the [presets.<name>] section of ~/.config/pbpctrl/config.toml.
+    Preset {
+        /// Name of the preset
+        name: String,
+
+        /// Write settings even if the buds run different firmware versions
+        #[arg(long)]
+        force: bool,
+    },
+
+    /// Control which connected device plays audio
+    Multipoint {
+        #[command(subcommand)]
+        command: MultipointCommand
+    },
+
+    /// Print a one-line summary of battery, ANC, and multipoint state
+    ///
+    /// Intended for status bars and shell prompts, e.g.
+    /// `L:84%- R:82%- C:61%+ ANC:active MP:on`. Battery levels are followed
+    /// by '+' if charging and '-' if not. The `waybar` format prints a JSON
+    /// object for Waybar custom modules.
+    Status {
+        /// Output format
+        #[arg(long, value_enum, default_value="plain")]
+        format: StatusFormat,
+    },
+
+    /// Print device state for consumption by scripts
+    ///
+    /// Includes battery, placement, firmware, serial numbers, and all
+    /// settings. In the `env` format, one `KEY=value` assignment is printed
+    /// per line, e.g. `PBP_BATTERY_LEFT=84` or `PBP_ANC=active`, suitable
+    /// for `eval` in shell scripts and for dotenv loaders. Unknown values
+    /// are left empty.
+    Export {
+        /// Output format
+        #[arg(long, value_enum, default_value="env")]
+        format: OutputFormat,
+    },
+
+    /// Monitor battery drain and report differences between the buds
+    ///
+    /// Records battery levels until interrupted (Ctrl-C) or the given
+    /// duration has elapsed, then reports charge and discharge rates and
+    /// warns if one bud drains significantly faster than the other. Reports
+    /// are saved to ~/.local/share/pbpctrl/battery.jsonl.
+    BatteryReport {
+        /// Stop monitoring after this duration (e.g. 30m, 2h)
+        #[arg(long, value_parser=parse_timeout)]
+        duration: Option<std::time::Duration>,
+    },
+
+    /// Print battery, placement, and ANC changes as they happen
+    ///
+    /// Prints the current state first, then one line per change until
+    /// interrupted (Ctrl-C). With `--output json`, the full state is printed
+    /// as one JSON object per change instead.
+    Monitor,
+
+    /// Show settings writes recorded in the audit log
+    ///
+    /// Recording is enabled via `audit-log = true` in the daemon
+    /// configuration file.
+    History {
+        /// Only show entries newer than this (e.g. 30m, 12h, 7d)
+        #[arg(long, value_parser=parse_age, default_value="7d")]
+        since: std::time::Duration,
+    },
+
+    /// Low-level RPC utilities
+    Rpc {
         #[command(subcommand)]
-        setting: SetSetting
+        command: RpcCommand
+    },
+
+    /// Back up and restore settings
+    Settings {
+        #[command(subcommand)]
+        command: SettingsCommand
+    },
+
+    /// Access dosimeter data
+    Dosimeter {
+        #[command(subcommand)]
+        command: DosimeterCommand
+    },
+
+    /// Run as daemon, keeping the connection open and providing a D-Bus
+    /// interface
+    ///
+    /// While the daemon is running, other commands will be forwarded to it
+    /// via D-Bus instead of connecting to the device on each invocation.
+    Daemon {
+        /// Configuration file (default: ~/.config/pbpctrl/daemon.toml)
+        #[arg(long)]
+        config: Option<std::path::PathBuf>,
+
+        /// Path of the JSON-RPC control socket (default:
+        /// $XDG_RUNTIME_DIR/pbpctrl.sock)
+        #[arg(long)]
+        socket: Option<std::path::PathBuf>,
+
+        /// Exit after being idle for the given number of seconds, 0 to
+        /// disable (default: 300 if socket-activated, disabled otherwise)
+        #[arg(long, value_name="SECONDS")]
+        idle_timeout: Option<u64>,
+
+        #[command(subcommand)]
+        command: Option<DaemonCommand>,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum RpcCommand {
+    /// Print the wire hashes of a service or method name
+    ///
+    /// For fully-qualified method names (e.g.
+    /// 'maestro_pw.Maestro/GetSoftwareInfo'), the hashes of both service and
+    /// method are printed.
+    Hash {
+        /// Service, method, or fully-qualified method name
+        name: String,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum DaemonCommand {
+    /// Install systemd user units for starting the daemon on demand
+    Install {
+        /// Overwrite existing unit files
+        #[arg(long)]
+        force: bool,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum MultipointCommand {
+    /// Switch audio to this host, e.g. away from a phone
+    ///
+    /// Uses Smart Audio Source Switching (SASS) and waits for the buds to
+    /// confirm the switch. Fails if the buds reject it, switch to another
+    /// device instead, or do not confirm it.
+    Claim {
+        /// Resume playback after switching
+        #[arg(long)]
+        resume: bool,
+
+        /// Disconnect the device audio is switched away from
+        #[arg(long)]
+        disconnect_other: bool,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum SettingsCommand {
+    /// Save all supported user settings to a file
+    ///
+    /// Writes a TOML document mapping setting names to values, or a JSON
+    /// object if the file name ends in .json. Settings used internally by
+    /// the device are not included.
+    Export {
+        /// File to write the settings to
+        file: std::path::PathBuf,
+    },
+
+    /// Restore settings from a file written by 'settings export'
+    ///
+    /// All values are validated before any setting is written.
+    Import {
+        /// File to read the settings from
+        file: std::path::PathBuf,
+
+        /// Write settings even if the buds run different firmware versions
+        #[arg(long)]
+        force: bool,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum DosimeterCommand {
+    /// Show the daily sound dose summaries stored on the device
+    Summary,
+
+    /// Show sound exposure history recorded by the daemon
+    History {
+        /// Only show data newer than this (e.g. 30m, 12h, 7d)
+        #[arg(long, value_parser=parse_age, default_value="7d")]
+        since: std::time::Duration,
     },
 }
 
 #[derive(Debug, Subcommand)]
 pub enum ShowCommand {
+    /// Show all device information and settings at once.
+    All {
+        /// Print information as a single JSON object, same as `--output json`
+        #[arg(long)]
+        json: bool,
+    },
+
     /// Show software information.
-    Software,
+    Software {
+        /// Cross-check the firmware version with the one reported via the
+        /// Fast Pair (GFPS) channel
+        #[arg(long)]
+        verify: bool,
+    },
 
     /// Show hardware information.
     Hardware,
 
     /// Show runtime information.
-    Runtime,
+    Runtime {
+        /// Keep running and print updates sent by the device
+        #[arg(long)]
+        follow: bool,
+
+        /// Print information as JSON, one object per update, same as
+        /// `--output json`
+        #[arg(long)]
+        json: bool,
+    },
 
     /// Show battery status.
-    Battery,
+    Battery {
+        /// Show a single estimate of the remaining listening time, including
+        /// the charge of the case
+        #[arg(long)]
+        total: bool,
+    },
 }
 
 #[derive(Debug, Subcommand)]
 pub enum GetSetting {
+    /// Get all settings, marking those not supported by the firmware
+    All,
+
     /// Get automatic over-the-air update status
     AutoOta,
 
@@ -202,6 +575,10 @@ pub enum SetSetting {
         /// New ANC state or action to change state
         #[arg(value_enum)]
         value: AncState,
+
+        /// Revert to the previous state after this duration (e.g. 30s, 10m)
+        #[arg(long="for", value_name="DURATION", value_parser=parse_timeout)]
+        duration: Option<std::time::Duration>,
     },
 
     /// Enable/disable volume-dependent EQ
@@ -241,6 +618,21 @@ pub enum SetSetting {
         value: i32,
     },
 
+    /// Set per-bud volume levels
+    ///
+    /// Note: The device only supports attenuating one side, so the louder bud
+    /// is always played at full volume and only the difference between both
+    /// levels is applied to the other one.
+    Audio {
+        /// Volume level of the left bud in percent
+        #[arg(long, value_parser=parse_level, default_value_t=100)]
+        left: u32,
+
+        /// Volume level of the right bud in percent
+        #[arg(long, value_parser=parse_level, default_value_t=100)]
+        right: u32,
+    },
+
     /// Set mono output
     Mono {
         /// Whether to force mono output
@@ -261,6 +653,88 @@ pub enum SetSetting {
         #[arg(action=clap::ArgAction::Set)]
         value: bool,
     },
+
+    /// Swap left and right, e.g. when wearing the buds on swapped sides
+    ///
+    /// Swaps the hold-gesture actions and inverts the volume balance. Whether
+    /// the sides are swapped is recorded per device, so that setting the
+    /// current state again does nothing.
+    SwapSides {
+        /// Whether the sides should be swapped
+        #[arg(action=clap::ArgAction::Set)]
+        value: bool,
+    },
+}
+
+#[derive(Debug, ValueEnum, Clone, Copy, PartialEq, Eq)]
+pub enum ConnectMode {
+    /// Register a BlueZ profile and let BlueZ connect to the device
+    Profile,
+
+    /// Open an RFCOMM socket directly to the channel resolved via SDP
+    Raw,
+}
+
+#[derive(Debug, ValueEnum, Clone, Copy, PartialEq, Eq)]
+pub enum StatusFormat {
+    Plain,
+    Emoji,
+    Json,
+    Waybar,
+}
+
+/// Output format of commands returning structured results.
+#[derive(Debug, ValueEnum, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// Human-readable text
+    Human,
+    /// A single JSON object
+    Json,
+    /// Shell variable assignments, one per line
+    Env,
+    /// JSON object for Waybar custom modules
+    Waybar,
+}
+
+/// Output format of `--timings`.
+#[derive(Debug, ValueEnum, Clone, Copy, PartialEq, Eq)]
+pub enum TimingsFormat {
+    /// Aligned table
+    Table,
+    /// A single JSON object
+    Json,
+}
+
+/// Device selected via `--device`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DeviceArg {
+    Address(Address),
+
+    /// Name of a device defined in the configuration file.
+    Name(String),
+}
+
+impl DeviceArg {
+    pub fn address(&self) -> Option<Address> {
+        match self {
+            DeviceArg::Address(address) => Some(*address),
+            DeviceArg::Name(_) => None,
+        }
+    }
+}
+
+#[derive(Debug, ValueEnum, Clone, Copy, PartialEq, Eq)]
+pub enum RingSide {
+    Left,
+    Right,
+    Both,
+}
+
+#[derive(Debug, ValueEnum, Clone, Copy, PartialEq, Eq)]
+pub enum Component {
+    Case,
+    Left,
+    Right,
 }
 
 #[derive(Debug, ValueEnum, Clone, Copy)]
@@ -310,3 +784,55 @@ fn parse_balance(s: &str) -> std::result::Result<i32, String> {
         Ok(val)
     }
 }
+
+fn parse_level(s: &str) -> std::result::Result<u32, String> {
+    let val = s.parse().map_err(|e| format!("{e}"))?;
+
+    if val > 100 {
+        Err("exceeds maximum of 100".to_string())
+    } else {
+        Ok(val)
+    }
+}
+
+fn parse_age(s: &str) -> std::result::Result<std::time::Duration, String> {
+    parse_duration(s, "d")
+}
+
+pub fn parse_device(s: &str) -> std::result::Result<DeviceArg, String> {
+    if s.is_empty() {
+        return Err("device must not be empty".to_owned());
+    }
+
+    match s.parse() {
+        Ok(address) => Ok(DeviceArg::Address(address)),
+        Err(_) => Ok(DeviceArg::Name(s.to_owned())),
+    }
+}
+
+pub fn parse_timeout(s: &str) -> std::result::Result<std::time::Duration, String> {
+    parse_duration(s, "s")
+}
+
+fn parse_duration(s: &str, default_unit: &str) -> std::result::Result<std::time::Duration, String> {
+    let (value, unit) = s.split_at(s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len()));
+
+    let value: u64 = value.parse()
+        .map_err(|_| format!("invalid duration '{s}'"))?;
+
+    let unit = if unit.is_empty() { default_unit } else { unit };
+
+    let factor = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        "w" => 7 * 24 * 60 * 60,
+        _ => return Err(format!("invalid unit '{unit}', expected one of 's', 'm', 'h', 'd', 'w'")),
+    };
+
+    let secs = value.checked_mul(factor)
+        .ok_or_else(|| format!("duration '{s}' too large"))?;
+
+    Ok(std::time::Duration::from_secs(secs))
+}
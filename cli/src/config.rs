@@ -0,0 +1,365 @@
+//! Command line configuration file.
+//!
+//! Defaults for global options can be specified in
+//! `$XDG_CONFIG_HOME/pbpctrl/config.toml`, for example:
+//!
+//! ```toml
+//! device = "24:29:34:AC:9F:D1"
+//! adapter = "hci1"
+//! timeout = "20s"
+//! output = "json"
+//!
+//! [devices.work-buds]
+//! address = "24:29:34:AC:9F:D2"
+//! adapter = "hci0"
+//!
+//! [presets.commute]
+//! current-ancr-state = "active"
+//! volume-eq-enable = true
+//! ```
+//!
+//! Options given on the command line take precedence. Devices defined in the
+//! `[devices]` table can be selected by name, i.e. via `--device work-buds`
+//! or `device = "work-buds"`, and may specify the adapter to use for them.
+//! Presets are applied via `pbpctrl preset <name>`, writing their settings in
+//! the given order. Settings and values are specified in the same way as for
+//! rules of the daemon configuration.
+
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+
+use clap::ValueEnum;
+
+use maestro::service::settings::SettingValue;
+
+use crate::cli::{self, Args, DeviceArg, OutputFormat};
+use crate::daemon::rules;
+use crate::transport::Address;
+
+
+#[derive(Debug, Clone, Default)]
+pub struct Config {
+    pub device: Option<DeviceArg>,
+    pub adapter: Option<String>,
+    pub timeout: Option<Duration>,
+    pub output: Option<OutputFormat>,
+    pub devices: Vec<NamedDevice>,
+    pub presets: Vec<Preset>,
+}
+
+/// Device defined in the configuration file, selectable by name.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NamedDevice {
+    pub name: String,
+    pub address: Address,
+
+    /// Adapter to use for the device, unless specified otherwise.
+    pub adapter: Option<String>,
+}
+
+/// Named set of settings values.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Preset {
+    pub name: String,
+    pub values: Vec<SettingValue>,
+}
+
+impl Config {
+    /// Default location of the configuration file, i.e.,
+    /// `$XDG_CONFIG_HOME/pbpctrl/config.toml`.
+    pub fn default_path() -> Option<PathBuf> {
+        let base = std::env::var_os("XDG_CONFIG_HOME")
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+
+        Some(base.join("pbpctrl").join("config.toml"))
+    }
+
+    /// Load the configuration from the default location. A missing file
+    /// results in the default configuration.
+    pub fn load() -> Result<Self> {
+        match Self::default_path() {
+            Some(path) => Self::load_from(&path),
+            None => Ok(Self::default()),
+        }
+    }
+
+    pub fn load_from(path: &Path) -> Result<Self> {
+        let text = match std::fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                tracing::debug!(path=%path.display(), "no configuration file found");
+                return Ok(Self::default());
+            },
+            Err(err) => {
+                return Err(err).with_context(|| format!("failed to read '{}'", path.display()));
+            },
+        };
+
+        tracing::debug!(path=%path.display(), "loading configuration");
+
+        Self::parse(&text)
+            .with_context(|| format!("invalid configuration file '{}'", path.display()))
+    }
+
+    pub fn parse(text: &str) -> Result<Self> {
+        let doc: toml_edit::DocumentMut = text.parse()?;
+        let mut config = Self::default();
+
+        for (key, item) in doc.iter() {
+            match key {
+                "device" => {
+                    config.device = Some(cli::parse_device(get_str(item, key)?).map_err(anyhow::Error::msg)?);
+                },
+                "adapter" => {
+                    config.adapter = Some(get_str(item, key)?.to_owned());
+                },
+                "timeout" => {
+                    let timeout = match item.as_integer() {
+                        Some(secs) if secs > 0 => Duration::from_secs(secs as u64),
+                        Some(_) => anyhow::bail!("'timeout' must be a positive number of seconds"),
+                        None => cli::parse_timeout(get_str(item, key)?).map_err(anyhow::Error::msg)?,
+                    };
+
+                    config.timeout = Some(timeout);
+                },
+                "output" => {
+                    let output = get_str(item, key)?;
+                    let output = OutputFormat::from_str(output, false)
+                        .map_err(|_| anyhow::anyhow!("invalid output format '{output}'"))?;
+
+                    config.output = Some(output);
+                },
+                "devices" => {
+                    let devices = item.as_table_like()
+                        .ok_or_else(|| anyhow::anyhow!("'devices' must be a table of devices"))?;
+
+                    for (name, item) in devices.iter() {
+                        let device = NamedDevice::parse(name, item)
+                            .with_context(|| format!("invalid device '{name}'"))?;
+
+                        config.devices.push(device);
+                    }
+                },
+                "presets" => {
+                    let presets = item.as_table_like()
+                        .ok_or_else(|| anyhow::anyhow!("'presets' must be a table of presets"))?;
+
+                    for (name, item) in presets.iter() {
+                        let preset = Preset::parse(name, item)
+                            .with_context(|| format!("invalid preset '{name}'"))?;
+
+                        config.presets.push(preset);
+                    }
+                },
+                _ => anyhow::bail!("unknown configuration key '{key}'"),
+            }
+        }
+
+        if let Some(DeviceArg::Name(name)) = &config.device
+            && config.named_device(name).is_none()
+        {
+            anyhow::bail!("default device '{name}' is not defined in the 'devices' table");
+        }
+
+        Ok(config)
+    }
+
+    /// Fill in options not given on the command line and resolve device
+    /// names to addresses.
+    ///
+    /// The adapter given on the command line takes precedence over the one
+    /// of a named device, which takes precedence over the default adapter.
+    pub fn apply(&self, mut args: Args) -> Result<Args> {
+        let device = match args.device.take().or_else(|| self.device.clone()) {
+            Some(DeviceArg::Name(name)) => {
+                let device = self.named_device(&name)
+                    .ok_or_else(|| self.unknown_device(&name))?;
+
+                args.adapter = args.adapter.or_else(|| device.adapter.clone());
+                Some(DeviceArg::Address(device.address))
+            },
+            device => device,
+        };
+
+        args.device = device;
+        args.adapter = args.adapter.or_else(|| self.adapter.clone());
+        args.timeout = args.timeout.or(self.timeout);
+        args.output = args.output.or(self.output);
+        Ok(args)
+    }
+
+    pub fn named_device(&self, name: &str) -> Option<&NamedDevice> {
+        self.devices.iter().find(|d| d.name == name)
+    }
+
+    fn unknown_device(&self, name: &str) -> anyhow::Error {
+        if self.devices.is_empty() {
+            return anyhow::anyhow!("unknown device '{name}', expected an address or a name defined in the \
+                'devices' table of the configuration file");
+        }
+
+        let names: Vec<_> = self.devices.iter().map(|d| d.name.as_str()).collect();
+        anyhow::anyhow!("unknown device '{name}', available devices: {}", names.join(", "))
+    }
+
+    pub fn preset(&self, name: &str) -> Option<&Preset> {
+        self.presets.iter().find(|p| p.name == name)
+    }
+}
+
+impl NamedDevice {
+    fn parse(name: &str, item: &toml_edit::Item) -> Result<Self> {
+        let table = item.as_table_like()
+            .ok_or_else(|| anyhow::anyhow!("device must be a table"))?;
+
+        if name.parse::<Address>().is_ok() {
+            anyhow::bail!("device names must not be addresses");
+        }
+
+        let mut address = None;
+        let mut adapter = None;
+
+        for (key, item) in table.iter() {
+            match key {
+                "address" => {
+                    let value = get_str(item, key)?;
+                    let value = Address::from_str(value)
+                        .map_err(|_| anyhow::anyhow!("invalid device address '{value}'"))?;
+
+                    address = Some(value);
+                },
+                "adapter" => adapter = Some(get_str(item, key)?.to_owned()),
+                _ => anyhow::bail!("unknown device option '{key}'"),
+            }
+        }
+
+        let address = address.ok_or_else(|| anyhow::anyhow!("missing device address ('address')"))?;
+        Ok(Self { name: name.to_owned(), address, adapter })
+    }
+}
+
+impl Preset {
+    fn parse(name: &str, item: &toml_edit::Item) -> Result<Self> {
+        let table = item.as_table_like()
+            .ok_or_else(|| anyhow::anyhow!("preset must be a table of settings"))?;
+
+        let mut values = Vec::new();
+        for (setting, item) in table.iter() {
+            let id = rules::parse_setting_id(setting)?;
+            values.push(rules::parse_setting_value(id, item)?);
+        }
+
+        if values.is_empty() {
+            anyhow::bail!("preset does not specify any settings");
+        }
+
+        Ok(Self { name: name.to_owned(), values })
+    }
+}
+
+fn get_str<'a>(item: &'a toml_edit::Item, key: &str) -> Result<&'a str> {
+    item.as_str()
+        .ok_or_else(|| anyhow::anyhow!("'{key}' must be a string"))
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use clap::Parser;
+
+    use maestro::service::settings::AncState;
+
+    #[test]
+    fn test_parse() {
+        let config = Config::parse(r#"
+            device = "24:29:34:AC:9F:D1"
+            adapter = "hci1"
+            timeout = "1m"
+            output = "json"
+
+            [presets.commute]
+            current-ancr-state = "active"
+            volume-eq-enable = true
+        "#).unwrap();
+
+        assert_eq!(config.device, Some(DeviceArg::Address("24:29:34:AC:9F:D1".parse().unwrap())));
+        assert_eq!(config.adapter.as_deref(), Some("hci1"));
+        assert_eq!(config.timeout, Some(Duration::from_secs(60)));
+        assert_eq!(config.output, Some(OutputFormat::Json));
+
+        let preset = config.preset("commute").unwrap();
+        assert_eq!(preset.values, [
+            SettingValue::CurrentAncrState(AncState::Active),
+            SettingValue::VolumeEqEnable(true),
+        ]);
+
+        assert_eq!(Config::parse("timeout = 20").unwrap().timeout, Some(Duration::from_secs(20)));
+
+        assert!(Config::parse("device = \"kitchen\"").is_err());
+        assert!(Config::parse("[devices.kitchen]\nadapter = \"hci0\"").is_err());
+        assert!(Config::parse("[devices.\"24:29:34:AC:9F:D1\"]\naddress = \"24:29:34:AC:9F:D1\"").is_err());
+        assert!(Config::parse("timeout = 0").is_err());
+        assert!(Config::parse("timeout = \"99999999999999999w\"").is_err());
+        assert!(Config::parse("output = \"xml\"").is_err());
+        assert!(Config::parse("[presets.empty]").is_err());
+        assert!(Config::parse("[presets.invalid]\nfoo = 1").is_err());
+        assert!(Config::parse("foo = 1").is_err());
+    }
+
+    #[test]
+    fn test_apply() {
+        let config = Config::parse(r#"
+            device = "24:29:34:AC:9F:D1"
+            output = "json"
+        "#).unwrap();
+
+        let args = config.apply(Args::parse_from(["pbpctrl", "--output", "human", "show", "hardware"])).unwrap();
+        assert_eq!(args.device, config.device);
+        assert_eq!(args.output, Some(OutputFormat::Human));
+
+        let args = config.apply(Args::parse_from(["pbpctrl", "-d", "24:29:34:AC:9F:D2", "show", "hardware"])).unwrap();
+        assert_eq!(args.device, Some(DeviceArg::Address("24:29:34:AC:9F:D2".parse().unwrap())));
+        assert_eq!(args.output, Some(OutputFormat::Json));
+
+        assert!(Args::try_parse_from(["pbpctrl", "set", "anc", "aware", "--for", "99999999999999999w"]).is_err());
+    }
+
+    #[test]
+    fn test_named_devices() {
+        let config = Config::parse(r#"
+            device = "home-buds"
+            adapter = "hci0"
+
+            [devices.home-buds]
+            address = "24:29:34:AC:9F:D1"
+
+            [devices.work-buds]
+            address = "24:29:34:AC:9F:D2"
+            adapter = "hci1"
+        "#).unwrap();
+
+        let address = |s: &str| Some(DeviceArg::Address(s.parse().unwrap()));
+
+        let args = config.apply(Args::parse_from(["pbpctrl", "show", "hardware"])).unwrap();
+        assert_eq!(args.device, address("24:29:34:AC:9F:D1"));
+        assert_eq!(args.adapter.as_deref(), Some("hci0"));
+
+        let args = config.apply(Args::parse_from(["pbpctrl", "-d", "work-buds", "show", "hardware"])).unwrap();
+        assert_eq!(args.device, address("24:29:34:AC:9F:D2"));
+        assert_eq!(args.adapter.as_deref(), Some("hci1"));
+
+        let args = ["pbpctrl", "-d", "work-buds", "--adapter", "hci2", "show", "hardware"];
+        let args = config.apply(Args::parse_from(args)).unwrap();
+        assert_eq!(args.adapter.as_deref(), Some("hci2"));
+
+        let err = config.apply(Args::parse_from(["pbpctrl", "-d", "car-buds", "show", "hardware"])).unwrap_err();
+        assert!(err.to_string().contains("available devices: home-buds, work-buds"), "{err}");
+    }
+}
@@ -0,0 +1,190 @@
+//! Audit log of settings writes.
+//!
+//! When enabled via `audit-log = true` in the daemon configuration file, all
+//! settings written by `pbpctrl`, either directly or via the daemon, are
+//! recorded to a local file, which can then be viewed via `pbpctrl history`.
+//!
+//! Entries are stored as JSON lines in `$XDG_STATE_HOME/pbpctrl/audit.jsonl`.
+
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+use anyhow::Result;
+
+use serde_json::{json, Value};
+
+use maestro::service::settings::SettingValue;
+
+use super::dosimeter::timestamp;
+
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    /// Milliseconds since the Unix epoch.
+    pub time: u64,
+
+    /// What performed the write, e.g. `cli` or `daemon`.
+    pub source: String,
+
+    pub setting: String,
+    pub old: Option<String>,
+    pub new: String,
+}
+
+impl Entry {
+    fn to_json(&self) -> Value {
+        json!({
+            "time": self.time,
+            "source": self.source,
+            "setting": self.setting,
+            "old": self.old,
+            "new": self.new,
+        })
+    }
+
+    fn from_json(value: &Value) -> Option<Self> {
+        let get_str = |key| value.get(key).and_then(Value::as_str).map(str::to_owned);
+
+        Some(Self {
+            time: value.get("time")?.as_u64()?,
+            source: get_str("source")?,
+            setting: get_str("setting")?,
+            old: get_str("old"),
+            new: get_str("new")?,
+        })
+    }
+}
+
+
+/// Append-only audit log of settings writes.
+///
+/// Keeps track of the last known settings values, which are recorded as old
+/// values if not provided explicitly.
+#[derive(Debug, Clone)]
+pub struct Log {
+    path: PathBuf,
+    cache: Vec<SettingValue>,
+}
+
+impl Log {
+    /// Default location of the audit log, i.e.,
+    /// `$XDG_STATE_HOME/pbpctrl/audit.jsonl`.
+    pub fn default_path() -> Option<PathBuf> {
+        let base = std::env::var_os("XDG_STATE_HOME")
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".local").join("state")))?;
+
+        Some(base.join("pbpctrl").join("audit.jsonl"))
+    }
+
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into(), cache: Vec::new() }
+    }
+
+    /// Remember the current value of a setting, e.g. as reported by the
+    /// device.
+    pub fn observe(&mut self, value: &SettingValue) {
+        match self.cache.iter_mut().find(|v| v.id() == value.id()) {
+            Some(cached) => *cached = value.clone(),
+            None => self.cache.push(value.clone()),
+        }
+    }
+
+    /// Record a write of the given value. Errors are logged, but otherwise
+    /// ignored.
+    pub fn record(&mut self, source: &str, value: &SettingValue, old: Option<&SettingValue>) {
+        let old = old.or_else(|| self.cache.iter().find(|v| v.id() == value.id()));
+
+        let entry = Entry {
+            time: timestamp(SystemTime::now()),
+            source: source.to_owned(),
+            setting: value.id().as_str().to_owned(),
+            old: old.map(ToString::to_string),
+            new: value.to_string(),
+        };
+
+        if let Err(err) = self.append(&entry) {
+            tracing::warn!(error=?err, path=%self.path.display(), "failed to write audit log entry");
+        }
+
+        self.observe(value);
+    }
+
+    pub fn append(&self, entry: &Entry) -> Result<()> {
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+
+        let mut line = serde_json::to_vec(&entry.to_json())?;
+        line.push(b'\n');
+
+        file.write_all(&line)?;
+        Ok(())
+    }
+
+    /// Read all entries with a timestamp at or after the given time. Invalid
+    /// lines are skipped.
+    pub fn read(&self, since: u64) -> Result<Vec<Entry>> {
+        let file = match File::open(&self.path) {
+            Ok(file) => file,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(err) => return Err(err.into()),
+        };
+
+        let mut entries = Vec::new();
+
+        for line in BufReader::new(file).lines() {
+            let line = line?;
+
+            let entry = serde_json::from_str(&line).ok()
+                .and_then(|value| Entry::from_json(&value));
+
+            match entry {
+                Some(entry) if entry.time >= since => entries.push(entry),
+                Some(_) => {},
+                None => tracing::debug!(line, "skipping invalid audit log entry"),
+            }
+        }
+
+        Ok(entries)
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use maestro::service::settings::AncState;
+
+    #[test]
+    fn test_record() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut log = Log::new(dir.path().join("audit.jsonl"));
+
+        log.record("cli", &SettingValue::CurrentAncrState(AncState::Aware), None);
+
+        // old value is taken from the cache if not specified
+        log.observe(&SettingValue::CurrentAncrState(AncState::Active));
+        log.record("daemon", &SettingValue::CurrentAncrState(AncState::Off), None);
+
+        let entries = log.read(0).unwrap();
+        assert_eq!(entries.len(), 2);
+
+        assert_eq!(entries[0].source, "cli");
+        assert_eq!(entries[0].old, None);
+
+        assert_eq!(entries[1].source, "daemon");
+        assert_eq!(entries[1].setting, SettingValue::CurrentAncrState(AncState::Off).id().as_str());
+        assert_eq!(entries[1].old.as_deref(), Some(SettingValue::CurrentAncrState(AncState::Active).to_string().as_str()));
+        assert_eq!(entries[1].new, SettingValue::CurrentAncrState(AncState::Off).to_string());
+    }
+}
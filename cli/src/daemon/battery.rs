@@ -0,0 +1,173 @@
+//! Battery reporting via the BlueZ battery provider API.
+//!
+//! BlueZ exposes provided batteries as `org.bluez.Battery1` interface on the
+//! device object, which is picked up by UPower and subsequently by desktop
+//! environments. BlueZ only supports a single battery per device, so we report
+//! the lower level of both buds.
+
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use anyhow::Result;
+
+use bluer::Address;
+
+use dbus::arg::{PropMap, RefArg, Variant};
+use dbus::channel::{MatchingReceiver, Sender};
+use dbus::message::{MatchRule, SignalArgs};
+use dbus::nonblock::stdintf::org_freedesktop_dbus::PropertiesPropertiesChanged;
+use dbus::nonblock::{Proxy, SyncConnection};
+use dbus::Path;
+use dbus_crossroads::{Crossroads, IfaceBuilder, IfaceToken};
+
+use maestro::protocol::types::BatteryInfo;
+
+
+const PROVIDER_PATH: &str = "/org/pbpctrl/battery";
+const BATTERY_PATH: &str = "/org/pbpctrl/battery/buds";
+const BATTERY_INTERFACE: &str = "org.bluez.BatteryProvider1";
+const MANAGER_INTERFACE: &str = "org.bluez.BatteryProviderManager1";
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+
+struct Battery {
+    device: Path<'static>,
+    percentage: u8,
+}
+
+
+pub struct BatteryProvider {
+    conn: Arc<SyncConnection>,
+    cr: Arc<Mutex<Crossroads>>,
+    token: IfaceToken<Battery>,
+    device: Path<'static>,
+    level: Mutex<Option<u8>>,
+}
+
+impl BatteryProvider {
+    pub async fn new(adapter: &str, address: Address) -> Result<Self> {
+        let (resource, conn) = tokio::task::spawn_blocking(dbus_tokio::connection::new_system_sync).await??;
+
+        tokio::spawn(async move {
+            let err = resource.await;
+            tracing::error!(error=%err, "lost connection to D-Bus system bus");
+        });
+
+        let mut cr = Crossroads::new();
+        cr.set_object_manager_support(Some(conn.clone()));
+
+        let token = cr.register(BATTERY_INTERFACE, register_interface);
+        let object_manager = cr.object_manager::<()>();
+        cr.insert(PROVIDER_PATH, &[object_manager], ());
+
+        let cr = Arc::new(Mutex::new(cr));
+        let cr_recv = cr.clone();
+
+        conn.start_receive(MatchRule::new_method_call(), Box::new(move |msg, conn| {
+            let _ = cr_recv.lock().unwrap().handle_message(msg, conn);
+            true
+        }));
+
+        let adapter_path = format!("/org/bluez/{adapter}");
+        let device = format!("{adapter_path}/dev_{}", address.to_string().replace(':', "_"));
+
+        let manager = Proxy::new("org.bluez", adapter_path, TIMEOUT, conn.clone());
+        manager.method_call::<(), _, _, _>(MANAGER_INTERFACE, "RegisterBatteryProvider", (Path::from(PROVIDER_PATH),))
+            .await?;
+
+        tracing::debug!(path=PROVIDER_PATH, "registered battery provider");
+
+        Ok(Self {
+            conn,
+            cr,
+            token,
+            device: Path::from(device),
+            level: Mutex::new(None),
+        })
+    }
+
+    /// Update the reported battery level. Removes the battery if the level is
+    /// not known.
+    pub fn update(&self, level: Option<u8>) {
+        let mut current = self.level.lock().unwrap();
+        if *current == level {
+            return;
+        }
+
+        let path = Path::from(BATTERY_PATH);
+        let mut cr = self.cr.lock().unwrap();
+
+        match (*current, level) {
+            (None, Some(percentage)) => {
+                let battery = Battery { device: self.device.clone(), percentage };
+                cr.insert(path, &[self.token], battery);
+            },
+            (Some(_), Some(percentage)) => {
+                if let Some(battery) = cr.data_mut::<Battery>(&path) {
+                    battery.percentage = percentage;
+                }
+
+                let mut changed = PropMap::new();
+                changed.insert("Percentage".into(), Variant(Box::new(percentage) as Box<dyn RefArg>));
+
+                let signal = PropertiesPropertiesChanged {
+                    interface_name: BATTERY_INTERFACE.to_owned(),
+                    changed_properties: changed,
+                    invalidated_properties: Vec::new(),
+                };
+
+                let _ = self.conn.send(signal.to_emit_message(&path));
+            },
+            (Some(_), None) => {
+                cr.remove::<Battery>(&path);
+            },
+            (None, None) => {},
+        }
+
+        *current = level;
+    }
+}
+
+fn register_interface(b: &mut IfaceBuilder<Battery>) {
+    b.property("Device")
+        .get(|_, battery| Ok(battery.device.clone()))
+        .emits_changed_const();
+
+    b.property("Percentage")
+        .get(|_, battery| Ok(battery.percentage));
+
+    b.property("Source")
+        .get(|_, _| Ok("pbpctrl".to_owned()))
+        .emits_changed_const();
+}
+
+/// Combined battery level of both buds, i.e., the lower of both levels.
+pub fn level(info: &BatteryInfo) -> Option<u8> {
+    [info.left.as_ref(), info.right.as_ref()].into_iter()
+        .flatten()
+        .map(|b| b.level.clamp(0, 100) as u8)
+        .min()
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use maestro::protocol::types::DeviceBatteryInfo;
+
+    #[test]
+    fn test_level() {
+        let bat = |level| Some(DeviceBatteryInfo { level, state: 1 });
+
+        let info = BatteryInfo { case: bat(10), left: bat(80), right: bat(65) };
+        assert_eq!(level(&info), Some(65));
+
+        let info = BatteryInfo { case: bat(10), left: None, right: bat(65) };
+        assert_eq!(level(&info), Some(65));
+
+        let info = BatteryInfo { case: bat(10), left: None, right: None };
+        assert_eq!(level(&info), None);
+    }
+}
@@ -0,0 +1,240 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::Result;
+
+use bluer::Address;
+
+use dbus::nonblock::stdintf::org_freedesktop_dbus::Properties;
+use dbus::nonblock::{Proxy, SyncConnection};
+
+use maestro::protocol::types::{BatteryInfo, DeviceBatteryInfo, FirmwareInfo, FirmwareVersion, RuntimeInfo};
+use maestro::service::settings::{Setting, SettingValue};
+
+use super::server::{BUS_NAME, INTERFACE, OBJECT_PATH};
+use super::value::{self, Value};
+
+
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Interval for polling a daemon that is not yet connected to its device.
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+
+/// Thin client forwarding commands to a running daemon.
+pub struct DaemonClient {
+    conn: Arc<SyncConnection>,
+}
+
+impl DaemonClient {
+    /// Connect to a running daemon.
+    ///
+    /// Returns `None` if no daemon is running, if it is not connected to its
+    /// device, or if it manages a different device than the one requested.
+    pub async fn connect(address: Option<Address>) -> Option<Self> {
+        match Self::try_connect(address).await {
+            Ok(client) => client,
+            Err(err) => {
+                tracing::debug!(error=?err, "failed to connect to daemon");
+                None
+            },
+        }
+    }
+
+    /// Connect to a running daemon, waiting up to the given duration for it
+    /// to connect to its device, e.g. if it has just been started.
+    ///
+    /// Returns `None` if no daemon is running, if it manages a different
+    /// device, or if it has not connected in time.
+    pub async fn connect_timeout(address: Option<Address>, timeout: Duration) -> Option<Self> {
+        let deadline = tokio::time::Instant::now() + timeout;
+
+        loop {
+            if let Some(client) = Self::connect(address).await {
+                return Some(client);
+            }
+
+            if tokio::time::Instant::now() >= deadline || !Self::is_running().await {
+                return None;
+            }
+
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+    }
+
+    /// Whether a daemon is running, independent of whether it is connected to
+    /// its device.
+    pub async fn is_running() -> bool {
+        match Self::try_connect_bus().await {
+            Ok(client) => client.is_some(),
+            Err(err) => {
+                tracing::debug!(error=?err, "failed to check for running daemon");
+                false
+            },
+        }
+    }
+
+    async fn try_connect_bus() -> Result<Option<Self>> {
+        let (resource, conn) = dbus_tokio::connection::new_session_sync()?;
+
+        tokio::spawn(async move {
+            let err = resource.await;
+            tracing::debug!(error=%err, "lost connection to D-Bus session bus");
+        });
+
+        let bus = Proxy::new("org.freedesktop.DBus", "/", TIMEOUT, conn.clone());
+        let (has_owner,): (bool,) = bus
+            .method_call("org.freedesktop.DBus", "NameHasOwner", (BUS_NAME,))
+            .await?;
+
+        if !has_owner {
+            tracing::debug!("no daemon running");
+            return Ok(None);
+        }
+
+        Ok(Some(Self { conn }))
+    }
+
+    async fn try_connect(address: Option<Address>) -> Result<Option<Self>> {
+        let Some(client) = Self::try_connect_bus().await? else {
+            return Ok(None);
+        };
+        let proxy = client.proxy();
+
+        if let Some(address) = address {
+            let daemon_address: String = proxy.get(INTERFACE, "Address").await?;
+
+            if daemon_address != address.to_string() {
+                tracing::debug!(address=%daemon_address, "daemon manages a different device");
+                return Ok(None);
+            }
+        }
+
+        let connected: bool = proxy.get(INTERFACE, "Connected").await?;
+        if !connected {
+            tracing::debug!("daemon is not connected to device");
+            return Ok(None);
+        }
+
+        tracing::debug!("using running daemon");
+        Ok(Some(client))
+    }
+
+    fn proxy(&self) -> Proxy<'_, Arc<SyncConnection>> {
+        Proxy::new(BUS_NAME, OBJECT_PATH, TIMEOUT, self.conn.clone())
+    }
+
+    pub async fn read_setting<T>(&self, setting: T) -> Result<T::Type>
+    where
+        T: Setting,
+    {
+        let id = setting.id();
+
+        let (value,): (Value,) = self.proxy()
+            .method_call(INTERFACE, "GetSetting", (id.as_str(),))
+            .await?;
+
+        let value = value::from_variant(id, &*value.0)
+            .map_err(|e| anyhow::anyhow!("received invalid value from daemon: {e}"))?;
+
+        T::from_var(value)
+            .ok_or_else(|| anyhow::anyhow!("failed to decode settings value"))
+    }
+
+    pub async fn write_setting(&self, value: SettingValue) -> Result<()> {
+        self.proxy()
+            .method_call::<(), _, _, _>(INTERFACE, "SetSetting", (value.id().as_str(), value::to_variant(&value)))
+            .await?;
+
+        Ok(())
+    }
+
+    /// Write the given setting and revert it to its previous value after the
+    /// given duration.
+    pub async fn write_setting_for(&self, value: SettingValue, duration: std::time::Duration) -> Result<()> {
+        let args = (value.id().as_str(), value::to_variant(&value), duration.as_secs());
+
+        self.proxy()
+            .method_call::<(), _, _, _>(INTERFACE, "SetSettingFor", args)
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn get_battery_info(&self) -> Result<RuntimeInfo> {
+        let proxy = self.proxy();
+
+        let case: (i32, String) = proxy.get(INTERFACE, "BatteryCase").await?;
+        let left: (i32, String) = proxy.get(INTERFACE, "BatteryLeft").await?;
+        let right: (i32, String) = proxy.get(INTERFACE, "BatteryRight").await?;
+
+        let battery = BatteryInfo {
+            case: battery_from_dbus(case),
+            left: battery_from_dbus(left),
+            right: battery_from_dbus(right),
+        };
+
+        Ok(RuntimeInfo {
+            battery_info: Some(battery),
+            ..Default::default()
+        })
+    }
+
+    /// Address of the device managed by the daemon.
+    pub async fn get_address(&self) -> Result<Address> {
+        let address: String = self.proxy().get(INTERFACE, "Address").await?;
+        Ok(address.parse()?)
+    }
+
+    pub async fn get_firmware(&self) -> Result<FirmwareInfo> {
+        let (case, left, right): (String, String, String) = self.proxy()
+            .method_call(INTERFACE, "GetFirmware", ())
+            .await?;
+
+        Ok(FirmwareInfo {
+            case: firmware_from_dbus(case),
+            left: firmware_from_dbus(left),
+            right: firmware_from_dbus(right),
+        })
+    }
+
+    /// Start ringing the buds in the background.
+    pub async fn ring(&self) -> Result<()> {
+        self.proxy()
+            .method_call::<(), _, _, _>(INTERFACE, "Ring", ())
+            .await?;
+
+        Ok(())
+    }
+
+    /// Stop ringing the buds. Returns `false` if they have not been ringing.
+    pub async fn stop_ringing(&self) -> Result<bool> {
+        let (stopped,): (bool,) = self.proxy()
+            .method_call(INTERFACE, "StopRinging", ())
+            .await?;
+
+        Ok(stopped)
+    }
+}
+
+fn firmware_from_dbus(version: String) -> Option<FirmwareVersion> {
+    if version.is_empty() {
+        return None;
+    }
+
+    Some(FirmwareVersion { version_string: version, ..Default::default() })
+}
+
+fn battery_from_dbus((level, state): (i32, String)) -> Option<DeviceBatteryInfo> {
+    if level < 0 {
+        return None;
+    }
+
+    let state = match state.as_str() {
+        "charging" => 2,
+        "not-charging" => 1,
+        _ => 0,
+    };
+
+    Some(DeviceBatteryInfo { level, state })
+}
@@ -0,0 +1,369 @@
+//! Daemon configuration file.
+
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+
+use maestro::retry::Backoff;
+
+use super::dosimeter::DosimeterConfig;
+use super::gestures::GestureConfig;
+use super::media::MediaConfig;
+use super::notify::NotifyConfig;
+use super::rules::Rule;
+
+use crate::transport::DeviceIdentity;
+
+
+/// Delays between attempts to reconnect after connecting to the device failed.
+pub const DEFAULT_RECONNECT: Backoff = Backoff {
+    initial: Duration::from_secs(10),
+    multiplier: 2.0,
+    max: Duration::from_secs(60),
+    jitter: 0.1,
+    attempts: None,
+};
+
+
+#[derive(Debug, Default)]
+pub struct Config {
+    pub rules: Vec<Rule>,
+    pub notifications: NotifyConfig,
+    pub dosimeter: Option<DosimeterConfig>,
+    pub media: Option<MediaConfig>,
+    pub gestures: Option<GestureConfig>,
+
+    /// Hold back settings change events for this long and drop them if
+    /// reverted in the meantime.
+    pub debounce: Option<Duration>,
+
+    /// Record all settings writes to the audit log.
+    pub audit_log: bool,
+
+    /// Reject all settings writes.
+    pub read_only: bool,
+
+    /// Delays between reconnect attempts, [`DEFAULT_RECONNECT`] if not
+    /// specified.
+    pub reconnect: Option<Backoff>,
+}
+
+impl Config {
+    /// Default location of the configuration file, i.e.,
+    /// `$XDG_CONFIG_HOME/pbpctrl/daemon.toml`.
+    pub fn default_path() -> Option<PathBuf> {
+        let base = std::env::var_os("XDG_CONFIG_HOME")
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+
+        Some(base.join("pbpctrl").join("daemon.toml"))
+    }
+
+    /// Load the configuration from the given file, or from the default
+    /// location if none is specified. A missing file at the default location
+    /// results in the default configuration.
+    ///
+    /// If a device is given, the values of its section override the
+    /// top-level ones, see [`Self::parse_for`].
+    pub fn load(path: Option<&Path>, device: Option<&DeviceIdentity>) -> Result<Self> {
+        let (path, required) = match path {
+            Some(path) => (path.to_owned(), true),
+            None => match Self::default_path() {
+                Some(path) => (path, false),
+                None => return Ok(Self::default()),
+            },
+        };
+
+        let text = match std::fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(err) if !required && err.kind() == std::io::ErrorKind::NotFound => {
+                tracing::debug!(path=%path.display(), "no configuration file found");
+                return Ok(Self::default());
+            },
+            Err(err) => {
+                return Err(err).with_context(|| format!("failed to read '{}'", path.display()));
+            },
+        };
+
+        tracing::debug!(path=%path.display(), "loading configuration");
+
+        Self::parse_for(&text, device)
+            .with_context(|| format!("invalid configuration file '{}'", path.display()))
+    }
+
+    /// Parse the top-level configuration, ignoring per-device sections.
+    #[cfg(test)]
+    pub fn parse(text: &str) -> Result<Self> {
+        Self::parse_for(text, None)
+    }
+
+    /// Parse the configuration for the given device.
+    ///
+    /// Keys in the `[device."<key>"]` section matching the address or alias of
+    /// the device replace the top-level keys of the same name. Sections of
+    /// other devices are validated but otherwise ignored.
+    pub fn parse_for(text: &str, device: Option<&DeviceIdentity>) -> Result<Self> {
+        let mut doc: toml_edit::DocumentMut = text.parse()?;
+        let sections = device_sections(&mut doc)?;
+
+        for (key, section) in &sections {
+            let mut doc = doc.clone();
+            merge(&mut doc, section);
+
+            Self::parse_doc(&doc)
+                .with_context(|| format!("invalid section for device '{key}'"))?;
+        }
+
+        // apply sections selected by alias first, so that sections selected
+        // by address take precedence
+        if let Some(device) = device {
+            let alias = sections.iter()
+                .filter(|(key, _)| device.alias.as_deref() == Some(key.as_str()));
+
+            let address = sections.iter()
+                .filter(|(key, _)| key.eq_ignore_ascii_case(&device.address.to_string()));
+
+            for (key, section) in alias.chain(address) {
+                tracing::debug!(device=%key, "applying per-device configuration");
+                merge(&mut doc, section);
+            }
+        }
+
+        Self::parse_doc(&doc)
+    }
+
+    fn parse_doc(doc: &toml_edit::DocumentMut) -> Result<Self> {
+        let mut config = Self::default();
+
+        for (key, item) in doc.iter() {
+            match key {
+                "rules" => {
+                    let tables = item.as_array_of_tables()
+                        .ok_or_else(|| anyhow::anyhow!("'rules' must be an array of tables"))?;
+
+                    for (i, table) in tables.iter().enumerate() {
+                        let rule = Rule::parse(i, table)
+                            .with_context(|| format!("invalid rule #{}", i + 1))?;
+
+                        config.rules.push(rule);
+                    }
+                },
+                "notifications" => {
+                    let table = item.as_table_like()
+                        .ok_or_else(|| anyhow::anyhow!("'notifications' must be a table"))?;
+
+                    config.notifications = NotifyConfig::parse(table)
+                        .context("invalid notification configuration")?;
+                },
+                "dosimeter" => {
+                    let table = item.as_table_like()
+                        .ok_or_else(|| anyhow::anyhow!("'dosimeter' must be a table"))?;
+
+                    let dosimeter = DosimeterConfig::parse(table)
+                        .context("invalid dosimeter configuration")?;
+
+                    config.dosimeter = Some(dosimeter);
+                },
+                "media" => {
+                    let table = item.as_table_like()
+                        .ok_or_else(|| anyhow::anyhow!("'media' must be a table"))?;
+
+                    let media = MediaConfig::parse(table)
+                        .context("invalid media configuration")?;
+
+                    config.media = Some(media);
+                },
+                "gestures" => {
+                    let table = item.as_table_like()
+                        .ok_or_else(|| anyhow::anyhow!("'gestures' must be a table"))?;
+
+                    let gestures = GestureConfig::parse(table)
+                        .context("invalid gestures configuration")?;
+
+                    config.gestures = Some(gestures);
+                },
+                "debounce" => {
+                    let secs = item.as_integer()
+                        .filter(|secs| *secs >= 0)
+                        .ok_or_else(|| anyhow::anyhow!("'debounce' must be a non-negative number of seconds"))?;
+
+                    config.debounce = Some(Duration::from_secs(secs as u64)).filter(|d| !d.is_zero());
+                },
+                "audit-log" => {
+                    config.audit_log = item.as_bool()
+                        .ok_or_else(|| anyhow::anyhow!("'audit-log' must be a boolean"))?;
+                },
+                "read-only" => {
+                    config.read_only = item.as_bool()
+                        .ok_or_else(|| anyhow::anyhow!("'read-only' must be a boolean"))?;
+                },
+                "reconnect" => {
+                    let table = item.as_table_like()
+                        .ok_or_else(|| anyhow::anyhow!("'reconnect' must be a table"))?;
+
+                    let reconnect = parse_reconnect(table)
+                        .context("invalid reconnect configuration")?;
+
+                    config.reconnect = Some(reconnect);
+                },
+                _ => anyhow::bail!("unknown configuration key '{key}'"),
+            }
+        }
+
+        Ok(config)
+    }
+}
+
+/// Parse the `[reconnect]` table, using [`DEFAULT_RECONNECT`] for values not
+/// specified.
+fn parse_reconnect(table: &dyn toml_edit::TableLike) -> Result<Backoff> {
+    let number = |item: &toml_edit::Item| item.as_float().or_else(|| item.as_integer().map(|i| i as f64));
+
+    let mut backoff = DEFAULT_RECONNECT;
+
+    for (key, item) in table.iter() {
+        match key {
+            "initial-delay" | "max-delay" => {
+                let delay = number(item)
+                    .and_then(|secs| Duration::try_from_secs_f64(secs).ok())
+                    .ok_or_else(|| anyhow::anyhow!("'{key}' must be a non-negative number of seconds"))?;
+
+                if key == "initial-delay" {
+                    backoff.initial = delay;
+                } else {
+                    backoff.max = delay;
+                }
+            },
+            "multiplier" => {
+                backoff.multiplier = number(item)
+                    .filter(|m| *m >= 1.0)
+                    .ok_or_else(|| anyhow::anyhow!("'multiplier' must be a number of at least 1"))?;
+            },
+            "jitter" => {
+                backoff.jitter = number(item)
+                    .filter(|j| (0.0..=1.0).contains(j))
+                    .ok_or_else(|| anyhow::anyhow!("'jitter' must be a number between 0 and 1"))?;
+            },
+            "attempts" => {
+                let attempts = item.as_integer()
+                    .filter(|n| *n >= 0)
+                    .and_then(|n| u32::try_from(n).ok())
+                    .ok_or_else(|| anyhow::anyhow!("'attempts' must be a non-negative number"))?;
+
+                // zero attempts means retrying indefinitely
+                backoff.attempts = Some(attempts).filter(|n| *n > 0);
+            },
+            _ => anyhow::bail!("unknown reconnect option '{key}'"),
+        }
+    }
+
+    if backoff.max < backoff.initial {
+        anyhow::bail!("'max-delay' must not be smaller than 'initial-delay'");
+    }
+
+    Ok(backoff)
+}
+
+/// Remove the per-device sections from the document, returning them by key.
+fn device_sections(doc: &mut toml_edit::DocumentMut) -> Result<Vec<(String, toml_edit::Table)>> {
+    let Some(item) = doc.remove("device") else {
+        return Ok(Vec::new());
+    };
+
+    let devices = item.as_table_like()
+        .ok_or_else(|| anyhow::anyhow!("'device' must be a table of per-device sections"))?;
+
+    let mut sections = Vec::new();
+    for (key, item) in devices.iter() {
+        let section = item.as_table_like()
+            .ok_or_else(|| anyhow::anyhow!("'device.\"{key}\"' must be a table"))?;
+
+        if section.contains_key("device") {
+            anyhow::bail!("per-device sections cannot be nested");
+        }
+
+        let mut table = toml_edit::Table::new();
+        for (k, v) in section.iter() {
+            table.insert(k, v.clone());
+        }
+
+        sections.push((key.to_owned(), table));
+    }
+
+    Ok(sections)
+}
+
+/// Replace the top-level keys of the document with those of the section.
+fn merge(doc: &mut toml_edit::DocumentMut, section: &toml_edit::Table) {
+    for (key, item) in section.iter() {
+        doc.insert(key, item.clone());
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_device_sections() {
+        let text = r#"
+            audit-log = true
+            debounce = 2
+
+            [device."24:29:34:AC:9F:D1"]
+            debounce = 5
+
+            [device."Kitchen Buds"]
+            audit-log = false
+            debounce = 1
+
+            [device."00:11:22:33:44:55".media]
+            resume = false
+        "#;
+
+        let config = Config::parse(text).unwrap();
+        assert!(config.audit_log);
+        assert_eq!(config.debounce, Some(Duration::from_secs(2)));
+
+        let mut device = DeviceIdentity::new("24:29:34:ac:9f:d1".parse().unwrap(), "hci0");
+        device.alias = Some("Kitchen Buds".to_owned());
+
+        let config = Config::parse_for(text, Some(&device)).unwrap();
+        assert!(!config.audit_log);
+        assert_eq!(config.debounce, Some(Duration::from_secs(5)));
+        assert_eq!(config.media, None);
+
+        // other sections are validated as well
+        assert!(Config::parse("[device.other]
+foo = 1
+").is_err());
+        assert!(Config::parse("[device.other.device.nested]
+debounce = 1
+").is_err());
+        assert!(Config::parse("device = 1
+").is_err());
+    }
+
+    #[test]
+    fn test_reconnect() {
+        assert_eq!(Config::parse("").unwrap().reconnect, None);
+
+        let config = Config::parse("[reconnect]\ninitial-delay = 0.5\nmax-delay = 30\nattempts = 5\n").unwrap();
+        let reconnect = config.reconnect.unwrap();
+        assert_eq!(reconnect.initial, Duration::from_millis(500));
+        assert_eq!(reconnect.max, Duration::from_secs(30));
+        assert_eq!(reconnect.multiplier, DEFAULT_RECONNECT.multiplier);
+        assert_eq!(reconnect.attempts, Some(5));
+
+        let config = Config::parse("[reconnect]\nattempts = 0\n").unwrap();
+        assert_eq!(config.reconnect.unwrap().attempts, None);
+
+        assert!(Config::parse("[reconnect]\nmultiplier = 0.5\n").is_err());
+        assert!(Config::parse("[reconnect]\njitter = 2\n").is_err());
+        assert!(Config::parse("[reconnect]\ninitial-delay = 20\nmax-delay = 10\n").is_err());
+        assert!(Config::parse("[reconnect]\ndelay = 1\n").is_err());
+    }
+}
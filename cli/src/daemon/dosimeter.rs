@@ -0,0 +1,462 @@
+//! Local history of dosimeter data.
+//!
+//! When enabled via the `[dosimeter]` section of the daemon configuration
+//! file, the daemon records live sound levels and daily summaries reported by
+//! the device to a local file, which can then be queried via `pbpctrl
+//! dosimeter history`:
+//!
+//! ```toml
+//! [dosimeter]
+//! retention = 90          # days to keep recorded data for
+//! sample-interval = 60    # seconds over which live samples are aggregated
+//! ```
+//!
+//! Data is stored as JSON lines in `$XDG_DATA_HOME/pbpctrl/dosimeter.jsonl`.
+
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use anyhow::{Context, Result};
+
+use serde_json::{json, Value};
+
+use maestro::protocol::types::DosimeterSummary;
+
+
+/// Time between pruning old records.
+const PRUNE_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DosimeterConfig {
+    pub retention: Duration,
+    pub sample_interval: Duration,
+}
+
+impl DosimeterConfig {
+    pub fn parse(table: &dyn toml_edit::TableLike) -> Result<Self> {
+        let mut config = Self::default();
+
+        for (key, item) in table.iter() {
+            let value = item.as_integer()
+                .and_then(|v| u64::try_from(v).ok())
+                .filter(|v| *v > 0)
+                .ok_or_else(|| anyhow::anyhow!("'{key}' must be a positive integer"))?;
+
+            match key {
+                "retention" => config.retention = Duration::from_secs(value * 24 * 60 * 60),
+                "sample-interval" => config.sample_interval = Duration::from_secs(value),
+                _ => anyhow::bail!("unknown key '{key}'"),
+            }
+        }
+
+        Ok(config)
+    }
+}
+
+impl Default for DosimeterConfig {
+    fn default() -> Self {
+        Self {
+            retention: Duration::from_secs(90 * 24 * 60 * 60),
+            sample_interval: Duration::from_secs(60),
+        }
+    }
+}
+
+
+/// Convert the intensity reported by the device to dB.
+pub fn intensity_to_db(intensity: f32) -> f32 {
+    intensity.log10() * 10.0
+}
+
+/// Milliseconds since the Unix epoch.
+pub fn timestamp(time: SystemTime) -> u64 {
+    time.duration_since(SystemTime::UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+/// Format a timestamp (in milliseconds since the Unix epoch) as UTC date and
+/// time.
+pub fn format_timestamp(time: u64) -> String {
+    let secs = time / 1000;
+    let days = (secs / 86400) as i64;
+    let rem = secs % 86400;
+
+    // civil date from days since epoch, see
+    // https://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!("{year:04}-{month:02}-{day:02} {:02}:{:02}:{:02} UTC", rem / 3600, rem % 3600 / 60, rem % 60)
+}
+
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Record {
+    /// Aggregated live sound level samples, in dB.
+    Live {
+        time: u64,
+        mean: f32,
+        max: f32,
+        samples: u32,
+    },
+
+    /// Daily summaries as reported by the device.
+    Summary {
+        time: u64,
+        summary: DosimeterSummary,
+    },
+}
+
+impl Record {
+    pub fn time(&self) -> u64 {
+        match self {
+            Record::Live { time, .. } => *time,
+            Record::Summary { time, .. } => *time,
+        }
+    }
+
+    fn to_json(&self) -> Value {
+        match self {
+            Record::Live { time, mean, max, samples } => {
+                json!({ "type": "live", "time": time, "mean": mean, "max": max, "samples": samples })
+            },
+            Record::Summary { time, summary } => {
+                let entries: Vec<_> = summary.unknown2.iter()
+                    .map(|e| json!({ "unknown1": e.unknown1, "unknown6": e.unknown6 }))
+                    .collect();
+
+                json!({
+                    "type": "summary",
+                    "time": time,
+                    "unknown1": summary.unknown1,
+                    "entries": entries,
+                    "unknown4": summary.unknown4,
+                    "unknown5": summary.unknown5,
+                })
+            },
+        }
+    }
+
+    fn from_json(value: &Value) -> Option<Self> {
+        let time = value.get("time")?.as_u64()?;
+        let get_f32 = |key| value.get(key).and_then(Value::as_f64).map(|v| v as f32);
+        let get_i32 = |v: &Value, key| v.get(key).and_then(Value::as_i64).map(|v| v as i32);
+
+        match value.get("type")?.as_str()? {
+            "live" => Some(Record::Live {
+                time,
+                mean: get_f32("mean")?,
+                max: get_f32("max")?,
+                samples: value.get("samples")?.as_u64()? as u32,
+            }),
+            "summary" => {
+                let entries = value.get("entries")?.as_array()?.iter()
+                    .map(|e| {
+                        Some(maestro::protocol::types::DosimeterSummaryEntry {
+                            unknown1: get_i32(e, "unknown1")?,
+                            unknown6: e.get("unknown6")?.as_f64()? as f32,
+                        })
+                    })
+                    .collect::<Option<Vec<_>>>()?;
+
+                let summary = DosimeterSummary {
+                    unknown1: get_i32(value, "unknown1")?,
+                    unknown2: entries,
+                    unknown4: get_i32(value, "unknown4")?,
+                    unknown5: get_f32("unknown5")?,
+                };
+
+                Some(Record::Summary { time, summary })
+            },
+            _ => None,
+        }
+    }
+}
+
+
+/// Combine live records into hourly buckets, returning start time, mean, and
+/// maximum sound level of each bucket.
+pub fn hourly(records: &[Record]) -> Vec<(u64, f32, f32)> {
+    const HOUR: u64 = 60 * 60 * 1000;
+
+    let mut buckets: Vec<(u64, f64, u64, f32)> = Vec::new();
+
+    for record in records {
+        let Record::Live { time, mean, max, samples } = record else { continue };
+
+        let start = time - time % HOUR;
+        let intensity = 10f64.powf(f64::from(*mean) / 10.0) * f64::from(*samples);
+
+        match buckets.last_mut() {
+            Some(bucket) if bucket.0 == start => {
+                bucket.1 += intensity;
+                bucket.2 += u64::from(*samples);
+                bucket.3 = bucket.3.max(*max);
+            },
+            _ => buckets.push((start, intensity, u64::from(*samples), *max)),
+        }
+    }
+
+    buckets.into_iter()
+        .filter(|(_, _, samples, _)| *samples > 0)
+        .map(|(start, sum, samples, max)| (start, intensity_to_db((sum / samples as f64) as f32), max))
+        .collect()
+}
+
+
+/// Append-only file storing dosimeter records.
+#[derive(Debug, Clone)]
+pub struct Store {
+    path: PathBuf,
+}
+
+impl Store {
+    /// Default location of the history file, i.e.,
+    /// `$XDG_DATA_HOME/pbpctrl/dosimeter.jsonl`.
+    pub fn default_path() -> Option<PathBuf> {
+        let base = std::env::var_os("XDG_DATA_HOME")
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".local").join("share")))?;
+
+        Some(base.join("pbpctrl").join("dosimeter.jsonl"))
+    }
+
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn append(&self, record: &Record) -> Result<()> {
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+
+        let mut line = serde_json::to_vec(&record.to_json())?;
+        line.push(b'\n');
+
+        file.write_all(&line)?;
+        Ok(())
+    }
+
+    /// Read all records with a timestamp at or after the given time. Invalid
+    /// lines are skipped.
+    pub fn read(&self, since: u64) -> Result<Vec<Record>> {
+        let file = match File::open(&self.path) {
+            Ok(file) => file,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(err) => return Err(err.into()),
+        };
+
+        let mut records = Vec::new();
+
+        for line in BufReader::new(file).lines() {
+            let line = line?;
+
+            let record = serde_json::from_str(&line).ok()
+                .and_then(|value| Record::from_json(&value));
+
+            match record {
+                Some(record) if record.time() >= since => records.push(record),
+                Some(_) => {},
+                None => tracing::debug!(line, "skipping invalid dosimeter record"),
+            }
+        }
+
+        Ok(records)
+    }
+
+    /// Remove all records older than the given time.
+    pub fn prune(&self, before: u64) -> Result<()> {
+        if !self.path.exists() {
+            return Ok(());
+        }
+
+        let records = self.read(before)?;
+
+        let tmp = self.path.with_extension("jsonl.tmp");
+        {
+            let mut file = File::create(&tmp)?;
+
+            for record in &records {
+                let mut line = serde_json::to_vec(&record.to_json())?;
+                line.push(b'\n');
+
+                file.write_all(&line)?;
+            }
+        }
+
+        std::fs::rename(&tmp, &self.path)?;
+        Ok(())
+    }
+}
+
+
+/// Aggregates dosimeter data reported by the device and writes it to the
+/// store.
+pub struct Recorder {
+    config: DosimeterConfig,
+    store: Store,
+    window: Option<Window>,
+    last_summary: Option<DosimeterSummary>,
+    last_prune: Option<SystemTime>,
+}
+
+struct Window {
+    start: SystemTime,
+    sum: f64,
+    max: f32,
+    samples: u32,
+}
+
+impl Recorder {
+    pub fn new(config: DosimeterConfig, store: Store) -> Self {
+        Self { config, store, window: None, last_summary: None, last_prune: None }
+    }
+
+    /// Record a live sample of the given intensity.
+    pub fn sample(&mut self, intensity: f32, now: SystemTime) {
+        let db = intensity_to_db(intensity);
+        if !db.is_finite() {
+            return;
+        }
+
+        // close the current window if it has expired
+        if let Some(window) = &self.window
+            && now.duration_since(window.start).unwrap_or_default() >= self.config.sample_interval
+        {
+            self.flush();
+        }
+
+        let window = self.window.get_or_insert(Window { start: now, sum: 0.0, max: f32::MIN, samples: 0 });
+        window.sum += f64::from(intensity);
+        window.max = window.max.max(db);
+        window.samples += 1;
+
+        self.prune(now);
+    }
+
+    /// Record daily summaries reported by the device. Summaries identical to
+    /// the previously recorded ones are skipped.
+    pub fn summary(&mut self, summary: DosimeterSummary, now: SystemTime) {
+        if self.last_summary.as_ref() == Some(&summary) {
+            return;
+        }
+
+        self.write(&Record::Summary { time: timestamp(now), summary: summary.clone() });
+        self.last_summary = Some(summary);
+
+        self.prune(now);
+    }
+
+    /// Write out any pending live samples.
+    pub fn flush(&mut self) {
+        let Some(window) = self.window.take() else { return };
+
+        // average intensity, not dB
+        let mean = intensity_to_db((window.sum / f64::from(window.samples)) as f32);
+
+        self.write(&Record::Live {
+            time: timestamp(window.start),
+            mean,
+            max: window.max,
+            samples: window.samples,
+        });
+    }
+
+    fn write(&self, record: &Record) {
+        if let Err(err) = self.store.append(record) {
+            tracing::warn!(error=?err, path=%self.store.path().display(), "failed to write dosimeter record");
+        }
+    }
+
+    fn prune(&mut self, now: SystemTime) {
+        if self.last_prune.is_some_and(|t| now.duration_since(t).unwrap_or_default() < PRUNE_INTERVAL) {
+            return;
+        }
+
+        self.last_prune = Some(now);
+
+        let before = timestamp(now).saturating_sub(self.config.retention.as_millis() as u64);
+        let result = self.store.prune(before)
+            .with_context(|| format!("failed to prune '{}'", self.store.path().display()));
+
+        if let Err(err) = result {
+            tracing::warn!(error=?err, "failed to prune dosimeter history");
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_format_timestamp() {
+        assert_eq!(format_timestamp(0), "1970-01-01 00:00:00 UTC");
+        assert_eq!(format_timestamp(951_827_696_000), "2000-02-29 12:34:56 UTC");
+    }
+
+    #[test]
+    fn test_hourly() {
+        let records = [
+            Record::Live { time: 0, mean: 60.0, max: 65.0, samples: 1 },
+            Record::Live { time: 60_000, mean: 60.0, max: 70.0, samples: 3 },
+            Record::Live { time: 3_600_000, mean: 50.0, max: 55.0, samples: 2 },
+        ];
+
+        let hours = hourly(&records);
+
+        assert_eq!(hours.len(), 2);
+        assert_eq!((hours[0].0, hours[0].2), (0, 70.0));
+        assert!((hours[0].1 - 60.0).abs() < 1e-3);
+        assert_eq!((hours[1].0, hours[1].2), (3_600_000, 55.0));
+    }
+
+    #[test]
+    fn test_store() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = Store::new(dir.path().join("dosimeter.jsonl"));
+
+        let live = |time| Record::Live { time, mean: 60.0, max: 70.5, samples: 12 };
+        let summary = Record::Summary {
+            time: 3000,
+            summary: DosimeterSummary {
+                unknown1: 1,
+                unknown2: vec![maestro::protocol::types::DosimeterSummaryEntry { unknown1: 2, unknown6: 0.5 }],
+                unknown4: 3,
+                unknown5: 1.5,
+            },
+        };
+
+        store.append(&live(1000)).unwrap();
+        store.append(&live(2000)).unwrap();
+        store.append(&summary).unwrap();
+
+        assert_eq!(store.read(0).unwrap(), vec![live(1000), live(2000), summary.clone()]);
+        assert_eq!(store.read(2000).unwrap(), vec![live(2000), summary.clone()]);
+
+        store.prune(1500).unwrap();
+        assert_eq!(store.read(0).unwrap(), vec![live(2000), summary]);
+    }
+}
@@ -0,0 +1,229 @@
+//! Device events derived from runtime information and settings changes.
+
+use maestro::protocol::types::{DeviceBatteryInfo, RuntimeInfo, SoftwareInfo};
+use maestro::service::settings::SettingValue;
+
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Bud {
+    Left,
+    Right,
+}
+
+impl Bud {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Bud::Left => "left",
+            Bud::Right => "right",
+        }
+    }
+}
+
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Component {
+    Case,
+    Left,
+    Right,
+}
+
+impl Component {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Component::Case => "case",
+            Component::Left => "left",
+            Component::Right => "right",
+        }
+    }
+}
+
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Event {
+    Connected,
+    Disconnected,
+    BudRemoved(Bud),
+    BudInserted(Bud),
+    BothRemoved,
+    BothInserted,
+    BatteryChanged {
+        component: Component,
+        level: Option<i32>,
+        previous: Option<i32>,
+    },
+    SettingChanged(SettingValue),
+    FirmwareMismatch {
+        left: String,
+        right: String,
+    },
+    SourceSwitched {
+        source: i32,
+    },
+}
+
+impl Event {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Event::Connected => "connected",
+            Event::Disconnected => "disconnected",
+            Event::BudRemoved(_) => "bud-removed",
+            Event::BudInserted(_) => "bud-inserted",
+            Event::BothRemoved => "both-removed",
+            Event::BothInserted => "both-inserted",
+            Event::BatteryChanged { .. } => "battery-changed",
+            Event::SettingChanged(_) => "setting-changed",
+            Event::FirmwareMismatch { .. } => "firmware-mismatch",
+            Event::SourceSwitched { .. } => "source-switched",
+        }
+    }
+}
+
+
+/// Check whether both buds run the same firmware version.
+pub fn firmware_mismatch(info: &SoftwareInfo) -> Option<Event> {
+    let firmware = info.firmware.as_ref()?;
+
+    if firmware.buds_match()? {
+        return None;
+    }
+
+    let left = firmware.left.as_ref()?.version_string.clone();
+    let right = firmware.right.as_ref()?.version_string.clone();
+
+    Some(Event::FirmwareMismatch { left, right })
+}
+
+
+/// Tracks the device state to turn runtime information updates into events.
+#[derive(Debug, Default)]
+pub struct Tracker {
+    placement: Option<(bool, bool)>,
+    battery: [Option<i32>; 3],
+    source: Option<i32>,
+}
+
+impl Tracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+
+    /// Whether both buds have last been reported to be in the case.
+    pub fn buds_in_case(&self) -> bool {
+        self.placement == Some((true, true))
+    }
+
+    pub fn update(&mut self, info: &RuntimeInfo) -> Vec<Event> {
+        let mut events = Vec::new();
+
+        if let Some(placement) = &info.placement {
+            let current = (placement.left_bud_in_case, placement.right_bud_in_case);
+
+            // only report transitions, not the initial state
+            if let Some(previous) = self.placement.replace(current) {
+                placement_events(previous, current, &mut events);
+            }
+        }
+
+        if let Some(battery) = &info.battery_info {
+            let levels = [
+                (Component::Case, battery.case.as_ref()),
+                (Component::Left, battery.left.as_ref()),
+                (Component::Right, battery.right.as_ref()),
+            ];
+
+            for (i, (component, info)) in levels.into_iter().enumerate() {
+                let level = info.map(|b: &DeviceBatteryInfo| b.level);
+                let previous = std::mem::replace(&mut self.battery[i], level);
+
+                if level != previous {
+                    events.push(Event::BatteryChanged { component, level, previous });
+                }
+            }
+        }
+
+        events
+    }
+
+    /// Update the active audio source, as reported by the multipoint quiet
+    /// mode status.
+    pub fn update_source(&mut self, source: i32) -> Option<Event> {
+        // only report transitions, not the initial state
+        match self.source.replace(source) {
+            Some(previous) if previous != source => Some(Event::SourceSwitched { source }),
+            _ => None,
+        }
+    }
+}
+
+fn placement_events(previous: (bool, bool), current: (bool, bool), events: &mut Vec<Event>) {
+    let buds = [
+        (Bud::Left, previous.0, current.0),
+        (Bud::Right, previous.1, current.1),
+    ];
+
+    for (bud, was_in_case, in_case) in buds {
+        match (was_in_case, in_case) {
+            (true, false) => events.push(Event::BudRemoved(bud)),
+            (false, true) => events.push(Event::BudInserted(bud)),
+            _ => {},
+        }
+    }
+
+    if previous != current {
+        match current {
+            (false, false) => events.push(Event::BothRemoved),
+            (true, true) => events.push(Event::BothInserted),
+            _ => {},
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use maestro::protocol::types::PlacementInfo;
+
+    fn placement(left_bud_in_case: bool, right_bud_in_case: bool) -> RuntimeInfo {
+        RuntimeInfo {
+            placement: Some(PlacementInfo { left_bud_in_case, right_bud_in_case }),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_placement_events() {
+        let mut tracker = Tracker::new();
+
+        assert_eq!(tracker.update(&placement(true, true)), vec![]);
+        assert!(tracker.buds_in_case());
+        assert_eq!(tracker.update(&placement(false, true)), vec![Event::BudRemoved(Bud::Left)]);
+        assert_eq!(tracker.update(&placement(false, false)), vec![
+            Event::BudRemoved(Bud::Right),
+            Event::BothRemoved,
+        ]);
+        assert_eq!(tracker.update(&placement(false, false)), vec![]);
+        assert_eq!(tracker.update(&placement(true, true)), vec![
+            Event::BudInserted(Bud::Left),
+            Event::BudInserted(Bud::Right),
+            Event::BothInserted,
+        ]);
+    }
+
+    #[test]
+    fn test_source_events() {
+        let mut tracker = Tracker::new();
+
+        assert_eq!(tracker.update_source(1), None);
+        assert_eq!(tracker.update_source(1), None);
+        assert_eq!(tracker.update_source(2), Some(Event::SourceSwitched { source: 2 }));
+
+        tracker.reset();
+        assert_eq!(tracker.update_source(1), None);
+    }
+}
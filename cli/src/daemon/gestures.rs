@@ -0,0 +1,261 @@
+//! Remapping of touch gestures.
+//!
+//! When enabled via the `[gestures]` section of the daemon configuration
+//! file, the daemon intercepts touch gestures by enabling the OOBE mode of the
+//! device and performs the configured actions instead:
+//!
+//! ```toml
+//! [gestures]
+//! single-tap = "play-pause"
+//! double-tap = "next"
+//! triple-tap = "previous"
+//! hold = "anc-cycle"
+//! ```
+//!
+//! Supported gestures are `single-tap`, `double-tap`, `triple-tap`, `hold`,
+//! `swipe-forward`, `swipe-backward`, `swipe-up`, and `swipe-down`. Supported
+//! actions are `play-pause`, `next`, `previous` (via MPRIS), `anc-cycle`,
+//! `anc-active`, `anc-aware`, and `anc-off`.
+//!
+//! Note that while intercepted, the buds do not perform any of their built-in
+//! gesture actions, so gestures not listed do nothing. Normal gesture handling
+//! is restored when the daemon exits.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::Result;
+
+use dbus::nonblock::SyncConnection;
+
+use maestro::protocol::types::OobeAction;
+use maestro::service::MaestroService;
+use maestro::service::settings::{self, AncState, SettingValue};
+
+use super::media;
+
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Gesture {
+    SingleTap,
+    DoubleTap,
+    TripleTap,
+    Hold,
+    SwipeForward,
+    SwipeBackward,
+    SwipeUp,
+    SwipeDown,
+}
+
+impl Gesture {
+    fn parse(name: &str) -> Option<Self> {
+        match name {
+            "single-tap" => Some(Self::SingleTap),
+            "double-tap" => Some(Self::DoubleTap),
+            "triple-tap" => Some(Self::TripleTap),
+            "hold" => Some(Self::Hold),
+            "swipe-forward" => Some(Self::SwipeForward),
+            "swipe-backward" => Some(Self::SwipeBackward),
+            "swipe-up" => Some(Self::SwipeUp),
+            "swipe-down" => Some(Self::SwipeDown),
+            _ => None,
+        }
+    }
+
+    /// Map the action reported by the device to a gesture. Returns `None` for
+    /// actions that are not gestures, e.g. on-head detection events.
+    pub fn from_action(action: OobeAction) -> Option<Self> {
+        match action {
+            OobeAction::SingleTap => Some(Self::SingleTap),
+            OobeAction::DoubleTap => Some(Self::DoubleTap),
+            OobeAction::TripleTap => Some(Self::TripleTap),
+            OobeAction::Hold => Some(Self::Hold),
+            OobeAction::SwipeForward => Some(Self::SwipeForward),
+            OobeAction::SwipeBackward => Some(Self::SwipeBackward),
+            OobeAction::SwipeUp => Some(Self::SwipeUp),
+            OobeAction::SwipeDown => Some(Self::SwipeDown),
+            _ => None,
+        }
+    }
+}
+
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    PlayPause,
+    Next,
+    Previous,
+    AncCycle,
+    Anc(AncState),
+}
+
+impl Action {
+    fn parse(name: &str) -> Option<Self> {
+        match name {
+            "play-pause" => Some(Self::PlayPause),
+            "next" => Some(Self::Next),
+            "previous" => Some(Self::Previous),
+            "anc-cycle" => Some(Self::AncCycle),
+            "anc-active" => Some(Self::Anc(AncState::Active)),
+            "anc-aware" => Some(Self::Anc(AncState::Aware)),
+            "anc-off" => Some(Self::Anc(AncState::Off)),
+            _ => None,
+        }
+    }
+}
+
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct GestureConfig {
+    pub actions: Vec<(Gesture, Action)>,
+}
+
+impl GestureConfig {
+    pub fn parse(table: &dyn toml_edit::TableLike) -> Result<Self> {
+        let mut config = Self::default();
+
+        for (key, item) in table.iter() {
+            let gesture = Gesture::parse(key)
+                .ok_or_else(|| anyhow::anyhow!("unknown gesture '{key}'"))?;
+
+            let action = item.as_str()
+                .and_then(Action::parse)
+                .ok_or_else(|| anyhow::anyhow!("invalid action for '{key}'"))?;
+
+            config.actions.push((gesture, action));
+        }
+
+        Ok(config)
+    }
+
+    pub fn action(&self, gesture: Gesture) -> Option<Action> {
+        self.actions.iter()
+            .find(|(g, _)| *g == gesture)
+            .map(|(_, a)| *a)
+    }
+}
+
+
+pub struct Gestures {
+    config: GestureConfig,
+    conn: Arc<SyncConnection>,
+
+    /// Whether gestures have been intercepted on the device.
+    pub intercepted: bool,
+}
+
+impl Gestures {
+    pub fn new(config: GestureConfig, conn: Arc<SyncConnection>) -> Self {
+        Self { config, conn, intercepted: false }
+    }
+
+    /// Perform the action configured for the given action reported by the
+    /// device, if any.
+    pub async fn handle(&self, service: &mut MaestroService, action: OobeAction) {
+        let Some(gesture) = Gesture::from_action(action) else {
+            return;
+        };
+
+        let Some(action) = self.config.action(gesture) else {
+            tracing::debug!(?gesture, "no action configured for gesture");
+            return;
+        };
+
+        tracing::debug!(?gesture, ?action, "performing gesture action");
+
+        let method = match action {
+            Action::PlayPause => "PlayPause",
+            Action::Next => "Next",
+            Action::Previous => "Previous",
+            Action::AncCycle => {
+                if let Err(err) = anc_cycle(service).await {
+                    tracing::warn!(error=?err, "failed to cycle ANC state");
+                }
+                return;
+            },
+            Action::Anc(state) => {
+                if let Err(err) = service.write_setting(SettingValue::CurrentAncrState(state)).await {
+                    tracing::warn!(error=%err, "failed to set ANC state");
+                }
+                return;
+            },
+        };
+
+        let conn = self.conn.clone();
+
+        tokio::spawn(async move {
+            if let Err(err) = media::command(&conn, method).await {
+                tracing::warn!(error=?err, method, "failed to control media player");
+            }
+        });
+    }
+}
+
+async fn anc_cycle(service: &mut MaestroService) -> Result<()> {
+    let enabled = service.read_setting(settings::id::AncrGestureLoop).await?;
+    let state = service.read_setting(settings::id::CurrentAncrState).await?;
+
+    if let Some(next) = crate::anc_cycle_next(enabled, state, true)? {
+        service.write_setting(SettingValue::CurrentAncrState(next)).await?;
+    }
+
+    Ok(())
+}
+
+/// Restore normal gesture handling on a new connection, e.g. when the daemon
+/// exits.
+pub async fn release<T: crate::transport::Transport>(transport: &T) -> Result<()> {
+    const TIMEOUT: Duration = Duration::from_secs(10);
+
+    tokio::time::timeout(TIMEOUT, release_inner(transport)).await
+        .map_err(|_| anyhow::anyhow!("timed out restoring gesture handling"))?
+}
+
+async fn release_inner<T: crate::transport::Transport>(transport: &T) -> Result<()> {
+    use maestro::protocol::codec::Codec;
+    use maestro::protocol::utils;
+    use maestro::pwrpc::client::Client;
+
+    let stream = transport.connect().await?;
+    let mut client = Client::new(Codec::new().wrap(stream));
+    let channel = utils::resolve_channel(&mut client).await?;
+
+    let mut service = MaestroService::new(client.handle(), channel);
+
+    tokio::select! {
+        res = client.run() => res?,
+        res = service.release_gestures() => res?,
+    }
+
+    client.terminate().await?;
+    Ok(())
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::daemon::config::Config;
+
+    #[test]
+    fn test_parse_config() {
+        assert_eq!(Config::parse("").unwrap().gestures, None);
+
+        let config = Config::parse("[gestures]\ndouble-tap = \"next\"\nhold = \"anc-off\"\n").unwrap();
+        let config = config.gestures.unwrap();
+
+        assert_eq!(config.action(Gesture::DoubleTap), Some(Action::Next));
+        assert_eq!(config.action(Gesture::Hold), Some(Action::Anc(AncState::Off)));
+        assert_eq!(config.action(Gesture::SingleTap), None);
+
+        assert!(Config::parse("[gestures]\nwiggle = \"next\"\n").is_err());
+        assert!(Config::parse("[gestures]\nhold = \"explode\"\n").is_err());
+    }
+
+    #[test]
+    fn test_from_action() {
+        assert_eq!(Gesture::from_action(OobeAction::SwipeUp), Some(Gesture::SwipeUp));
+        assert_eq!(Gesture::from_action(OobeAction::LeftOnHead), None);
+    }
+}
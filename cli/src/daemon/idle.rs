@@ -0,0 +1,106 @@
+//! Inactivity tracking for idle-exit.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use tokio::time::Instant;
+
+
+#[derive(Debug)]
+struct Inner {
+    clients: AtomicUsize,
+    last: Mutex<Instant>,
+}
+
+
+#[derive(Debug, Clone)]
+pub struct Activity {
+    inner: Arc<Inner>,
+}
+
+impl Activity {
+    pub fn new() -> Self {
+        let inner = Inner {
+            clients: AtomicUsize::new(0),
+            last: Mutex::new(Instant::now()),
+        };
+
+        Self { inner: Arc::new(inner) }
+    }
+
+    /// Record activity, resetting the idle timer.
+    pub fn touch(&self) {
+        *self.inner.last.lock().unwrap() = Instant::now();
+    }
+
+    /// Register a connected client. The daemon is not considered idle while
+    /// the returned guard is alive.
+    pub fn client(&self) -> ClientGuard {
+        self.inner.clients.fetch_add(1, Ordering::SeqCst);
+        self.touch();
+
+        ClientGuard { activity: self.clone() }
+    }
+
+    /// Wait until there has been no activity and no connected client for the
+    /// given duration.
+    pub async fn wait_idle(&self, timeout: Duration) {
+        loop {
+            let deadline = *self.inner.last.lock().unwrap() + timeout;
+            tokio::time::sleep_until(deadline).await;
+
+            let last = *self.inner.last.lock().unwrap();
+            let clients = self.inner.clients.load(Ordering::SeqCst);
+
+            if clients == 0 && last.elapsed() >= timeout {
+                return;
+            }
+
+            if clients > 0 {
+                // re-check after another full timeout period
+                self.touch();
+            }
+        }
+    }
+}
+
+
+pub struct ClientGuard {
+    activity: Activity,
+}
+
+impl Drop for ClientGuard {
+    fn drop(&mut self) {
+        self.activity.inner.clients.fetch_sub(1, Ordering::SeqCst);
+        self.activity.touch();
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[tokio::test(start_paused = true)]
+    async fn test_wait_idle() {
+        let timeout = Duration::from_secs(60);
+        let activity = Activity::new();
+
+        let start = Instant::now();
+        let guard = activity.client();
+
+        let waiter = {
+            let activity = activity.clone();
+            tokio::spawn(async move { activity.wait_idle(timeout).await })
+        };
+
+        tokio::time::sleep(Duration::from_secs(90)).await;
+        assert!(!waiter.is_finished());
+
+        drop(guard);
+        waiter.await.unwrap();
+
+        assert!(start.elapsed() >= Duration::from_secs(150));
+    }
+}
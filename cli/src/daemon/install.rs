@@ -0,0 +1,107 @@
+//! Installation of systemd user units for socket activation.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+
+use bluer::Address;
+
+
+const SERVICE_NAME: &str = "pbpctrl.service";
+const SOCKET_NAME: &str = "pbpctrl.socket";
+
+
+/// Directory for systemd user units, i.e., `$XDG_CONFIG_HOME/systemd/user`.
+fn unit_dir() -> Result<PathBuf> {
+    let base = std::env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+        .ok_or_else(|| anyhow::anyhow!("cannot determine configuration directory"))?;
+
+    Ok(base.join("systemd").join("user"))
+}
+
+pub fn service_unit(exe: &Path, device: Option<Address>) -> String {
+    let device = device.map(|addr| format!(" --device {addr}")).unwrap_or_default();
+
+    format!(
+        "[Unit]\n\
+         Description=Google Pixel Buds Pro control daemon\n\
+         Requires={SOCKET_NAME}\n\
+         After={SOCKET_NAME}\n\
+         \n\
+         [Service]\n\
+         ExecStart={}{device} daemon\n\
+         Restart=on-failure\n\
+         \n\
+         [Install]\n\
+         Also={SOCKET_NAME}\n",
+        exe.display(),
+    )
+}
+
+pub fn socket_unit() -> String {
+    format!(
+        "[Unit]\n\
+         Description=Google Pixel Buds Pro control daemon socket\n\
+         \n\
+         [Socket]\n\
+         ListenStream=%t/pbpctrl.sock\n\
+         SocketMode=0600\n\
+         Service={SERVICE_NAME}\n\
+         \n\
+         [Install]\n\
+         WantedBy=sockets.target\n"
+    )
+}
+
+pub fn install(device: Option<Address>, force: bool) -> Result<()> {
+    let exe = std::env::current_exe()
+        .context("failed to determine executable path")?;
+
+    let dir = unit_dir()?;
+    std::fs::create_dir_all(&dir)
+        .with_context(|| format!("failed to create '{}'", dir.display()))?;
+
+    let units = [
+        (dir.join(SERVICE_NAME), service_unit(&exe, device)),
+        (dir.join(SOCKET_NAME), socket_unit()),
+    ];
+
+    if !force && let Some((path, _)) = units.iter().find(|(path, _)| path.exists()) {
+        anyhow::bail!("'{}' already exists, use --force to overwrite", path.display());
+    }
+
+    for (path, content) in &units {
+        std::fs::write(path, content)
+            .with_context(|| format!("failed to write '{}'", path.display()))?;
+
+        println!("written {}", path.display());
+    }
+
+    println!();
+    println!("To enable the daemon, run:");
+    println!("  systemctl --user daemon-reload");
+    println!("  systemctl --user enable --now {SOCKET_NAME}");
+
+    Ok(())
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_service_unit() {
+        let addr: Address = "01:23:45:67:89:AB".parse().unwrap();
+        let unit = service_unit(Path::new("/usr/bin/pbpctrl"), Some(addr));
+
+        assert!(unit.contains("\nExecStart=/usr/bin/pbpctrl --device 01:23:45:67:89:AB daemon\n"));
+        assert!(unit.contains("\nAlso=pbpctrl.socket\n"));
+
+        let unit = service_unit(Path::new("/usr/bin/pbpctrl"), None);
+        assert!(unit.contains("\nExecStart=/usr/bin/pbpctrl daemon\n"));
+    }
+}
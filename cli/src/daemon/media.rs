@@ -0,0 +1,191 @@
+//! Automatic pausing of media players via MPRIS.
+//!
+//! When enabled via the `[media]` section of the daemon configuration file,
+//! all playing MPRIS media players are paused when a bud is put back into the
+//! case, and resumed again when it is taken out:
+//!
+//! ```toml
+//! [media]
+//! auto-pause = true   # pause playback when a bud is put into the case
+//! resume = true       # resume playback paused by us when the bud is taken out
+//! ```
+//!
+//! Note that the device only reports whether buds are in the case, not
+//! whether they are worn, so removing a bud from the ear alone does not pause
+//! playback.
+
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use anyhow::Result;
+
+use dbus::nonblock::stdintf::org_freedesktop_dbus::Properties;
+use dbus::nonblock::{Proxy, SyncConnection};
+
+use super::event::Event;
+
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+const MPRIS_PREFIX: &str = "org.mpris.MediaPlayer2.";
+const MPRIS_PATH: &str = "/org/mpris/MediaPlayer2";
+const MPRIS_PLAYER: &str = "org.mpris.MediaPlayer2.Player";
+
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MediaConfig {
+    pub auto_pause: bool,
+    pub resume: bool,
+}
+
+impl MediaConfig {
+    pub fn parse(table: &dyn toml_edit::TableLike) -> Result<Self> {
+        let mut config = Self { auto_pause: true, resume: true };
+
+        for (key, item) in table.iter() {
+            let value = item.as_bool()
+                .ok_or_else(|| anyhow::anyhow!("'{key}' must be a boolean"))?;
+
+            match key {
+                "auto-pause" => config.auto_pause = value,
+                "resume" => config.resume = value,
+                _ => anyhow::bail!("unknown key '{key}'"),
+            }
+        }
+
+        Ok(config)
+    }
+}
+
+
+pub struct Media {
+    config: MediaConfig,
+    conn: Arc<SyncConnection>,
+    paused: Arc<Mutex<Vec<String>>>,
+}
+
+impl Media {
+    pub fn new(config: MediaConfig, conn: Arc<SyncConnection>) -> Self {
+        Self { config, conn, paused: Arc::new(Mutex::new(Vec::new())) }
+    }
+
+    pub fn handle(&self, event: &Event) {
+        match event {
+            Event::BudInserted(_) if self.config.auto_pause => {
+                let conn = self.conn.clone();
+                let paused = self.paused.clone();
+
+                tokio::spawn(async move {
+                    if let Err(err) = pause_all(&conn, &paused).await {
+                        tracing::warn!(error=?err, "failed to pause media players");
+                    }
+                });
+            },
+            Event::BudRemoved(_) if self.config.resume => {
+                let players = std::mem::take(&mut *self.paused.lock().unwrap());
+                if players.is_empty() {
+                    return;
+                }
+
+                let conn = self.conn.clone();
+
+                tokio::spawn(async move {
+                    for player in players {
+                        tracing::debug!(player, "resuming media player");
+
+                        if let Err(err) = call(&conn, &player, "Play").await {
+                            tracing::warn!(error=%err, player, "failed to resume media player");
+                        }
+                    }
+                });
+            },
+            _ => {},
+        }
+    }
+}
+
+async fn pause_all(conn: &Arc<SyncConnection>, paused: &Mutex<Vec<String>>) -> Result<()> {
+    let bus = Proxy::new("org.freedesktop.DBus", "/", TIMEOUT, conn.clone());
+    let (names,): (Vec<String>,) = bus.method_call("org.freedesktop.DBus", "ListNames", ()).await?;
+
+    for name in names.into_iter().filter(|n| n.starts_with(MPRIS_PREFIX)) {
+        let proxy = Proxy::new(name.as_str(), MPRIS_PATH, TIMEOUT, conn.clone());
+
+        let status: String = match proxy.get(MPRIS_PLAYER, "PlaybackStatus").await {
+            Ok(status) => status,
+            Err(err) => {
+                tracing::debug!(error=%err, player=name, "failed to get playback status");
+                continue;
+            },
+        };
+
+        if status != "Playing" {
+            continue;
+        }
+
+        tracing::debug!(player=name, "pausing media player");
+
+        match call(conn, &name, "Pause").await {
+            Ok(()) => paused.lock().unwrap().push(name),
+            Err(err) => tracing::warn!(error=%err, player=name, "failed to pause media player"),
+        }
+    }
+
+    Ok(())
+}
+
+/// Send a command, e.g. `PlayPause`, to the first playing media player, or to
+/// the first media player if none is playing.
+pub async fn command(conn: &Arc<SyncConnection>, method: &str) -> Result<()> {
+    let bus = Proxy::new("org.freedesktop.DBus", "/", TIMEOUT, conn.clone());
+    let (names,): (Vec<String>,) = bus.method_call("org.freedesktop.DBus", "ListNames", ()).await?;
+
+    let players: Vec<_> = names.into_iter()
+        .filter(|n| n.starts_with(MPRIS_PREFIX))
+        .collect();
+
+    let mut target = None;
+    for name in &players {
+        let proxy = Proxy::new(name.as_str(), MPRIS_PATH, TIMEOUT, conn.clone());
+
+        if let Ok(status) = proxy.get::<String>(MPRIS_PLAYER, "PlaybackStatus").await
+            && status == "Playing"
+        {
+            target = Some(name);
+            break;
+        }
+    }
+
+    let Some(player) = target.or(players.first()) else {
+        tracing::debug!(method, "no media player to control");
+        return Ok(());
+    };
+
+    tracing::debug!(player, method, "controlling media player");
+    call(conn, player, method).await?;
+
+    Ok(())
+}
+
+async fn call(conn: &Arc<SyncConnection>, player: &str, method: &str) -> Result<(), dbus::Error> {
+    let proxy = Proxy::new(player, MPRIS_PATH, TIMEOUT, conn.clone());
+    proxy.method_call::<(), _, _, _>(MPRIS_PLAYER, method, ()).await
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::daemon::config::Config;
+
+    #[test]
+    fn test_parse_config() {
+        assert_eq!(Config::parse("").unwrap().media, None);
+
+        let config = Config::parse("[media]\nresume = false\n").unwrap();
+        assert_eq!(config.media, Some(MediaConfig { auto_pause: true, resume: false }));
+
+        assert!(Config::parse("[media]\nauto-pause = 1\n").is_err());
+    }
+}
@@ -0,0 +1,863 @@
+//! Daemon mode: Keep a persistent connection to the device and provide access
+//! to it via D-Bus.
+
+pub mod audit;
+pub mod battery;
+pub mod client;
+pub mod config;
+pub mod dosimeter;
+pub mod event;
+pub mod gestures;
+pub mod idle;
+pub mod install;
+pub mod media;
+pub mod notify;
+pub mod ring;
+pub mod rules;
+pub mod server;
+pub mod socket;
+pub mod state;
+pub mod value;
+
+use std::path::Path;
+use std::time::{Duration, Instant, SystemTime};
+
+use anyhow::Result;
+
+use bluer::Address;
+
+use futures::{Future, Stream, StreamExt};
+use futures::channel::mpsc;
+
+use tokio::sync::broadcast;
+
+use maestro::protocol::codec::Codec;
+use maestro::protocol::types::{settings_rsp, RuntimeInfo, SoftwareInfo};
+use maestro::protocol::utils;
+use maestro::pwrpc::client::{Client, ClientHandle, Event as ClientEvent};
+use maestro::retry::Backoff;
+use maestro::service::{DosimeterService, MaestroService, MultipointService};
+use maestro::service::debounce::Debouncer;
+use maestro::service::settings::SettingValue;
+
+use crate::cli::ConnectMode;
+use crate::lock::InstanceLock;
+use crate::transport::{self, Transport};
+
+use audit::Log;
+use battery::BatteryProvider;
+use config::Config;
+use dosimeter::{Recorder, Store};
+use event::{Event, Tracker};
+use gestures::Gestures;
+use idle::Activity;
+use media::Media;
+use notify::{Notifications, Notifier};
+use ring::Ringer;
+use rules::Engine;
+use server::{Request, Server};
+use socket::{Context, SocketServer};
+use state::SharedState;
+
+
+/// Time to wait before reconnecting after the connection has been reset.
+const RECONNECT_DELAY: Duration = Duration::from_secs(1);
+
+/// Time to wait before trying again after watching the Bluetooth connection
+/// failed.
+const RETRY_DELAY: Duration = Duration::from_secs(10);
+
+/// Default idle timeout when started via socket activation.
+const DEFAULT_IDLE_TIMEOUT: Duration = Duration::from_secs(300);
+
+
+/// Consumers of device state updates.
+struct Handlers {
+    server: Server,
+    battery: Option<BatteryProvider>,
+    rules: Engine,
+    notifications: Notifications,
+    dosimeter: Option<Recorder>,
+    media: Option<Media>,
+    gestures: Option<Gestures>,
+    events: broadcast::Sender<Event>,
+    tracker: Tracker,
+    debounce: Option<Debouncer>,
+    audit: Option<Log>,
+    activity: Activity,
+    requests: mpsc::UnboundedSender<Request>,
+    connected: bool,
+    read_only: bool,
+}
+
+impl Handlers {
+    fn set_connected(&mut self, connected: bool) {
+        self.server.set_connected(connected);
+
+        if !connected && let Some(battery) = &self.battery {
+            battery.update(None);
+        }
+
+        if !connected && let Some(dosimeter) = &mut self.dosimeter {
+            dosimeter.flush();
+        }
+
+        if self.connected != connected {
+            self.connected = connected;
+            self.tracker.reset();
+
+            if let Some(debounce) = &mut self.debounce {
+                debounce.reset();
+            }
+
+            self.dispatch(if connected { Event::Connected } else { Event::Disconnected });
+        }
+    }
+
+    /// Reply for a failed request, noting if both buds are in the case, in
+    /// which case most requests fail.
+    fn error_str(&self, err: impl std::fmt::Display) -> String {
+        if self.tracker.buds_in_case() {
+            format!("buds are in the case; limited data available: {err}")
+        } else {
+            err.to_string()
+        }
+    }
+
+    fn update_runtime_info(&mut self, info: &RuntimeInfo) {
+        self.server.update_runtime_info(info);
+
+        if let Some(battery) = &self.battery
+            && let Some(info) = &info.battery_info
+        {
+            battery.update(battery::level(info));
+        }
+
+        for event in self.tracker.update(info) {
+            self.dispatch(event);
+        }
+    }
+
+    fn setting_changed(&mut self, value: SettingValue) {
+        self.server.setting_changed(&value);
+
+        if let Some(audit) = &mut self.audit {
+            audit.observe(&value);
+        }
+
+        match &mut self.debounce {
+            Some(debounce) => debounce.push(value, Instant::now()),
+            None => self.dispatch(Event::SettingChanged(value)),
+        }
+    }
+
+    fn debounce_deadline(&self) -> Option<Instant> {
+        self.debounce.as_ref().and_then(Debouncer::deadline)
+    }
+
+    fn flush_settings(&mut self) {
+        let Some(debounce) = &mut self.debounce else { return };
+
+        for value in debounce.poll(Instant::now()) {
+            self.dispatch(Event::SettingChanged(value));
+        }
+    }
+
+    fn software_info(&mut self, info: &SoftwareInfo) {
+        if let Some(event) = event::firmware_mismatch(info) {
+            self.dispatch(event);
+        }
+    }
+
+    fn source_changed(&mut self, source: i32) {
+        if let Some(event) = self.tracker.update_source(source) {
+            self.dispatch(event);
+        }
+    }
+
+    fn audit(&mut self, value: &SettingValue, old: Option<&SettingValue>) {
+        if let Some(audit) = &mut self.audit {
+            audit.record("daemon", value, old);
+        }
+    }
+
+    fn dispatch(&mut self, event: Event) {
+        tracing::trace!(?event, "dispatching event");
+        self.rules.handle(&event);
+        self.notifications.handle(&event);
+
+        if let Some(media) = &self.media {
+            media.handle(&event);
+        }
+
+        // no receivers is fine, sending only fails in that case
+        let _ = self.events.send(event);
+    }
+}
+
+
+/// Start a daemon for the given device in the background, exiting after
+/// being idle for the given number of seconds.
+///
+/// The daemon waits for the connection lock held by the calling instance and
+/// takes over once it has been released.
+pub fn spawn(address: Address, adapter: &str, mode: ConnectMode, idle_timeout: u64, read_only: bool) -> Result<()> {
+    use std::os::unix::process::CommandExt;
+    use std::process::{Command, Stdio};
+
+    use clap::ValueEnum;
+
+    let mode = mode.to_possible_value()
+        .expect("connect modes are not skipped");
+
+    let mut command = Command::new(std::env::current_exe()?);
+
+    if read_only {
+        command.arg("--read-only");
+    }
+
+    let mut child = command
+        .arg("daemon")
+        .args(["--idle-timeout", &idle_timeout.to_string()])
+        .args(["--device", &address.to_string()])
+        .args(["--adapter", adapter])
+        .args(["--connect-mode", mode.get_name()])
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        // don't receive signals meant for the calling instance, e.g. Ctrl-C
+        .process_group(0)
+        .spawn()?;
+
+    tracing::debug!(pid=child.id(), timeout=idle_timeout, "started background daemon");
+
+    // reap the daemon if it exits before we do, e.g. because another daemon
+    // has grabbed the D-Bus name in the meantime
+    std::thread::spawn(move || child.wait());
+
+    Ok(())
+}
+
+pub async fn run(
+    address: Option<Address>,
+    adapter: Option<&str>,
+    mode: ConnectMode,
+    config: Option<&Path>,
+    socket: Option<&Path>,
+    idle_timeout: Option<u64>,
+    read_only: bool,
+) -> Result<()> {
+    let transport = transport::Platform::open(address, adapter).await?
+        .with_connect_mode(mode);
+    let address = transport.address();
+
+    let config = Config::load(config, Some(transport.identity()))?;
+    let read_only = read_only || config.read_only;
+
+    let (conn_resource, conn) = tokio::task::spawn_blocking(dbus_tokio::connection::new_session_sync).await??;
+
+    tokio::spawn(async move {
+        let err = conn_resource.await;
+        tracing::error!(error=%err, "lost connection to D-Bus session bus");
+    });
+
+    let state = SharedState::new();
+    let (events_tx, _) = broadcast::channel(64);
+
+    let activity = Activity::new();
+    let ringer = Ringer::new(transport.clone(), activity.clone());
+
+    // hold the connection lock, so that other instances route their commands
+    // through us instead of competing for the connection. Acquire it before
+    // registering on D-Bus, so that commands are not routed to us while
+    // another instance still holds the connection.
+    let _lock = match InstanceLock::default_path() {
+        Some(path) => match InstanceLock::try_acquire(&path)? {
+            Some(lock) => Some(lock),
+            None => {
+                tracing::info!(path=%path.display(), "connection locked by another instance, waiting");
+                InstanceLock::acquire(&path, None).await?
+            },
+        },
+        None => {
+            tracing::debug!("no runtime directory, not locking connection");
+            None
+        },
+    };
+
+    let (requests_tx, mut requests_rx) = mpsc::unbounded();
+    let server = Server::new(conn.clone(), address, state.clone(), requests_tx.clone(), ringer).await?;
+    let notifier = Notifier::new(conn.clone());
+
+    let battery = match BatteryProvider::new(&transport.identity().adapter, address).await {
+        Ok(battery) => Some(battery),
+        Err(err) => {
+            tracing::warn!(error=?err, "failed to register battery provider");
+            None
+        },
+    };
+
+    let ctx = Context {
+        state,
+        requests: requests_tx.clone(),
+        events: events_tx.clone(),
+        activity: activity.clone(),
+    };
+
+    let (socket, activated) = match setup_socket(socket, ctx) {
+        Ok(Some((socket, activated))) => (Some(socket), activated),
+        Ok(None) => {
+            tracing::debug!("no runtime directory, not setting up control socket");
+            (None, false)
+        },
+        Err(err) => {
+            tracing::warn!(error=?err, "failed to set up control socket");
+            (None, false)
+        },
+    };
+
+    // exit on inactivity only if we can be started again on demand, unless
+    // explicitly requested
+    let idle_timeout = match idle_timeout {
+        Some(0) => None,
+        Some(secs) => Some(Duration::from_secs(secs)),
+        None if activated => Some(DEFAULT_IDLE_TIMEOUT),
+        None => None,
+    };
+
+    let socket_task = socket.map(|socket| tokio::spawn(socket.run()));
+
+    tracing::debug!(rules=config.rules.len(), "loaded rules");
+    let rules = Engine::new(config.rules, requests_tx.clone(), notifier.clone());
+    let notifications = Notifications::new(config.notifications, notifier);
+
+    let dosimeter = match (config.dosimeter, Store::default_path()) {
+        (Some(config), Some(path)) => {
+            tracing::debug!(path=%path.display(), "recording dosimeter history");
+            Some(Recorder::new(config, Store::new(path)))
+        },
+        (Some(_), None) => {
+            tracing::warn!("no data directory, not recording dosimeter history");
+            None
+        },
+        (None, _) => None,
+    };
+
+    let gestures = config.gestures.map(|config| Gestures::new(config, conn.clone()));
+    let media = config.media.map(|config| Media::new(config, conn));
+
+    let audit = match (config.audit_log, Log::default_path()) {
+        (true, Some(path)) => {
+            tracing::debug!(path=%path.display(), "recording settings writes");
+            Some(Log::new(path))
+        },
+        (true, None) => {
+            tracing::warn!("no state directory, not recording settings writes");
+            None
+        },
+        (false, _) => None,
+    };
+
+    let reconnect = config.reconnect.unwrap_or(config::DEFAULT_RECONNECT);
+
+    let mut handlers = Handlers {
+        server,
+        battery,
+        rules,
+        notifications,
+        dosimeter,
+        media,
+        gestures,
+        events: events_tx,
+        tracker: Tracker::new(),
+        debounce: config.debounce.map(Debouncer::new),
+        audit,
+        activity: activity.clone(),
+        requests: requests_tx,
+        connected: false,
+        read_only,
+    };
+
+    tracing::info!(device=%transport.identity(), read_only, "daemon running");
+
+    let result = tokio::select! {
+        res = connection_loop(&transport, reconnect, &mut handlers, &mut requests_rx) => res,
+        _ = wait_idle(&activity, idle_timeout) => {
+            tracing::info!("idle timeout reached, exiting");
+            Ok(())
+        },
+    };
+
+    // the OOBE mode persists on the device, so make sure to restore normal
+    // gesture handling
+    if handlers.gestures.as_ref().is_some_and(|g| g.intercepted) {
+        tracing::debug!("restoring gesture handling");
+
+        if let Err(err) = gestures::release(&transport).await {
+            tracing::warn!(error=?err, "failed to restore gesture handling");
+        }
+    }
+
+    // stop the socket server and remove the socket file
+    if let Some(task) = socket_task {
+        task.abort();
+        let _ = task.await;
+    }
+
+    result
+}
+
+fn setup_socket(path: Option<&Path>, ctx: Context) -> Result<Option<(SocketServer, bool)>> {
+    if std::env::var_os("LISTEN_FDS").is_some() {
+        if let Some(socket) = SocketServer::from_systemd(ctx)? {
+            return Ok(Some((socket, true)));
+        }
+
+        anyhow::bail!("no usable socket passed via socket activation");
+    }
+
+    let Some(path) = path.map(Path::to_owned).or_else(socket::default_path) else {
+        return Ok(None);
+    };
+
+    let socket = SocketServer::bind(&path, ctx)
+        .map_err(|e| e.context(format!("failed to bind to '{}'", path.display())))?;
+
+    Ok(Some((socket, false)))
+}
+
+async fn wait_idle(activity: &Activity, timeout: Option<Duration>) {
+    match timeout {
+        Some(timeout) => activity.wait_idle(timeout).await,
+        None => std::future::pending().await,
+    }
+}
+
+async fn connection_loop(
+    transport: &transport::Platform,
+    reconnect: Backoff,
+    handlers: &mut Handlers,
+    requests_rx: &mut mpsc::UnboundedReceiver<Request>,
+) -> Result<()> {
+    let mut retries = reconnect.delays();
+
+    loop {
+        // Wait for the Bluetooth connection instead of repeatedly trying to
+        // connect to the Maestro service of an absent device. Assume that the
+        // device is connected if we cannot tell.
+        if !transport.is_connected().await.unwrap_or(true) {
+            tracing::info!("device not connected via bluetooth, waiting for connection");
+
+            let connected = async {
+                if let Err(err) = transport.wait_connected().await {
+                    tracing::warn!(error=%err, "failed to watch bluetooth connection");
+                    tokio::time::sleep(RETRY_DELAY).await;
+                }
+            };
+
+            if !wait_rejecting(connected, handlers, requests_rx, "device not connected via bluetooth").await? {
+                return Ok(());
+            }
+        }
+
+        let result = tokio::select! {
+            res = serve(transport, handlers, requests_rx) => res,
+            sig = tokio::signal::ctrl_c() => {
+                sig?;
+                tracing::trace!("daemon termination requested");
+                break;
+            },
+        };
+
+        handlers.set_connected(false);
+
+        // back off on repeated failures, start over once the connection has
+        // been lost for other reasons
+        let delay = match result {
+            Ok(()) => {
+                tracing::info!("device disconnected");
+                retries = reconnect.delays();
+                RECONNECT_DELAY
+            },
+            Err(err) if is_connection_reset(&err) => {
+                tracing::info!("connection reset");
+                retries = reconnect.delays();
+                RECONNECT_DELAY
+            },
+            Err(err) if !transport.is_connected().await.unwrap_or(true) => {
+                tracing::info!(error=%err, "device not connected via bluetooth");
+                retries = reconnect.delays();
+                RECONNECT_DELAY
+            },
+            Err(err) => {
+                tracing::warn!(error=?err, "connection failed, maestro service unresponsive");
+
+                let Some(delay) = retries.next() else {
+                    anyhow::bail!("giving up after {} failed reconnect attempts", retries.retries());
+                };
+
+                delay
+            },
+        };
+
+        // reject any requests that have been queued up while connecting
+        while let Ok(Some(req)) = requests_rx.try_next() {
+            req.fail("device not connected");
+        }
+
+        let sleep = tokio::time::sleep(delay);
+
+        if !wait_rejecting(sleep, handlers, requests_rx, "device not connected").await? {
+            return Ok(());
+        }
+    }
+
+    Ok(())
+}
+
+/// Wait for the given future, rejecting any requests in the meantime. Returns
+/// `false` if the daemon should terminate instead.
+async fn wait_rejecting<F: Future<Output = ()>>(
+    wait: F,
+    handlers: &mut Handlers,
+    requests_rx: &mut mpsc::UnboundedReceiver<Request>,
+    message: &str,
+) -> Result<bool> {
+    tokio::pin!(wait);
+
+    loop {
+        tokio::select! {
+            _ = &mut wait => return Ok(true),
+            req = requests_rx.next() => if let Some(req) = req {
+                handlers.activity.touch();
+                req.fail(message);
+            },
+            sig = tokio::signal::ctrl_c() => {
+                sig?;
+                tracing::trace!("daemon termination requested");
+                return Ok(false);
+            },
+        }
+    }
+}
+
+#[tracing::instrument(level = "debug", skip_all, fields(device = %transport.address()))]
+async fn serve(
+    transport: &transport::Platform,
+    handlers: &mut Handlers,
+    requests: &mut mpsc::UnboundedReceiver<Request>,
+) -> Result<()> {
+    tracing::debug!(address=%transport.address(), "connecting to device");
+
+    let stream = transport.connect().await?;
+
+    let codec = Codec::new();
+    let stream = codec.wrap(stream);
+
+    let mut client = Client::new(stream);
+    let handle = client.handle();
+
+    let channel = utils::resolve_channel(&mut client).await?;
+
+    let result = tokio::select! {
+        res = client.run() => {
+            res.map_err(anyhow::Error::from)
+        },
+        res = follow_device(handle, channel, handlers, requests) => {
+            res
+        },
+    };
+
+    client.terminate().await?;
+    result
+}
+
+/// Handle the device, restarting on a new channel whenever the device moves
+/// its traffic, e.g. due to the buds handing off processing.
+async fn follow_device(
+    mut handle: ClientHandle,
+    mut channel: u32,
+    handlers: &mut Handlers,
+    requests: &mut mpsc::UnboundedReceiver<Request>,
+) -> Result<()> {
+    let mut events = handle.subscribe_events()?;
+
+    loop {
+        // only restart on channel changes, other events are emitted for
+        // every call, including the ones made by handle_device itself
+        let device = handle_device(handle.clone(), channel, handlers, requests);
+        tokio::pin!(device);
+
+        channel = loop {
+            tokio::select! {
+                res = &mut device => {
+                    return res;
+                },
+                evt = events.next() => match evt {
+                    Some(ClientEvent::ChannelChanged { old, new }) => {
+                        tracing::info!(old, new, "device moved to new channel, re-subscribing");
+                        break new;
+                    },
+                    Some(_) => continue,
+                    None => return Err(anyhow::anyhow!("client event stream terminated")),
+                },
+            }
+        };
+    }
+}
+
+async fn handle_device(
+    handle: ClientHandle,
+    channel: u32,
+    handlers: &mut Handlers,
+    requests: &mut mpsc::UnboundedReceiver<Request>,
+) -> Result<()> {
+    let mut service = MaestroService::new(handle.clone(), channel);
+    let mut multipoint = MultipointService::new(handle.clone(), channel);
+    let mut dosimeter = DosimeterService::new(handle, channel);
+
+    let mut runtime = service.subscribe_to_runtime_info()?;
+    tracing::debug!(trace_id=runtime.trace_id(), "subscribed to runtime info");
+    let mut runtime = runtime.stream();
+
+    let mut changes = service.subscribe_to_settings_changes()?;
+    tracing::debug!(trace_id=changes.trace_id(), "subscribed to settings changes");
+    let mut changes = changes.stream();
+
+    let mut quiet_mode = multipoint.subscribe_to_quiet_mode_status()?;
+    tracing::debug!(trace_id=quiet_mode.trace_id(), "subscribed to quiet mode status");
+    let mut quiet_mode = Some(quiet_mode.stream());
+
+    let mut live_db = match handlers.dosimeter {
+        Some(_) => Some(dosimeter.subscribe_to_live_db()?),
+        None => None,
+    };
+    let mut live_db = live_db.as_mut().map(|rsp| rsp.stream());
+
+    tracing::info!("device connected");
+    handlers.set_connected(true);
+
+    let mut interception = match &mut handlers.gestures {
+        Some(gestures) => match service.intercept_gestures().await {
+            Ok(interception) => {
+                gestures.intercepted = true;
+                Some(interception)
+            },
+            Err(err) => {
+                tracing::warn!(error=%err, "failed to intercept gestures");
+                None
+            },
+        },
+        None => None,
+    };
+    let mut gesture_events = interception.as_mut().map(|i| Box::pin(i.stream()));
+
+    match service.get_software_info().await {
+        Ok(info) => handlers.software_info(&info),
+        Err(err) => tracing::warn!(error=%err, "failed to get software info"),
+    }
+
+    if let Some(recorder) = &mut handlers.dosimeter {
+        match dosimeter.fetch_daily_summaries().await {
+            Ok(summary) => recorder.summary(summary, SystemTime::now()),
+            Err(err) => tracing::warn!(error=%err, "failed to fetch dosimeter summaries"),
+        }
+    }
+
+    loop {
+        tokio::select! {
+            info = runtime.next() => {
+                let info = info.ok_or_else(|| anyhow::anyhow!("runtime info stream terminated"))??;
+                tracing::trace!(?info, "received runtime info");
+
+                handlers.update_runtime_info(&info);
+            },
+            rsp = changes.next() => {
+                let rsp = rsp.ok_or_else(|| anyhow::anyhow!("settings stream terminated"))??;
+                tracing::trace!(?rsp, "received settings change");
+
+                if let Some(settings_rsp::ValueOneof::Value(value)) = rsp.value_oneof
+                    && let Some(value) = value.value_oneof
+                {
+                    handlers.setting_changed(value.into());
+                }
+            },
+            evt = next_or_pending(&mut quiet_mode) => {
+                // not essential, so don't drop the connection if unsupported
+                match evt {
+                    Some(Ok(evt)) => {
+                        tracing::trace!(?evt, "received quiet mode status");
+                        handlers.source_changed(evt.source);
+                    },
+                    Some(Err(err)) => {
+                        tracing::debug!(error=%err, "quiet mode status stream failed");
+                        quiet_mode = None;
+                    },
+                    None => {
+                        tracing::debug!("quiet mode status stream terminated");
+                        quiet_mode = None;
+                    },
+                }
+            },
+            msg = next_or_pending(&mut live_db) => {
+                match msg {
+                    Some(Ok(msg)) => {
+                        if let Some(recorder) = &mut handlers.dosimeter {
+                            recorder.sample(msg.intensity, SystemTime::now());
+                        }
+                    },
+                    Some(Err(err)) => {
+                        tracing::warn!(error=%err, "dosimeter stream failed");
+                        live_db = None;
+                    },
+                    None => {
+                        tracing::debug!("dosimeter stream terminated");
+                        live_db = None;
+                    },
+                }
+            },
+            action = next_or_pending(&mut gesture_events) => {
+                match action {
+                    Some(Ok(action)) => {
+                        tracing::trace!(?action, "received gesture");
+
+                        if let Some(gestures) = &handlers.gestures {
+                            gestures.handle(&mut service, action).await;
+                        }
+                    },
+                    Some(Err(err)) => {
+                        tracing::warn!(error=%err, "gesture stream failed");
+                        gesture_events = None;
+                    },
+                    None => {
+                        tracing::debug!("gesture stream terminated");
+                        gesture_events = None;
+                    },
+                }
+            },
+            _ = sleep_until(handlers.debounce_deadline()) => {
+                handlers.flush_settings();
+            },
+            req = requests.next() => {
+                let Some(req) = req else { return Ok(()) };
+
+                if let Request::SetSetting { value, .. } | Request::SetSettingFor { value, .. } = &req {
+                    handlers.notifications.expect_write(value);
+                }
+
+                handlers.activity.touch();
+                handle_request(&mut service, req, handlers).await;
+            },
+        }
+    }
+}
+
+/// Sleep until the given deadline, or forever if there is none.
+async fn sleep_until(deadline: Option<Instant>) {
+    match deadline {
+        Some(deadline) => tokio::time::sleep_until(deadline.into()).await,
+        None => std::future::pending().await,
+    }
+}
+
+/// Get the next item of an optional stream, or wait forever if there is none.
+async fn next_or_pending<S: Stream + Unpin>(stream: &mut Option<S>) -> Option<S::Item> {
+    match stream {
+        Some(stream) => stream.next().await,
+        None => std::future::pending().await,
+    }
+}
+
+async fn handle_request(service: &mut MaestroService, req: Request, handlers: &mut Handlers) {
+    const READ_ONLY: &str = "daemon is in read-only mode, not writing settings";
+
+    match req {
+        Request::GetSetting { id, reply } => {
+            tracing::debug!(setting=%id, "reading setting");
+
+            let value = service.read_setting_var(id).await
+                .map_err(|e| handlers.error_str(e));
+
+            let _ = reply.send(value);
+        },
+        Request::SetSetting { value, reply } if handlers.read_only => {
+            tracing::debug!(setting=%value.id(), %value, "rejecting write in read-only mode");
+            let _ = reply.send(Err(READ_ONLY.to_owned()));
+        },
+        Request::SetSettingFor { value, reply, .. } if handlers.read_only => {
+            tracing::debug!(setting=%value.id(), %value, "rejecting write in read-only mode");
+            let _ = reply.send(Err(READ_ONLY.to_owned()));
+        },
+        Request::SetSetting { value, reply } => {
+            tracing::debug!(setting=%value.id(), %value, "writing setting");
+
+            let result = service.write_setting(value.clone()).await
+                .map_err(|e| handlers.error_str(e));
+
+            if result.is_ok() {
+                handlers.audit(&value, None);
+            }
+
+            let _ = reply.send(result);
+        },
+        Request::SetSettingFor { value, duration, reply } => {
+            tracing::debug!(setting=%value.id(), %value, ?duration, "temporarily writing setting");
+
+            let previous = match service.read_setting_var(value.id()).await {
+                Ok(previous) => previous,
+                Err(err) => {
+                    let _ = reply.send(Err(handlers.error_str(err)));
+                    return;
+                },
+            };
+
+            let result = service.write_setting(value.clone()).await
+                .map_err(|e| handlers.error_str(e));
+
+            if result.is_ok() {
+                handlers.audit(&value, Some(&previous));
+                schedule_revert(handlers, previous, duration);
+            }
+
+            let _ = reply.send(result);
+        },
+        Request::GetFirmware { reply } => {
+            tracing::debug!("reading firmware versions");
+
+            let info = service.get_software_info().await
+                .map(|info| info.firmware.unwrap_or_default())
+                .map_err(|e| e.to_string());
+
+            let _ = reply.send(info);
+        },
+    }
+}
+
+/// Write the given value after the specified duration, keeping the daemon
+/// from exiting due to inactivity until then.
+fn schedule_revert(handlers: &Handlers, value: SettingValue, duration: Duration) {
+    let requests = handlers.requests.clone();
+    let guard = handlers.activity.client();
+
+    tokio::spawn(async move {
+        let _guard = guard;
+
+        tokio::time::sleep(duration).await;
+        tracing::debug!(setting=%value.id(), %value, "reverting setting");
+
+        let (reply, rx) = futures::channel::oneshot::channel();
+        if requests.unbounded_send(Request::SetSetting { value, reply }).is_err() {
+            return;
+        }
+
+        if let Ok(Err(err)) = rx.await {
+            tracing::warn!(error=%err, "failed to revert setting");
+        }
+    });
+}
+
+fn is_connection_reset(err: &anyhow::Error) -> bool {
+    err.chain()
+        .filter_map(|cause| cause.downcast_ref::<std::io::Error>())
+        .any(|cause| cause.raw_os_error() == Some(104))
+}
@@ -0,0 +1,330 @@
+//! Desktop notifications via `org.freedesktop.Notifications`.
+//!
+//! Besides notifications sent by rules, the daemon can notify about a set of
+//! built-in events, configured via the `[notifications]` section of the
+//! daemon configuration file:
+//!
+//! ```toml
+//! [notifications]
+//! battery-low = true          # battery of any component drops below threshold
+//! battery-threshold = 15      # threshold for battery-low, in percent
+//! firmware-mismatch = true    # buds run different firmware versions
+//! anc = true                  # ANC mode changed on the device, e.g. via gestures
+//! multipoint = true           # active audio source switched
+//! connection = true           # connection to the device lost or restored
+//! rate-limit = 60             # minimum time between notifications of the same kind, in seconds
+//! ```
+//!
+//! Built-in notifications are disabled unless the section is present, in which
+//! case all of them are enabled by default.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+
+use dbus::arg::PropMap;
+use dbus::nonblock::{Proxy, SyncConnection};
+
+use maestro::service::settings::SettingValue;
+
+use super::event::{Component, Event};
+
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Time after a write by the daemon during which the corresponding change
+/// notification from the device is attributed to that write.
+const WRITE_ECHO_TIMEOUT: Duration = Duration::from_secs(5);
+
+
+#[derive(Clone)]
+pub struct Notifier {
+    conn: Arc<SyncConnection>,
+}
+
+impl Notifier {
+    pub fn new(conn: Arc<SyncConnection>) -> Self {
+        Self { conn }
+    }
+
+    /// Show a notification. Failures are logged but otherwise ignored.
+    pub fn notify(&self, summary: &str, body: &str) {
+        let proxy = Proxy::new(
+            "org.freedesktop.Notifications",
+            "/org/freedesktop/Notifications",
+            TIMEOUT,
+            self.conn.clone(),
+        );
+
+        let args = (
+            "pbpctrl",
+            0u32,
+            "audio-headphones",
+            summary,
+            body,
+            Vec::<String>::new(),
+            PropMap::new(),
+            -1i32,
+        );
+
+        let call = proxy.method_call::<(u32,), _, _, _>("org.freedesktop.Notifications", "Notify", args);
+
+        tokio::spawn(async move {
+            if let Err(err) = call.await {
+                tracing::warn!(error=%err, "failed to send notification");
+            }
+        });
+    }
+}
+
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct NotifyConfig {
+    pub battery_low: bool,
+    pub battery_threshold: i32,
+    pub firmware_mismatch: bool,
+    pub anc: bool,
+    pub multipoint: bool,
+    pub connection: bool,
+    pub rate_limit: Duration,
+}
+
+impl NotifyConfig {
+    /// Configuration with all built-in notifications disabled.
+    pub fn disabled() -> Self {
+        Self {
+            battery_low: false,
+            firmware_mismatch: false,
+            anc: false,
+            multipoint: false,
+            connection: false,
+            ..Self::enabled()
+        }
+    }
+
+    /// Configuration with all built-in notifications enabled.
+    pub fn enabled() -> Self {
+        Self {
+            battery_low: true,
+            battery_threshold: 15,
+            firmware_mismatch: true,
+            anc: true,
+            multipoint: true,
+            connection: true,
+            rate_limit: Duration::from_secs(60),
+        }
+    }
+
+    pub fn parse(table: &dyn toml_edit::TableLike) -> Result<Self> {
+        let mut config = Self::enabled();
+
+        for (key, item) in table.iter() {
+            match key {
+                "battery-low" => config.battery_low = get_bool(item, key)?,
+                "battery-threshold" => {
+                    config.battery_threshold = item.as_integer()
+                        .filter(|v| (0..=100).contains(v))
+                        .ok_or_else(|| anyhow::anyhow!("'{key}' must be an integer between 0 and 100"))?
+                        as i32;
+                },
+                "firmware-mismatch" => config.firmware_mismatch = get_bool(item, key)?,
+                "anc" => config.anc = get_bool(item, key)?,
+                "multipoint" => config.multipoint = get_bool(item, key)?,
+                "connection" => config.connection = get_bool(item, key)?,
+                "rate-limit" => {
+                    let secs = item.as_integer()
+                        .and_then(|v| u64::try_from(v).ok())
+                        .ok_or_else(|| anyhow::anyhow!("'{key}' must be a non-negative integer"))?;
+
+                    config.rate_limit = Duration::from_secs(secs);
+                },
+                _ => anyhow::bail!("unknown key '{key}'"),
+            }
+        }
+
+        Ok(config)
+    }
+}
+
+impl Default for NotifyConfig {
+    fn default() -> Self {
+        Self::disabled()
+    }
+}
+
+fn get_bool(item: &toml_edit::Item, key: &str) -> Result<bool> {
+    item.as_bool()
+        .ok_or_else(|| anyhow::anyhow!("'{key}' must be a boolean"))
+}
+
+
+/// Decides which events result in built-in notifications.
+#[derive(Debug)]
+pub struct Filter {
+    config: NotifyConfig,
+    last: HashMap<&'static str, Instant>,
+    write: Option<(SettingValue, Instant)>,
+    lost: bool,
+}
+
+impl Filter {
+    pub fn new(config: NotifyConfig) -> Self {
+        Self { config, last: HashMap::new(), write: None, lost: false }
+    }
+
+    /// Record a setting write by the daemon, so that the resulting change
+    /// reported by the device is not mistaken for a change on the device.
+    pub fn expect_write(&mut self, value: &SettingValue, now: Instant) {
+        self.write = Some((value.clone(), now));
+    }
+
+    /// Get the notification (summary and body) to show for the given event,
+    /// if any.
+    pub fn check(&mut self, event: &Event, now: Instant) -> Option<(&'static str, String)> {
+        let (key, summary, body) = self.notification(event, now)?;
+
+        if let Some(last) = self.last.get(key)
+            && now.duration_since(*last) < self.config.rate_limit
+        {
+            tracing::debug!(kind=key, "notification rate-limited");
+            return None;
+        }
+
+        self.last.insert(key, now);
+        Some((summary, body))
+    }
+
+    fn notification(&mut self, event: &Event, now: Instant) -> Option<(&'static str, &'static str, String)> {
+        let config = &self.config;
+
+        match event {
+            Event::Connected if config.connection => {
+                // only notify when the connection has been lost before
+                if !std::mem::take(&mut self.lost) {
+                    return None;
+                }
+
+                Some(("connected", "Pixel Buds connected", "Connection to the device has been restored".to_owned()))
+            },
+            Event::Disconnected if config.connection => {
+                self.lost = true;
+                Some(("disconnected", "Pixel Buds disconnected", "Connection to the device has been lost".to_owned()))
+            },
+            Event::BatteryChanged { component, level: Some(level), previous } if config.battery_low => {
+                let threshold = config.battery_threshold;
+
+                // only notify when crossing the threshold
+                if *level >= threshold || previous.is_some_and(|p| p < threshold) {
+                    return None;
+                }
+
+                let (key, name) = match component {
+                    Component::Case => ("battery-low-case", "Case"),
+                    Component::Left => ("battery-low-left", "Left bud"),
+                    Component::Right => ("battery-low-right", "Right bud"),
+                };
+
+                Some((key, "Pixel Buds battery low", format!("{name} battery at {level}%")))
+            },
+            Event::FirmwareMismatch { left, right } if config.firmware_mismatch => {
+                let body = format!("Left bud runs firmware {left}, right bud runs {right}");
+                Some(("firmware-mismatch", "Pixel Buds firmware mismatch", body))
+            },
+            Event::SettingChanged(value @ SettingValue::CurrentAncrState(state)) if config.anc => {
+                // ignore changes caused by our own writes
+                if let Some((expected, time)) = self.write.take()
+                    && expected == *value
+                    && now.duration_since(time) < WRITE_ECHO_TIMEOUT
+                {
+                    return None;
+                }
+
+                Some(("anc", "Pixel Buds ANC mode changed", format!("Noise control: {state}")))
+            },
+            Event::SourceSwitched { .. } if config.multipoint => {
+                Some(("multipoint", "Pixel Buds switched source", "Audio source has been switched".to_owned()))
+            },
+            _ => None,
+        }
+    }
+}
+
+
+/// Built-in notifications for device events.
+pub struct Notifications {
+    filter: Filter,
+    notifier: Notifier,
+}
+
+impl Notifications {
+    pub fn new(config: NotifyConfig, notifier: Notifier) -> Self {
+        Self { filter: Filter::new(config), notifier }
+    }
+
+    pub fn expect_write(&mut self, value: &SettingValue) {
+        self.filter.expect_write(value, Instant::now());
+    }
+
+    pub fn handle(&mut self, event: &Event) {
+        if let Some((summary, body)) = self.filter.check(event, Instant::now()) {
+            self.notifier.notify(summary, &body);
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use maestro::service::settings::AncState;
+
+    use crate::daemon::config::Config;
+
+    #[test]
+    fn test_parse_config() {
+        let config = Config::parse("[notifications]\nanc = false\nrate-limit = 10\n").unwrap();
+
+        assert_eq!(config.notifications, NotifyConfig {
+            anc: false,
+            rate_limit: Duration::from_secs(10),
+            ..NotifyConfig::enabled()
+        });
+
+        assert_eq!(Config::parse("").unwrap().notifications, NotifyConfig::disabled());
+        assert!(Config::parse("[notifications]\nfoo = true\n").is_err());
+        assert!(Config::parse("[notifications]\nbattery-threshold = 200\n").is_err());
+    }
+
+    #[test]
+    fn test_filter() {
+        let mut filter = Filter::new(NotifyConfig::enabled());
+        let start = Instant::now();
+
+        let battery = |level, previous| Event::BatteryChanged { component: Component::Case, level, previous };
+
+        assert!(filter.check(&battery(Some(14), Some(15)), start).is_some());
+        assert!(filter.check(&battery(Some(13), Some(14)), start).is_none());
+        assert!(filter.check(&battery(Some(20), Some(21)), start).is_none());
+
+        // rate limiting
+        assert!(filter.check(&battery(Some(14), Some(15)), start + Duration::from_secs(30)).is_none());
+        assert!(filter.check(&battery(Some(14), Some(15)), start + Duration::from_secs(60)).is_some());
+
+        // changes caused by own writes
+        let anc = SettingValue::CurrentAncrState(AncState::Active);
+        filter.expect_write(&anc, start);
+        assert!(filter.check(&Event::SettingChanged(anc.clone()), start).is_none());
+        assert!(filter.check(&Event::SettingChanged(anc), start).is_some());
+
+        // connection restored only after it has been lost
+        assert!(filter.check(&Event::Connected, start).is_none());
+        assert!(filter.check(&Event::Disconnected, start).is_some());
+        assert!(filter.check(&Event::Connected, start).is_some());
+
+        let mut filter = Filter::new(NotifyConfig::disabled());
+        assert!(filter.check(&Event::Disconnected, start).is_none());
+    }
+}
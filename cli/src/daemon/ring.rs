@@ -0,0 +1,85 @@
+//! Find-device ringing requested via D-Bus.
+//!
+//! Ringing runs in the background over its own GFPS connection, independent
+//! of the connection to the Maestro service, and escalates according to
+//! [`find::escalation`] until a bud is touched or ringing is stopped.
+
+use std::sync::{Arc, Mutex};
+
+use futures::channel::oneshot;
+
+use crate::find::{self, Outcome};
+use crate::transport;
+
+use super::idle::Activity;
+
+
+pub struct Ringer {
+    transport: transport::Platform,
+    activity: Activity,
+    active: Arc<Mutex<Option<oneshot::Sender<()>>>>,
+}
+
+impl Ringer {
+    pub fn new(transport: transport::Platform, activity: Activity) -> Self {
+        Self { transport, activity, active: Arc::new(Mutex::new(None)) }
+    }
+
+    pub fn is_ringing(&self) -> bool {
+        self.active.lock().unwrap().as_ref().is_some_and(|tx| !tx.is_canceled())
+    }
+
+    /// Start ringing in the background. Fails if already ringing.
+    pub fn start(&self) -> Result<(), String> {
+        let (tx, rx) = oneshot::channel();
+
+        {
+            let mut active = self.active.lock().unwrap();
+
+            if active.as_ref().is_some_and(|tx| !tx.is_canceled()) {
+                return Err("already ringing".to_owned());
+            }
+
+            *active = Some(tx);
+        }
+
+        let transport = self.transport.clone();
+        let active = self.active.clone();
+        let guard = self.activity.client();
+
+        tokio::spawn(async move {
+            let _guard = guard;
+
+            let result = async {
+                let mut stream = transport.gfps_connect().await?;
+
+                let stop = async { let _ = rx.await; };
+                let progress = |stage: &find::Stage| {
+                    tracing::info!(buds=%stage.buds, duration=?stage.duration, "ringing");
+                };
+
+                Ok::<_, anyhow::Error>(find::run(&mut stream, &find::escalation(), stop, progress).await?)
+            };
+
+            match result.await {
+                Ok(Outcome::Found) => tracing::info!("ringing acknowledged"),
+                Ok(Outcome::Stopped) => tracing::info!("ringing stopped"),
+                Ok(Outcome::GaveUp) => tracing::info!("ringing not acknowledged, giving up"),
+                Err(err) => tracing::warn!(error=?err, "failed to ring device"),
+            }
+
+            // the receiver has been dropped, so this only clears our own slot
+            active.lock().unwrap().take_if(|tx| tx.is_canceled());
+        });
+
+        Ok(())
+    }
+
+    /// Stop ringing. Returns `false` if not ringing.
+    pub fn stop(&self) -> bool {
+        match self.active.lock().unwrap().take() {
+            Some(tx) => tx.send(()).is_ok(),
+            None => false,
+        }
+    }
+}
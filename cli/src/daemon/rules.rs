@@ -0,0 +1,416 @@
+//! Rules for automatic actions in response to device events.
+//!
+//! Rules are specified in the `[[rules]]` array of the daemon configuration
+//! file, for example:
+//!
+//! ```toml
+//! [[rules]]
+//! on = "bud-removed"
+//! set = { setting = "current-ancr-state", value = "off" }
+//!
+//! [[rules]]
+//! on = "battery-low"
+//! component = "case"
+//! below = 15
+//! notify = "Case battery at {level}%"
+//! ```
+//!
+//! Supported events are `connected`, `disconnected`, `bud-removed`,
+//! `bud-inserted` (both with optional `bud = "left" | "right"` filter),
+//! `both-removed`, `both-inserted`, `battery-low` (with `below` threshold and
+//! optional `component = "case" | "left" | "right"` filter), and
+//! `setting-changed` (with `setting` and optional `value` filter). Actions are
+//! `set` (a table or array of tables with `setting` and `value`) and `notify`
+//! (a summary string or a table with `summary` and `body`).
+
+use std::collections::VecDeque;
+
+use anyhow::Result;
+
+use dbus::arg::RefArg;
+
+use futures::channel::{mpsc, oneshot};
+
+use maestro::service::settings::{SettingId, SettingValue};
+
+use super::event::{Bud, Component, Event};
+use super::notify::Notifier;
+use super::server::Request;
+use super::value;
+
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Trigger {
+    Connected,
+    Disconnected,
+    BudRemoved(Option<Bud>),
+    BudInserted(Option<Bud>),
+    BothRemoved,
+    BothInserted,
+    BatteryLow {
+        component: Option<Component>,
+        below: i32,
+    },
+    SettingChanged {
+        setting: SettingId,
+        value: Option<SettingValue>,
+    },
+}
+
+impl Trigger {
+    pub fn matches(&self, event: &Event) -> bool {
+        match (self, event) {
+            (Trigger::Connected, Event::Connected) => true,
+            (Trigger::Disconnected, Event::Disconnected) => true,
+            (Trigger::BudRemoved(filter), Event::BudRemoved(bud)) => filter.is_none_or(|f| f == *bud),
+            (Trigger::BudInserted(filter), Event::BudInserted(bud)) => filter.is_none_or(|f| f == *bud),
+            (Trigger::BothRemoved, Event::BothRemoved) => true,
+            (Trigger::BothInserted, Event::BothInserted) => true,
+            (Trigger::BatteryLow { component: filter, below }, Event::BatteryChanged { component, level, previous }) => {
+                // only trigger when crossing the threshold
+                filter.is_none_or(|f| f == *component)
+                    && level.is_some_and(|l| l < *below)
+                    && previous.is_none_or(|p| p >= *below)
+            },
+            (Trigger::SettingChanged { setting, value: filter }, Event::SettingChanged(value)) => {
+                value.id() == *setting && filter.as_ref().is_none_or(|f| f == value)
+            },
+            _ => false,
+        }
+    }
+}
+
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Action {
+    Set(SettingValue),
+    Notify {
+        summary: String,
+        body: String,
+    },
+}
+
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rule {
+    pub name: String,
+    pub trigger: Trigger,
+    pub actions: Vec<Action>,
+}
+
+impl Rule {
+    pub fn parse(index: usize, table: &dyn toml_edit::TableLike) -> Result<Self> {
+        let name = match table.get("name") {
+            Some(name) => get_str(name, "name")?.to_owned(),
+            None => format!("rule #{}", index + 1),
+        };
+
+        let on = table.get("on")
+            .ok_or_else(|| anyhow::anyhow!("missing event ('on')"))?;
+        let on = get_str(on, "on")?;
+
+        let (trigger, params): (_, &[&str]) = match on {
+            "connected" => (Trigger::Connected, &[]),
+            "disconnected" => (Trigger::Disconnected, &[]),
+            "bud-removed" => (Trigger::BudRemoved(parse_bud(table)?), &["bud"]),
+            "bud-inserted" => (Trigger::BudInserted(parse_bud(table)?), &["bud"]),
+            "both-removed" => (Trigger::BothRemoved, &[]),
+            "both-inserted" => (Trigger::BothInserted, &[]),
+            "battery-low" => {
+                let component = match table.get("component") {
+                    Some(item) => Some(match get_str(item, "component")? {
+                        "case" => Component::Case,
+                        "left" => Component::Left,
+                        "right" => Component::Right,
+                        other => anyhow::bail!("invalid component '{other}'"),
+                    }),
+                    None => None,
+                };
+
+                let below = table.get("below")
+                    .ok_or_else(|| anyhow::anyhow!("missing battery threshold ('below')"))?;
+                let below = below.as_integer()
+                    .filter(|v| (0..=100).contains(v))
+                    .ok_or_else(|| anyhow::anyhow!("'below' must be an integer between 0 and 100"))?;
+
+                (Trigger::BatteryLow { component, below: below as i32 }, &["component", "below"])
+            },
+            "setting-changed" => {
+                let setting = table.get("setting")
+                    .ok_or_else(|| anyhow::anyhow!("missing setting name ('setting')"))?;
+                let setting = parse_setting_id(get_str(setting, "setting")?)?;
+
+                let value = match table.get("value") {
+                    Some(item) => Some(parse_setting_value(setting, item)?),
+                    None => None,
+                };
+
+                (Trigger::SettingChanged { setting, value }, &["setting", "value"])
+            },
+            other => anyhow::bail!("unknown event '{other}'"),
+        };
+
+        for (key, _) in table.iter() {
+            if !["name", "on", "set", "notify"].contains(&key) && !params.contains(&key) {
+                anyhow::bail!("unknown key '{key}' for event '{on}'");
+            }
+        }
+
+        let mut actions = Vec::new();
+
+        if let Some(item) = table.get("set") {
+            for set in get_tables(item, "set")? {
+                let setting = set.get("setting")
+                    .ok_or_else(|| anyhow::anyhow!("missing setting name ('set.setting')"))?;
+                let setting = parse_setting_id(get_str(setting, "set.setting")?)?;
+
+                let value = set.get("value")
+                    .ok_or_else(|| anyhow::anyhow!("missing setting value ('set.value')"))?;
+
+                actions.push(Action::Set(parse_setting_value(setting, value)?));
+            }
+        }
+
+        if let Some(item) = table.get("notify") {
+            let action = if let Some(summary) = item.as_str() {
+                Action::Notify { summary: summary.to_owned(), body: String::new() }
+            } else if let Some(notify) = item.as_table_like() {
+                let summary = notify.get("summary")
+                    .ok_or_else(|| anyhow::anyhow!("missing notification summary ('notify.summary')"))?;
+                let body = notify.get("body")
+                    .map(|body| get_str(body, "notify.body"))
+                    .transpose()?
+                    .unwrap_or_default();
+
+                Action::Notify {
+                    summary: get_str(summary, "notify.summary")?.to_owned(),
+                    body: body.to_owned(),
+                }
+            } else {
+                anyhow::bail!("'notify' must be a string or a table");
+            };
+
+            actions.push(action);
+        }
+
+        if actions.is_empty() {
+            anyhow::bail!("no actions specified");
+        }
+
+        Ok(Self { name, trigger, actions })
+    }
+}
+
+
+/// Executes the actions of all rules matching an event.
+pub struct Engine {
+    rules: Vec<Rule>,
+    requests: mpsc::UnboundedSender<Request>,
+    notifier: Notifier,
+}
+
+impl Engine {
+    pub fn new(rules: Vec<Rule>, requests: mpsc::UnboundedSender<Request>, notifier: Notifier) -> Self {
+        Self { rules, requests, notifier }
+    }
+
+    pub fn handle(&self, event: &Event) {
+        for rule in self.rules.iter().filter(|r| r.trigger.matches(event)) {
+            tracing::debug!(rule=%rule.name, ?event, "rule triggered");
+
+            for action in &rule.actions {
+                self.execute(rule, event, action);
+            }
+        }
+    }
+
+    fn execute(&self, rule: &Rule, event: &Event, action: &Action) {
+        match action {
+            Action::Set(value) => {
+                let (reply, rx) = oneshot::channel();
+
+                let req = Request::SetSetting { value: value.clone(), reply };
+                if self.requests.unbounded_send(req).is_err() {
+                    return;
+                }
+
+                let name = rule.name.clone();
+                tokio::spawn(async move {
+                    if let Ok(Err(err)) = rx.await {
+                        tracing::warn!(rule=%name, error=%err, "failed to execute rule");
+                    }
+                });
+            },
+            Action::Notify { summary, body } => {
+                self.notifier.notify(&expand(summary, event), &expand(body, event));
+            },
+        }
+    }
+}
+
+
+/// Replace `{bud}`, `{component}`, `{level}`, `{setting}`, and `{value}`
+/// placeholders with their values from the event.
+fn expand(template: &str, event: &Event) -> String {
+    let mut text = template.to_owned();
+
+    match event {
+        Event::BudRemoved(bud) | Event::BudInserted(bud) => {
+            text = text.replace("{bud}", bud.as_str());
+        },
+        Event::BatteryChanged { component, level, .. } => {
+            let level = level.map(|l| l.to_string()).unwrap_or_else(|| "unknown".to_owned());
+
+            text = text.replace("{component}", component.as_str());
+            text = text.replace("{level}", &level);
+        },
+        Event::SettingChanged(value) => {
+            text = text.replace("{setting}", value.id().as_str());
+            text = text.replace("{value}", &value.to_string());
+        },
+        _ => {},
+    }
+
+    text
+}
+
+fn get_str<'a>(item: &'a toml_edit::Item, key: &str) -> Result<&'a str> {
+    item.as_str()
+        .ok_or_else(|| anyhow::anyhow!("'{key}' must be a string"))
+}
+
+fn get_tables<'a>(item: &'a toml_edit::Item, key: &str) -> Result<Vec<&'a dyn toml_edit::TableLike>> {
+    if let Some(table) = item.as_table_like() {
+        Ok(vec![table])
+    } else if let Some(tables) = item.as_array_of_tables() {
+        Ok(tables.iter().map(|t| t as &dyn toml_edit::TableLike).collect())
+    } else if let Some(array) = item.as_array() {
+        array.iter()
+            .map(|v| v.as_inline_table().map(|t| t as &dyn toml_edit::TableLike))
+            .collect::<Option<Vec<_>>>()
+            .ok_or_else(|| anyhow::anyhow!("'{key}' must be a table or an array of tables"))
+    } else {
+        anyhow::bail!("'{key}' must be a table or an array of tables")
+    }
+}
+
+fn parse_bud(table: &dyn toml_edit::TableLike) -> Result<Option<Bud>> {
+    let Some(item) = table.get("bud") else { return Ok(None) };
+
+    match get_str(item, "bud")? {
+        "left" => Ok(Some(Bud::Left)),
+        "right" => Ok(Some(Bud::Right)),
+        other => anyhow::bail!("invalid bud '{other}'"),
+    }
+}
+
+pub fn parse_setting_id(name: &str) -> Result<SettingId> {
+    value::setting_id(name)
+        .ok_or_else(|| anyhow::anyhow!("unknown setting '{name}'"))
+}
+
+pub fn parse_setting_value(id: SettingId, item: &toml_edit::Item) -> Result<SettingValue> {
+    let value = item.as_value()
+        .ok_or_else(|| anyhow::anyhow!("invalid value for setting '{id}'"))?;
+
+    let value = to_refarg(value)
+        .map_err(|e| anyhow::anyhow!("invalid value for setting '{id}': {e}"))?;
+
+    value::from_variant(id, &*value)
+        .map_err(|e| anyhow::anyhow!("invalid value for setting '{id}': {e}"))
+}
+
+fn to_refarg(value: &toml_edit::Value) -> Result<Box<dyn RefArg>, String> {
+    use toml_edit::Value;
+
+    match value {
+        Value::Boolean(x) => Ok(Box::new(*x.value())),
+        Value::Integer(x) => {
+            let x = i32::try_from(*x.value()).map_err(|e| e.to_string())?;
+            Ok(Box::new(x))
+        },
+        Value::Float(x) => Ok(Box::new(*x.value())),
+        Value::String(x) => Ok(Box::new(x.value().clone())),
+        Value::Array(x) => {
+            let items = x.iter()
+                .map(to_refarg)
+                .collect::<Result<VecDeque<_>, _>>()?;
+
+            Ok(Box::new(items))
+        },
+        _ => Err("unsupported type".to_owned()),
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use maestro::service::settings::{AncState, EqBands};
+
+    use crate::daemon::config::Config;
+
+    #[test]
+    fn test_parse_rules() {
+        let config = Config::parse(r#"
+            [[rules]]
+            name = "anc off"
+            on = "bud-removed"
+            set = { setting = "current-ancr-state", value = "off" }
+
+            [[rules]]
+            on = "battery-low"
+            component = "case"
+            below = 15
+            notify = "Case battery low"
+
+            [[rules]]
+            on = "setting-changed"
+            setting = "current-ancr-state"
+            value = "aware"
+            set = [{ setting = "current-user-eq", value = [-2, -1.5, 0, 0, 0] }]
+        "#).unwrap();
+
+        assert_eq!(config.rules, vec![
+            Rule {
+                name: "anc off".to_owned(),
+                trigger: Trigger::BudRemoved(None),
+                actions: vec![Action::Set(SettingValue::CurrentAncrState(AncState::Off))],
+            },
+            Rule {
+                name: "rule #2".to_owned(),
+                trigger: Trigger::BatteryLow { component: Some(Component::Case), below: 15 },
+                actions: vec![Action::Notify { summary: "Case battery low".to_owned(), body: String::new() }],
+            },
+            Rule {
+                name: "rule #3".to_owned(),
+                trigger: Trigger::SettingChanged {
+                    setting: SettingId::CurrentAncrState,
+                    value: Some(SettingValue::CurrentAncrState(AncState::Aware)),
+                },
+                actions: vec![Action::Set(SettingValue::CurrentUserEq(EqBands::new(-2.0, -1.5, 0.0, 0.0, 0.0)))],
+            },
+        ]);
+    }
+
+    #[test]
+    fn test_parse_rules_invalid() {
+        assert!(Config::parse("[[rules]]\non = \"bud-removed\"\n").is_err());
+        assert!(Config::parse("[[rules]]\non = \"foo\"\nnotify = \"x\"\n").is_err());
+        assert!(Config::parse("[[rules]]\non = \"bud-removed\"\nbelow = 3\nnotify = \"x\"\n").is_err());
+    }
+
+    #[test]
+    fn test_battery_low_trigger() {
+        let trigger = Trigger::BatteryLow { component: None, below: 15 };
+
+        let event = |level, previous| Event::BatteryChanged { component: Component::Left, level, previous };
+
+        assert!(trigger.matches(&event(Some(14), Some(15))));
+        assert!(trigger.matches(&event(Some(10), None)));
+        assert!(!trigger.matches(&event(Some(13), Some(14))));
+        assert!(!trigger.matches(&event(Some(20), Some(21))));
+        assert!(!trigger.matches(&event(None, Some(10))));
+    }
+}
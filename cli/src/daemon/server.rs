@@ -0,0 +1,346 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::Result;
+
+use bluer::Address;
+
+use dbus::arg::{PropMap, RefArg, Variant};
+use dbus::channel::{MatchingReceiver, Sender};
+use dbus::message::{MatchRule, SignalArgs};
+use dbus::nonblock::SyncConnection;
+use dbus::nonblock::stdintf::org_freedesktop_dbus::{PropertiesPropertiesChanged, RequestNameReply};
+use dbus::{Message, Path};
+use dbus_crossroads::{Crossroads, IfaceBuilder, MethodErr};
+
+use futures::channel::{mpsc, oneshot};
+
+use maestro::protocol::types::{FirmwareInfo, FirmwareVersion, RuntimeInfo};
+use maestro::service::settings::{SettingId, SettingValue};
+
+use super::ring::Ringer;
+use super::state::{Battery, SharedState, State};
+use super::value::{self, Value};
+
+
+pub const BUS_NAME: &str = "org.pbpctrl";
+pub const OBJECT_PATH: &str = "/org/pbpctrl/Device";
+pub const INTERFACE: &str = "org.pbpctrl.Device1";
+
+
+/// Request forwarded from the D-Bus interface to the device connection.
+pub enum Request {
+    GetSetting {
+        id: SettingId,
+        reply: oneshot::Sender<Result<SettingValue, String>>,
+    },
+    SetSetting {
+        value: SettingValue,
+        reply: oneshot::Sender<Result<(), String>>,
+    },
+    SetSettingFor {
+        value: SettingValue,
+        duration: Duration,
+        reply: oneshot::Sender<Result<(), String>>,
+    },
+    GetFirmware {
+        reply: oneshot::Sender<Result<FirmwareInfo, String>>,
+    },
+}
+
+impl Request {
+    pub fn fail(self, message: &str) {
+        match self {
+            Request::GetSetting { reply, .. } => { let _ = reply.send(Err(message.to_owned())); },
+            Request::SetSetting { reply, .. } => { let _ = reply.send(Err(message.to_owned())); },
+            Request::SetSettingFor { reply, .. } => { let _ = reply.send(Err(message.to_owned())); },
+            Request::GetFirmware { reply } => { let _ = reply.send(Err(message.to_owned())); },
+        }
+    }
+}
+
+
+fn battery_to_dbus(battery: Battery) -> (i32, String) {
+    (battery.level.unwrap_or(-1), battery.state_str().to_owned())
+}
+
+/// Firmware versions of case, left, and right bud, empty if unknown.
+fn firmware_to_dbus(info: &FirmwareInfo) -> (String, String, String) {
+    let version = |fw: &Option<FirmwareVersion>| fw.as_ref()
+        .map(|fw| fw.version_string.clone())
+        .unwrap_or_default();
+
+    (version(&info.case), version(&info.left), version(&info.right))
+}
+
+
+struct Shared {
+    address: Address,
+    state: SharedState,
+    requests: mpsc::UnboundedSender<Request>,
+    ringer: Ringer,
+}
+
+impl Shared {
+    fn state(&self) -> State {
+        self.state.get()
+    }
+
+    async fn get_setting(&self, name: &str) -> Result<SettingValue, MethodErr> {
+        let id = value::setting_id(name)
+            .ok_or_else(|| MethodErr::invalid_arg(&format!("unknown setting: '{name}'")))?;
+
+        let (reply, rx) = oneshot::channel();
+        self.submit(Request::GetSetting { id, reply }, rx).await
+    }
+
+    async fn set_setting(&self, name: &str, value: &dyn RefArg) -> Result<(), MethodErr> {
+        let id = value::setting_id(name)
+            .ok_or_else(|| MethodErr::invalid_arg(&format!("unknown setting: '{name}'")))?;
+
+        let value = value::from_variant(id, value)
+            .map_err(|e| MethodErr::invalid_arg(&e))?;
+
+        let (reply, rx) = oneshot::channel();
+        self.submit(Request::SetSetting { value, reply }, rx).await
+    }
+
+    async fn set_setting_for(&self, name: &str, value: &dyn RefArg, seconds: u64) -> Result<(), MethodErr> {
+        let id = value::setting_id(name)
+            .ok_or_else(|| MethodErr::invalid_arg(&format!("unknown setting: '{name}'")))?;
+
+        let value = value::from_variant(id, value)
+            .map_err(|e| MethodErr::invalid_arg(&e))?;
+
+        let duration = Duration::from_secs(seconds);
+
+        let (reply, rx) = oneshot::channel();
+        self.submit(Request::SetSettingFor { value, duration, reply }, rx).await
+    }
+
+    async fn get_firmware(&self) -> Result<FirmwareInfo, MethodErr> {
+        let (reply, rx) = oneshot::channel();
+        self.submit(Request::GetFirmware { reply }, rx).await
+    }
+
+    async fn submit<T>(&self, req: Request, rx: oneshot::Receiver<Result<T, String>>) -> Result<T, MethodErr> {
+        self.requests.unbounded_send(req)
+            .map_err(|_| MethodErr::failed("daemon is shutting down"))?;
+
+        rx.await
+            .map_err(|_| MethodErr::failed("request has been dropped"))?
+            .map_err(|e| MethodErr::failed(&e))
+    }
+}
+
+
+pub struct Server {
+    conn: Arc<SyncConnection>,
+    shared: Arc<Shared>,
+}
+
+impl Server {
+    pub async fn new(
+        conn: Arc<SyncConnection>,
+        address: Address,
+        state: SharedState,
+        requests: mpsc::UnboundedSender<Request>,
+        ringer: Ringer,
+    ) -> Result<Self> {
+        let reply = conn.request_name(BUS_NAME, false, false, true).await?;
+        if reply != RequestNameReply::PrimaryOwner {
+            anyhow::bail!("failed to acquire D-Bus name '{BUS_NAME}', is another daemon already running?");
+        }
+
+        tracing::debug!(name=BUS_NAME, "acquired D-Bus name");
+
+        let shared = Arc::new(Shared {
+            address,
+            state,
+            requests,
+            ringer,
+        });
+
+        let mut cr = Crossroads::new();
+        cr.set_async_support(Some((
+            conn.clone(),
+            Box::new(|x| {
+                tokio::spawn(x);
+            }),
+        )));
+
+        let token = cr.register(INTERFACE, register_interface);
+        cr.insert(OBJECT_PATH, &[token], shared.clone());
+
+        conn.start_receive(MatchRule::new_method_call(), Box::new(move |msg, conn| {
+            let _ = cr.handle_message(msg, conn);
+            true
+        }));
+
+        Ok(Self { conn, shared })
+    }
+
+    pub fn set_connected(&self, connected: bool) {
+        let mut changed = PropMap::new();
+
+        {
+            let mut state = self.shared.state.lock();
+
+            if state.connected != connected {
+                state.connected = connected;
+                changed.insert("Connected".into(), Variant(Box::new(connected)));
+            }
+        }
+
+        self.emit_properties_changed(changed);
+    }
+
+    pub fn update_runtime_info(&self, info: &RuntimeInfo) {
+        let mut changed = PropMap::new();
+
+        {
+            let mut state = self.shared.state.lock();
+
+            if let Some(battery) = &info.battery_info {
+                let case = Battery::from_info(battery.case.as_ref());
+                let left = Battery::from_info(battery.left.as_ref());
+                let right = Battery::from_info(battery.right.as_ref());
+
+                if state.battery_case != case {
+                    state.battery_case = case;
+                    changed.insert("BatteryCase".into(), Variant(Box::new(battery_to_dbus(case))));
+                }
+
+                if state.battery_left != left {
+                    state.battery_left = left;
+                    changed.insert("BatteryLeft".into(), Variant(Box::new(battery_to_dbus(left))));
+                }
+
+                if state.battery_right != right {
+                    state.battery_right = right;
+                    changed.insert("BatteryRight".into(), Variant(Box::new(battery_to_dbus(right))));
+                }
+            }
+
+            if let Some(placement) = &info.placement {
+                let placement = (placement.left_bud_in_case, placement.right_bud_in_case);
+
+                if state.placement != Some(placement) {
+                    state.placement = Some(placement);
+                    changed.insert("Placement".into(), Variant(Box::new(placement)));
+                }
+            }
+        }
+
+        self.emit_properties_changed(changed);
+    }
+
+    pub fn setting_changed(&self, value: &SettingValue) {
+        let path = Path::from(OBJECT_PATH);
+        let msg = Message::signal(&path, &INTERFACE.into(), &"SettingChanged".into())
+            .append2(value.id().as_str(), value::to_variant(value));
+
+        let _ = self.conn.send(msg);
+    }
+
+    fn emit_properties_changed(&self, changed: PropMap) {
+        if changed.is_empty() {
+            return;
+        }
+
+        let signal = PropertiesPropertiesChanged {
+            interface_name: INTERFACE.to_owned(),
+            changed_properties: changed,
+            invalidated_properties: Vec::new(),
+        };
+
+        let _ = self.conn.send(signal.to_emit_message(&Path::from(OBJECT_PATH)));
+    }
+}
+
+fn register_interface(b: &mut IfaceBuilder<Arc<Shared>>) {
+    b.property("Address")
+        .get(|_, shared| Ok(shared.address.to_string()))
+        .emits_changed_const();
+
+    b.property("Connected")
+        .get(|_, shared| Ok(shared.state().connected));
+
+    b.property("BatteryCase")
+        .get(|_, shared| Ok(battery_to_dbus(shared.state().battery_case)));
+
+    b.property("BatteryLeft")
+        .get(|_, shared| Ok(battery_to_dbus(shared.state().battery_left)));
+
+    b.property("BatteryRight")
+        .get(|_, shared| Ok(battery_to_dbus(shared.state().battery_right)));
+
+    b.property("Placement")
+        .get(|_, shared| Ok(shared.state().placement.unwrap_or_default()));
+
+    b.property("Ringing")
+        .get(|_, shared| Ok(shared.ringer.is_ringing()))
+        .emits_changed_false();
+
+    b.signal::<(String, Value), _>("SettingChanged", ("name", "value"));
+
+    b.method_with_cr_async("GetSetting", ("name",), ("value",), |mut ctx, cr, (name,): (String,)| {
+        let shared = cr.data_mut::<Arc<Shared>>(ctx.path()).cloned();
+
+        async move {
+            let result = match shared {
+                Some(shared) => shared.get_setting(&name).await,
+                None => Err(MethodErr::no_path(ctx.path())),
+            };
+
+            ctx.reply(result.map(|value| (value::to_variant(&value),)))
+        }
+    });
+
+    b.method_with_cr_async("SetSetting", ("name", "value"), (), |mut ctx, cr, (name, value): (String, Value)| {
+        let shared = cr.data_mut::<Arc<Shared>>(ctx.path()).cloned();
+
+        async move {
+            let result = match shared {
+                Some(shared) => shared.set_setting(&name, &*value.0).await,
+                None => Err(MethodErr::no_path(ctx.path())),
+            };
+
+            ctx.reply(result)
+        }
+    });
+
+    b.method_with_cr_async("SetSettingFor", ("name", "value", "seconds"), (), |mut ctx, cr, (name, value, seconds): (String, Value, u64)| {
+        let shared = cr.data_mut::<Arc<Shared>>(ctx.path()).cloned();
+
+        async move {
+            let result = match shared {
+                Some(shared) => shared.set_setting_for(&name, &*value.0, seconds).await,
+                None => Err(MethodErr::no_path(ctx.path())),
+            };
+
+            ctx.reply(result)
+        }
+    });
+
+    b.method_with_cr_async("GetFirmware", (), ("case", "left", "right"), |mut ctx, cr, (): ()| {
+        let shared = cr.data_mut::<Arc<Shared>>(ctx.path()).cloned();
+
+        async move {
+            let result = match shared {
+                Some(shared) => shared.get_firmware().await,
+                None => Err(MethodErr::no_path(ctx.path())),
+            };
+
+            ctx.reply(result.map(|fw| firmware_to_dbus(&fw)))
+        }
+    });
+
+    b.method("Ring", (), (), |_, shared: &mut Arc<Shared>, (): ()| {
+        shared.ringer.start().map_err(|e| MethodErr::failed(&e))
+    });
+
+    b.method("StopRinging", (), ("stopped",), |_, shared: &mut Arc<Shared>, (): ()| {
+        Ok((shared.ringer.stop(),))
+    });
+}
@@ -0,0 +1,432 @@
+//! JSON-RPC control API on a Unix socket.
+//!
+//! Messages are newline-delimited JSON-RPC 2.0 objects. Supported methods:
+//!
+//! - `get` (`{"setting": name}`): Read a setting, returns its value.
+//! - `set` (`{"setting": name, "value": value}`): Write a setting.
+//! - `show` (`{"what": "battery" | "status"}`): Return battery or full device
+//!   status.
+//! - `subscribe`, `unsubscribe`: Enable or disable `event` notifications for
+//!   device events on this connection.
+//!
+//! Settings names and value types are the same as for the D-Bus interface,
+//! with D-Bus structs represented as JSON arrays.
+
+use std::collections::VecDeque;
+use std::os::fd::{FromRawFd, RawFd};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use anyhow::Result;
+
+use dbus::arg::{ArgType, RefArg};
+
+use futures::channel::{mpsc, oneshot};
+
+use serde_json::{json, Value};
+
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{UnixListener, UnixStream};
+use tokio::sync::broadcast;
+
+use maestro::service::settings::SettingValue;
+
+use super::event::Event;
+use super::idle::Activity;
+use super::server::Request;
+use super::state::{Battery, SharedState, State};
+use super::value;
+
+
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+const DEVICE_ERROR: i64 = -32000;
+
+
+/// Default socket location, i.e., `$XDG_RUNTIME_DIR/pbpctrl.sock`.
+pub fn default_path() -> Option<PathBuf> {
+    std::env::var_os("XDG_RUNTIME_DIR")
+        .filter(|dir| !dir.is_empty())
+        .map(|dir| PathBuf::from(dir).join("pbpctrl.sock"))
+}
+
+
+pub struct Context {
+    pub state: SharedState,
+    pub requests: mpsc::UnboundedSender<Request>,
+    pub events: broadcast::Sender<Event>,
+    pub activity: Activity,
+}
+
+
+pub struct SocketServer {
+    listener: UnixListener,
+    path: Option<PathBuf>,
+    ctx: Arc<Context>,
+}
+
+impl SocketServer {
+    /// Bind to the given path, replacing any stale socket file. The socket
+    /// file is removed again when the server is dropped.
+    pub fn bind(path: &Path, ctx: Context) -> Result<Self> {
+        match std::fs::remove_file(path) {
+            Ok(()) => tracing::debug!(path=%path.display(), "removed stale socket"),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {},
+            Err(err) => return Err(err.into()),
+        }
+
+        let listener = UnixListener::bind(path)?;
+        tracing::debug!(path=%path.display(), "listening on socket");
+
+        Ok(Self { listener, path: Some(path.to_owned()), ctx: Arc::new(ctx) })
+    }
+
+    /// Take over the listening socket passed by systemd via socket activation,
+    /// if any.
+    pub fn from_systemd(ctx: Context) -> Result<Option<Self>> {
+        // see sd_listen_fds(3)
+        const SD_LISTEN_FDS_START: RawFd = 3;
+
+        let pid = std::env::var("LISTEN_PID").ok().and_then(|pid| pid.parse::<u32>().ok());
+        if pid != Some(std::process::id()) {
+            return Ok(None);
+        }
+
+        let fds = std::env::var("LISTEN_FDS").ok().and_then(|n| n.parse::<u32>().ok());
+        match fds {
+            None | Some(0) => return Ok(None),
+            Some(1) => {},
+            Some(n) => tracing::warn!("received {n} sockets via socket activation, using only the first one"),
+        }
+
+        // SAFETY: systemd passes ownership of the listening socket starting at
+        // SD_LISTEN_FDS_START, and we take it over exactly once.
+        let listener = unsafe { std::os::unix::net::UnixListener::from_raw_fd(SD_LISTEN_FDS_START) };
+        listener.set_nonblocking(true)?;
+
+        let listener = UnixListener::from_std(listener)?;
+        tracing::debug!("using socket from socket activation");
+
+        Ok(Some(Self { listener, path: None, ctx: Arc::new(ctx) }))
+    }
+
+    pub async fn run(self) {
+        loop {
+            let stream = match self.listener.accept().await {
+                Ok((stream, _addr)) => stream,
+                Err(err) => {
+                    tracing::warn!(error=%err, "failed to accept socket connection");
+                    continue;
+                },
+            };
+
+            tracing::debug!("accepted socket connection");
+
+            let ctx = self.ctx.clone();
+            tokio::spawn(async move {
+                let _client = ctx.activity.client();
+
+                if let Err(err) = handle_connection(stream, &ctx).await {
+                    tracing::debug!(error=%err, "socket connection terminated with error");
+                }
+            });
+        }
+    }
+}
+
+impl Drop for SocketServer {
+    fn drop(&mut self) {
+        if let Some(path) = &self.path {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+}
+
+
+async fn handle_connection(stream: UnixStream, ctx: &Context) -> Result<()> {
+    let (rx, mut tx) = stream.into_split();
+    let mut lines = BufReader::new(rx).lines();
+    let mut events: Option<broadcast::Receiver<Event>> = None;
+
+    loop {
+        let message = tokio::select! {
+            line = lines.next_line() => {
+                let Some(line) = line? else { return Ok(()) };
+
+                if line.trim().is_empty() {
+                    continue;
+                }
+
+                ctx.activity.touch();
+                handle_message(ctx, &line, &mut events).await
+            },
+            event = next_event(&mut events) => {
+                json!({
+                    "jsonrpc": "2.0",
+                    "method": "event",
+                    "params": event_to_json(&event),
+                })
+            },
+        };
+
+        let mut data = serde_json::to_vec(&message)?;
+        data.push(b'\n');
+
+        tx.write_all(&data).await?;
+    }
+}
+
+async fn next_event(events: &mut Option<broadcast::Receiver<Event>>) -> Event {
+    let Some(rx) = events else {
+        return std::future::pending().await;
+    };
+
+    loop {
+        match rx.recv().await {
+            Ok(event) => return event,
+            Err(broadcast::error::RecvError::Lagged(n)) => {
+                tracing::warn!("socket client lagging, dropped {n} events");
+            },
+            Err(broadcast::error::RecvError::Closed) => {
+                *events = None;
+                return std::future::pending().await;
+            },
+        }
+    }
+}
+
+async fn handle_message(ctx: &Context, line: &str, events: &mut Option<broadcast::Receiver<Event>>) -> Value {
+    let msg: Value = match serde_json::from_str(line) {
+        Ok(msg) => msg,
+        Err(err) => return error_response(Value::Null, PARSE_ERROR, &err.to_string()),
+    };
+
+    let id = msg.get("id").cloned().unwrap_or(Value::Null);
+
+    let Some(method) = msg.get("method").and_then(|m| m.as_str()) else {
+        return error_response(id, INVALID_REQUEST, "missing method");
+    };
+
+    let params = msg.get("params").cloned().unwrap_or(json!({}));
+
+    let result = match method {
+        "get" => method_get(ctx, &params).await,
+        "set" => method_set(ctx, &params).await,
+        "show" => method_show(ctx, &params),
+        "subscribe" => {
+            *events = Some(ctx.events.subscribe());
+            Ok(Value::Bool(true))
+        },
+        "unsubscribe" => {
+            *events = None;
+            Ok(Value::Bool(true))
+        },
+        _ => Err((METHOD_NOT_FOUND, format!("unknown method '{method}'"))),
+    };
+
+    match result {
+        Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+        Err((code, message)) => error_response(id, code, &message),
+    }
+}
+
+fn error_response(id: Value, code: i64, message: &str) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "error": { "code": code, "message": message },
+    })
+}
+
+async fn method_get(ctx: &Context, params: &Value) -> Result<Value, (i64, String)> {
+    let name = get_param_str(params, "setting")?;
+    let id = value::setting_id(name)
+        .ok_or_else(|| (INVALID_PARAMS, format!("unknown setting '{name}'")))?;
+
+    let (reply, rx) = oneshot::channel();
+    let value = submit(ctx, Request::GetSetting { id, reply }, rx).await?;
+
+    Ok(setting_to_json(&value))
+}
+
+async fn method_set(ctx: &Context, params: &Value) -> Result<Value, (i64, String)> {
+    let name = get_param_str(params, "setting")?;
+    let id = value::setting_id(name)
+        .ok_or_else(|| (INVALID_PARAMS, format!("unknown setting '{name}'")))?;
+
+    let value = params.get("value")
+        .ok_or_else(|| (INVALID_PARAMS, "missing parameter 'value'".to_owned()))?;
+
+    let value = json_to_refarg(value)
+        .and_then(|v| value::from_variant(id, &*v))
+        .map_err(|e| (INVALID_PARAMS, format!("invalid value for setting '{name}': {e}")))?;
+
+    let (reply, rx) = oneshot::channel();
+    submit(ctx, Request::SetSetting { value, reply }, rx).await?;
+
+    Ok(Value::Null)
+}
+
+fn method_show(ctx: &Context, params: &Value) -> Result<Value, (i64, String)> {
+    let state = ctx.state.get();
+
+    match params.get("what").and_then(|w| w.as_str()).unwrap_or("status") {
+        "battery" => Ok(battery_state_to_json(&state)),
+        "status" => Ok(state_to_json(&state)),
+        other => Err((INVALID_PARAMS, format!("unknown value for 'what': '{other}'"))),
+    }
+}
+
+async fn submit<T>(ctx: &Context, req: Request, rx: oneshot::Receiver<Result<T, String>>) -> Result<T, (i64, String)> {
+    ctx.requests.unbounded_send(req)
+        .map_err(|_| (DEVICE_ERROR, "daemon is shutting down".to_owned()))?;
+
+    rx.await
+        .map_err(|_| (DEVICE_ERROR, "request has been dropped".to_owned()))?
+        .map_err(|e| (DEVICE_ERROR, e))
+}
+
+fn get_param_str<'a>(params: &'a Value, key: &str) -> Result<&'a str, (i64, String)> {
+    params.get(key)
+        .and_then(|v| v.as_str())
+        .ok_or_else(|| (INVALID_PARAMS, format!("missing or invalid parameter '{key}'")))
+}
+
+
+fn battery_to_json(battery: &Battery) -> Value {
+    match battery.level {
+        Some(level) => json!({ "level": level, "state": battery.state_str() }),
+        None => Value::Null,
+    }
+}
+
+fn battery_state_to_json(state: &State) -> Value {
+    json!({
+        "case": battery_to_json(&state.battery_case),
+        "left": battery_to_json(&state.battery_left),
+        "right": battery_to_json(&state.battery_right),
+    })
+}
+
+fn state_to_json(state: &State) -> Value {
+    let placement = match state.placement {
+        Some((left, right)) => json!({ "left_in_case": left, "right_in_case": right }),
+        None => Value::Null,
+    };
+
+    json!({
+        "connected": state.connected,
+        "battery": battery_state_to_json(state),
+        "placement": placement,
+    })
+}
+
+fn event_to_json(event: &Event) -> Value {
+    let mut obj = json!({ "event": event.name() });
+
+    match event {
+        Event::BudRemoved(bud) | Event::BudInserted(bud) => {
+            obj["bud"] = json!(bud.as_str());
+        },
+        Event::BatteryChanged { component, level, previous } => {
+            obj["component"] = json!(component.as_str());
+            obj["level"] = json!(level);
+            obj["previous"] = json!(previous);
+        },
+        Event::SettingChanged(value) => {
+            obj["setting"] = json!(value.id().as_str());
+            obj["value"] = setting_to_json(value);
+        },
+        Event::FirmwareMismatch { left, right } => {
+            obj["left"] = json!(left);
+            obj["right"] = json!(right);
+        },
+        Event::SourceSwitched { source } => {
+            obj["source"] = json!(source);
+        },
+        _ => {},
+    }
+
+    obj
+}
+
+fn setting_to_json(value: &SettingValue) -> Value {
+    refarg_to_json(&*value::to_variant(value).0)
+}
+
+fn refarg_to_json(value: &dyn RefArg) -> Value {
+    match value.arg_type() {
+        ArgType::Boolean => json!(value.as_u64() != Some(0)),
+        ArgType::Double => json!(value.as_f64()),
+        ArgType::String => json!(value.as_str()),
+        ArgType::Struct | ArgType::Array => {
+            let items = value.as_iter()
+                .map(|it| it.map(refarg_to_json).collect())
+                .unwrap_or_default();
+
+            Value::Array(items)
+        },
+        _ => value.as_i64().map(Value::from).unwrap_or(Value::Null),
+    }
+}
+
+fn json_to_refarg(value: &Value) -> Result<Box<dyn RefArg>, String> {
+    match value {
+        Value::Bool(x) => Ok(Box::new(*x)),
+        Value::Number(x) => {
+            if let Some(x) = x.as_i64() {
+                let x = i32::try_from(x).map_err(|e| e.to_string())?;
+                Ok(Box::new(x))
+            } else {
+                Ok(Box::new(x.as_f64().unwrap_or_default()))
+            }
+        },
+        Value::String(x) => Ok(Box::new(x.clone())),
+        Value::Array(x) => {
+            let items = x.iter()
+                .map(json_to_refarg)
+                .collect::<Result<VecDeque<_>, _>>()?;
+
+            Ok(Box::new(items))
+        },
+        _ => Err("unsupported type".to_owned()),
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use maestro::service::settings::{AncState, EqBands, GestureControl, RegularActionTarget};
+
+    #[test]
+    fn test_json_roundtrip() {
+        let values = [
+            SettingValue::SumToMono(false),
+            SettingValue::CurrentAncrState(AncState::Active),
+            SettingValue::CurrentUserEq(EqBands::new(1.0, 0.5, 0.0, -0.5, -1.0)),
+            SettingValue::GestureControl(GestureControl {
+                left: RegularActionTarget::AssistantQuery,
+                right: RegularActionTarget::AncControl,
+            }),
+        ];
+
+        for value in values {
+            let json = setting_to_json(&value);
+            let var = json_to_refarg(&json).unwrap();
+            assert_eq!(value::from_variant(value.id(), &*var), Ok(value));
+        }
+    }
+
+    #[test]
+    fn test_json_values() {
+        let value = SettingValue::CurrentAncrState(AncState::Aware);
+        assert_eq!(setting_to_json(&value), json!("aware"));
+
+        let value = SettingValue::CurrentUserEq(EqBands::new(1.0, 0.5, 0.0, -0.5, -1.0));
+        assert_eq!(setting_to_json(&value), json!([1.0, 0.5, 0.0, -0.5, -1.0]));
+    }
+}
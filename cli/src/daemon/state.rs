@@ -0,0 +1,60 @@
+//! Device state shared between the device connection and the daemon
+//! interfaces.
+
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use maestro::protocol::types::DeviceBatteryInfo;
+
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Battery {
+    pub level: Option<i32>,
+    pub state: i32,
+}
+
+impl Battery {
+    pub fn from_info(info: Option<&DeviceBatteryInfo>) -> Self {
+        Self {
+            level: info.map(|b| b.level),
+            state: info.map(|b| b.state).unwrap_or(0),
+        }
+    }
+
+    pub fn state_str(&self) -> &'static str {
+        match self.state {
+            2 => "charging",
+            1 => "not-charging",
+            _ => "unknown",
+        }
+    }
+}
+
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct State {
+    pub connected: bool,
+    pub battery_case: Battery,
+    pub battery_left: Battery,
+    pub battery_right: Battery,
+    pub placement: Option<(bool, bool)>,
+}
+
+
+#[derive(Debug, Default, Clone)]
+pub struct SharedState {
+    inner: Arc<Mutex<State>>,
+}
+
+impl SharedState {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn lock(&self) -> MutexGuard<'_, State> {
+        self.inner.lock().unwrap()
+    }
+
+    pub fn get(&self) -> State {
+        *self.lock()
+    }
+}
@@ -0,0 +1,272 @@
+use dbus::arg::{ArgType, RefArg, Variant};
+
+use maestro::service::settings::{
+    AncState, AncrGestureLoop, EqBands, GestureControl, RegularActionTarget, SettingId,
+    SettingValue, VolumeAsymmetry,
+};
+
+
+pub type Value = Variant<Box<dyn RefArg>>;
+
+
+/// Settings that can be read and written via the D-Bus interface.
+pub const SETTINGS: [SettingId; 17] = [
+    SettingId::AutoOtaEnable,
+    SettingId::OhdEnable,
+    SettingId::OobeIsFinished,
+    SettingId::GestureEnable,
+    SettingId::DiagnosticsEnable,
+    SettingId::OobeMode,
+    SettingId::GestureControl,
+    SettingId::MultipointEnable,
+    SettingId::AncrGestureLoop,
+    SettingId::CurrentAncrState,
+    SettingId::OttsMode,
+    SettingId::VolumeEqEnable,
+    SettingId::CurrentUserEq,
+    SettingId::VolumeAsymmetry,
+    SettingId::SumToMono,
+    SettingId::VolumeExposureNotifications,
+    SettingId::SpeechDetection,
+];
+
+const ACTIONS: [RegularActionTarget; 6] = [
+    RegularActionTarget::CheckNotifications,
+    RegularActionTarget::PreviousTrackRepeat,
+    RegularActionTarget::NextTrack,
+    RegularActionTarget::PlayPauseTrack,
+    RegularActionTarget::AncControl,
+    RegularActionTarget::AssistantQuery,
+];
+
+const ANC_STATES: [AncState; 3] = [
+    AncState::Off,
+    AncState::Active,
+    AncState::Aware,
+];
+
+
+pub fn setting_id(name: &str) -> Option<SettingId> {
+    SETTINGS.iter().copied().find(|id| id.as_str() == name)
+}
+
+/// Convert a settings value to its D-Bus representation.
+///
+/// Boolean settings map to `b`, the ANC state to `s`, the gesture control
+/// actions to `(ss)` (left, right), the ANC gesture loop to `(bbb)` (active,
+/// off, aware), the EQ to `(ddddd)`, and the volume balance to `i` (-100 to
+/// 100). Values not known here are represented by their string form, `s`.
+pub fn to_variant(value: &SettingValue) -> Value {
+    let value: Box<dyn RefArg> = match value {
+        SettingValue::AutoOtaEnable(x) => Box::new(*x),
+        SettingValue::OhdEnable(x) => Box::new(*x),
+        SettingValue::OobeIsFinished(x) => Box::new(*x),
+        SettingValue::GestureEnable(x) => Box::new(*x),
+        SettingValue::DiagnosticsEnable(x) => Box::new(*x),
+        SettingValue::OobeMode(x) => Box::new(*x),
+        SettingValue::GestureControl(x) => {
+            Box::new((x.left.as_str().to_owned(), x.right.as_str().to_owned()))
+        },
+        SettingValue::MultipointEnable(x) => Box::new(*x),
+        SettingValue::AncrGestureLoop(x) => Box::new((x.active, x.off, x.aware)),
+        SettingValue::CurrentAncrState(x) => Box::new(x.as_str().to_owned()),
+        SettingValue::OttsMode(x) => Box::new(*x),
+        SettingValue::VolumeEqEnable(x) => Box::new(*x),
+        SettingValue::CurrentUserEq(x) => Box::new((
+            x.low_bass() as f64,
+            x.bass() as f64,
+            x.mid() as f64,
+            x.treble() as f64,
+            x.upper_treble() as f64,
+        )),
+        SettingValue::VolumeAsymmetry(x) => Box::new(x.value()),
+        SettingValue::SumToMono(x) => Box::new(*x),
+        SettingValue::VolumeExposureNotifications(x) => Box::new(*x),
+        SettingValue::SpeechDetection(x) => Box::new(*x),
+        value => Box::new(value.to_string()),
+    };
+
+    Variant(value)
+}
+
+/// Parse a settings value from its D-Bus representation.
+///
+/// See [`to_variant`] for the expected types.
+pub fn from_variant(id: SettingId, value: &dyn RefArg) -> Result<SettingValue, String> {
+    let value = match id {
+        SettingId::AutoOtaEnable => SettingValue::AutoOtaEnable(get_bool(value)?),
+        SettingId::OhdEnable => SettingValue::OhdEnable(get_bool(value)?),
+        SettingId::OobeIsFinished => SettingValue::OobeIsFinished(get_bool(value)?),
+        SettingId::GestureEnable => SettingValue::GestureEnable(get_bool(value)?),
+        SettingId::DiagnosticsEnable => SettingValue::DiagnosticsEnable(get_bool(value)?),
+        SettingId::OobeMode => SettingValue::OobeMode(get_bool(value)?),
+        SettingId::GestureControl => {
+            let [left, right] = get_struct(value)?;
+
+            let left = get_action(left)?;
+            let right = get_action(right)?;
+
+            SettingValue::GestureControl(GestureControl { left, right })
+        },
+        SettingId::MultipointEnable => SettingValue::MultipointEnable(get_bool(value)?),
+        SettingId::AncrGestureLoop => {
+            let [active, off, aware] = get_struct(value)?;
+
+            let value = AncrGestureLoop {
+                active: get_bool(active)?,
+                off: get_bool(off)?,
+                aware: get_bool(aware)?,
+            };
+
+            if !value.is_valid() {
+                return Err("at least two modes need to be enabled".to_owned());
+            }
+
+            SettingValue::AncrGestureLoop(value)
+        },
+        SettingId::CurrentAncrState => {
+            let value = get_str(value)?;
+
+            let state = ANC_STATES.iter().copied()
+                .find(|s| s.as_str() == value)
+                .ok_or_else(|| format!("invalid ANC state: '{value}'"))?;
+
+            SettingValue::CurrentAncrState(state)
+        },
+        SettingId::OttsMode => SettingValue::OttsMode(get_i32(value)?),
+        SettingId::VolumeEqEnable => SettingValue::VolumeEqEnable(get_bool(value)?),
+        SettingId::CurrentUserEq => {
+            let [low_bass, bass, mid, treble, upper_treble] = get_struct(value)?;
+
+            let bands = EqBands::new(
+                get_eq_value(low_bass)?,
+                get_eq_value(bass)?,
+                get_eq_value(mid)?,
+                get_eq_value(treble)?,
+                get_eq_value(upper_treble)?,
+            );
+
+            SettingValue::CurrentUserEq(bands)
+        },
+        SettingId::VolumeAsymmetry => {
+            let value = get_i32(value)?;
+
+            if !(-100..=100).contains(&value) {
+                return Err(format!("volume balance out of range: {value}"));
+            }
+
+            SettingValue::VolumeAsymmetry(VolumeAsymmetry::from_normalized(value))
+        },
+        SettingId::SumToMono => SettingValue::SumToMono(get_bool(value)?),
+        SettingId::VolumeExposureNotifications => {
+            SettingValue::VolumeExposureNotifications(get_bool(value)?)
+        },
+        SettingId::SpeechDetection => SettingValue::SpeechDetection(get_bool(value)?),
+        _ => return Err(format!("unsupported setting: {id}")),
+    };
+
+    Ok(value)
+}
+
+fn get_bool(value: &dyn RefArg) -> Result<bool, String> {
+    match value.arg_type() {
+        ArgType::Boolean => Ok(value.as_u64() != Some(0)),
+        _ => Err(type_error("b", value)),
+    }
+}
+
+fn get_i32(value: &dyn RefArg) -> Result<i32, String> {
+    match value.arg_type() {
+        ArgType::Int32 => value.as_i64().map(|x| x as i32).ok_or_else(|| type_error("i", value)),
+        _ => Err(type_error("i", value)),
+    }
+}
+
+fn get_str(value: &dyn RefArg) -> Result<&str, String> {
+    match value.arg_type() {
+        ArgType::String => value.as_str().ok_or_else(|| type_error("s", value)),
+        _ => Err(type_error("s", value)),
+    }
+}
+
+fn get_f64(value: &dyn RefArg) -> Result<f64, String> {
+    match value.arg_type() {
+        ArgType::Double | ArgType::Int32 => value.as_f64().ok_or_else(|| type_error("d", value)),
+        _ => Err(type_error("d", value)),
+    }
+}
+
+fn get_struct<const N: usize>(value: &dyn RefArg) -> Result<[&dyn RefArg; N], String> {
+    if value.arg_type() != ArgType::Struct {
+        return Err(type_error("a struct", value));
+    }
+
+    let items: Vec<_> = value.as_iter()
+        .ok_or_else(|| type_error("a struct", value))?
+        .collect();
+
+    items.try_into()
+        .map_err(|items: Vec<_>| format!("expected {N} struct fields, got {}", items.len()))
+}
+
+fn get_action(value: &dyn RefArg) -> Result<RegularActionTarget, String> {
+    let value = get_str(value)?;
+
+    ACTIONS.iter().copied()
+        .find(|a| a.as_str() == value)
+        .ok_or_else(|| format!("invalid gesture action: '{value}'"))
+}
+
+fn get_eq_value(value: &dyn RefArg) -> Result<f32, String> {
+    let value = get_f64(value)? as f32;
+
+    if value > EqBands::MAX_VALUE {
+        Err(format!("exceeds maximum of {}", EqBands::MAX_VALUE))
+    } else if value < EqBands::MIN_VALUE {
+        Err(format!("exceeds minimum of {}", EqBands::MIN_VALUE))
+    } else {
+        Ok(value)
+    }
+}
+
+fn type_error(expected: &str, value: &dyn RefArg) -> String {
+    format!("invalid type: expected {expected}, got '{}'", value.signature())
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_variant_roundtrip() {
+        let values = [
+            SettingValue::OhdEnable(true),
+            SettingValue::GestureControl(GestureControl {
+                left: RegularActionTarget::AncControl,
+                right: RegularActionTarget::AssistantQuery,
+            }),
+            SettingValue::AncrGestureLoop(AncrGestureLoop { active: true, off: false, aware: true }),
+            SettingValue::CurrentAncrState(AncState::Aware),
+            SettingValue::CurrentUserEq(EqBands::new(-6.0, -1.5, 0.0, 2.25, 6.0)),
+            SettingValue::VolumeAsymmetry(VolumeAsymmetry::from_normalized(-42)),
+        ];
+
+        for value in values {
+            let var = to_variant(&value);
+            assert_eq!(from_variant(value.id(), &*var.0), Ok(value));
+        }
+    }
+
+    #[test]
+    fn test_variant_invalid() {
+        let var = to_variant(&SettingValue::OhdEnable(true));
+        assert!(from_variant(SettingId::CurrentAncrState, &*var.0).is_err());
+
+        let var: Value = Variant(Box::new((true, false, false)));
+        assert!(from_variant(SettingId::AncrGestureLoop, &*var.0).is_err());
+
+        let var: Value = Variant(Box::new(101));
+        assert!(from_variant(SettingId::VolumeAsymmetry, &*var.0).is_err());
+    }
+}
@@ -0,0 +1,301 @@
+//! Step-by-step diagnostics of the connection to the device.
+//!
+//! Walks through the stages of connecting, from the Bluetooth adapter to the
+//! resolved Maestro channel, and stops at the first stage that fails, as all
+//! later ones depend on it. Each failed stage comes with a hint on what to
+//! check.
+
+use std::time::Duration;
+
+use bluer::{Address, Session};
+use bluer::rfcomm::{Profile, Role};
+
+use serde_json::{json, Value};
+
+use maestro::protocol::codec::Codec;
+use maestro::protocol::utils;
+use maestro::pwrpc::client::Client;
+use maestro::service::format::Formatter;
+
+use crate::cli::ConnectMode;
+use crate::render::Output;
+use crate::transport::{self, Transport};
+
+
+/// Result of a single stage.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Outcome {
+    Pass { detail: String },
+    Fail { detail: String, hint: String },
+
+    /// Not checked, as an earlier stage has failed.
+    Skipped,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Check {
+    pub stage: &'static str,
+    pub outcome: Outcome,
+}
+
+
+/// Results of all stages, in the order they have been checked.
+#[derive(Debug, Clone, Default)]
+pub struct Report {
+    pub checks: Vec<Check>,
+}
+
+impl Report {
+    pub fn failed(&self) -> bool {
+        self.checks.iter().any(|c| matches!(c.outcome, Outcome::Fail { .. }))
+    }
+
+    fn pass(&mut self, stage: &'static str, detail: impl Into<String>) {
+        self.checks.push(Check { stage, outcome: Outcome::Pass { detail: detail.into() } });
+    }
+
+    /// Record a failed stage and skip the given remaining ones.
+    fn fail(mut self, stage: &'static str, detail: impl Into<String>, hint: impl Into<String>, skipped: &[&'static str])
+        -> Self
+    {
+        let outcome = Outcome::Fail { detail: detail.into(), hint: hint.into() };
+        self.checks.push(Check { stage, outcome });
+
+        for stage in skipped {
+            self.checks.push(Check { stage, outcome: Outcome::Skipped });
+        }
+
+        self
+    }
+}
+
+impl Output for Report {
+    fn human(&self, _fmt: &Formatter) -> String {
+        let width = self.checks.iter().map(|c| c.stage.len() + 1).max().unwrap_or_default();
+        let mut lines = Vec::new();
+
+        for check in &self.checks {
+            let stage = format!("{}:", check.stage);
+
+            match &check.outcome {
+                Outcome::Pass { detail } => lines.push(format!("[ ok ] {stage:width$} {detail}")),
+                Outcome::Fail { detail, hint } => {
+                    lines.push(format!("[fail] {stage:width$} {detail}"));
+                    lines.push(format!("       hint: {hint}"));
+                },
+                Outcome::Skipped => lines.push(format!("[skip] {stage:width$} not checked")),
+            }
+        }
+
+        lines.join("\n")
+    }
+
+    fn json(&self) -> Value {
+        let checks: Vec<_> = self.checks.iter()
+            .map(|check| match &check.outcome {
+                Outcome::Pass { detail } => json!({ "stage": check.stage, "result": "pass", "detail": detail }),
+                Outcome::Fail { detail, hint } => {
+                    json!({ "stage": check.stage, "result": "fail", "detail": detail, "hint": hint })
+                },
+                Outcome::Skipped => json!({ "stage": check.stage, "result": "skipped" }),
+            })
+            .collect();
+
+        json!({ "checks": checks, "failed": self.failed() })
+    }
+}
+
+
+const ADAPTER: &str = "bluetooth adapter";
+const DEVICE: &str = "device";
+const PAIRED: &str = "paired";
+const CONNECTED: &str = "connected";
+const SERVICE: &str = "maestro service";
+const PROFILE: &str = "profile registration";
+const RFCOMM: &str = "rfcomm connection";
+const FRAMES: &str = "first frame received";
+const CHANNEL: &str = "channel resolved";
+
+
+/// Check all stages of connecting to the device with the given address, or
+/// to the first compatible device if unspecified.
+pub async fn run(address: Option<Address>, adapter: Option<&str>, mode: ConnectMode, timeout: Duration) -> Report {
+    let mut report = Report::default();
+
+    // adapter
+    let session = match Session::new().await {
+        Ok(session) => session,
+        Err(err) => {
+            return report.fail(ADAPTER, format!("cannot connect to BlueZ: {err}"),
+                "make sure the bluetooth service is running, e.g. via 'systemctl status bluetooth'",
+                &[DEVICE, PAIRED, CONNECTED, SERVICE, PROFILE, RFCOMM, FRAMES, CHANNEL]);
+        },
+    };
+
+    let adapter = match transport::bluez::open_adapter(&session, adapter).await {
+        Ok(adapter) => adapter,
+        Err(err) => {
+            return report.fail(ADAPTER, format!("no adapter found: {err}"),
+                "make sure a bluetooth adapter is present and not blocked, e.g. via 'rfkill list'",
+                &[DEVICE, PAIRED, CONNECTED, SERVICE, PROFILE, RFCOMM, FRAMES, CHANNEL]);
+        },
+    };
+
+    if !adapter.is_powered().await.unwrap_or(false) {
+        return report.fail(ADAPTER, format!("{} is powered off", adapter.name()),
+            "power it on, e.g. via 'bluetoothctl power on'",
+            &[DEVICE, PAIRED, CONNECTED, SERVICE, PROFILE, RFCOMM, FRAMES, CHANNEL]);
+    }
+
+    report.pass(ADAPTER, format!("{} (powered)", adapter.name()));
+
+    // device
+    let transport = match transport::Platform::open(address, Some(adapter.name())).await {
+        Ok(transport) => transport.with_connect_mode(mode),
+        Err(err) => {
+            return report.fail(DEVICE, err.to_string(),
+                "pair the buds via 'pbpctrl pair', or select the device via '--device', see 'pbpctrl devices'",
+                &[PAIRED, CONNECTED, SERVICE, PROFILE, RFCOMM, FRAMES, CHANNEL]);
+        },
+    };
+
+    let device = match adapter.device(transport.address()) {
+        Ok(device) => device,
+        Err(err) => {
+            return report.fail(DEVICE, err.to_string(), "select the device via '--device', see 'pbpctrl devices'",
+                &[PAIRED, CONNECTED, SERVICE, PROFILE, RFCOMM, FRAMES, CHANNEL]);
+        },
+    };
+
+    report.pass(DEVICE, transport.identity().to_string());
+
+    if !device.is_paired().await.unwrap_or(false) {
+        return report.fail(PAIRED, "no", "pair the buds via 'pbpctrl pair'",
+            &[CONNECTED, SERVICE, PROFILE, RFCOMM, FRAMES, CHANNEL]);
+    }
+
+    report.pass(PAIRED, "yes");
+
+    if !device.is_connected().await.unwrap_or(false) {
+        return report.fail(CONNECTED, "no",
+            format!("take the buds out of the case, or connect them via 'bluetoothctl connect {}'", device.address()),
+            &[SERVICE, PROFILE, RFCOMM, FRAMES, CHANNEL]);
+    }
+
+    report.pass(CONNECTED, "yes");
+
+    let uuids = device.uuids().await.ok().flatten().unwrap_or_default();
+    if !uuids.contains(&maestro::UUID) {
+        return report.fail(SERVICE, "not advertised",
+            "the device may not be supported, or its services have not been resolved yet; try reconnecting it",
+            &[PROFILE, RFCOMM, FRAMES, CHANNEL]);
+    }
+
+    report.pass(SERVICE, "advertised");
+
+    // the profile is registered again when connecting, this only tells
+    // registration failures apart from connection failures
+    match mode {
+        ConnectMode::Profile => {
+            let profile = Profile {
+                uuid: maestro::UUID,
+                role: Some(Role::Client),
+                auto_connect: Some(false),
+                ..Default::default()
+            };
+
+            match session.register_profile(profile).await {
+                Ok(handle) => {
+                    drop(handle);
+                    report.pass(PROFILE, "ok");
+                },
+                Err(err) => {
+                    return report.fail(PROFILE, err.to_string(),
+                        "another application may have registered the profile, e.g. a running pbpctrl daemon; \
+                        stop it or try '--connect-mode raw'",
+                        &[RFCOMM, FRAMES, CHANNEL]);
+                },
+            }
+        },
+        ConnectMode::Raw => report.pass(PROFILE, "not required in raw mode"),
+    }
+
+    // connection
+    let stream = match tokio::time::timeout(timeout, transport.connect()).await {
+        Ok(Ok(stream)) => stream,
+        Ok(Err(err)) => {
+            return report.fail(RFCOMM, err.to_string(), "try '--connect-mode raw', or reconnect the device",
+                &[FRAMES, CHANNEL]);
+        },
+        Err(_) => {
+            return report.fail(RFCOMM, "timed out", "try '--connect-mode raw', or reconnect the device",
+                &[FRAMES, CHANNEL]);
+        },
+    };
+
+    report.pass(RFCOMM, "connected");
+
+    let codec = Codec::new();
+    let stats = codec.stats();
+    let mut client = Client::new(codec.wrap(stream));
+
+    let resolve = utils::resolve_channel_probing(&mut client, None, (timeout / 2).min(Duration::from_secs(3)));
+    let channel = tokio::time::timeout(timeout, resolve).await;
+
+    let stats = stats.get();
+    if stats.frames == 0 {
+        let detail = match stats.bytes {
+            0 => "no data received".to_owned(),
+            n => format!("received {n} bytes, but no valid frames ({} checksum errors)", stats.crc_errors),
+        };
+
+        return report.fail(FRAMES, detail,
+            "make sure the buds are out of the case, or record the traffic via '--capture <file>' when reporting this",
+            &[CHANNEL]);
+    }
+
+    report.pass(FRAMES, format!("{} frames", stats.frames));
+
+    let report = match channel {
+        Ok(Ok(channel)) => {
+            report.pass(CHANNEL, format!("channel {channel}"));
+            report
+        },
+        Ok(Err(err)) => report.fail(CHANNEL, err.to_string(), "reconnect the device and try again", &[]),
+        Err(_) => report.fail(CHANNEL, "timed out",
+            "the device sends data but did not respond; record the traffic via '--capture <file>' when reporting this",
+            &[]),
+    };
+
+    if let Err(err) = client.terminate().await {
+        tracing::debug!(error=?err, "failed to terminate client");
+    }
+
+    report
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_report() {
+        let mut report = Report::default();
+        report.pass(ADAPTER, "hci0 (powered)");
+
+        let report = report.fail(CONNECTED, "no", "take the buds out of the case", &[SERVICE]);
+        assert!(report.failed());
+
+        assert_eq!(report.human(&Formatter::english()), "\
+            [ ok ] bluetooth adapter: hci0 (powered)\n\
+            [fail] connected:         no\n       \
+            hint: take the buds out of the case\n\
+            [skip] maestro service:   not checked");
+
+        let json = report.json();
+        assert_eq!(json["checks"][1]["result"], "fail");
+        assert_eq!(json["checks"][2], json!({ "stage": "maestro service", "result": "skipped" }));
+        assert_eq!(json["failed"], true);
+    }
+}
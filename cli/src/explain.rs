@@ -0,0 +1,118 @@
+//! Human-readable explanations for RPC status errors.
+//!
+//! The generic status descriptions of pw_rpc say little about what went wrong
+//! with a specific operation. This maps the status codes returned by the
+//! device for each method, as far as they are known, to explanations of their
+//! typical cause.
+
+use maestro::pwrpc::{self, Status};
+
+
+/// Known explanations, by `service/method` path and status. A method of `*`
+/// matches all methods of the service, a path of `*` matches any method. The
+/// first match wins, so more specific entries go first.
+const EXPLANATIONS: &[(&str, Status, &str)] = &[
+    ("maestro_pw.Maestro/ReadSetting", Status::Unknown,
+        "the setting is not supported by the firmware of the device"),
+    ("maestro_pw.Maestro/ReadSetting", Status::InvalidArgument,
+        "the device does not know this setting, it may require a newer firmware version"),
+    ("maestro_pw.Maestro/WriteSetting", Status::InvalidArgument,
+        "the device rejected the value, it may not be supported by this firmware version"),
+    ("maestro_pw.Maestro/WriteSetting", Status::FailedPrecondition,
+        "the setting cannot be changed in the current state, e.g. because a bud is in the case"),
+    ("maestro_pw.Maestro/WriteSetting", Status::Unknown,
+        "the setting is not supported by the firmware of the device"),
+    ("maestro_pw.Maestro/SubscribeToOobeActions", Status::FailedPrecondition,
+        "gestures cannot be intercepted in the current state, e.g. because a bud is in the case"),
+    ("maestro_pw.Maestro/*", Status::FailedPrecondition,
+        "the device is not in a state that allows this, e.g. because a bud is in the case"),
+    ("maestro_pw.Dosimeter/*", Status::Unimplemented,
+        "the firmware of the device does not provide dosimeter data"),
+    ("maestro_pw.Dosimeter/*", Status::Unavailable,
+        "no dosimeter data is available, e.g. because the buds have not been worn"),
+    ("maestro_pw.Multipoint/*", Status::Unimplemented,
+        "the firmware of the device does not support multipoint"),
+    ("*", Status::Unimplemented,
+        "the firmware of the device does not provide this operation"),
+    ("*", Status::AlreadyExists,
+        "another call of the same method is still in progress"),
+    ("*", Status::Cancelled,
+        "the call was cancelled, e.g. because the buds have handed off processing or the connection was lost"),
+    ("*", Status::Aborted,
+        "the connection to the device was closed before the call completed"),
+    ("*", Status::DeadlineExceeded,
+        "the device did not respond in time"),
+    ("*", Status::Unavailable,
+        "the device is not reachable, e.g. because the buds are in the closed case"),
+];
+
+
+/// Look up the explanation for the given status of the given method.
+pub fn lookup(path: Option<&str>, status: Status) -> Option<&'static str> {
+    let service = path.and_then(|p| p.rsplit_once('/')).map(|(service, _)| service);
+
+    EXPLANATIONS.iter()
+        .filter(|(_, code, _)| *code == status)
+        .find(|(pattern, _, _)| match pattern.strip_suffix("/*") {
+            Some(pattern) => service == Some(pattern),
+            None => *pattern == "*" || Some(*pattern) == path,
+        })
+        .map(|(_, _, explanation)| *explanation)
+}
+
+/// Print an explanation for the RPC error causing the given error, if any.
+pub fn print(err: &anyhow::Error) {
+    let rpc = err.chain().find_map(|cause| {
+        cause.downcast_ref::<pwrpc::Error>().or_else(|| match cause.downcast_ref::<maestro::Error>() {
+            Some(maestro::Error::Rpc(err)) => Some(err),
+            _ => None,
+        })
+    });
+
+    let Some(rpc) = rpc else {
+        eprintln!("explanation: not caused by a failed RPC, nothing to explain");
+        return;
+    };
+
+    let status = rpc.code();
+    let code: u32 = status.into();
+
+    match lookup(rpc.path(), status) {
+        Some(explanation) => eprintln!("explanation: {explanation}"),
+        None => eprintln!("explanation: {}", status.description().to_lowercase()),
+    }
+
+    match rpc.path() {
+        Some(path) => eprintln!("status:      {status:?} ({code}) from {path}"),
+        None => eprintln!("status:      {status:?} ({code})"),
+    }
+
+    if !rpc.message().is_empty() && rpc.message() != status.description() {
+        eprintln!("message:     {}", rpc.message());
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_lookup() {
+        let write = Some("maestro_pw.Maestro/WriteSetting");
+
+        let explanation = lookup(write, Status::FailedPrecondition).unwrap();
+        assert!(explanation.contains("in the case"));
+
+        // falls back to service-wide and generic entries
+        let explanation = lookup(Some("maestro_pw.Maestro/GetSoftwareInfo"), Status::FailedPrecondition);
+        assert!(explanation.is_some());
+
+        let explanation = lookup(Some("maestro_pw.Dosimeter/FetchDailySummaries"), Status::Unimplemented).unwrap();
+        assert!(explanation.contains("dosimeter"));
+
+        assert!(lookup(None, Status::DeadlineExceeded).is_some());
+        assert!(lookup(None, Status::FailedPrecondition).is_none());
+        assert!(lookup(write, Status::DataLoss).is_none());
+    }
+}
@@ -0,0 +1,173 @@
+//! Escalating "find my device" ringing.
+//!
+//! Mirrors the find-device flow of the phone: ring the right bud first, then
+//! both, and keep ringing both for increasingly long rounds until a bud is
+//! touched or the schedule is exhausted. The device reports that ringing has
+//! stopped once a bud has been touched, which is taken as acknowledgement.
+
+use std::time::Duration;
+
+use futures::{Future, Sink, Stream};
+
+use gfps::msg::{Message, RingState};
+
+
+/// A single round of ringing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Stage {
+    pub buds: RingState,
+    pub duration: Duration,
+}
+
+/// How the search ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    /// A bud has been touched.
+    Found,
+
+    /// Ringing has been stopped on request.
+    Stopped,
+
+    /// Nobody acknowledged the ringing before the schedule was exhausted.
+    GaveUp,
+}
+
+
+/// The default escalation schedule.
+pub fn escalation() -> Vec<Stage> {
+    let stage = |buds, secs| Stage { buds, duration: Duration::from_secs(secs) };
+
+    vec![
+        stage(RingState::RIGHT, 10),
+        stage(RingState::BOTH, 10),
+        stage(RingState::BOTH, 20),
+        stage(RingState::BOTH, 40),
+        stage(RingState::BOTH, 60),
+    ]
+}
+
+/// Schedule for the given buds and duration. Without a duration, the buds
+/// ring for the rounds of the default escalation; without buds, they ring in
+/// the order of the default escalation.
+pub fn schedule(buds: Option<RingState>, duration: Option<Duration>) -> Vec<Stage> {
+    match (buds, duration) {
+        (None, None) => escalation(),
+        (buds, Some(duration)) => vec![Stage { buds: buds.unwrap_or(RingState::BOTH), duration }],
+        (Some(buds), None) => escalation().into_iter().map(|s| Stage { buds, ..s }).collect(),
+    }
+}
+
+/// Ring the buds according to the given schedule until acknowledged, the
+/// schedule is exhausted, or `stop` completes. Ringing is always stopped
+/// before returning successfully.
+pub async fn run<S, F>(stream: &mut S, stages: &[Stage], stop: F, mut progress: impl FnMut(&Stage))
+    -> std::io::Result<Outcome>
+where
+    S: Stream<Item = std::io::Result<Message>> + for<'a> Sink<&'a Message, Error = std::io::Error> + Unpin,
+    F: Future<Output = ()>,
+{
+    tokio::pin!(stop);
+
+    for stage in stages {
+        tracing::debug!(buds=%stage.buds, duration=?stage.duration, "ringing");
+        progress(stage);
+
+        gfps::ring::set(stream, stage.buds).await?;
+
+        let deadline = tokio::time::Instant::now() + stage.duration;
+
+        loop {
+            tokio::select! {
+                state = gfps::ring::next_update(stream) => {
+                    let state = state?;
+                    tracing::trace!(%state, "received ring state");
+
+                    if !state.is_ringing() {
+                        return Ok(Outcome::Found);
+                    }
+                },
+                _ = tokio::time::sleep_until(deadline) => break,
+                _ = &mut stop => {
+                    gfps::ring::set(stream, RingState::NONE).await?;
+                    return Ok(Outcome::Stopped);
+                },
+            }
+        }
+    }
+
+    gfps::ring::set(stream, RingState::NONE).await?;
+    Ok(Outcome::GaveUp)
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use futures::{SinkExt, StreamExt};
+
+    use gfps::msg::Codec;
+
+    #[tokio::test(start_paused = true)]
+    async fn test_escalation() {
+        let (local, remote) = tokio::io::duplex(256);
+        let mut local = Codec::new().wrap(local);
+        let mut remote = Codec::new().wrap(remote);
+
+        // acknowledge requests, stop ringing on the second one
+        let device = async {
+            for buds in [RingState::RIGHT, RingState::BOTH] {
+                let request = remote.next().await.unwrap().unwrap();
+                assert_eq!(request.ring_state(), Some(buds));
+                remote.send(&request.ack()).await.unwrap();
+            }
+
+            remote.send(&Message::ring(RingState::NONE)).await.unwrap();
+            remote.next().await.unwrap().unwrap();
+        };
+
+        let mut rounds = Vec::new();
+        let stages = escalation();
+        let find = run(&mut local, &stages, std::future::pending(), |s| rounds.push(s.buds));
+
+        let (_, outcome) = tokio::join!(device, find);
+
+        assert_eq!(outcome.unwrap(), Outcome::Found);
+        assert_eq!(rounds, [RingState::RIGHT, RingState::BOTH]);
+    }
+
+    #[test]
+    fn test_schedule() {
+        assert_eq!(schedule(None, None), escalation());
+
+        let secs = Duration::from_secs(30);
+        assert_eq!(schedule(None, Some(secs)), [Stage { buds: RingState::BOTH, duration: secs }]);
+        assert_eq!(schedule(Some(RingState::LEFT), Some(secs)), [Stage { buds: RingState::LEFT, duration: secs }]);
+
+        let stages = schedule(Some(RingState::LEFT), None);
+        assert_eq!(stages.len(), escalation().len());
+        assert!(stages.iter().all(|s| s.buds == RingState::LEFT));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_stop() {
+        let (local, remote) = tokio::io::duplex(256);
+        let mut local = Codec::new().wrap(local);
+        let mut remote = Codec::new().wrap(remote);
+
+        let device = async {
+            for buds in [RingState::RIGHT, RingState::NONE] {
+                let request = remote.next().await.unwrap().unwrap();
+                assert_eq!(request.ring_state(), Some(buds));
+                remote.send(&request.ack()).await.unwrap();
+            }
+        };
+
+        let stages = escalation();
+        let stop = tokio::time::sleep(Duration::from_secs(5));
+        let find = run(&mut local, &stages, stop, |_| {});
+
+        let (_, outcome) = tokio::join!(device, find);
+        assert_eq!(outcome.unwrap(), Outcome::Stopped);
+    }
+}
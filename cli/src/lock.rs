@@ -0,0 +1,89 @@
+//! Instance lock for the connection to the device.
+//!
+//! Only one process should hold a connection to the Maestro service at a
+//! time. Competing connections fight over the RFCOMM profile and can leave
+//! both hanging. The process holding the connection, e.g. the daemon, holds an
+//! advisory lock on `$XDG_RUNTIME_DIR/pbpctrl.lock`, which other instances use
+//! to tell that they should route their commands through it instead.
+
+use std::fs::{File, TryLockError};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use tokio::time::Instant;
+
+
+/// Interval for polling a lock held by another instance.
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+
+/// Exclusive lock on the connection, released when dropped.
+#[derive(Debug)]
+pub struct InstanceLock {
+    _file: File,
+}
+
+impl InstanceLock {
+    /// Default location of the lock file, i.e.,
+    /// `$XDG_RUNTIME_DIR/pbpctrl.lock`.
+    pub fn default_path() -> Option<PathBuf> {
+        std::env::var_os("XDG_RUNTIME_DIR")
+            .filter(|dir| !dir.is_empty())
+            .map(|dir| PathBuf::from(dir).join("pbpctrl.lock"))
+    }
+
+    /// Try to acquire the lock. Returns `None` if another instance holds it.
+    pub fn try_acquire(path: &Path) -> std::io::Result<Option<Self>> {
+        let file = File::options()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(path)?;
+
+        match file.try_lock() {
+            Ok(()) => Ok(Some(Self { _file: file })),
+            Err(TryLockError::WouldBlock) => Ok(None),
+            Err(TryLockError::Error(err)) => Err(err),
+        }
+    }
+
+    /// Acquire the lock, waiting until another instance releases it or, if
+    /// specified, the timeout has elapsed. Returns `None` on timeout.
+    pub async fn acquire(path: &Path, timeout: Option<Duration>) -> std::io::Result<Option<Self>> {
+        let deadline = timeout.map(|t| Instant::now() + t);
+
+        loop {
+            if let Some(lock) = Self::try_acquire(path)? {
+                return Ok(Some(lock));
+            }
+
+            if deadline.is_some_and(|d| Instant::now() >= d) {
+                return Ok(None);
+            }
+
+            tracing::trace!(path=%path.display(), "connection locked by another instance, waiting");
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[tokio::test(start_paused = true)]
+    async fn test_lock() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("pbpctrl.lock");
+
+        let lock = InstanceLock::try_acquire(&path).unwrap().unwrap();
+        assert!(InstanceLock::try_acquire(&path).unwrap().is_none());
+
+        let timeout = Some(Duration::from_secs(1));
+        assert!(InstanceLock::acquire(&path, timeout).await.unwrap().is_none());
+
+        drop(lock);
+        assert!(InstanceLock::acquire(&path, timeout).await.unwrap().is_some());
+    }
+}
@@ -1,448 +1,1695 @@
-mod bt;
+mod backup;
+mod battery;
+mod cache;
+mod chain;
 mod cli;
+mod config;
+mod daemon;
+mod doctor;
+mod explain;
+mod find;
+mod lock;
+mod output;
+mod render;
+mod sides;
+mod timings;
+mod transport;
 
 use anyhow::Result;
-use clap::{Parser, CommandFactory};
+use clap::CommandFactory;
 use futures::{Future, StreamExt};
 
-use maestro::protocol::{utils, addr};
+use maestro::protocol::utils;
+use maestro::protocol::types::{settings_rsp, FirmwareInfo, FirmwareVersion, RuntimeInfo};
 use maestro::pwrpc::client::{Client, ClientHandle};
+use maestro::hdlc::codec::{Stats, StatsHandle};
 use maestro::protocol::codec::Codec;
-use maestro::service::MaestroService;
-use maestro::service::settings::{self, Setting, SettingValue};
+use maestro::models::Model;
+use maestro::service::{DosimeterService, MaestroService, Retry};
+use maestro::service::clock::{ClockEvent, ClockTracker};
+use maestro::service::format::Formatter;
+use maestro::service::settings::{self, SettingId, SettingValue};
 
 use cli::*;
+use daemon::client::DaemonClient;
+use lock::InstanceLock;
+use render::Output;
+use timings::Timings;
+use transport::Transport;
+
+
+/// Maximum time to wait for a running daemon to connect to its device before
+/// connecting directly.
+const DAEMON_CONNECT_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
+
+/// Time to wait for each stage of connecting and each RPC, unless specified
+/// via `--timeout`.
+const DEFAULT_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+
+/// Time to wait for a device in pairing mode, unless specified via
+/// `--timeout`.
+const PAIR_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(60);
+
+
+enum Action {
+    Show { command: ShowCommand, component: Option<Component> },
+    VerifySoftware { component: Option<Component>, gfps_firmware: String },
+    BatteryTotal { bud_minutes: Option<u32> },
+    Status { format: StatusFormat },
+    Export { format: OutputFormat },
+    BatteryReport { duration: Option<std::time::Duration> },
+    Monitor,
+    DosimeterSummary,
+    Get(SettingId),
+    GetAll { model: Option<&'static Model> },
+    Set { value: SettingValue, force: bool },
+    SetFor { value: SettingValue, duration: std::time::Duration },
+    AncCycle { forward: bool },
+    SwapSides { swapped: bool, force: bool },
+    SwapSidesOf { address: transport::Address, swapped: bool, force: bool },
+    SetMany { values: Vec<SettingValue>, force: bool },
+    ExportSettings { path: std::path::PathBuf },
+}
+
+impl Action {
+    /// Whether the action writes settings.
+    fn writes(&self) -> bool {
+        matches!(self, Action::Set { .. } | Action::SetFor { .. } | Action::AncCycle { .. }
+            | Action::SwapSides { .. } | Action::SwapSidesOf { .. } | Action::SetMany { .. })
+    }
+}
+
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() -> Result<()> {
+    let args = chain::parse(std::env::args_os().collect());
+
+    init_logging(args.verbose, args.log_file.as_deref())?;
+
+    let explain = args.explain;
+
+    match execute(args).await {
+        Err(err) if explain => {
+            eprintln!("Error: {err:?}");
+            eprintln!();
+            explain::print(&err);
+
+            std::process::exit(1);
+        },
+        result => result,
+    }
+}
+
+/// Log to stdout, or to the given file instead.
+fn init_logging(verbose: u8, file: Option<&std::path::Path>) -> Result<()> {
+    use tracing_subscriber::layer::SubscriberExt;
+    use tracing_subscriber::util::SubscriberInitExt;
+    use tracing_subscriber::Layer;
+
+    let filter = log_filter(verbose);
+
+    let layer = match file {
+        Some(path) => {
+            let file = std::fs::OpenOptions::new().create(true).append(true).open(path)
+                .map_err(|err| anyhow::anyhow!("failed to open log file {}: {err}", path.display()))?;
+
+            tracing_subscriber::fmt::layer()
+                .with_writer(std::sync::Mutex::new(file))
+                .with_ansi(false)
+                .with_filter(filter)
+                .boxed()
+        },
+        None => {
+            tracing_subscriber::fmt::layer()
+                .with_filter(filter)
+                .boxed()
+        },
+    };
+
+    tracing_subscriber::registry().with(layer).init();
+    Ok(())
+}
+
+/// Log levels for the given verbosity. Individual HDLC frames are only logged
+/// at the highest level.
+fn log_filter(verbose: u8) -> tracing_subscriber::filter::Targets {
+    use tracing::Level;
+    use tracing_subscriber::filter::Targets;
+
+    match verbose {
+        0 => Targets::new().with_default(Level::INFO),
+        1 => Targets::new().with_default(Level::DEBUG),
+        2 => Targets::new().with_default(Level::TRACE).with_target("maestro::hdlc", Level::DEBUG),
+        _ => Targets::new().with_default(Level::TRACE),
+    }
+}
+
+async fn execute(args: Args) -> Result<()> {
+    let config = config::Config::load()?;
+    let args = config.apply(args)?;
+
+    let format = args.timings;
+    let mut timings = match format {
+        Some(_) => Timings::new(),
+        None => Timings::disabled(),
+    };
+
+    let result = execute_timed(args, &config, &mut timings).await;
+
+    if let Some(format) = format {
+        timings.print(format);
+    }
+
+    result
+}
+
+async fn execute_timed(args: Args, config: &config::Config, timings: &mut Timings) -> Result<()> {
+    // device names have been resolved via the configuration
+    let device = args.device.as_ref().and_then(DeviceArg::address);
+
+    let format = args.output.unwrap_or(OutputFormat::Human);
+    let timeout = args.timeout.unwrap_or(DEFAULT_TIMEOUT);
+
+    let action = match args.command {
+        Command::Show { command, component } => Action::Show { command, component },
+        Command::Get { setting, describe: true } => {
+            return match get_setting_id(setting) {
+                Some(setting) => cmd_describe_setting(setting),
+                None => SETTINGS.into_iter().try_for_each(cmd_describe_setting),
+            }
+        },
+        Command::Get { setting, describe: false } => match get_setting_id(setting) {
+            Some(setting) => Action::Get(setting),
+            None => Action::GetAll { model: None },
+        },
+        Command::Set { setting, more, from_file, force } => set_settings_action(setting, more, from_file, force)?,
+        Command::Status { format } => Action::Status { format },
+        Command::Export { format } => Action::Export { format },
+        Command::BatteryReport { duration } => Action::BatteryReport { duration },
+        Command::Monitor => Action::Monitor,
+        Command::Dosimeter { command: DosimeterCommand::Summary } => Action::DosimeterSummary,
+        Command::Preset { name, force } => preset_action(config, &name, force)?,
+        Command::Settings { command: SettingsCommand::Export { file } } => Action::ExportSettings { path: file },
+        Command::Settings { command: SettingsCommand::Import { file, force } } => {
+            Action::SetMany { values: backup::read(&file)?, force }
+        },
+        Command::Doctor => {
+            return cmd_doctor(device, args.adapter.as_deref(), args.connect_mode, timeout, format).await
+        },
+        Command::Devices => {
+            return render(format, cmd_devices(args.adapter.as_deref())).await
+        },
+        Command::Find { stop, side, duration } => {
+            return cmd_find(device, args.adapter.as_deref(), args.no_daemon, stop, side, duration).await
+        },
+        Command::Multipoint { command: MultipointCommand::Claim { resume, disconnect_other } } => {
+            return cmd_multipoint_claim(device, args.adapter.as_deref(), resume, disconnect_other).await
+        },
+        Command::Pair => {
+            let timeout = args.timeout.unwrap_or(PAIR_TIMEOUT);
+            return cmd_pair(device, args.adapter.as_deref(), args.connect_mode, timeout).await
+        },
+        Command::History { since } => {
+            return cmd_history(since)
+        },
+        Command::Rpc { command: RpcCommand::Hash { name } } => {
+            cmd_rpc_hash(&name);
+            return Ok(())
+        },
+        Command::Dosimeter { command: DosimeterCommand::History { since } } => {
+            return cmd_dosimeter_history(since)
+        },
+        Command::Daemon { command: Some(DaemonCommand::Install { force }), .. } => {
+            return daemon::install::install(device, force)
+        },
+        Command::Daemon { config, socket, idle_timeout, command: None } => {
+            return daemon::run(device, args.adapter.as_deref(), args.connect_mode, config.as_deref(), socket.as_deref(),
+                idle_timeout, args.read_only).await
+        },
+    };
+
+    if args.read_only && action.writes() {
+        anyhow::bail!("refusing to write settings in read-only mode (--read-only)");
+    }
+
+    // forward to daemon if one is running, giving one that has just been
+    // started, e.g. via --keep-alive, a moment to connect
+    if !args.no_daemon
+        && let Some(daemon) = DaemonClient::connect_timeout(device, DAEMON_CONNECT_TIMEOUT).await
+        && let Some(result) = timings.measure("daemon request", run_via_daemon(&daemon, &action, format)).await
+    {
+        return result;
+    }
+
+    // don't fight over the connection with another instance, e.g. a daemon
+    // that is not connected to the device at the moment
+    let _lock = lock_connection().await?;
+
+    // set up transport, trying the last used device first
+    let cached = cache::Connection::load();
+
+    let transport = timings.measure("discovery", open_transport(device, args.adapter.as_deref(), cached.as_ref())).await?
+        .with_connect_mode(args.connect_mode);
+
+    // keep the connection open for subsequent commands
+    if let Some(secs) = args.keep_alive.filter(|secs| *secs > 0)
+        && let Err(err) = daemon::spawn(transport.address(), &transport.identity().adapter, args.connect_mode, secs,
+            args.read_only)
+    {
+        tracing::warn!(error=?err, "failed to start background daemon");
+    }
+
+    let hint = cached.as_ref()
+        .filter(|c| c.address == transport.address() && c.adapter == transport.identity().adapter)
+        .map(|c| c.channel);
+
+    let update_cache = |channel| {
+        let connection = cache::Connection {
+            adapter: transport.identity().adapter.clone(),
+            address: transport.address(),
+            channel,
+        };
+
+        if cached.as_ref() != Some(&connection) {
+            connection.store();
+        }
+    };
+
+    // read the firmware version via GFPS for cross-checking
+    let action = match action {
+        Action::Show { command: ShowCommand::Software { verify: true }, component } => {
+            let gfps_firmware = transport.gfps_firmware_version().await?;
+            Action::VerifySoftware { component, gfps_firmware }
+        },
+        Action::Show { command: ShowCommand::Battery { total: true }, .. } => {
+            let bud_minutes = match transport.gfps_battery_time().await {
+                Ok(minutes) => Some(minutes.into()),
+                Err(err) => {
+                    tracing::debug!(error=?err, "failed to read remaining battery time via gfps");
+                    None
+                },
+            };
+
+            Action::BatteryTotal { bud_minutes }
+        },
+        Action::SwapSides { swapped, force } => {
+            Action::SwapSidesOf { address: transport.address(), swapped, force }
+        },
+        Action::GetAll { .. } => {
+            Action::GetAll { model: transport.identity().model }
+        },
+        action => action,
+    };
+
+    // connect to device
+    let stream = match timings.measure("connect", connect(&transport, timeout)).await {
+        Ok(stream) => stream,
+        Err(err) => return Err(presence_context(&transport, err).await),
+    };
+
+    let result = match args.capture {
+        Some(path) => {
+            let file = std::io::BufWriter::new(std::fs::File::create(path)?);
+            let stream = maestro::capture::Recorder::new(stream, file);
+            run_action(stream, action, format, timeout, hint, update_cache, timings).await
+        },
+        None => run_action(stream, action, format, timeout, hint, update_cache, timings).await,
+    };
+
+    if let Err(err) = &result
+        && err.downcast_ref::<BudsInCase>().is_some()
+    {
+        print_case_battery(&transport).await;
+    }
+
+    result
+}
+
+/// Connect to the Maestro service of the device, giving up with suggestions
+/// if this does not complete in time.
+async fn connect(transport: &transport::Platform, timeout: std::time::Duration)
+    -> Result<<transport::Platform as Transport>::Stream>
+{
+    match tokio::time::timeout(timeout, transport.connect()).await {
+        Ok(stream) => Ok(stream?),
+        Err(_) => {
+            let mut msg = format!("timed out connecting to the maestro profile after {}", output::duration_str(timeout));
+            msg += "\n  make sure BlueZ runs with experimental features enabled (see README),";
+            msg += "\n  try '--connect-mode raw' to bypass profile registration, or increase the timeout via '--timeout'";
+            Err(anyhow::anyhow!(msg))
+        },
+    }
+}
+
+/// Lock the connection to the device, giving up if another instance keeps
+/// holding it. Proceeds without lock if there is no runtime directory.
+async fn lock_connection() -> Result<Option<InstanceLock>> {
+    const TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
+
+    let Some(path) = InstanceLock::default_path() else {
+        return Ok(None);
+    };
+
+    if let Some(lock) = InstanceLock::acquire(&path, Some(TIMEOUT)).await? {
+        return Ok(Some(lock));
+    }
+
+    if DaemonClient::is_running().await {
+        anyhow::bail!("the daemon holds the connection to the device, but is not connected at the moment \
+            or does not support this command; stop it to connect directly");
+    }
+
+    anyhow::bail!("another pbpctrl instance holds the connection to the device");
+}
+
+/// Resolve the channel, giving up with a diagnostic message if the device does
+/// not respond.
+///
+/// If the device does not announce itself within a few seconds, all channels
+/// are queried directly.
+async fn resolve_channel<S, E>(client: &mut Client<S>, stats: &StatsHandle, hint: Option<u32>, timeout: std::time::Duration)
+    -> Result<u32>
+where
+    S: futures::Sink<maestro::pwrpc::types::RpcPacket>,
+    S: futures::Stream<Item = Result<maestro::pwrpc::types::RpcPacket, E>> + Unpin,
+    maestro::pwrpc::Error: From<E>,
+    maestro::pwrpc::Error: From<S::Error>,
+{
+    use std::time::Duration;
+
+    const PROBE_AFTER: Duration = Duration::from_secs(3);
+
+    // leave time for probing with short timeouts
+    let resolve = utils::resolve_channel_probing(client, hint, PROBE_AFTER.min(timeout / 2));
+
+    match tokio::time::timeout(timeout, resolve).await {
+        Ok(channel) => Ok(channel?),
+        Err(_) => Err(anyhow::anyhow!(resolve_timeout_message(&stats.get(), timeout))),
+    }
+}
+
+fn resolve_timeout_message(stats: &Stats, timeout: std::time::Duration) -> String {
+    // We have an RFCOMM connection at this point, so the device is connected
+    // via Bluetooth but its Maestro service is unresponsive.
+    let mut msg = format!("timed out resolving maestro channel after {}, the device is connected but did not respond",
+        output::duration_str(timeout));
+
+    if stats.bytes == 0 {
+        msg += "\n  no data has been received from the device";
+    } else {
+        let head: Vec<_> = stats.head.iter().map(|b| format!("{b:02x}")).collect();
+
+        msg += &format!("\n  received {} bytes: {} frames, {} checksum errors, {} other errors",
+            stats.bytes, stats.frames, stats.crc_errors, stats.errors);
+        msg += &format!("\n  first bytes received: {}", head.join(" "));
+    }
+
+    msg += "\n  make sure the buds are connected and out of the case, try '--connect-mode raw',";
+    msg += "\n  run 'pbpctrl doctor' to check the setup,";
+    msg += "\n  or record the traffic via '--capture <file>' when reporting this issue";
+    msg
+}
+
+/// Tell whether the device is connected via Bluetooth at all, to distinguish
+/// an absent device from an unresponsive Maestro service.
+async fn presence_context(transport: &transport::Platform, err: anyhow::Error) -> anyhow::Error {
+    match transport.is_connected().await {
+        Ok(false) => err.context(format!(
+            "device {} is not connected via bluetooth, make sure it is turned on, out of the case, and in range",
+            transport.identity()
+        )),
+        Ok(true) => err.context(format!(
+            "device {} is connected via bluetooth, but connecting to its maestro service failed",
+            transport.identity()
+        )),
+        Err(_) => err,
+    }
+}
+
+/// Error context for commands that failed while both buds are in the case.
+#[derive(Debug, Clone, Copy)]
+struct BudsInCase;
+
+impl std::fmt::Display for BudsInCase {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "buds are in the case; limited data available")
+    }
+}
+
+/// Check whether both buds are in the case if the given task fails, in which
+/// case most RPCs fail or return stale data.
+async fn case_context<T>(handle: ClientHandle, channel: u32, task: impl Future<Output = Result<T>>) -> Result<T> {
+    const TIMEOUT: std::time::Duration = std::time::Duration::from_secs(2);
+
+    let err = match task.await {
+        Ok(value) => return Ok(value),
+        Err(err) => err,
+    };
+
+    let mut service = MaestroService::new(handle, channel);
+
+    match tokio::time::timeout(TIMEOUT, read_runtime_info(&mut service)).await {
+        Ok(Ok(info)) if output::buds_in_case(&info) => Err(err.context(BudsInCase)),
+        _ => Err(err),
+    }
+}
+
+/// Show the case battery reported via GFPS, which remains available while
+/// the buds are in the case.
+async fn print_case_battery(transport: &transport::Platform) {
+    match transport.gfps_battery_info().await {
+        Ok(levels) => println!("case battery: {}", levels.case),
+        Err(err) => tracing::debug!(error=?err, "failed to read battery levels via gfps"),
+    }
+}
+
+async fn open_transport(address: Option<transport::Address>, adapter: Option<&str>,
+    cached: Option<&cache::Connection>) -> Result<transport::Platform, maestro::Error>
+{
+    if address.is_none() && let Some(cached) = cached && adapter.is_none_or(|a| a == cached.adapter) {
+        let identity = transport::DeviceIdentity::new(cached.address, &cached.adapter);
+
+        match transport::Platform::open_cached(&identity).await {
+            Ok(transport) => return Ok(transport),
+            Err(err) => tracing::debug!(error=?err, "cached device unavailable, searching for compatible one"),
+        }
+    }
+
+    transport::Platform::open(address, adapter).await
+}
+
+async fn run_action<S>(stream: S, action: Action, format: OutputFormat, timeout: std::time::Duration, hint: Option<u32>,
+    resolved: impl FnOnce(u32), timings: &mut Timings) -> Result<()>
+where
+    S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Unpin,
+{
+    // set up codec
+    let codec = Codec::new();
+    let stats = codec.stats();
+    let stream = codec.wrap(stream);
+
+    // set up RPC client
+    let mut client = Client::new(stream);
+    let handle = client.handle();
+    handle.set_call_timeout(Some(timeout));
+
+    // record the duration of each RPC, the stream ends once the client has
+    // been dropped
+    let mut events = timings.is_enabled()
+        .then(|| client.handle().subscribe_events())
+        .transpose()?;
+
+    // resolve channel, trying the last used one first
+    let channel = timings.measure("resolve channel", resolve_channel(&mut client, &stats, hint, timeout)).await?;
+
+    resolved(channel);
+
+    // handle used to check the placement of the buds if a command fails
+    let check = client.handle();
+
+    let mut log = action.writes().then(audit_log).flatten();
+
+    let result = match action {
+        Action::Show { command, component } => match command {
+            ShowCommand::All { json } => {
+                let task = case_context(check, channel, cmd_show_all(handle, channel, component));
+                run(client, render(json_or(json, format), task)).await
+            },
+            ShowCommand::Software { .. } => {
+                run(client, render(format, cmd_show_software(handle, channel, component, None))).await
+            },
+            ShowCommand::Hardware => run(client, render(format, cmd_show_hardware(handle, channel, component))).await,
+            ShowCommand::Runtime { follow, json } => {
+                run(client, cmd_show_runtime(handle, channel, component, follow, json_or(json, format))).await
+            },
+            ShowCommand::Battery { .. } => {
+                run(client, render(format, cmd_show_battery(handle, channel, component))).await
+            },
+        },
+        Action::BatteryTotal { bud_minutes } => {
+            run(client, render(format, cmd_battery_total(handle, channel, bud_minutes))).await
+        },
+        Action::Status { format } => {
+            let task = async {
+                let status = cmd_status(handle, channel).await?;
+                println!("{}", output::status_str(&status, format));
+                Ok(())
+            };
+
+            run(client, task).await
+        },
+        Action::Export { format } => {
+            run(client, render(format, case_context(check, channel, cmd_export(handle, channel)))).await
+        },
+        Action::VerifySoftware { component, gfps_firmware } => {
+            run(client, render(format, cmd_show_software(handle, channel, component, Some(gfps_firmware)))).await
+        },
+        Action::BatteryReport { duration } => {
+            // report what has been recorded, even if the connection is lost
+            let mut session = battery::Session::new();
+            let result = run(client, cmd_battery_report(handle, channel, duration, &mut session)).await;
+
+            finish_battery_report(&session, result)
+        },
+        Action::Monitor => run(client, cmd_monitor(handle, channel, format)).await,
+        Action::DosimeterSummary => run(client, render(format, cmd_dosimeter_summary(handle, channel))).await,
+        Action::Get(setting) => {
+            let task = case_context(check, channel, cmd_get_setting(handle, channel, setting));
+            run(client, render(format, task)).await
+        },
+        Action::GetAll { model } => {
+            run(client, render(format, case_context(check, channel, cmd_get_all(handle, channel, model)))).await
+        },
+        Action::Set { value, force } => {
+            run(client, case_context(check, channel, cmd_set_setting(handle, channel, value, force, &mut log))).await
+        },
+        Action::SetFor { value, duration } => {
+            // the task handles Ctrl+C itself to revert the setting early
+            let task = case_context(check, channel, cmd_set_setting_for(handle, channel, value, duration, &mut log));
+            run_task(client, task).await
+        },
+        Action::AncCycle { forward } => {
+            run(client, case_context(check, channel, cmd_anc_cycle(handle, channel, forward, &mut log))).await
+        },
+        Action::SwapSides { .. } => unreachable!("address resolved before connecting"),
+        Action::SwapSidesOf { address, swapped, force } => {
+            let task = cmd_swap_sides(handle, channel, address, swapped, force, &mut log);
+            run(client, case_context(check, channel, task)).await
+        },
+        Action::SetMany { values, force } => {
+            run(client, case_context(check, channel, cmd_set_settings(handle, channel, values, force, &mut log))).await
+        },
+        Action::ExportSettings { path } => {
+            run(client, case_context(check, channel, cmd_export_settings(handle, channel, path))).await
+        },
+    };
+
+    if let Some(events) = &mut events {
+        while let Some(event) = events.next().await {
+            timings.record_event(&event);
+        }
+    }
+
+    result.map_err(|err| rpc_timeout_context(err, timeout))
+}
+
+/// Point out the RPC that did not complete in time, if any.
+fn rpc_timeout_context(err: anyhow::Error, timeout: std::time::Duration) -> anyhow::Error {
+    use maestro::pwrpc::Status;
+
+    let rpc = err.chain().find_map(|cause| {
+        cause.downcast_ref::<maestro::pwrpc::Error>().or_else(|| match cause.downcast_ref::<maestro::Error>() {
+            Some(maestro::Error::Rpc(err)) => Some(err),
+            _ => None,
+        })
+    });
+
+    let Some(rpc) = rpc.filter(|rpc| rpc.code() == Status::DeadlineExceeded) else {
+        return err;
+    };
+
+    let method = rpc.path().unwrap_or("unknown method").to_owned();
+
+    err.context(format!("timed out waiting for a response to {method} after {}\
+        \n  make sure the buds are out of the case and in range, or increase the timeout via '--timeout'",
+        output::duration_str(timeout)))
+}
+
+/// All settings accessible via the `get` command.
+const SETTINGS: [SettingId; 16] = [
+    SettingId::AutoOtaEnable,
+    SettingId::OhdEnable,
+    SettingId::OobeIsFinished,
+    SettingId::GestureEnable,
+    SettingId::DiagnosticsEnable,
+    SettingId::OobeMode,
+    SettingId::GestureControl,
+    SettingId::MultipointEnable,
+    SettingId::AncrGestureLoop,
+    SettingId::CurrentAncrState,
+    SettingId::VolumeEqEnable,
+    SettingId::CurrentUserEq,
+    SettingId::VolumeAsymmetry,
+    SettingId::SumToMono,
+    SettingId::VolumeExposureNotifications,
+    SettingId::SpeechDetection,
+];
+
+/// Map the given setting to its ID, returns `None` for all settings.
+fn get_setting_id(setting: GetSetting) -> Option<SettingId> {
+    let id = match setting {
+        GetSetting::All => return None,
+        GetSetting::AutoOta => SettingId::AutoOtaEnable,
+        GetSetting::Ohd => SettingId::OhdEnable,
+        GetSetting::OobeIsFinished => SettingId::OobeIsFinished,
+        GetSetting::Gestures => SettingId::GestureEnable,
+        GetSetting::Diagnostics => SettingId::DiagnosticsEnable,
+        GetSetting::OobeMode => SettingId::OobeMode,
+        GetSetting::GestureControl => SettingId::GestureControl,
+        GetSetting::Multipoint => SettingId::MultipointEnable,
+        GetSetting::AncGestureLoop => SettingId::AncrGestureLoop,
+        GetSetting::Anc => SettingId::CurrentAncrState,
+        GetSetting::VolumeEq => SettingId::VolumeEqEnable,
+        GetSetting::Eq => SettingId::CurrentUserEq,
+        GetSetting::Balance => SettingId::VolumeAsymmetry,
+        GetSetting::Mono => SettingId::SumToMono,
+        GetSetting::VolumeExposureNotifications => SettingId::VolumeExposureNotifications,
+        GetSetting::SpeechDetection => SettingId::SpeechDetection,
+    };
+
+    Some(id)
+}
+
+fn preset_action(config: &config::Config, name: &str, force: bool) -> Result<Action> {
+    let Some(preset) = config.preset(name) else {
+        let names: Vec<_> = config.presets.iter().map(|p| p.name.as_str()).collect();
+
+        if names.is_empty() {
+            anyhow::bail!("unknown preset '{name}', no presets are defined in the configuration file");
+        }
+
+        anyhow::bail!("unknown preset '{name}', available presets: {}", names.join(", "));
+    };
+
+    Ok(Action::SetMany { values: preset.values.clone(), force })
+}
+
+/// Combine the given settings into a single action. Settings read from the
+/// file are written first.
+fn set_settings_action(setting: Option<SetSetting>, more: Vec<SetSetting>, file: Option<std::path::PathBuf>,
+    force: bool) -> Result<Action>
+{
+    let mut values = match &file {
+        Some(file) => backup::read(file)?,
+        None => Vec::new(),
+    };
+
+    let settings: Vec<_> = setting.into_iter().chain(more).collect();
+
+    if file.is_none() && settings.len() == 1 {
+        return Ok(set_setting_action(settings.into_iter().next().unwrap(), force));
+    }
+
+    for setting in settings {
+        match set_setting_action(setting, force) {
+            Action::Set { value, .. } => values.push(value),
+            _ => anyhow::bail!("'--for', cycling the ANC state, and 'swap-sides' cannot be combined with other settings"),
+        }
+    }
+
+    if values.is_empty() {
+        anyhow::bail!("no settings to write");
+    }
+
+    Ok(Action::SetMany { values, force })
+}
+
+fn set_setting_action(setting: SetSetting, force: bool) -> Action {
+    let value = match setting {
+        SetSetting::AutoOta { value } => SettingValue::AutoOtaEnable(value),
+        SetSetting::Ohd { value } => SettingValue::OhdEnable(value),
+        SetSetting::OobeIsFinished { value } => SettingValue::OobeIsFinished(value),
+        SetSetting::Gestures { value } => SettingValue::GestureEnable(value),
+        SetSetting::Diagnostics { value } => SettingValue::DiagnosticsEnable(value),
+        SetSetting::OobeMode { value } => SettingValue::OobeMode(value),
+        SetSetting::GestureControl { left, right } => {
+            let value = settings::GestureControl { left: left.into(), right: right.into() };
+            SettingValue::GestureControl(value)
+        },
+        SetSetting::Multipoint { value } => SettingValue::MultipointEnable(value),
+        SetSetting::AncGestureLoop { off, active, aware } => {
+            let value = settings::AncrGestureLoop { off, active, aware };
+
+            if !value.is_valid() {
+                use clap::error::ErrorKind;
+
+                let mut cmd = Args::command();
+                let err = cmd.error(
+                    ErrorKind::InvalidValue,
+                    "This command requires at least tow enabled ('true') modes"
+                );
+                err.exit();
+            }
+
+            SettingValue::AncrGestureLoop(value)
+        },
+        SetSetting::Anc { value, duration } => {
+            let value = match value {
+                AncState::Off => SettingValue::CurrentAncrState(settings::AncState::Off),
+                AncState::Aware => SettingValue::CurrentAncrState(settings::AncState::Aware),
+                AncState::Active => SettingValue::CurrentAncrState(settings::AncState::Active),
+                AncState::CycleNext | AncState::CyclePrev if duration.is_some() => {
+                    use clap::error::ErrorKind;
+
+                    let mut cmd = Args::command();
+                    let err = cmd.error(
+                        ErrorKind::ArgumentConflict,
+                        "The '--for' option requires an explicit ANC state"
+                    );
+                    err.exit();
+                },
+                AncState::CycleNext => return Action::AncCycle { forward: true },
+                AncState::CyclePrev => return Action::AncCycle { forward: false },
+            };
+
+            if let Some(duration) = duration {
+                return Action::SetFor { value, duration };
+            }
+
+            value
+        },
+        SetSetting::VolumeEq { value } => SettingValue::VolumeEqEnable(value),
+        SetSetting::Eq { low_bass, bass, mid, treble, upper_treble } => {
+            let value = settings::EqBands::new(low_bass, bass, mid, treble, upper_treble);
+            SettingValue::CurrentUserEq(value)
+        },
+        SetSetting::Balance { value } => {
+            let value = settings::VolumeAsymmetry::from_normalized(value);
+            SettingValue::VolumeAsymmetry(value)
+        },
+        SetSetting::Audio { left, right } => {
+            let value = settings::VolumeAsymmetry::from_levels(left, right);
+            SettingValue::VolumeAsymmetry(value)
+        },
+        SetSetting::Mono { value } => SettingValue::SumToMono(value),
+        SetSetting::VolumeExposureNotifications { value } => {
+            SettingValue::VolumeExposureNotifications(value)
+        },
+        SetSetting::SpeechDetection { value } => SettingValue::SpeechDetection(value),
+        SetSetting::SwapSides { value } => return Action::SwapSides { swapped: value, force },
+    };
+
+    Action::Set { value, force }
+}
+
+/// Run the given action via the daemon. Returns `None` if the action is not
+/// supported by the daemon.
+async fn run_via_daemon(daemon: &DaemonClient, action: &Action, format: OutputFormat) -> Option<Result<()>> {
+    let result = match action {
+        Action::Show { command: ShowCommand::Battery { total: false }, component } => {
+            daemon.get_battery_info().await
+                .map(|info| render::print(format, &output::Battery { info, component: *component }))
+        },
+        Action::Show { command: ShowCommand::Battery { total: true }, .. } => {
+            daemon.get_battery_info().await
+                .map(|info| output::BatteryTotal { minutes: output::estimate_minutes(&info, None) })
+                .map(|total| render::print(format, &total))
+        },
+        Action::Status { format } => {
+            daemon_status(daemon).await
+                .map(|status| println!("{}", output::status_str(&status, *format)))
+        },
+        Action::BatteryReport { duration } => {
+            daemon_battery_report(daemon, *duration).await
+        },
+        Action::Show { .. } | Action::VerifySoftware { .. } | Action::BatteryTotal { .. } | Action::GetAll { .. }
+            | Action::Export { .. } | Action::Monitor | Action::DosimeterSummary | Action::SwapSidesOf { .. }
+            | Action::ExportSettings { .. } => {
+            return None;
+        },
+        Action::Get(setting) => {
+            daemon.read_setting(*setting).await
+                .map(|value| render::print(format, &output::Setting { value }))
+        },
+        Action::Set { value, force } => {
+            daemon_set_setting(daemon, value, *force).await
+        },
+        Action::SetFor { value, duration } => {
+            daemon.write_setting_for(value.clone(), *duration).await
+        },
+        Action::AncCycle { forward } => {
+            daemon_anc_cycle(daemon, *forward).await
+        },
+        Action::SwapSides { swapped, force } => {
+            daemon_swap_sides(daemon, *swapped, *force).await
+        },
+        Action::SetMany { values, force } => {
+            daemon_set_settings(daemon, values, *force).await
+        },
+    };
+
+    Some(result)
+}
+
+async fn daemon_set_setting(daemon: &DaemonClient, value: &SettingValue, force: bool) -> Result<()> {
+    if value.id().info().requires_matching_firmware {
+        let firmware = daemon.get_firmware().await?;
+        check_firmware(&firmware, value, force)?;
+    }
+
+    daemon.write_setting(value.clone()).await
+}
+
+async fn daemon_set_settings(daemon: &DaemonClient, values: &[SettingValue], force: bool) -> Result<()> {
+    // check all values first, so that none are written if one is refused
+    if values.iter().any(|v| v.id().info().requires_matching_firmware) {
+        let firmware = daemon.get_firmware().await?;
+
+        for value in values {
+            check_firmware(&firmware, value, force)?;
+        }
+    }
+
+    for value in values {
+        daemon.write_setting(value.clone()).await?;
+    }
+
+    Ok(())
+}
+
+async fn daemon_anc_cycle(daemon: &DaemonClient, forward: bool) -> Result<()> {
+    let enabled = daemon.read_setting(settings::id::AncrGestureLoop).await?;
+    let state = daemon.read_setting(settings::id::CurrentAncrState).await?;
+
+    if let Some(state) = anc_cycle_next(enabled, state, forward)? {
+        daemon.write_setting(SettingValue::CurrentAncrState(state)).await?;
+    }
+
+    Ok(())
+}
+
+async fn daemon_swap_sides(daemon: &DaemonClient, swapped: bool, force: bool) -> Result<()> {
+    let address = daemon.get_address().await?;
+
+    let Some(store) = sides_store(address, swapped)? else {
+        return Ok(());
+    };
+
+    let gestures = daemon.read_setting(settings::id::GestureControl).await?;
+    let balance = daemon.read_setting(settings::id::VolumeAsymmetry).await?;
+    let (new_gestures, new_balance) = sides::swap(gestures, balance);
+
+    let firmware = daemon.get_firmware().await?;
+    check_firmware(&firmware, &SettingValue::GestureControl(new_gestures), force)?;
+
+    daemon.write_setting(SettingValue::GestureControl(new_gestures)).await?;
+
+    // don't leave the sides half-swapped
+    if let Err(err) = daemon.write_setting(SettingValue::VolumeAsymmetry(new_balance)).await {
+        if let Err(err) = daemon.write_setting(SettingValue::GestureControl(gestures)).await {
+            tracing::warn!(error=?err, "failed to restore gesture control");
+        }
+
+        return Err(err);
+    }
+
+    store.set_swapped(address, swapped)
+}
+
+async fn daemon_status(daemon: &DaemonClient) -> Result<output::Status> {
+    let info = daemon.get_battery_info().await?;
+
+    // settings may not be supported by the firmware, leave them out if so
+    let anc = daemon.read_setting(settings::id::CurrentAncrState).await
+        .inspect_err(|err| tracing::debug!(error=?err, "failed to read ANC state"))
+        .ok();
+
+    let multipoint = daemon.read_setting(settings::id::MultipointEnable).await
+        .inspect_err(|err| tracing::debug!(error=?err, "failed to read multipoint state"))
+        .ok();
+
+    Ok(output::Status { info, anc, multipoint })
+}
+
+async fn daemon_battery_report(daemon: &DaemonClient, duration: Option<std::time::Duration>) -> Result<()> {
+    const INTERVAL: std::time::Duration = std::time::Duration::from_secs(60);
+
+    let mut session = battery::Session::new();
+    let mut interval = tokio::time::interval(INTERVAL);
+
+    let deadline = deadline(duration);
+    tokio::pin!(deadline);
+
+    println!("recording battery levels, press Ctrl-C to stop");
+
+    let result = loop {
+        tokio::select! {
+            _ = interval.tick() => match daemon.get_battery_info().await {
+                Ok(info) => {
+                    if let Some(battery) = info.battery_info {
+                        session.record(&battery);
+                    }
+                },
+                Err(err) => break Err(err),
+            },
+            _ = &mut deadline => break Ok(()),
+            sig = tokio::signal::ctrl_c() => break sig.map_err(Into::into),
+        }
+    };
+
+    finish_battery_report(&session, result)
+}
+
+async fn cmd_show_software(handle: ClientHandle, channel: u32, component: Option<Component>, gfps_firmware: Option<String>)
+    -> Result<output::Software>
+{
+    let mut service = MaestroService::new(handle, channel);
+    let info = service.get_software_info().await?;
+
+    Ok(output::Software { info, component, gfps_firmware })
+}
+
+async fn cmd_show_hardware(handle: ClientHandle, channel: u32, component: Option<Component>)
+    -> Result<output::Hardware>
+{
+    let mut service = MaestroService::new(handle, channel);
+    let info = service.get_hardware_info().await?;
+
+    Ok(output::Hardware { info, component })
+}
+
+/// Show software, hardware, and runtime information as well as all settings,
+/// gathered concurrently.
+async fn cmd_show_all(handle: ClientHandle, channel: u32, component: Option<Component>) -> Result<output::All> {
+    let mut service = MaestroService::new(handle, channel);
+
+    let snapshot = service.snapshot(&SETTINGS).await?;
+    tracing::debug!(timing=?snapshot.timing, "gathered device snapshot");
+
+    let mut clock = ClockTracker::new();
+    clock.record(std::time::SystemTime::now(), snapshot.runtime_info.timestamp_ms);
+
+    let mut settings = Vec::with_capacity(snapshot.settings.len());
+    for (setting, value) in snapshot.settings {
+        match value {
+            Ok(value) => settings.push((setting, Some(value))),
+            Err(err) if is_unsupported(&err) => settings.push((setting, None)),
+            Err(err) => return Err(err.into()),
+        }
+    }
+
+    Ok(output::All {
+        software: output::Software { info: snapshot.software_info, component, gfps_firmware: None },
+        hardware: output::Hardware { info: snapshot.hardware_info, component },
+        runtime: output::Runtime { info: snapshot.runtime_info, clock, channel, component },
+        settings: output::Settings { values: settings },
+        timing: snapshot.timing,
+    })
+}
+
+/// Gather the exported device state from a device snapshot.
+async fn cmd_export(handle: ClientHandle, channel: u32) -> Result<output::Export> {
+    let mut service = MaestroService::new(handle, channel);
+
+    let snapshot = service.snapshot(&SETTINGS).await?;
+    tracing::debug!(timing=?snapshot.timing, "gathered device snapshot");
+
+    let mut settings = Vec::with_capacity(snapshot.settings.len());
+    for (setting, value) in snapshot.settings {
+        match value {
+            Ok(value) => settings.push((setting, Some(value))),
+            Err(err) if is_unsupported(&err) => settings.push((setting, None)),
+            Err(err) => return Err(err.into()),
+        }
+    }
+
+    Ok(output::Export {
+        software: snapshot.software_info,
+        hardware: snapshot.hardware_info,
+        runtime: snapshot.runtime_info,
+        settings,
+    })
+}
+
+async fn cmd_show_runtime(handle: ClientHandle, channel: u32, component: Option<Component>, follow: bool,
+    format: OutputFormat) -> Result<()>
+{
+    let mut service = MaestroService::new(handle, channel);
+
+    let mut call = service.subscribe_to_runtime_info()?;
+    let mut stream = call.stream();
+
+    let info = stream.next().await
+        .ok_or_else(|| anyhow::anyhow!("stream terminated without item"))??;
+
+    let mut clock = ClockTracker::new();
+    clock.record(std::time::SystemTime::now(), info.timestamp_ms);
+
+    render::print(format, &output::Runtime { info, clock: clock.clone(), channel, component });
+
+    if !follow {
+        return Ok(());
+    }
+
+    while let Some(info) = stream.next().await {
+        let info = info?;
+
+        if let Some(ClockEvent::Reset { previous, current }) = clock.record(std::time::SystemTime::now(), info.timestamp_ms) {
+            tracing::warn!(previous=previous.device_ms, current=current.device_ms,
+                "device clock went backwards, the device may have rebooted");
+        }
+
+        // separate updates by an empty line, keep one object per line otherwise
+        if format == OutputFormat::Human {
+            println!();
+        }
+
+        render::print(format, &output::Runtime { info, clock: clock.clone(), channel, component });
+    }
+
+    Ok(())
+}
+
+/// Print battery, placement, and ANC state whenever it changes.
+async fn cmd_monitor(handle: ClientHandle, channel: u32, format: OutputFormat) -> Result<()> {
+    let mut service = MaestroService::new(handle, channel);
+
+    let anc = match service.read_setting(settings::id::CurrentAncrState).await {
+        Ok(anc) => Some(anc),
+        Err(err) if is_unsupported(&err) => None,
+        Err(err) => return Err(err.into()),
+    };
+
+    let mut runtime = service.subscribe_to_runtime_info()?;
+    let mut runtime = runtime.stream();
+
+    let mut changes = service.subscribe_to_settings_changes()?;
+    let mut changes = changes.stream();
+
+    let info = runtime.next().await
+        .ok_or_else(|| anyhow::anyhow!("stream terminated without item"))??;
+
+    let formatter = Formatter::english();
+    let mut state = output::Monitor { info, anc };
+    let mut previous = None;
+
+    loop {
+        let lines = state.changes(previous.as_ref(), &formatter);
+
+        if !lines.is_empty() {
+            match format {
+                OutputFormat::Human => lines.iter().for_each(|line| println!("{line}")),
+                format => render::print(format, &state),
+            }
+
+            previous = Some(state.clone());
+        }
+
+        tokio::select! {
+            info = runtime.next() => {
+                state.info = info.ok_or_else(|| anyhow::anyhow!("runtime info stream terminated"))??;
+            },
+            rsp = changes.next() => {
+                let rsp = rsp.ok_or_else(|| anyhow::anyhow!("settings stream terminated"))??;
+
+                if let Some(settings_rsp::ValueOneof::Value(value)) = rsp.value_oneof
+                    && let Some(value) = value.value_oneof
+                    && let SettingValue::CurrentAncrState(anc) = value.into()
+                {
+                    state.anc = Some(anc);
+                }
+            },
+        }
+    }
+}
+
+/// Record battery levels until the given duration has elapsed, or until
+/// interrupted if unspecified.
+async fn cmd_battery_report(handle: ClientHandle, channel: u32, duration: Option<std::time::Duration>,
+    session: &mut battery::Session) -> Result<()>
+{
+    let mut service = MaestroService::new(handle, channel);
+
+    let mut call = service.subscribe_to_runtime_info()?;
+    let mut stream = call.stream();
+
+    let deadline = deadline(duration);
+    tokio::pin!(deadline);
+
+    println!("recording battery levels, press Ctrl-C to stop");
+
+    loop {
+        tokio::select! {
+            info = stream.next() => match info {
+                Some(info) => {
+                    if let Some(battery) = info?.battery_info {
+                        session.record(&battery);
+                    }
+                },
+                None => anyhow::bail!("stream terminated unexpectedly"),
+            },
+            _ = &mut deadline => return Ok(()),
+        }
+    }
+}
 
+/// Write the report of the session, returning the error of the recording
+/// itself in favor of any error writing the report.
+fn finish_battery_report(session: &battery::Session, result: Result<()>) -> Result<()> {
+    let finished = session.finish();
 
-#[tokio::main(flavor = "current_thread")]
-async fn main() -> Result<()> {
-    tracing_subscriber::fmt::init();
+    if result.is_err() && let Err(err) = &finished {
+        tracing::warn!(error=?err, "failed to write battery report");
+    }
 
-    let args = Args::parse();
+    result.and(finished)
+}
 
-    // set up session
-    let session = bluer::Session::new().await?;
-    let adapter = session.default_adapter().await?;
+/// Complete after the given duration, or never if unspecified.
+async fn deadline(duration: Option<std::time::Duration>) {
+    match duration {
+        Some(duration) => tokio::time::sleep(duration).await,
+        None => std::future::pending().await,
+    }
+}
 
-    // set up device
-    let dev = if let Some(address) = args.device {
-        tracing::debug!("using provided address: {}", address);
-        adapter.device(address)?
-    } else {
-        tracing::debug!("no device specified, searching for compatible one");
-        bt::find_maestro_device(&adapter).await?
+async fn cmd_show_battery(handle: ClientHandle, channel: u32, component: Option<Component>)
+    -> Result<output::Battery>
+{
+    let mut service = MaestroService::new(handle, channel);
+    let info = read_runtime_info(&mut service).await?;
+
+    Ok(output::Battery { info, component })
+}
+
+/// Estimate the total listening time, optionally based on the remaining time
+/// reported by the buds.
+async fn cmd_battery_total(handle: ClientHandle, channel: u32, bud_minutes: Option<u32>)
+    -> Result<output::BatteryTotal>
+{
+    let mut service = MaestroService::new(handle, channel);
+    let info = read_runtime_info(&mut service).await?;
+
+    Ok(output::BatteryTotal { minutes: output::estimate_minutes(&info, bud_minutes) })
+}
+
+/// Read the current runtime info.
+async fn read_runtime_info(service: &mut MaestroService) -> Result<RuntimeInfo> {
+    let mut call = service.subscribe_to_runtime_info()?;
+
+    let info = call.stream().next().await
+        .ok_or_else(|| anyhow::anyhow!("stream terminated without item"))??;
+
+    call.cancel();
+    Ok(info)
+}
+
+async fn cmd_status(handle: ClientHandle, channel: u32) -> Result<output::Status> {
+    let mut service = MaestroService::new(handle, channel);
+    let info = read_runtime_info(&mut service).await?;
+
+    let anc = match service.read_setting(settings::id::CurrentAncrState).await {
+        Ok(state) => Some(state),
+        Err(err) if is_unsupported(&err) => None,
+        Err(err) => return Err(err.into()),
     };
 
-    // set up profile
-    let stream = bt::connect_maestro_rfcomm(&session, &dev).await?;
+    let multipoint = match service.read_setting(settings::id::MultipointEnable).await {
+        Ok(enabled) => Some(enabled),
+        Err(err) if is_unsupported(&err) => None,
+        Err(err) => return Err(err.into()),
+    };
 
-    // set up codec
-    let codec = Codec::new();
-    let stream = codec.wrap(stream);
+    Ok(output::Status { info, anc, multipoint })
+}
 
-    // set up RPC client
-    let mut client = Client::new(stream);
-    let handle = client.handle();
+fn cmd_history(since: std::time::Duration) -> Result<()> {
+    use daemon::audit::Log;
+    use daemon::dosimeter;
 
-    // resolve channel
-    let channel = utils::resolve_channel(&mut client).await?;
+    let path = Log::default_path()
+        .ok_or_else(|| anyhow::anyhow!("cannot determine state directory"))?;
 
-    match args.command {
-        Command::Show { command } => match command {
-            ShowCommand::Software => run(client, cmd_show_software(handle, channel)).await,
-            ShowCommand::Hardware => run(client, cmd_show_hardware(handle, channel)).await,
-            ShowCommand::Runtime => run(client, cmd_show_runtime(handle, channel)).await,
-            ShowCommand::Battery => run(client, cmd_show_battery(handle, channel)).await,
-        },
-        Command::Get { setting } => match setting {
-            GetSetting::AutoOta => {
-                run(client, cmd_get_setting(handle, channel, settings::id::AutoOtaEnable)).await
-            },
-            GetSetting::Ohd => {
-                run(client, cmd_get_setting(handle, channel, settings::id::OhdEnable)).await
-            },
-            GetSetting::OobeIsFinished => {
-                run(client, cmd_get_setting(handle, channel, settings::id::OobeIsFinished)).await
-            },
-            GetSetting::Gestures => {
-                run(client, cmd_get_setting(handle, channel, settings::id::GestureEnable)).await
-            },
-            GetSetting::Diagnostics => {
-                run(client, cmd_get_setting(handle, channel, settings::id::DiagnosticsEnable)).await
-            }
-            GetSetting::OobeMode => {
-                run(client, cmd_get_setting(handle, channel, settings::id::OobeMode)).await
-            },
-            GetSetting::GestureControl => {
-                run(client, cmd_get_setting(handle, channel, settings::id::GestureControl)).await
-            },
-            GetSetting::Multipoint => {
-                run(client, cmd_get_setting(handle, channel, settings::id::MultipointEnable)).await
-            },
-            GetSetting::AncGestureLoop => {
-                run(client, cmd_get_setting(handle, channel, settings::id::AncrGestureLoop)).await
-            }
-            GetSetting::Anc => {
-                run(client, cmd_get_setting(handle, channel, settings::id::CurrentAncrState)).await
-            },
-            GetSetting::VolumeEq => {
-                run(client, cmd_get_setting(handle, channel, settings::id::VolumeEqEnable)).await
-            },
-            GetSetting::Eq => {
-                run(client, cmd_get_setting(handle, channel, settings::id::CurrentUserEq)).await
-            },
-            GetSetting::Balance => {
-                run(client, cmd_get_setting(handle, channel, settings::id::VolumeAsymmetry)).await
-            },
-            GetSetting::Mono => {
-                run(client, cmd_get_setting(handle, channel, settings::id::SumToMono)).await
-            },
-            GetSetting::VolumeExposureNotifications => {
-                run(client, cmd_get_setting(handle, channel, settings::id::VolumeExposureNotifications)).await
-            },
-            GetSetting::SpeechDetection => {
-                run(client, cmd_get_setting(handle, channel, settings::id::SpeechDetection)).await
-            },
-        },
-        Command::Set { setting } => match setting {
-            SetSetting::AutoOta { value } => {
-                let value = SettingValue::AutoOtaEnable(value);
-                run(client, cmd_set_setting(handle, channel, value)).await
-            },
-            SetSetting::Ohd { value } => {
-                let value = SettingValue::OhdEnable(value);
-                run(client, cmd_set_setting(handle, channel, value)).await
-            },
-            SetSetting::OobeIsFinished { value } => {
-                let value = SettingValue::OobeIsFinished(value);
-                run(client, cmd_set_setting(handle, channel, value)).await
-            },
-            SetSetting::Gestures { value } => {
-                let value = SettingValue::GestureEnable(value);
-                run(client, cmd_set_setting(handle, channel, value)).await
-            },
-            SetSetting::Diagnostics { value } => {
-                let value = SettingValue::DiagnosticsEnable(value);
-                run(client, cmd_set_setting(handle, channel, value)).await
-            },
-            SetSetting::OobeMode { value } => {
-                let value = SettingValue::OobeMode(value);
-                run(client, cmd_set_setting(handle, channel, value)).await
-            },
-            SetSetting::GestureControl { left, right } => {
-                let value = settings::GestureControl { left: left.into(), right: right.into() };
-                let value = SettingValue::GestureControl(value);
-                run(client, cmd_set_setting(handle, channel, value)).await
-            },
-            SetSetting::Multipoint { value } => {
-                let value = SettingValue::MultipointEnable(value);
-                run(client, cmd_set_setting(handle, channel, value)).await
-            },
-            SetSetting::AncGestureLoop { off, active, aware } => {
-                let value = settings::AncrGestureLoop { off, active, aware };
+    let now = dosimeter::timestamp(std::time::SystemTime::now());
+    let since = now.saturating_sub(since.as_millis() as u64);
 
-                if !value.is_valid() {
-                    use clap::error::ErrorKind;
+    let entries = Log::new(path).read(since)?;
 
-                    let mut cmd = Args::command();
-                    let err = cmd.error(
-                        ErrorKind::InvalidValue,
-                        "This command requires at least tow enabled ('true') modes"
-                    );
-                    err.exit();
-                }
+    if entries.is_empty() {
+        println!("no settings writes recorded, enable recording via 'audit-log = true' in the daemon configuration");
+        return Ok(());
+    }
 
-                let value = SettingValue::AncrGestureLoop(value);
-                run(client, cmd_set_setting(handle, channel, value)).await
-            },
-            SetSetting::Anc { value } => {
-                match value {
-                    AncState::Off => {
-                        let value = SettingValue::CurrentAncrState(settings::AncState::Off);
-                        run(client, cmd_set_setting(handle, channel, value)).await
-                    },
-                    AncState::Aware => {
-                        let value = SettingValue::CurrentAncrState(settings::AncState::Aware);
-                        run(client, cmd_set_setting(handle, channel, value)).await
-                    },
-                    AncState::Active => {
-                        let value = SettingValue::CurrentAncrState(settings::AncState::Active);
-                        run(client, cmd_set_setting(handle, channel, value)).await
-                    },
-                    AncState::CycleNext => {
-                        run(client, cmd_anc_cycle(handle, channel, true)).await
-                    },
-                    AncState::CyclePrev => {
-                        run(client, cmd_anc_cycle(handle, channel, false)).await
-                    },
-                }
-            },
-            SetSetting::VolumeEq { value } => {
-                let value = SettingValue::VolumeEqEnable(value);
-                run(client, cmd_set_setting(handle, channel, value)).await
-            },
-            SetSetting::Eq { low_bass, bass, mid, treble, upper_treble } => {
-                let value = settings::EqBands::new(low_bass, bass, mid, treble, upper_treble);
-                let value = SettingValue::CurrentUserEq(value);
-                run(client, cmd_set_setting(handle, channel, value)).await
-            },
-            SetSetting::Balance { value } => {
-                let value = settings::VolumeAsymmetry::from_normalized(value);
-                let value = SettingValue::VolumeAsymmetry(value);
-                run(client, cmd_set_setting(handle, channel, value)).await
-            },
-            SetSetting::Mono { value } => {
-                let value = SettingValue::SumToMono(value);
-                run(client, cmd_set_setting(handle, channel, value)).await
-            },
-            SetSetting::VolumeExposureNotifications { value } => {
-                let value = SettingValue::VolumeExposureNotifications(value);
-                run(client, cmd_set_setting(handle, channel, value)).await
-            },
-            SetSetting::SpeechDetection { value } => {
-                let value = SettingValue::SpeechDetection(value);
-                run(client, cmd_set_setting(handle, channel, value)).await
-            },
+    for entry in entries {
+        let old = entry.old.as_deref().unwrap_or("unknown");
+
+        println!("{}  {:<7} {}: {old} -> {}", dosimeter::format_timestamp(entry.time), entry.source, entry.setting, entry.new);
+    }
+
+    Ok(())
+}
+
+/// Audit log for settings writes performed by the CLI, if enabled in the
+/// daemon configuration.
+fn audit_log() -> Option<daemon::audit::Log> {
+    let config = match daemon::config::Config::load(None, None) {
+        Ok(config) => config,
+        Err(err) => {
+            tracing::warn!(error=?err, "failed to load daemon configuration, not recording settings writes");
+            return None;
         },
+    };
+
+    config.audit_log
+        .then(daemon::audit::Log::default_path)
+        .flatten()
+        .map(daemon::audit::Log::new)
+}
+
+/// Record a settings write performed by the CLI to the audit log, if enabled.
+fn audit(log: &mut Option<daemon::audit::Log>, value: &SettingValue, old: Option<&SettingValue>) {
+    if let Some(log) = log {
+        log.record("cli", value, old);
     }
 }
 
-async fn cmd_show_software(handle: ClientHandle, channel: u32) -> Result<()> {
-    let mut service = MaestroService::new(handle, channel);
+fn cmd_rpc_hash(name: &str) {
+    use maestro::pwrpc::id::{self, PathRef};
 
-    let info = service.get_software_info().await?;
+    if !name.contains('/') {
+        println!("0x{:08x}", id::hash(name));
+        return;
+    }
+
+    let path = PathRef::new(name);
+    println!("service: 0x{:08x} ({})", path.service().hash(), path.service().name());
+    println!("method:  0x{:08x} ({})", path.method().hash(), path.method().name());
+}
 
-    let fw_ver_case = info.firmware.as_ref()
-        .and_then(|fw| fw.case.as_ref())
-        .map(|fw| fw.version_string.as_str())
-        .unwrap_or("unknown");
-
-    let fw_ver_left = info.firmware.as_ref()
-        .and_then(|fw| fw.left.as_ref())
-        .map(|fw| fw.version_string.as_str())
-        .unwrap_or("unknown");
-
-    let fw_ver_right = info.firmware.as_ref()
-        .and_then(|fw| fw.right.as_ref())
-        .map(|fw| fw.version_string.as_str())
-        .unwrap_or("unknown");
-
-    let fw_unk_case = info.firmware.as_ref()
-        .and_then(|fw| fw.case.as_ref())
-        .map(|fw| fw.unknown.as_str())
-        .unwrap_or("unknown");
-
-    let fw_unk_left = info.firmware.as_ref()
-        .and_then(|fw| fw.left.as_ref())
-        .map(|fw| fw.unknown.as_str())
-        .unwrap_or("unknown");
-
-    let fw_unk_right = info.firmware.as_ref()
-        .and_then(|fw| fw.right.as_ref())
-        .map(|fw| fw.unknown.as_str())
-        .unwrap_or("unknown");
-
-    println!("firmware:");
-    println!("  case:      {fw_ver_case} ({fw_unk_case})");
-    println!("  left bud:  {fw_ver_left} ({fw_unk_left})");
-    println!("  right bud: {fw_ver_right} ({fw_unk_right})");
+async fn cmd_dosimeter_summary(handle: ClientHandle, channel: u32) -> Result<output::Dosimeter> {
+    let mut service = DosimeterService::new(handle, channel);
+    let summary = service.fetch_daily_summaries().await?;
 
-    Ok(())
+    Ok(output::Dosimeter { summary })
 }
 
-async fn cmd_show_hardware(handle: ClientHandle, channel: u32) -> Result<()> {
-    let mut service = MaestroService::new(handle, channel);
+fn cmd_dosimeter_history(since: std::time::Duration) -> Result<()> {
+    use daemon::dosimeter::{self, Record, Store};
 
-    let info = service.get_hardware_info().await?;
+    let path = Store::default_path()
+        .ok_or_else(|| anyhow::anyhow!("cannot determine data directory"))?;
+
+    let now = dosimeter::timestamp(std::time::SystemTime::now());
+    let since = now.saturating_sub(since.as_millis() as u64);
+
+    let records = Store::new(path).read(since)?;
 
-    let serial_case = info.serial_number.as_ref()
-        .map(|ser| ser.case.as_str())
-        .unwrap_or("unknown");
+    if records.is_empty() {
+        println!("no dosimeter data recorded, enable recording via the [dosimeter] section of the daemon configuration");
+        return Ok(());
+    }
+
+    println!("sound level (hourly):");
+    for (time, mean, max) in dosimeter::hourly(&records) {
+        println!("  {}  mean: {mean:.0} dB, max: {max:.0} dB", dosimeter::format_timestamp(time));
+    }
 
-    let serial_left = info.serial_number.as_ref()
-        .map(|ser| ser.left.as_str())
-        .unwrap_or("unknown");
+    println!();
+    println!("daily summaries (raw):");
+    for record in &records {
+        let Record::Summary { time, summary } = record else { continue };
 
-    let serial_right = info.serial_number.as_ref()
-        .map(|ser| ser.right.as_str())
-        .unwrap_or("unknown");
+        let entries: Vec<_> = summary.unknown2.iter()
+            .map(|e| format!("{:.2}", e.unknown6))
+            .collect();
 
-    println!("serial numbers:");
-    println!("  case:      {serial_case}");
-    println!("  left bud:  {serial_left}");
-    println!("  right bud: {serial_right}");
+        println!("  {}  {:.2} [{}]", dosimeter::format_timestamp(*time), summary.unknown5, entries.join(", "));
+    }
 
     Ok(())
 }
 
-async fn cmd_show_runtime(handle: ClientHandle, channel: u32) -> Result<()> {
-    let mut service = MaestroService::new(handle, channel);
+async fn cmd_find(address: Option<transport::Address>, adapter: Option<&str>, no_daemon: bool, stop: bool, side: Option<RingSide>,
+    duration: Option<std::time::Duration>) -> Result<()>
+{
+    use gfps::msg::RingState;
 
-    let mut call = service.subscribe_to_runtime_info()?;
+    // the daemon only supports the default escalation
+    let custom = side.is_some() || duration.is_some();
 
-    let info = call.stream().next().await
-        .ok_or_else(|| anyhow::anyhow!("stream terminated without item"))??;
+    if !no_daemon && !custom && let Some(daemon) = DaemonClient::connect(address).await {
+        if stop {
+            if !daemon.stop_ringing().await? {
+                println!("not ringing");
+            }
+        } else {
+            daemon.ring().await?;
+            println!("ringing, touch a bud or run 'pbpctrl find --stop' to stop");
+        }
 
-    let bat_level_case = info.battery_info.as_ref()
-        .and_then(|b| b.case.as_ref())
-        .map(|b| b.level);
+        return Ok(());
+    }
 
-    let bat_state_case = info.battery_info.as_ref()
-        .and_then(|b| b.case.as_ref())
-        .map(|b| if b.state == 2 { "charging" } else if b.state == 1 { "not charging" } else { "unknown" })
-        .unwrap_or("unknown");
+    let transport = transport::Platform::open(address, adapter).await?;
+    let mut stream = transport.gfps_connect().await?;
 
-    let bat_level_left = info.battery_info.as_ref()
-        .and_then(|b| b.left.as_ref())
-        .map(|b| b.level);
+    // without a daemon, ring in the foreground until found or interrupted
+    if stop {
+        gfps::ring::set(&mut stream, RingState::NONE).await?;
+        return Ok(());
+    }
 
-    let bat_state_left = info.battery_info.as_ref()
-        .and_then(|b| b.left.as_ref())
-        .map(|b| if b.state == 2 { "charging" } else if b.state == 1 { "not charging" } else { "unknown" })
-        .unwrap_or("unknown");
+    let buds = side.map(|side| match side {
+        RingSide::Left => RingState::LEFT,
+        RingSide::Right => RingState::RIGHT,
+        RingSide::Both => RingState::BOTH,
+    });
 
-    let bat_level_right = info.battery_info.as_ref()
-        .and_then(|b| b.right.as_ref())
-        .map(|b| b.level);
+    let stages = find::schedule(buds, duration);
 
-    let bat_state_right = info.battery_info.as_ref()
-        .and_then(|b| b.right.as_ref())
-        .map(|b| if b.state == 2 { "charging" } else if b.state == 1 { "not charging" } else { "unknown" })
-        .unwrap_or("unknown");
+    // stop on Ctrl-C, or on Enter if run interactively
+    let stop = async {
+        let enter = async {
+            if std::io::IsTerminal::is_terminal(&std::io::stdin()) {
+                use tokio::io::AsyncBufReadExt;
 
-    let place_left = info.placement.as_ref()
-        .map(|p| if p.left_bud_in_case { "in case" } else { "out of case" })
-        .unwrap_or("unknown");
+                let mut line = String::new();
+                let _ = tokio::io::BufReader::new(tokio::io::stdin()).read_line(&mut line).await;
+            } else {
+                std::future::pending::<()>().await;
+            }
+        };
 
-    let place_right = info.placement.as_ref()
-        .map(|p| if p.right_bud_in_case { "in case" } else { "out of case" })
-        .unwrap_or("unknown");
+        tokio::select! {
+            _ = tokio::signal::ctrl_c() => {},
+            _ = enter => {},
+        }
+    };
 
-    println!("clock: {} ms", info.timestamp_ms);
-    println!();
+    let progress = |stage: &find::Stage| {
+        println!("ringing {} for {}s, touch a bud or press Enter to stop...", stage.buds, stage.duration.as_secs());
+    };
 
-    println!("battery:");
-    if let Some(lvl) = bat_level_case {
-        println!("  case:      {lvl}% ({bat_state_case})");
-    } else {
-        println!("  case:      unknown");
-    }
-    if let Some(lvl) = bat_level_left {
-        println!("  left bud:  {lvl}% ({bat_state_left})");
-    } else {
-        println!("  left bud:  unknown");
-    }
-    if let Some(lvl) = bat_level_right {
-        println!("  right bud: {lvl}% ({bat_state_right})");
-    } else {
-        println!("  right bud: unknown");
+    match find::run(&mut stream, &stages, stop, progress).await? {
+        find::Outcome::Found => println!("found"),
+        find::Outcome::Stopped => println!("stopped"),
+        find::Outcome::GaveUp if duration.is_some() => println!("done"),
+        find::Outcome::GaveUp => println!("not found, giving up"),
     }
-    println!();
 
-    println!("placement:");
-    println!("  left bud:  {place_left}");
-    println!("  right bud: {place_right}");
+    Ok(())
+}
+
+async fn cmd_multipoint_claim(address: Option<transport::Address>, adapter: Option<&str>, resume: bool,
+    disconnect_other: bool) -> Result<()>
+{
+    use gfps::sass::SwitchResult;
 
-    let address = addr::address_for_channel(channel);
-    let peer_local = address.map(|a| a.source());
-    let peer_remote = address.map(|a| a.target());
+    let transport = transport::Platform::open(address, adapter).await?;
+    let mut stream = transport.gfps_connect().await?;
 
-    println!();
-    println!("connection:");
-    if let Some(peer) = peer_local {
-        println!("  local:  {peer:?}");
-    } else {
-        println!("  local:  unknown");
+    let flags = gfps::msg::SwitchFlags { resume_playing: resume, disconnect_other, ..Default::default() };
+
+    match gfps::sass::switch_to(&mut stream, flags).await? {
+        SwitchResult::Switched => println!("audio switched to this device"),
+        SwitchResult::AlreadyActive => println!("this device already is the active audio source"),
+        SwitchResult::SwitchedAway { device_name } => {
+            anyhow::bail!("buds switched audio to '{device_name}' instead")
+        },
+        SwitchResult::Rejected(gfps::msg::NakReason::IncorrectMac) => {
+            anyhow::bail!("switch rejected: the buds require requests authenticated with an account key, \
+                which is not supported")
+        },
+        SwitchResult::Rejected(reason) => anyhow::bail!("switch rejected by buds: {reason}"),
+        SwitchResult::Unconfirmed => anyhow::bail!("buds acknowledged the switch but did not confirm it"),
     }
-    if let Some(peer) = peer_remote {
-        println!("  remote: {peer:?}");
-    } else {
-        println!("  remote: unknown");
+
+    Ok(())
+}
+
+async fn cmd_doctor(address: Option<transport::Address>, adapter: Option<&str>, mode: ConnectMode,
+    timeout: std::time::Duration, format: OutputFormat) -> Result<()>
+{
+    let _lock = lock_connection().await?;
+
+    let report = doctor::run(address, adapter, mode, timeout).await;
+    render::print(format, &report);
+
+    if report.failed() {
+        anyhow::bail!("connection diagnostics failed");
     }
 
     Ok(())
 }
 
-async fn cmd_show_battery(handle: ClientHandle, channel: u32) -> Result<()> {
-    let mut service = MaestroService::new(handle, channel);
+async fn cmd_devices(adapter: Option<&str>) -> Result<output::Devices> {
+    let devices = transport::bluez::devices(adapter).await?;
+    Ok(output::Devices { devices })
+}
 
-    let mut call = service.subscribe_to_runtime_info()?;
+async fn cmd_pair(address: Option<transport::Address>, adapter: Option<&str>, mode: ConnectMode,
+    timeout: std::time::Duration) -> Result<()>
+{
+    println!("searching for devices in pairing mode...");
 
-    let info = call.stream().next().await
-        .ok_or_else(|| anyhow::anyhow!("stream terminated without item"))??;
+    let _lock = lock_connection().await?;
+
+    let transport = transport::bluez::pair(address, adapter, timeout).await?
+        .with_connect_mode(mode);
+    println!("paired with {}", transport.identity());
 
-    let bat_level_case = info.battery_info.as_ref()
-        .and_then(|b| b.case.as_ref())
-        .map(|b| b.level);
+    // verify that we can talk to the device
+    let stream = connect(&transport, DEFAULT_TIMEOUT).await?;
+    let codec = Codec::new();
+    let stats = codec.stats();
+    let mut client = Client::new(codec.wrap(stream));
 
-    let bat_state_case = info.battery_info.as_ref()
-        .and_then(|b| b.case.as_ref())
-        .map(|b| if b.state == 2 { "charging" } else if b.state == 1 { "not charging" } else { "unknown" })
-        .unwrap_or("unknown");
+    let channel = resolve_channel(&mut client, &stats, None, DEFAULT_TIMEOUT).await?;
 
-    let bat_level_left = info.battery_info.as_ref()
-        .and_then(|b| b.left.as_ref())
-        .map(|b| b.level);
+    println!("maestro channel resolved: {channel}");
+    println!("setup complete");
 
-    let bat_state_left = info.battery_info.as_ref()
-        .and_then(|b| b.left.as_ref())
-        .map(|b| if b.state == 2 { "charging" } else if b.state == 1 { "not charging" } else { "unknown" })
-        .unwrap_or("unknown");
+    client.terminate().await?;
+    Ok(())
+}
 
-    let bat_level_right = info.battery_info.as_ref()
-        .and_then(|b| b.right.as_ref())
-        .map(|b| b.level);
+fn cmd_describe_setting(setting: SettingId) -> Result<()> {
+    let info = setting.info();
 
-    let bat_state_right = info.battery_info.as_ref()
-        .and_then(|b| b.right.as_ref())
-        .map(|b| if b.state == 2 { "charging" } else if b.state == 1 { "not charging" } else { "unknown" })
-        .unwrap_or("unknown");
+    println!("{setting}");
+    println!("  description:  {}", info.description);
+    println!("  danger:       {}", info.danger);
+    println!("  unit:         {}", info.unit.unwrap_or("none"));
+    println!("  min firmware: {}", info.min_firmware.unwrap_or("unknown"));
 
-    if let Some(lvl) = bat_level_case {
-        println!("case:      {lvl}% ({bat_state_case})");
-    } else {
-        println!("case:      unknown");
+    if info.requires_matching_firmware {
+        println!("  note:         requires both buds to run the same firmware");
     }
-    if let Some(lvl) = bat_level_left {
-        println!("left bud:  {lvl}% ({bat_state_left})");
-    } else {
-        println!("left bud:  unknown");
+
+    Ok(())
+}
+
+async fn cmd_get_setting(handle: ClientHandle, channel: u32, setting: SettingId) -> Result<output::Setting> {
+    let mut service = MaestroService::new(handle, channel);
+
+    let value = match service.read_setting_with_retry(setting, Retry::default()).await {
+        Ok(value) => value,
+        Err(err) if is_unsupported(&err) => {
+            let info = service.get_software_info().await.ok();
+            anyhow::bail!(output::unsupported_str(setting, info.as_ref()));
+        },
+        Err(err) => return Err(err.into()),
+    };
+
+    Ok(output::Setting { value })
+}
+
+/// Read all settings, skipping those the given model is known not to support.
+async fn cmd_get_all(handle: ClientHandle, channel: u32, model: Option<&Model>) -> Result<output::Settings> {
+    let mut service = MaestroService::new(handle, channel);
+
+    let settings = SETTINGS.into_iter()
+        .filter(|s| model.is_none_or(|m| m.supports_setting(*s)));
+
+    let mut values = Vec::new();
+    for setting in settings {
+        match service.read_setting_with_retry(setting, Retry::default()).await {
+            Ok(value) => values.push((setting, Some(value))),
+            Err(err) if is_unsupported(&err) => values.push((setting, None)),
+            Err(err) => return Err(err.into()),
+        }
     }
-    if let Some(lvl) = bat_level_right {
-        println!("right bud: {lvl}% ({bat_state_right})");
-    } else {
-        println!("right bud: unknown");
+
+    Ok(output::Settings { values })
+}
+
+/// Save all supported settings included in backups to the given file.
+async fn cmd_export_settings(handle: ClientHandle, channel: u32, path: std::path::PathBuf) -> Result<()> {
+    let mut service = MaestroService::new(handle, channel);
+
+    let mut values = Vec::new();
+    for setting in SETTINGS.into_iter().filter(|s| backup::is_backed_up(*s)) {
+        match service.read_setting_with_retry(setting, Retry::default()).await {
+            Ok(value) => values.push(value),
+            Err(err) if is_unsupported(&err) => tracing::debug!(%setting, "setting not supported, skipping"),
+            Err(err) => return Err(err.into()),
+        }
     }
 
+    backup::write(&path, &values)?;
+    println!("saved {} settings to {}", values.len(), path.display());
+
     Ok(())
 }
 
-async fn cmd_get_setting<T>(handle: ClientHandle, channel: u32, setting: T) -> Result<()>
-where
-    T: Setting,
-    T::Type: std::fmt::Display,
+/// Whether the given error indicates that the firmware does not support the
+/// requested setting.
+fn is_unsupported(err: &maestro::pwrpc::Error) -> bool {
+    use maestro::pwrpc::Status;
+
+    matches!(err.code(), Status::Unimplemented | Status::InvalidArgument)
+}
+
+async fn cmd_set_setting(handle: ClientHandle, channel: u32, setting: SettingValue, force: bool,
+    log: &mut Option<daemon::audit::Log>) -> Result<()>
+{
+    let mut service = MaestroService::new(handle, channel);
+
+    if setting.id().info().requires_matching_firmware {
+        let info = service.get_software_info().await?;
+        check_firmware(&info.firmware.unwrap_or_default(), &setting, force)?;
+    }
+
+    service.write_setting(setting.clone()).await?;
+    audit(log, &setting, None);
+
+    Ok(())
+}
+
+async fn cmd_set_settings(handle: ClientHandle, channel: u32, values: Vec<SettingValue>, force: bool,
+    log: &mut Option<daemon::audit::Log>) -> Result<()>
 {
     let mut service = MaestroService::new(handle, channel);
 
-    let value = service.read_setting(setting).await?;
-    println!("{value}");
+    // check all values first, so that none are written if one is refused
+    if values.iter().any(|v| v.id().info().requires_matching_firmware) {
+        let firmware = service.get_software_info().await?.firmware.unwrap_or_default();
+
+        for value in &values {
+            check_firmware(&firmware, value, force)?;
+        }
+    }
+
+    for value in values {
+        service.write_setting(value.clone()).await?;
+        audit(log, &value, None);
+    }
 
     Ok(())
 }
 
-async fn cmd_set_setting(handle: ClientHandle, channel: u32, setting: SettingValue) -> Result<()> {
+/// Refuse to write settings known to desync the buds if they run different
+/// firmware versions, unless forced.
+fn check_firmware(firmware: &FirmwareInfo, value: &SettingValue, force: bool) -> Result<()> {
+    if !value.id().info().requires_matching_firmware || firmware.buds_match() != Some(false) {
+        return Ok(());
+    }
+
+    let version = |fw: &Option<FirmwareVersion>| fw.as_ref()
+        .map(|fw| fw.version_string.clone())
+        .unwrap_or_default();
+
+    let left = version(&firmware.left);
+    let right = version(&firmware.right);
+
+    if force {
+        tracing::warn!(%left, %right, setting=%value.id(), "buds run different firmware versions, writing anyway");
+        return Ok(());
+    }
+
+    anyhow::bail!(
+        "the buds run different firmware versions ({left} on the left, {right} on the right bud), \
+        writing {} may desync them; update both buds or use --force to write anyway",
+        value.id()
+    )
+}
+
+async fn cmd_set_setting_for(handle: ClientHandle, channel: u32, setting: SettingValue, duration: std::time::Duration,
+    log: &mut Option<daemon::audit::Log>) -> Result<()>
+{
     let mut service = MaestroService::new(handle, channel);
 
-    service.write_setting(setting).await?;
+    let previous = service.read_setting_var(setting.id()).await?;
+    service.write_setting(setting.clone()).await?;
+    audit(log, &setting, Some(&previous));
+
+    println!("reverting to '{previous}' in {}s, press Ctrl+C to revert now", duration.as_secs());
+
+    // this must be run via run_task(), run() would drop this future on
+    // Ctrl+C before the setting has been reverted
+    tokio::select! {
+        _ = tokio::time::sleep(duration) => {},
+        _ = tokio::signal::ctrl_c() => {},
+    }
+
+    service.write_setting(previous.clone()).await?;
+    audit(log, &previous, Some(&setting));
+
     Ok(())
 }
 
-async fn cmd_anc_cycle(handle: ClientHandle, channel: u32, forward: bool) -> Result<()> {
+async fn cmd_anc_cycle(handle: ClientHandle, channel: u32, forward: bool, log: &mut Option<daemon::audit::Log>)
+    -> Result<()>
+{
     let mut service = MaestroService::new(handle, channel);
 
     let enabled = service.read_setting(settings::id::AncrGestureLoop).await?;
     let state = service.read_setting(settings::id::CurrentAncrState).await?;
 
+    if let Some(next) = anc_cycle_next(enabled, state, forward)? {
+        let value = SettingValue::CurrentAncrState(next);
+
+        service.write_setting(value.clone()).await?;
+        audit(log, &value, Some(&SettingValue::CurrentAncrState(state)));
+    }
+
+    Ok(())
+}
+
+/// Swap the hold-gesture actions and invert the volume balance, unless the
+/// sides of the device already are in the requested state.
+async fn cmd_swap_sides(handle: ClientHandle, channel: u32, address: transport::Address, swapped: bool, force: bool,
+    log: &mut Option<daemon::audit::Log>) -> Result<()>
+{
+    let Some(store) = sides_store(address, swapped)? else {
+        return Ok(());
+    };
+
+    let mut service = MaestroService::new(handle, channel);
+
+    let gestures = service.read_setting(settings::id::GestureControl).await?;
+    let balance = service.read_setting(settings::id::VolumeAsymmetry).await?;
+    let (new_gestures, new_balance) = sides::swap(gestures, balance);
+
+    let info = service.get_software_info().await?;
+    check_firmware(&info.firmware.unwrap_or_default(), &SettingValue::GestureControl(new_gestures), force)?;
+
+    service.write_setting(SettingValue::GestureControl(new_gestures)).await?;
+
+    // don't leave the sides half-swapped
+    if let Err(err) = service.write_setting(SettingValue::VolumeAsymmetry(new_balance)).await {
+        if let Err(err) = service.write_setting(SettingValue::GestureControl(gestures)).await {
+            tracing::warn!(error=?err, "failed to restore gesture control");
+        }
+
+        return Err(err.into());
+    }
+
+    audit(log, &SettingValue::GestureControl(new_gestures), Some(&SettingValue::GestureControl(gestures)));
+    audit(log, &SettingValue::VolumeAsymmetry(new_balance), Some(&SettingValue::VolumeAsymmetry(balance)));
+
+    store.set_swapped(address, swapped)
+}
+
+/// Open the swapped-sides state. Returns `None` if the sides of the device
+/// already are in the requested state.
+fn sides_store(address: transport::Address, swapped: bool) -> Result<Option<sides::Store>> {
+    let path = sides::Store::default_path()
+        .ok_or_else(|| anyhow::anyhow!("no state directory for recording swapped sides"))?;
+
+    let store = sides::Store::new(path);
+
+    if store.is_swapped(address) == swapped {
+        println!("sides are already {}", if swapped { "swapped" } else { "not swapped" });
+        return Ok(None);
+    }
+
+    Ok(Some(store))
+}
+
+fn anc_cycle_next(enabled: settings::AncrGestureLoop, state: settings::AncState, forward: bool)
+    -> Result<Option<settings::AncState>>
+{
     if let settings::AncState::Unknown(x) = state {
         anyhow::bail!("unknown ANC state: {x}");
     }
@@ -464,15 +1711,51 @@ async fn cmd_anc_cycle(handle: ClientHandle, channel: u32, forward: bool) -> Res
 
         let (state, enabled) = states[next];
         if enabled {
-            service.write_setting(SettingValue::CurrentAncrState(state)).await?;
-            break;
+            return Ok(Some(state));
         }
     }
 
+    Ok(None)
+}
+
+/// Output format of commands with a legacy `--json` flag.
+fn json_or(json: bool, format: OutputFormat) -> OutputFormat {
+    if json { OutputFormat::Json } else { format }
+}
+
+/// Run the given command handler and print its result in the given format.
+async fn render<T: Output>(format: OutputFormat, task: impl Future<Output = Result<T>>) -> Result<()> {
+    let output = task.await?;
+    render::print(format, &output);
     Ok(())
 }
 
-pub async fn run<S, E, F>(mut client: Client<S>, task: F) -> Result<()>
+/// Run the task on the client, stopping it on Ctrl+C.
+pub async fn run<S, E, F>(client: Client<S>, task: F) -> Result<()>
+where
+    S: futures::Sink<maestro::pwrpc::types::RpcPacket>,
+    S: futures::Stream<Item = Result<maestro::pwrpc::types::RpcPacket, E>> + Unpin,
+    maestro::pwrpc::Error: From<E>,
+    maestro::pwrpc::Error: From<S::Error>,
+    F: Future<Output=Result<(), anyhow::Error>>,
+{
+    let task = async {
+        tokio::select! {
+            res = task => res,
+            sig = tokio::signal::ctrl_c() => {
+                sig?;
+                tracing::trace!("client termination requested");
+                Ok(())
+            },
+        }
+    };
+
+    run_task(client, task).await
+}
+
+/// Run the task on the client until it has completed, leaving Ctrl+C to the
+/// task itself.
+pub async fn run_task<S, E, F>(mut client: Client<S>, task: F) -> Result<()>
 where
     S: futures::Sink<maestro::pwrpc::types::RpcPacket>,
     S: futures::Stream<Item = Result<maestro::pwrpc::types::RpcPacket, E>> + Unpin,
@@ -489,10 +1772,6 @@ where
             res?;
             tracing::trace!("task terminated successfully");
         }
-        sig = tokio::signal::ctrl_c() => {
-            sig?;
-            tracing::trace!("client termination requested");
-        },
     }
 
     client.terminate().await?;
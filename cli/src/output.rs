@@ -0,0 +1,1218 @@
+//! Output of per-component device information.
+
+use std::borrow::Cow;
+use std::fmt::Display;
+use std::time::Duration;
+
+use maestro::protocol::addr;
+use maestro::protocol::types::{
+    DeviceBatteryInfo, DosimeterSummary, FirmwareVersion, HardwareInfo, RuntimeInfo, SoftwareInfo,
+};
+use maestro::service::SnapshotTiming;
+use maestro::service::clock::{ClockTracker, DeviceTime};
+use maestro::service::format::{Formatter, Term};
+use maestro::service::settings::{AncState, SettingId, SettingValue};
+
+use serde_json::{json, Map, Value};
+
+use crate::cli::{Component, StatusFormat};
+use crate::daemon::state;
+use crate::render::{self, Output, Renderer};
+use crate::transport::KnownDevice;
+
+
+const COMPONENTS: [Component; 3] = [Component::Case, Component::Left, Component::Right];
+
+/// Order of the components in the `status` output.
+const STATUS_ORDER: [Component; 3] = [Component::Left, Component::Right, Component::Case];
+
+/// Approximate listening time provided by fully charged buds, in minutes.
+const BUD_MINUTES: u32 = 7 * 60;
+
+/// Approximate additional listening time provided by a fully charged case,
+/// in minutes.
+const CASE_MINUTES: u32 = 13 * 60;
+
+
+/// Iterate over all components matching the given filter.
+pub fn components(filter: Option<Component>) -> impl Iterator<Item = Component> {
+    COMPONENTS.into_iter()
+        .filter(move |c| filter.is_none_or(|f| f == *c))
+}
+
+pub fn label(component: Component) -> Cow<'static, str> {
+    let term = match component {
+        Component::Case => Term::Case,
+        Component::Left => Term::LeftBud,
+        Component::Right => Term::RightBud,
+    };
+
+    Formatter::english().text(term)
+}
+
+pub fn key(component: Component) -> &'static str {
+    match component {
+        Component::Case => "case",
+        Component::Left => "left",
+        Component::Right => "right",
+    }
+}
+
+/// Format a single labeled line for the given component.
+pub fn line(indent: &str, component: Component, value: impl Display) -> String {
+    let label = format!("{}:", label(component));
+    format!("{indent}{label:<10} {value}")
+}
+
+
+pub fn firmware(info: &SoftwareInfo, component: Component) -> Option<&FirmwareVersion> {
+    let fw = info.firmware.as_ref()?;
+
+    match component {
+        Component::Case => fw.case.as_ref(),
+        Component::Left => fw.left.as_ref(),
+        Component::Right => fw.right.as_ref(),
+    }
+}
+
+pub fn serial(info: &HardwareInfo, component: Component) -> Option<&str> {
+    let serial = info.serial_number.as_ref()?;
+
+    let serial = match component {
+        Component::Case => &serial.case,
+        Component::Left => &serial.left,
+        Component::Right => &serial.right,
+    };
+
+    Some(serial.as_str())
+}
+
+pub fn battery(info: &RuntimeInfo, component: Component) -> Option<&DeviceBatteryInfo> {
+    let battery = info.battery_info.as_ref()?;
+
+    match component {
+        Component::Case => battery.case.as_ref(),
+        Component::Left => battery.left.as_ref(),
+        Component::Right => battery.right.as_ref(),
+    }
+}
+
+/// Whether the given bud is placed in the case. Returns `None` for the case
+/// itself or if the placement is unknown.
+pub fn in_case(info: &RuntimeInfo, component: Component) -> Option<bool> {
+    let placement = info.placement.as_ref()?;
+
+    match component {
+        Component::Case => None,
+        Component::Left => Some(placement.left_bud_in_case),
+        Component::Right => Some(placement.right_bud_in_case),
+    }
+}
+
+/// Whether both buds are placed in the case, in which case only limited data
+/// is available.
+pub fn buds_in_case(info: &RuntimeInfo) -> bool {
+    info.placement.is_some_and(|p| p.left_bud_in_case && p.right_bud_in_case)
+}
+
+
+pub fn firmware_str(fw: Option<&FirmwareVersion>) -> String {
+    match fw {
+        Some(fw) => format!("{} ({})", fw.version_string, fw.unknown),
+        None => "unknown (unknown)".to_owned(),
+    }
+}
+
+pub fn battery_str(battery: Option<&DeviceBatteryInfo>) -> String {
+    Formatter::english().battery(battery)
+}
+
+/// Estimate the total remaining listening time in minutes, including the
+/// charge of the case.
+///
+/// Uses the remaining time reported by the buds, if available, and the
+/// lower of both bud levels otherwise.
+pub fn estimate_minutes(info: &RuntimeInfo, bud_minutes: Option<u32>) -> Option<u32> {
+    let bud_level = [Component::Left, Component::Right].into_iter()
+        .filter_map(|c| battery(info, c))
+        .map(|b| b.level.clamp(0, 100) as u32)
+        .min();
+
+    let buds = bud_minutes.or(bud_level.map(|level| level * BUD_MINUTES / 100))?;
+
+    let case = battery(info, Component::Case)
+        .map(|b| b.level.clamp(0, 100) as u32 * CASE_MINUTES / 100)
+        .unwrap_or(0);
+
+    Some(buds + case)
+}
+
+/// Describe a setting not supported by the firmware of the buds.
+pub fn unsupported_str(setting: SettingId, info: Option<&SoftwareInfo>) -> String {
+    let version = |c| {
+        info.and_then(|info| firmware(info, c))
+            .map(|fw| fw.version_string.as_str())
+            .unwrap_or("unknown")
+    };
+
+    let left = version(Component::Left);
+    let right = version(Component::Right);
+
+    if left == right {
+        format!("setting {setting} is not supported by firmware {left}")
+    } else {
+        format!("setting {setting} is not supported by firmware {left} ({}) or {right} ({})",
+            label(Component::Left), label(Component::Right))
+    }
+}
+
+pub fn minutes_str(minutes: Option<u32>) -> String {
+    match minutes {
+        Some(minutes) => format!("{}h {}m", minutes / 60, minutes % 60),
+        None => "unknown".to_owned(),
+    }
+}
+
+pub fn duration_str(duration: Duration) -> String {
+    let secs = duration.as_secs();
+
+    match secs {
+        0..60 => format!("{secs}s"),
+        60..3600 => format!("{}m {}s", secs / 60, secs % 60),
+        _ => format!("{}h {}m {}s", secs / 3600, secs / 60 % 60, secs % 60),
+    }
+}
+
+
+/// Device state shown by the `status` command.
+#[derive(Debug, Clone, Default)]
+pub struct Status {
+    pub info: RuntimeInfo,
+
+    /// Current ANC state, `None` if unknown or not supported.
+    pub anc: Option<AncState>,
+
+    /// Whether multipoint is enabled, `None` if unknown or not supported.
+    pub multipoint: Option<bool>,
+}
+
+impl Output for Status {
+    fn human(&self, _fmt: &Formatter) -> String {
+        status_str(self, StatusFormat::Plain)
+    }
+
+    fn json(&self) -> Value {
+        let battery: Map<_, _> = STATUS_ORDER.into_iter()
+            .map(|c| {
+                let value = match battery(&self.info, c) {
+                    Some(b) => json!({ "level": b.level, "charging": b.state == 2 }),
+                    None => Value::Null,
+                };
+
+                (key(c).to_owned(), value)
+            })
+            .collect();
+
+        json!({
+            "battery": battery,
+            "anc": self.anc.map(|anc| anc.as_str()),
+            "multipoint": self.multipoint,
+        })
+    }
+
+    /// Level of the emptier bud.
+    fn percentage(&self) -> Option<u32> {
+        [Component::Left, Component::Right].into_iter()
+            .filter_map(|c| battery(&self.info, c))
+            .filter_map(|b| u32::try_from(b.level).ok())
+            .min()
+    }
+}
+
+/// Format the status as a single line. Battery levels are listed for the
+/// left bud, right bud, and case, in this order.
+pub fn status_str(status: &Status, format: StatusFormat) -> String {
+
+    let level = |c| match battery(&status.info, c) {
+        Some(b) => format!("{}%", b.level),
+        None => "?".to_owned(),
+    };
+
+    let state = |c| battery(&status.info, c).map(|b| b.state).unwrap_or(0);
+    let mut parts = Vec::new();
+
+    match format {
+        StatusFormat::Plain => {
+            for c in STATUS_ORDER {
+                let key = key(c)[..1].to_uppercase();
+
+                let suffix = match state(c) {
+                    2 => "+",
+                    1 => "-",
+                    _ => "",
+                };
+
+                parts.push(format!("{key}:{}{suffix}", level(c)));
+            }
+
+            if let Some(anc) = status.anc {
+                parts.push(format!("ANC:{}", anc.as_str()));
+            }
+
+            if let Some(mp) = status.multipoint {
+                parts.push(format!("MP:{}", if mp { "on" } else { "off" }));
+            }
+        },
+        StatusFormat::Emoji => {
+            for c in STATUS_ORDER {
+                let icon = match c {
+                    Component::Case => "📦",
+                    Component::Left | Component::Right => "🎧",
+                };
+
+                let charging = if state(c) == 2 { "⚡" } else { "" };
+                let key = key(c)[..1].to_uppercase();
+
+                parts.push(format!("{icon}{key} {}{charging}", level(c)));
+            }
+
+            if let Some(anc) = status.anc {
+                let icon = match anc {
+                    AncState::Active => "🔇",
+                    AncState::Aware => "👂",
+                    _ => "🔈",
+                };
+
+                parts.push(format!("{icon} {}", anc.as_str()));
+            }
+
+            if let Some(mp) = status.multipoint {
+                parts.push(format!("🔗 {}", if mp { "on" } else { "off" }));
+            }
+        },
+        StatusFormat::Json => return render::Json.render(status),
+        StatusFormat::Waybar => return render::Waybar { formatter: Formatter::english() }.render(status),
+    }
+
+    parts.join(" ")
+}
+
+
+/// Battery state shown by the `show battery` command.
+#[derive(Debug, Clone, Default)]
+pub struct Battery {
+    pub info: RuntimeInfo,
+    pub component: Option<Component>,
+}
+
+impl Output for Battery {
+    fn human(&self, fmt: &Formatter) -> String {
+        let lines: Vec<_> = components(self.component)
+            .map(|c| line("", c, fmt.battery(battery(&self.info, c))))
+            .collect();
+
+        lines.join("\n")
+    }
+
+    fn json(&self) -> Value {
+        let battery: Map<_, _> = components(self.component)
+            .map(|c| {
+                let value = match battery(&self.info, c) {
+                    Some(b) => json!({ "level": b.level, "charging": b.state == 2 }),
+                    None => Value::Null,
+                };
+
+                (key(c).to_owned(), value)
+            })
+            .collect();
+
+        Value::Object(battery)
+    }
+
+    fn percentage(&self) -> Option<u32> {
+        components(self.component)
+            .filter(|c| self.component.is_some() || *c != Component::Case)
+            .filter_map(|c| battery(&self.info, c))
+            .filter_map(|b| u32::try_from(b.level).ok())
+            .min()
+    }
+}
+
+
+/// Estimated total listening time shown by `show battery --total`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BatteryTotal {
+    /// Remaining listening time in minutes.
+    pub minutes: Option<u32>,
+}
+
+impl Output for BatteryTotal {
+    fn human(&self, _fmt: &Formatter) -> String {
+        minutes_str(self.minutes)
+    }
+
+    fn json(&self) -> Value {
+        json!({ "minutes": self.minutes })
+    }
+}
+
+
+/// Value of a single setting shown by the `get` command.
+#[derive(Debug, Clone)]
+pub struct Setting {
+    pub value: SettingValue,
+}
+
+impl Output for Setting {
+    fn human(&self, fmt: &Formatter) -> String {
+        fmt.setting_value(&self.value)
+    }
+
+    fn json(&self) -> Value {
+        json!({ "setting": self.value.id().as_str(), "value": self.value.to_string() })
+    }
+}
+
+
+/// Values of all settings shown by the `get` command.
+#[derive(Debug, Clone, Default)]
+pub struct Settings {
+    /// Values of the settings, `None` if not supported by the firmware.
+    pub values: Vec<(SettingId, Option<SettingValue>)>,
+}
+
+impl Settings {
+    fn lines(&self, indent: &str) -> Vec<String> {
+        self.values.iter()
+            .map(|(id, value)| match value {
+                Some(value) => format!("{indent}{id}: {value}"),
+                None => format!("{indent}{id}: unsupported"),
+            })
+            .collect()
+    }
+}
+
+impl Output for Settings {
+    fn human(&self, _fmt: &Formatter) -> String {
+        self.lines("").join("\n")
+    }
+
+    fn json(&self) -> Value {
+        let settings: Map<_, _> = self.values.iter()
+            .map(|(id, value)| (id.as_str().to_owned(), json!(value.as_ref().map(|v| v.to_string()))))
+            .collect();
+
+        Value::Object(settings)
+    }
+}
+
+
+/// Firmware versions shown by the `show software` command.
+#[derive(Debug, Clone, Default)]
+pub struct Software {
+    pub info: SoftwareInfo,
+    pub component: Option<Component>,
+
+    /// Firmware version reported via GFPS, if requested for cross-checking.
+    pub gfps_firmware: Option<String>,
+}
+
+impl Software {
+    /// Buds whose firmware version does not match the one reported via GFPS.
+    ///
+    /// GFPS only reports a single version for the buds, the case may differ.
+    pub fn mismatches(&self) -> Vec<Component> {
+        let Some(gfps_firmware) = &self.gfps_firmware else {
+            return Vec::new();
+        };
+
+        components(self.component)
+            .filter(|c| *c != Component::Case)
+            .filter(|c| firmware(&self.info, *c).map(|fw| &fw.version_string) != Some(gfps_firmware))
+            .collect()
+    }
+}
+
+impl Output for Software {
+    fn human(&self, _fmt: &Formatter) -> String {
+        let mut lines = vec!["firmware:".to_owned()];
+        for c in components(self.component) {
+            lines.push(line("  ", c, firmware_str(firmware(&self.info, c))));
+        }
+
+        let Some(gfps_firmware) = &self.gfps_firmware else {
+            return lines.join("\n");
+        };
+
+        lines.push(String::new());
+        lines.push(format!("gfps firmware: {gfps_firmware}"));
+
+        let mismatches = self.mismatches();
+        if mismatches.is_empty() {
+            lines.push("firmware versions match".to_owned());
+        }
+
+        for c in mismatches {
+            let version = firmware(&self.info, c)
+                .map(|fw| fw.version_string.as_str())
+                .unwrap_or("unknown");
+
+            lines.push(format!("warning: {} firmware {version} does not match gfps firmware, \
+                the update may be incomplete", label(c)));
+        }
+
+        lines.join("\n")
+    }
+
+    fn json(&self) -> Value {
+        let firmware: Map<_, _> = components(self.component)
+            .map(|c| (key(c).to_owned(), json!(firmware(&self.info, c).map(|fw| &fw.version_string))))
+            .collect();
+
+        let mut value = json!({ "firmware": firmware });
+
+        if let Some(gfps_firmware) = &self.gfps_firmware {
+            let mismatches: Vec<_> = self.mismatches().into_iter().map(key).collect();
+            value["gfps"] = json!({ "firmware": gfps_firmware, "mismatches": mismatches });
+        }
+
+        value
+    }
+}
+
+
+/// Serial numbers shown by the `show hardware` command.
+#[derive(Debug, Clone, Default)]
+pub struct Hardware {
+    pub info: HardwareInfo,
+    pub component: Option<Component>,
+}
+
+impl Output for Hardware {
+    fn human(&self, _fmt: &Formatter) -> String {
+        let mut lines = vec!["serial numbers:".to_owned()];
+        for c in components(self.component) {
+            lines.push(line("  ", c, serial(&self.info, c).unwrap_or("unknown")));
+        }
+
+        lines.join("\n")
+    }
+
+    fn json(&self) -> Value {
+        let serial: Map<_, _> = components(self.component)
+            .map(|c| (key(c).to_owned(), json!(serial(&self.info, c))))
+            .collect();
+
+        json!({ "serial": serial })
+    }
+}
+
+
+/// Runtime information shown by the `show runtime` command.
+#[derive(Debug, Clone, Default)]
+pub struct Runtime {
+    pub info: RuntimeInfo,
+
+    /// Correlation of the device clock with host time, including the
+    /// current info.
+    pub clock: ClockTracker,
+
+    pub channel: u32,
+    pub component: Option<Component>,
+}
+
+impl Output for Runtime {
+    fn human(&self, fmt: &Formatter) -> String {
+        let mut lines = vec![format!("clock: {} ms", self.info.timestamp_ms)];
+
+        match DeviceTime::from_ms(self.info.timestamp_ms) {
+            Some(DeviceTime::Uptime(uptime)) => lines.push(format!("  uptime: {}", duration_str(uptime))),
+            Some(DeviceTime::Wall(_)) => {
+                if let Some(offset) = self.clock.offset_ms() {
+                    lines.push(format!("  offset: {:+.3} s to host", offset as f64 / 1000.0));
+                }
+            },
+            None => {},
+        }
+        if let Some(drift) = self.clock.drift_ppm() {
+            lines.push(format!("  drift:  {drift:+.1} ppm"));
+        }
+        if self.clock.resets() > 0 {
+            lines.push(format!("  resets: {}", self.clock.resets()));
+        }
+
+        lines.push(String::new());
+        lines.push("battery:".to_owned());
+        for c in components(self.component) {
+            lines.push(line("  ", c, battery_str(battery(&self.info, c))));
+        }
+
+        let buds: Vec<_> = components(self.component)
+            .filter(|c| *c != Component::Case)
+            .collect();
+
+        if !buds.is_empty() {
+            lines.push(String::new());
+            lines.push("placement:".to_owned());
+            for c in buds {
+                lines.push(line("  ", c, fmt.placement(in_case(&self.info, c))));
+            }
+        }
+
+        let address = addr::address_for_channel(self.channel);
+        let peer = |peer: Option<_>| match peer {
+            Some(peer) => format!("{peer:?}"),
+            None => "unknown".to_owned(),
+        };
+
+        lines.push(String::new());
+        lines.push("connection:".to_owned());
+        lines.push(format!("  local:  {}", peer(address.map(|a| a.source()))));
+        lines.push(format!("  remote: {}", peer(address.map(|a| a.target()))));
+
+        lines.join("\n")
+    }
+
+    fn json(&self) -> Value {
+        let battery: Map<_, _> = components(self.component)
+            .map(|c| {
+                let battery = state::Battery::from_info(battery(&self.info, c));
+
+                let value = match battery.level {
+                    Some(level) => json!({ "level": level, "state": battery.state_str() }),
+                    None => Value::Null,
+                };
+
+                (key(c).to_owned(), value)
+            })
+            .collect();
+
+        let placement = match self.info.placement {
+            Some(p) => json!({ "left_in_case": p.left_bud_in_case, "right_in_case": p.right_bud_in_case }),
+            None => Value::Null,
+        };
+
+        let address = addr::address_for_channel(self.channel);
+        let uptime = DeviceTime::from_ms(self.info.timestamp_ms).and_then(|t| t.uptime());
+
+        json!({
+            "timestamp_ms": self.info.timestamp_ms,
+            "clock": {
+                "uptime_ms": uptime.map(|t| t.as_millis() as u64),
+                "offset_ms": self.clock.offset_ms(),
+                "drift_ppm": self.clock.drift_ppm(),
+                "resets": self.clock.resets(),
+            },
+            "battery": battery,
+            "placement": placement,
+            "connection": {
+                "local": address.map(|a| format!("{:?}", a.source())),
+                "remote": address.map(|a| format!("{:?}", a.target())),
+            },
+        })
+    }
+}
+
+
+/// All device information and settings shown by the `show all` command.
+#[derive(Debug, Clone, Default)]
+pub struct All {
+    pub software: Software,
+    pub hardware: Hardware,
+    pub runtime: Runtime,
+    pub settings: Settings,
+    pub timing: SnapshotTiming,
+}
+
+impl Output for All {
+    fn human(&self, fmt: &Formatter) -> String {
+        let mut lines = vec![
+            self.software.human(fmt),
+            String::new(),
+            self.hardware.human(fmt),
+            String::new(),
+            self.runtime.human(fmt),
+            String::new(),
+            "settings:".to_owned(),
+        ];
+
+        lines.extend(self.settings.lines("  "));
+        lines.join("\n")
+    }
+
+    fn json(&self) -> Value {
+        json!({
+            "software": self.software.json(),
+            "hardware": self.hardware.json(),
+            "runtime": self.runtime.json(),
+            "settings": self.settings.json(),
+            "timing_ms": {
+                "software": self.timing.software_info.as_millis() as u64,
+                "hardware": self.timing.hardware_info.as_millis() as u64,
+                "runtime": self.timing.runtime_info.as_millis() as u64,
+                "settings": self.timing.settings.as_millis() as u64,
+                "total": self.timing.total.as_millis() as u64,
+            },
+        })
+    }
+}
+
+
+/// Battery, placement, and ANC state shown by the `monitor` command.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Monitor {
+    pub info: RuntimeInfo,
+
+    /// Current ANC state, `None` if unknown or not supported.
+    pub anc: Option<AncState>,
+}
+
+impl Monitor {
+    /// Describe the changes compared to the given previous state, one line
+    /// per change. Lists all known state if there is no previous state.
+    pub fn changes(&self, previous: Option<&Monitor>, fmt: &Formatter) -> Vec<String> {
+        let mut lines = Vec::new();
+
+        for c in COMPONENTS {
+            let current = battery(&self.info, c);
+            if previous.is_none_or(|p| battery(&p.info, c) != current) {
+                lines.push(format!("{}: battery {}", label(c), fmt.battery(current)));
+            }
+        }
+
+        for c in [Component::Left, Component::Right] {
+            let current = in_case(&self.info, c);
+            if previous.is_none_or(|p| in_case(&p.info, c) != current) {
+                lines.push(format!("{}: {}", label(c), fmt.placement(current)));
+            }
+        }
+
+        if previous.is_none_or(|p| p.anc != self.anc) {
+            lines.push(format!("anc: {}", self.anc.map(|anc| anc.as_str()).unwrap_or("unknown")));
+        }
+
+        lines
+    }
+}
+
+impl Output for Monitor {
+    fn human(&self, fmt: &Formatter) -> String {
+        self.changes(None, fmt).join("\n")
+    }
+
+    fn json(&self) -> Value {
+        let battery: Map<_, _> = COMPONENTS.into_iter()
+            .map(|c| {
+                let value = match battery(&self.info, c) {
+                    Some(b) => json!({ "level": b.level, "charging": b.state == 2 }),
+                    None => Value::Null,
+                };
+
+                (key(c).to_owned(), value)
+            })
+            .collect();
+
+        json!({
+            "battery": battery,
+            "placement": {
+                "left": in_case(&self.info, Component::Left),
+                "right": in_case(&self.info, Component::Right),
+            },
+            "anc": self.anc.map(|anc| anc.as_str()),
+        })
+    }
+}
+
+
+/// Compatible devices listed by the `devices` command.
+#[derive(Debug, Clone, Default)]
+pub struct Devices {
+    pub devices: Vec<KnownDevice>,
+}
+
+impl Output for Devices {
+    fn human(&self, _fmt: &Formatter) -> String {
+        if self.devices.is_empty() {
+            return "no compatible devices found".to_owned();
+        }
+
+        let alias = |d: &KnownDevice| d.identity.alias.clone().unwrap_or_else(|| "unknown".to_owned());
+        let width = self.devices.iter().map(|d| alias(d).chars().count()).max().unwrap_or_default();
+
+        let lines: Vec<_> = self.devices.iter()
+            .map(|d| {
+                let model = d.identity.model.map(|m| m.name).unwrap_or("unknown model");
+                let state = if d.connected { "connected" } else { "disconnected" };
+
+                format!("{}  {:width$}  {state:<12}  {model}", d.identity.address, alias(d))
+            })
+            .collect();
+
+        lines.join("\n")
+    }
+
+    fn json(&self) -> Value {
+        let devices: Vec<_> = self.devices.iter()
+            .map(|d| json!({
+                "address": d.identity.address.to_string(),
+                "alias": d.identity.alias,
+                "model": d.identity.model.map(|m| m.name),
+                "adapter": d.identity.adapter,
+                "connected": d.connected,
+            }))
+            .collect();
+
+        json!({ "devices": devices })
+    }
+}
+
+
+/// Daily sound dose summaries reported by the device.
+///
+/// The meaning of most fields has not been fully decoded yet. Entries appear
+/// to be one per day, and are reported as-is.
+#[derive(Debug, Clone)]
+pub struct Dosimeter {
+    pub summary: DosimeterSummary,
+}
+
+impl Output for Dosimeter {
+    fn human(&self, _fmt: &Formatter) -> String {
+        if self.summary.unknown2.is_empty() {
+            return "no daily summaries available".to_owned();
+        }
+
+        let mut lines = vec!["day  dose".to_owned()];
+
+        for entry in &self.summary.unknown2 {
+            lines.push(format!("{:>3}  {:.2}", entry.unknown1, entry.unknown6));
+        }
+
+        lines.push(format!("overall: {:.2}", self.summary.unknown5));
+        lines.join("\n")
+    }
+
+    fn json(&self) -> Value {
+        let days: Vec<_> = self.summary.unknown2.iter()
+            .map(|e| json!({ "day": e.unknown1, "dose": e.unknown6 }))
+            .collect();
+
+        json!({
+            "days": days,
+            "overall": self.summary.unknown5,
+            "unknown1": self.summary.unknown1,
+            "unknown4": self.summary.unknown4,
+        })
+    }
+}
+
+
+/// Device state exported by the `export` command.
+#[derive(Debug, Clone, Default)]
+pub struct Export {
+    pub software: SoftwareInfo,
+    pub hardware: HardwareInfo,
+    pub runtime: RuntimeInfo,
+
+    /// Values of all settings, `None` if not supported by the firmware.
+    pub settings: Vec<(SettingId, Option<SettingValue>)>,
+}
+
+impl Output for Export {
+    fn human(&self, fmt: &Formatter) -> String {
+        let mut lines = vec!["battery:".to_owned()];
+        for c in COMPONENTS {
+            lines.push(line("  ", c, fmt.battery(battery(&self.runtime, c))));
+        }
+
+        lines.push("placement:".to_owned());
+        for c in [Component::Left, Component::Right] {
+            lines.push(line("  ", c, fmt.placement(in_case(&self.runtime, c))));
+        }
+
+        lines.push("firmware:".to_owned());
+        for c in COMPONENTS {
+            lines.push(line("  ", c, firmware_str(firmware(&self.software, c))));
+        }
+
+        lines.push("settings:".to_owned());
+        for (id, value) in &self.settings {
+            match value {
+                Some(value) => lines.push(format!("  {id}: {}", fmt.setting_value(value))),
+                None => lines.push(format!("  {id}: unsupported")),
+            }
+        }
+
+        lines.join("\n")
+    }
+
+    fn json(&self) -> Value {
+        let component = |f: &dyn Fn(Component) -> Value| -> Map<_, _> {
+            COMPONENTS.into_iter().map(|c| (key(c).to_owned(), f(c))).collect()
+        };
+
+        let battery = component(&|c| match battery(&self.runtime, c) {
+            Some(b) => json!({ "level": b.level, "charging": b.state == 2 }),
+            None => Value::Null,
+        });
+
+        let firmware = component(&|c| json!(firmware(&self.software, c).map(|fw| &fw.version_string)));
+        let serial = component(&|c| json!(serial(&self.hardware, c)));
+
+        let settings: Map<_, _> = self.settings.iter()
+            .map(|(id, value)| (id.as_str().to_owned(), json!(value.as_ref().map(|v| v.to_string()))))
+            .collect();
+
+        json!({
+            "battery": battery,
+            "placement": {
+                "left": in_case(&self.runtime, Component::Left),
+                "right": in_case(&self.runtime, Component::Right),
+            },
+            "firmware": firmware,
+            "serial": serial,
+            "settings": settings,
+        })
+    }
+
+    /// Variables named after the state, e.g. `PBP_BATTERY_LEFT` or
+    /// `PBP_ANC`. Unknown values are empty.
+    fn env(&self) -> Vec<(String, String)> {
+        let name = |prefix: &str, c: Component| format!("PBP_{prefix}_{}", key(c).to_uppercase());
+        let mut vars = Vec::new();
+
+        for c in COMPONENTS {
+            let battery = battery(&self.runtime, c);
+            let level = battery.map(|b| b.level.to_string());
+            let charging = battery.and_then(|b| match b.state {
+                2 => Some(true),
+                1 => Some(false),
+                _ => None,
+            });
+
+            vars.push((name("BATTERY", c), level.unwrap_or_default()));
+            vars.push((name("CHARGING", c), opt_str(charging)));
+        }
+
+        for c in [Component::Left, Component::Right] {
+            vars.push((name("IN_CASE", c), opt_str(in_case(&self.runtime, c))));
+        }
+
+        for c in COMPONENTS {
+            let version = firmware(&self.software, c).map(|fw| fw.version_string.clone());
+            vars.push((name("FIRMWARE", c), version.unwrap_or_default()));
+        }
+
+        for c in COMPONENTS {
+            vars.push((name("SERIAL", c), serial(&self.hardware, c).unwrap_or_default().to_owned()));
+        }
+
+        let setting = |id| self.settings.iter()
+            .find(|(i, _)| *i == id)
+            .and_then(|(_, v)| v.as_ref());
+
+        // short names for the state also shown by the status command
+        let anc = match setting(SettingId::CurrentAncrState) {
+            Some(SettingValue::CurrentAncrState(anc)) => Some(anc.as_str()),
+            _ => None,
+        };
+
+        let multipoint = match setting(SettingId::MultipointEnable) {
+            Some(SettingValue::MultipointEnable(mp)) => Some(*mp),
+            _ => None,
+        };
+
+        vars.push(("PBP_ANC".to_owned(), opt_str(anc)));
+        vars.push(("PBP_MULTIPOINT".to_owned(), opt_str(multipoint)));
+
+        for (id, value) in &self.settings {
+            let name = format!("PBP_SETTING_{}", id.as_str().replace('-', "_").to_uppercase());
+            vars.push((name, opt_str(value.as_ref())));
+        }
+
+        vars
+    }
+}
+
+fn opt_str(value: Option<impl Display>) -> String {
+    value.map(|v| v.to_string()).unwrap_or_default()
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use maestro::protocol::types::PlacementInfo;
+
+    #[test]
+    fn test_components() {
+        assert_eq!(components(None).count(), 3);
+        assert_eq!(components(Some(Component::Left)).collect::<Vec<_>>(), [Component::Left]);
+    }
+
+    #[test]
+    fn test_battery_str() {
+        let battery = DeviceBatteryInfo { level: 42, state: 2 };
+
+        assert_eq!(battery_str(Some(&battery)), "42% (charging)");
+        assert_eq!(battery_str(None), "unknown");
+    }
+
+    #[test]
+    fn test_duration_str() {
+        assert_eq!(duration_str(Duration::from_secs(42)), "42s");
+        assert_eq!(duration_str(Duration::from_secs(125)), "2m 5s");
+        assert_eq!(duration_str(Duration::from_secs(3 * 3600 + 62)), "3h 1m 2s");
+    }
+
+    #[test]
+    fn test_buds_in_case() {
+        let info = |left_bud_in_case, right_bud_in_case| RuntimeInfo {
+            placement: Some(PlacementInfo { left_bud_in_case, right_bud_in_case }),
+            ..Default::default()
+        };
+
+        assert!(buds_in_case(&info(true, true)));
+        assert!(!buds_in_case(&info(true, false)));
+        assert!(!buds_in_case(&RuntimeInfo::default()));
+    }
+
+    #[test]
+    fn test_estimate_minutes() {
+        use maestro::protocol::types::BatteryInfo;
+
+        let level = |level| Some(DeviceBatteryInfo { level, state: 1 });
+
+        let info = RuntimeInfo {
+            battery_info: Some(BatteryInfo { case: level(50), left: level(100), right: level(80) }),
+            ..Default::default()
+        };
+
+        assert_eq!(estimate_minutes(&info, None), Some(336 + 390));
+        assert_eq!(estimate_minutes(&info, Some(300)), Some(300 + 390));
+        assert_eq!(estimate_minutes(&RuntimeInfo::default(), None), None);
+
+        assert_eq!(minutes_str(Some(726)), "12h 6m");
+    }
+
+    #[test]
+    fn test_status_str() {
+        use maestro::protocol::types::BatteryInfo;
+
+        let battery = |level, state| Some(DeviceBatteryInfo { level, state });
+
+        let mut status = Status {
+            info: RuntimeInfo {
+                battery_info: Some(BatteryInfo {
+                    case: battery(61, 2),
+                    left: battery(84, 1),
+                    right: battery(82, 1),
+                }),
+                ..Default::default()
+            },
+            anc: Some(AncState::Active),
+            multipoint: Some(true),
+        };
+
+        assert_eq!(status_str(&status, StatusFormat::Plain), "L:84%- R:82%- C:61%+ ANC:active MP:on");
+        assert_eq!(status_str(&status, StatusFormat::Emoji), "🎧L 84% 🎧R 82% 📦C 61%⚡ 🔇 active 🔗 on");
+
+        let json: serde_json::Value = serde_json::from_str(&status_str(&status, StatusFormat::Json)).unwrap();
+        assert_eq!(json["battery"]["case"]["charging"], true);
+        assert_eq!(json["anc"], "active");
+
+        let waybar: serde_json::Value = serde_json::from_str(&status_str(&status, StatusFormat::Waybar)).unwrap();
+        assert_eq!(waybar["text"], "L:84%- R:82%- C:61%+ ANC:active MP:on");
+        assert_eq!(waybar["percentage"], 82);
+
+        status.info.battery_info.as_mut().unwrap().case = None;
+        status.multipoint = None;
+        assert_eq!(status_str(&status, StatusFormat::Plain), "L:84%- R:82%- C:? ANC:active");
+    }
+
+    #[test]
+    fn test_env_vars() {
+        use maestro::protocol::types::BatteryInfo;
+
+        let export = Export {
+            runtime: RuntimeInfo {
+                battery_info: Some(BatteryInfo {
+                    case: None,
+                    left: Some(DeviceBatteryInfo { level: 84, state: 2 }),
+                    right: Some(DeviceBatteryInfo { level: 82, state: 1 }),
+                }),
+                ..Default::default()
+            },
+            settings: vec![
+                (SettingId::CurrentAncrState, Some(SettingValue::CurrentAncrState(AncState::Active))),
+                (SettingId::MultipointEnable, None),
+            ],
+            ..Default::default()
+        };
+
+        let vars = export.env();
+        let var = |key: &str| vars.iter().find(|(k, _)| k == key).map(|(_, v)| v.as_str());
+
+        assert_eq!(var("PBP_BATTERY_LEFT"), Some("84"));
+        assert_eq!(var("PBP_CHARGING_LEFT"), Some("true"));
+        assert_eq!(var("PBP_BATTERY_CASE"), Some(""));
+        assert_eq!(var("PBP_IN_CASE_RIGHT"), Some(""));
+        assert_eq!(var("PBP_ANC"), Some("active"));
+        assert_eq!(var("PBP_MULTIPOINT"), Some(""));
+        assert_eq!(var("PBP_SETTING_CURRENT_ANCR_STATE"), Some("active"));
+    }
+
+    #[test]
+    fn test_software() {
+        use maestro::protocol::types::FirmwareInfo;
+
+        let fw = |version: &str| Some(FirmwareVersion { version_string: version.to_owned(), ..Default::default() });
+
+        let mut software = Software {
+            info: SoftwareInfo {
+                firmware: Some(FirmwareInfo { case: fw("1.0"), left: fw("2.0"), right: fw("2.1") }),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        assert_eq!(software.json(), json!({ "firmware": { "case": "1.0", "left": "2.0", "right": "2.1" } }));
+        assert!(software.mismatches().is_empty());
+
+        software.gfps_firmware = Some("2.0".to_owned());
+        assert_eq!(software.mismatches(), [Component::Right]);
+        assert_eq!(software.json()["gfps"], json!({ "firmware": "2.0", "mismatches": ["right"] }));
+
+        let human = software.human(&Formatter::english());
+        assert!(human.ends_with("\ngfps firmware: 2.0\nwarning: right bud firmware 2.1 does not match gfps firmware, \
+            the update may be incomplete"));
+    }
+
+    #[test]
+    fn test_settings() {
+        let settings = Settings {
+            values: vec![
+                (SettingId::CurrentAncrState, Some(SettingValue::CurrentAncrState(AncState::Active))),
+                (SettingId::SpeechDetection, None),
+            ],
+        };
+
+        assert_eq!(settings.human(&Formatter::english()), "current-ancr-state: active\nspeech-detection: unsupported");
+        assert_eq!(settings.json(), json!({ "current-ancr-state": "active", "speech-detection": null }));
+    }
+
+    #[test]
+    fn test_monitor_changes() {
+        use maestro::protocol::types::BatteryInfo;
+
+        let fmt = Formatter::english();
+
+        let previous = Monitor {
+            info: RuntimeInfo {
+                battery_info: Some(BatteryInfo {
+                    case: None,
+                    left: Some(DeviceBatteryInfo { level: 84, state: 1 }),
+                    right: Some(DeviceBatteryInfo { level: 82, state: 1 }),
+                }),
+                placement: Some(PlacementInfo { left_bud_in_case: false, right_bud_in_case: false }),
+                ..Default::default()
+            },
+            anc: Some(AncState::Active),
+        };
+
+        assert_eq!(previous.changes(None, &fmt).len(), 6);
+        assert!(previous.changes(Some(&previous), &fmt).is_empty());
+
+        let mut current = previous.clone();
+        current.info.battery_info.as_mut().unwrap().left = Some(DeviceBatteryInfo { level: 83, state: 1 });
+        current.info.placement.as_mut().unwrap().right_bud_in_case = true;
+        current.info.timestamp_ms = 1000;
+        current.anc = Some(AncState::Aware);
+
+        let changes = current.changes(Some(&previous), &fmt);
+        assert_eq!(changes.len(), 3);
+        assert!(changes[0].starts_with("left bud: battery 83%"));
+        assert_eq!(changes[2], "anc: aware");
+
+        assert_eq!(current.json()["placement"]["right"], true);
+    }
+
+    #[test]
+    fn test_devices() {
+        use crate::transport::DeviceIdentity;
+
+        let mut identity = DeviceIdentity::new("24:29:34:AC:9F:D1".parse().unwrap(), "hci0");
+        identity.alias = Some("Kitchen Buds".to_owned());
+        identity.model = maestro::models::by_name("Pixel Buds Pro");
+
+        let devices = Devices {
+            devices: vec![
+                KnownDevice { identity, connected: true },
+                KnownDevice { identity: DeviceIdentity::new("24:29:34:AC:9F:D2".parse().unwrap(), "hci0"), connected: false },
+            ],
+        };
+
+        assert_eq!(devices.human(&Formatter::english()), "\
+            24:29:34:AC:9F:D1  Kitchen Buds  connected     Pixel Buds Pro\n\
+            24:29:34:AC:9F:D2  unknown       disconnected  unknown model");
+
+        let json = devices.json();
+        assert_eq!(json["devices"][0]["model"], "Pixel Buds Pro");
+        assert_eq!(json["devices"][1]["alias"], Value::Null);
+
+        assert_eq!(Devices::default().human(&Formatter::english()), "no compatible devices found");
+    }
+
+    #[test]
+    fn test_dosimeter() {
+        use maestro::protocol::types::DosimeterSummaryEntry;
+
+        let summary = DosimeterSummary {
+            unknown1: 1,
+            unknown2: vec![
+                DosimeterSummaryEntry { unknown1: 0, unknown6: 12.5 },
+                DosimeterSummaryEntry { unknown1: 1, unknown6: 3.25 },
+            ],
+            unknown4: 2,
+            unknown5: 15.75,
+        };
+
+        let dosimeter = Dosimeter { summary };
+
+        assert_eq!(dosimeter.human(&Formatter::english()), "\
+            day  dose\n  \
+              0  12.50\n  \
+              1  3.25\n\
+            overall: 15.75");
+
+        let json = dosimeter.json();
+        assert_eq!(json["days"][1], json!({ "day": 1, "dose": 3.25 }));
+        assert_eq!(json["overall"], 15.75);
+
+        let empty = Dosimeter { summary: DosimeterSummary::default() };
+        assert_eq!(empty.human(&Formatter::english()), "no daily summaries available");
+    }
+
+    #[test]
+    fn test_unsupported_str() {
+        use maestro::protocol::types::FirmwareInfo;
+
+        let fw = |version: &str| Some(FirmwareVersion { version_string: version.to_owned(), ..Default::default() });
+
+        let mut info = SoftwareInfo {
+            firmware: Some(FirmwareInfo { case: fw("1.0"), left: fw("2.0"), right: fw("2.0") }),
+            ..Default::default()
+        };
+
+        assert_eq!(unsupported_str(SettingId::SpeechDetection, Some(&info)),
+            "setting speech-detection is not supported by firmware 2.0");
+
+        info.firmware.as_mut().unwrap().right = fw("2.1");
+        assert_eq!(unsupported_str(SettingId::SpeechDetection, Some(&info)),
+            "setting speech-detection is not supported by firmware 2.0 (left bud) or 2.1 (right bud)");
+
+        assert_eq!(unsupported_str(SettingId::SpeechDetection, None),
+            "setting speech-detection is not supported by firmware unknown");
+    }
+}
@@ -0,0 +1,196 @@
+//! Rendering of command results.
+//!
+//! Command handlers return typed results implementing [`Output`], which are
+//! then rendered in the requested [`OutputFormat`] via a [`Renderer`].
+
+use serde_json::{json, Value};
+
+use maestro::service::format::Formatter;
+
+use crate::cli::OutputFormat;
+
+
+/// Result of a command that can be rendered in all output formats.
+pub trait Output {
+    /// Human-readable representation, may span multiple lines.
+    fn human(&self, fmt: &Formatter) -> String;
+
+    /// Structured representation.
+    fn json(&self) -> Value;
+
+    /// Short single-line summary, e.g. for status bars. Defaults to the first
+    /// line of the human-readable representation.
+    fn summary(&self, fmt: &Formatter) -> String {
+        self.human(fmt).lines().next().unwrap_or_default().to_owned()
+    }
+
+    /// Percentage shown by status bars supporting it, e.g. a battery level.
+    fn percentage(&self) -> Option<u32> {
+        None
+    }
+
+    /// Environment variables. Defaults to the flattened structured
+    /// representation, with keys prefixed by `PBP_`.
+    fn env(&self) -> Vec<(String, String)> {
+        let mut vars = Vec::new();
+        flatten("PBP", &self.json(), &mut vars);
+        vars
+    }
+}
+
+fn flatten(prefix: &str, value: &Value, vars: &mut Vec<(String, String)>) {
+    match value {
+        Value::Object(map) => {
+            for (key, value) in map {
+                let key = key.replace(['-', ' '], "_").to_uppercase();
+                flatten(&format!("{prefix}_{key}"), value, vars);
+            }
+        },
+        Value::Array(values) => {
+            for (i, value) in values.iter().enumerate() {
+                flatten(&format!("{prefix}_{i}"), value, vars);
+            }
+        },
+        Value::Null => vars.push((prefix.to_owned(), String::new())),
+        Value::String(s) => vars.push((prefix.to_owned(), s.clone())),
+        value => vars.push((prefix.to_owned(), value.to_string())),
+    }
+}
+
+
+/// Renders command results into text.
+pub trait Renderer {
+    fn render(&self, output: &dyn Output) -> String;
+}
+
+/// Human-readable text.
+pub struct Human<'a> {
+    pub formatter: Formatter<'a>,
+}
+
+impl Renderer for Human<'_> {
+    fn render(&self, output: &dyn Output) -> String {
+        output.human(&self.formatter)
+    }
+}
+
+/// A single JSON object.
+pub struct Json;
+
+impl Renderer for Json {
+    fn render(&self, output: &dyn Output) -> String {
+        output.json().to_string()
+    }
+}
+
+/// Shell variable assignments, one per line.
+pub struct Env;
+
+impl Renderer for Env {
+    fn render(&self, output: &dyn Output) -> String {
+        env_str(&output.env())
+    }
+}
+
+/// JSON object for Waybar's custom modules (`"return-type": "json"`).
+pub struct Waybar<'a> {
+    pub formatter: Formatter<'a>,
+}
+
+impl Renderer for Waybar<'_> {
+    fn render(&self, output: &dyn Output) -> String {
+        let mut value = json!({
+            "text": output.summary(&self.formatter),
+            "tooltip": output.human(&self.formatter),
+        });
+
+        if let Some(percentage) = output.percentage() {
+            value["percentage"] = percentage.into();
+        }
+
+        value.to_string()
+    }
+}
+
+
+pub fn renderer(format: OutputFormat) -> Box<dyn Renderer> {
+    match format {
+        OutputFormat::Human => Box::new(Human { formatter: Formatter::english() }),
+        OutputFormat::Json => Box::new(Json),
+        OutputFormat::Env => Box::new(Env),
+        OutputFormat::Waybar => Box::new(Waybar { formatter: Formatter::english() }),
+    }
+}
+
+/// Render the output in the given format and print it.
+pub fn print(format: OutputFormat, output: &dyn Output) {
+    let text = renderer(format).render(output);
+    println!("{}", text.trim_end_matches('\n'));
+}
+
+
+/// Format the given variables as `KEY=value` lines, quoting values for use
+/// with `eval` in POSIX shells.
+pub fn env_str(vars: &[(String, String)]) -> String {
+    let plain = |c: char| c.is_ascii_alphanumeric() || "_-.,:%+/@".contains(c);
+
+    vars.iter()
+        .map(|(key, value)| {
+            if value.chars().all(plain) {
+                format!("{key}={value}\n")
+            } else {
+                format!("{key}='{}'\n", value.replace('\'', "'\\''"))
+            }
+        })
+        .collect()
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    struct Level(Option<u32>);
+
+    impl Output for Level {
+        fn human(&self, _fmt: &Formatter) -> String {
+            match self.0 {
+                Some(level) => format!("{level}%\nmore details"),
+                None => "unknown".to_owned(),
+            }
+        }
+
+        fn json(&self) -> Value {
+            json!({ "battery": { "level": self.0, "left-bud": true } })
+        }
+
+        fn percentage(&self) -> Option<u32> {
+            self.0
+        }
+    }
+
+    #[test]
+    fn test_renderers() {
+        let output = Level(Some(84));
+
+        assert_eq!(renderer(OutputFormat::Human).render(&output), "84%\nmore details");
+        assert_eq!(renderer(OutputFormat::Env).render(&output), "PBP_BATTERY_LEFT_BUD=true\nPBP_BATTERY_LEVEL=84\n");
+        assert_eq!(renderer(OutputFormat::Env).render(&Level(None)), "PBP_BATTERY_LEFT_BUD=true\nPBP_BATTERY_LEVEL=\n");
+
+        let waybar: Value = serde_json::from_str(&renderer(OutputFormat::Waybar).render(&output)).unwrap();
+        assert_eq!(waybar, json!({ "text": "84%", "tooltip": "84%\nmore details", "percentage": 84 }));
+
+        let waybar: Value = serde_json::from_str(&renderer(OutputFormat::Waybar).render(&Level(None))).unwrap();
+        assert!(waybar.get("percentage").is_none());
+    }
+
+    #[test]
+    fn test_env_str() {
+        let vars = [
+            ("PBP_ANC".to_owned(), "active".to_owned()),
+            ("PBP_SETTING_GESTURE_CONTROL".to_owned(), "left: anc, right: it's".to_owned()),
+        ];
+
+        assert_eq!(env_str(&vars), "PBP_ANC=active\nPBP_SETTING_GESTURE_CONTROL='left: anc, right: it'\\''s'\n");
+    }
+}
@@ -0,0 +1,133 @@
+//! Swapped-sides state.
+//!
+//! The device has no setting for buds being worn on swapped sides. `pbpctrl
+//! set swap-sides` emulates one by swapping the gesture mapping and inverting
+//! the volume balance, and records per device whether the sides are swapped in
+//! `$XDG_STATE_HOME/pbpctrl/sides.json`, so that the command can be repeated
+//! safely.
+
+use std::path::PathBuf;
+
+use anyhow::Result;
+
+use serde_json::{Map, Value};
+
+use maestro::service::settings::{GestureControl, VolumeAsymmetry};
+
+use crate::transport::Address;
+
+
+/// File storing the swapped-sides state of all devices.
+#[derive(Debug, Clone)]
+pub struct Store {
+    path: PathBuf,
+}
+
+impl Store {
+    /// Default location of the state file, i.e.,
+    /// `$XDG_STATE_HOME/pbpctrl/sides.json`.
+    pub fn default_path() -> Option<PathBuf> {
+        let base = std::env::var_os("XDG_STATE_HOME")
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".local").join("state")))?;
+
+        Some(base.join("pbpctrl").join("sides.json"))
+    }
+
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    /// Whether the sides of the given device have been swapped. Invalid state
+    /// files are treated as empty.
+    pub fn is_swapped(&self, address: Address) -> bool {
+        self.load().get(&address.to_string())
+            .and_then(Value::as_bool)
+            .unwrap_or(false)
+    }
+
+    pub fn set_swapped(&self, address: Address, swapped: bool) -> Result<()> {
+        let mut devices = self.load();
+
+        if swapped {
+            devices.insert(address.to_string(), Value::Bool(true));
+        } else {
+            devices.remove(&address.to_string());
+        }
+
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+
+        std::fs::write(&self.path, serde_json::to_vec(&Value::Object(devices))?)?;
+        Ok(())
+    }
+
+    fn load(&self) -> Map<String, Value> {
+        let Ok(data) = std::fs::read(&self.path) else {
+            return Map::new();
+        };
+
+        match serde_json::from_slice(&data) {
+            Ok(Value::Object(devices)) => devices,
+            _ => {
+                tracing::debug!(path=%self.path.display(), "ignoring invalid sides state");
+                Map::new()
+            },
+        }
+    }
+}
+
+
+/// Gesture mapping and volume balance with left and right swapped.
+pub fn swap(gestures: GestureControl, balance: VolumeAsymmetry) -> (GestureControl, VolumeAsymmetry) {
+    let gestures = GestureControl { left: gestures.right, right: gestures.left };
+    let balance = VolumeAsymmetry::from_normalized(-balance.value());
+
+    (gestures, balance)
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use maestro::service::settings::RegularActionTarget;
+
+    #[test]
+    fn test_swap() {
+        let gestures = GestureControl {
+            left: RegularActionTarget::AncControl,
+            right: RegularActionTarget::AssistantQuery,
+        };
+
+        let balance = VolumeAsymmetry::from_levels(100, 70);
+        let (swapped, inverted) = swap(gestures, balance);
+
+        assert_eq!(swapped.left, RegularActionTarget::AssistantQuery);
+        assert_eq!(swapped.right, RegularActionTarget::AncControl);
+        assert_eq!((inverted.left(), inverted.right()), (70, 100));
+        assert_eq!(swap(swapped, inverted), (gestures, balance));
+    }
+
+    #[test]
+    fn test_store() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("pbpctrl").join("sides.json");
+        let store = Store::new(&path);
+        let address: Address = "01:23:45:67:89:AB".parse().unwrap();
+
+        assert!(!store.is_swapped(address));
+
+        store.set_swapped(address, true).unwrap();
+        assert!(store.is_swapped(address));
+        assert!(!store.is_swapped("01:23:45:67:89:AC".parse().unwrap()));
+
+        store.set_swapped(address, false).unwrap();
+        assert!(!store.is_swapped(address));
+
+        std::fs::write(&path, b"[1, 2]").unwrap();
+        assert!(!store.is_swapped(address));
+    }
+}
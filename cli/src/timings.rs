@@ -0,0 +1,189 @@
+//! Timing of command phases, reported via `--timings`.
+//!
+//! Phases of the command itself, e.g. device discovery and connecting, are
+//! measured directly. RPCs are recorded from the completion events of the
+//! client, mapped to their method names where known.
+
+use std::future::Future;
+use std::time::{Duration, Instant};
+
+use maestro::pwrpc::client::Event;
+use maestro::pwrpc::id::PathRef;
+
+use serde_json::{json, Value};
+
+use crate::cli::TimingsFormat;
+
+
+/// Methods shown by name, all others are shown by their IDs.
+const METHODS: &[&str] = &[
+    "maestro_pw.Maestro/GetSoftwareInfo",
+    "maestro_pw.Maestro/GetHardwareInfo",
+    "maestro_pw.Maestro/SubscribeRuntimeInfo",
+    "maestro_pw.Maestro/WriteSetting",
+    "maestro_pw.Maestro/ReadSetting",
+    "maestro_pw.Maestro/SubscribeToSettingsChanges",
+    "maestro_pw.Maestro/SubscribeToOobeActions",
+    "maestro_pw.Multipoint/SubscribeToQuietModeStatus",
+    "maestro_pw.Dosimeter/FetchDailySummaries",
+    "maestro_pw.Dosimeter/SubscribeToLiveDb",
+];
+
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Phase {
+    pub name: String,
+    pub duration: Duration,
+
+    /// Status of RPCs, `None` for other phases.
+    pub status: Option<String>,
+}
+
+
+/// Phases of a command, in the order they have been completed.
+#[derive(Debug)]
+pub struct Timings {
+    start: Instant,
+    phases: Vec<Phase>,
+    enabled: bool,
+}
+
+impl Timings {
+    pub fn new() -> Self {
+        Self { start: Instant::now(), phases: Vec::new(), enabled: true }
+    }
+
+    /// Timings that are not reported. Phases are still recorded, but RPCs
+    /// should not be subscribed to, as long-running commands would
+    /// accumulate their events until the command has completed.
+    pub fn disabled() -> Self {
+        Self { enabled: false, ..Self::new() }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn record(&mut self, name: impl Into<String>, duration: Duration) {
+        self.phases.push(Phase { name: name.into(), duration, status: None });
+    }
+
+    /// Run the given future and record how long it took.
+    pub async fn measure<T>(&mut self, name: &str, task: impl Future<Output = T>) -> T {
+        let start = Instant::now();
+        let result = task.await;
+
+        self.record(name, start.elapsed());
+        result
+    }
+
+    /// Record the RPC of the given client event, ignoring other events.
+    pub fn record_event(&mut self, event: &Event) {
+        let Event::CallCompleted { trace, service, method, status, duration } = *event else {
+            return;
+        };
+
+        let path = METHODS.iter().find(|name| {
+            let path = PathRef::new(name);
+            path.service().hash() == service && path.method().hash() == method
+        });
+
+        let name = match path {
+            Some(path) => format!("rpc {path} (trace {trace})"),
+            None => format!("rpc 0x{service:08x}/0x{method:08x} (trace {trace})"),
+        };
+
+        self.phases.push(Phase { name, duration, status: Some(format!("{status:?}")) });
+    }
+
+    pub fn table(&self) -> String {
+        let width = self.phases.iter()
+            .map(|p| p.name.len())
+            .chain(std::iter::once("total".len()))
+            .max()
+            .unwrap_or_default();
+
+        let mut table = String::new();
+
+        for phase in &self.phases {
+            let ms = phase.duration.as_secs_f64() * 1000.0;
+
+            match &phase.status {
+                Some(status) => table += &format!("{:width$}  {ms:>9.1} ms  {status}\n", phase.name),
+                None => table += &format!("{:width$}  {ms:>9.1} ms\n", phase.name),
+            }
+        }
+
+        let total = self.start.elapsed().as_secs_f64() * 1000.0;
+        table += &format!("{:width$}  {total:>9.1} ms\n", "total");
+        table
+    }
+
+    pub fn json(&self) -> Value {
+        let phases: Vec<_> = self.phases.iter()
+            .map(|p| json!({
+                "name": p.name,
+                "duration_ms": p.duration.as_secs_f64() * 1000.0,
+                "status": p.status,
+            }))
+            .collect();
+
+        json!({
+            "phases": phases,
+            "total_ms": self.start.elapsed().as_secs_f64() * 1000.0,
+        })
+    }
+
+    /// Print the timings to stderr, keeping the output of the command itself
+    /// parseable.
+    pub fn print(&self, format: TimingsFormat) {
+        match format {
+            TimingsFormat::Table => eprint!("{}", self.table()),
+            TimingsFormat::Json => eprintln!("{}", self.json()),
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use maestro::pwrpc::Status;
+    use maestro::pwrpc::id::hash;
+
+    #[test]
+    fn test_record_event() {
+        let mut timings = Timings::new();
+        timings.record("connect", Duration::from_millis(1500));
+
+        timings.record_event(&Event::CallCompleted {
+            trace: 3,
+            service: hash("maestro_pw.Maestro"),
+            method: hash("ReadSetting"),
+            status: Status::Ok,
+            duration: Duration::from_millis(42),
+        });
+
+        timings.record_event(&Event::CallCompleted {
+            trace: 4,
+            service: 0x12345678,
+            method: 0x9abcdef0,
+            status: Status::Unimplemented,
+            duration: Duration::from_millis(7),
+        });
+
+        timings.record_event(&Event::ChannelChanged { old: 1, new: 2 });
+
+        let names: Vec<_> = timings.phases.iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(names, [
+            "connect",
+            "rpc maestro_pw.Maestro/ReadSetting (trace 3)",
+            "rpc 0x12345678/0x9abcdef0 (trace 4)",
+        ]);
+
+        let table = timings.table();
+        assert!(table.contains("rpc maestro_pw.Maestro/ReadSetting (trace 3)       42.0 ms  Ok\n"));
+        assert_eq!(timings.json()["phases"][0]["duration_ms"], 1500.0);
+    }
+}
@@ -0,0 +1,389 @@
+//! Transport via BlueZ on Linux.
+
+use std::time::Duration;
+
+use anyhow::Result;
+
+use bluer::{Adapter, AdapterEvent, Address, Device, DeviceEvent, DeviceProperty, Session};
+use bluer::rfcomm::{ProfileHandle, Role, ReqError, Stream, Profile, SocketAddr};
+
+use futures::{Sink, StreamExt};
+
+use gfps::msg::Message;
+
+use maestro::Error;
+use maestro::retry::Backoff;
+
+use crate::cli::ConnectMode;
+
+use super::{sdp, DeviceIdentity, KnownDevice, Transport};
+
+
+const PIXEL_BUDS_CLASS: u32 = 0x240404;
+const PIXEL_BUDS2_CLASS: u32 = 0x244404;
+
+
+#[derive(Clone)]
+pub struct BluezTransport {
+    session: Session,
+    device: Device,
+    identity: DeviceIdentity,
+    mode: ConnectMode,
+}
+
+impl BluezTransport {
+    pub fn with_connect_mode(self, mode: ConnectMode) -> Self {
+        Self { mode, ..self }
+    }
+
+    /// Set up the transport for a previously used device, without searching
+    /// for it. Fails if the device is not a known compatible device on the
+    /// adapter of the given identity.
+    pub async fn open_cached(identity: &DeviceIdentity) -> Result<Self, Error> {
+        let address = identity.address;
+
+        let session = Session::new().await.map_err(Error::profile)?;
+        let adapter = session.adapter(&identity.adapter).map_err(Error::profile)?;
+        let device = adapter.device(address).map_err(Error::profile)?;
+
+        if !is_maestro_device(&device).await? {
+            return Err(Error::discovery(format!("cached device {address} is not a compatible device")));
+        }
+
+        tracing::debug!(adapter=%adapter.name(), %address, "using cached device");
+        Ok(Self::new(session, device).await)
+    }
+
+    async fn new(session: Session, device: Device) -> Self {
+        let identity = identify(&device).await;
+        Self { session, device, identity, mode: ConnectMode::Profile }
+    }
+
+    /// Whether the device is connected via Bluetooth at all, independent of
+    /// any connection to its Maestro service.
+    pub async fn is_connected(&self) -> Result<bool, Error> {
+        self.device.is_connected().await.map_err(Error::profile)
+    }
+
+    /// Wait until the device is connected via Bluetooth, e.g. once the buds
+    /// have been taken out of the case. Returns immediately if the device is
+    /// already connected.
+    pub async fn wait_connected(&self) -> Result<(), Error> {
+        // subscribe first so that we do not miss the connection
+        let mut events = self.device.events().await.map_err(Error::profile)?;
+
+        if self.is_connected().await? {
+            return Ok(());
+        }
+
+        tracing::debug!(address=%self.device.address(), "waiting for bluetooth connection");
+
+        while let Some(event) = events.next().await {
+            if let DeviceEvent::PropertyChanged(DeviceProperty::Connected(true)) = event {
+                tracing::debug!(address=%self.device.address(), "device connected via bluetooth");
+                return Ok(());
+            }
+        }
+
+        Err(Error::profile("device event stream terminated"))
+    }
+
+    /// Open a new connection to the GFPS message stream of the device.
+    pub async fn gfps_connect(&self)
+        -> Result<impl futures::Stream<Item = std::io::Result<Message>> + for<'a> Sink<&'a Message, Error = std::io::Error> + Unpin>
+    {
+        tracing::debug!(address=%self.device.address(), "connecting to gfps message stream");
+        Ok(gfps::connect::connect(&self.session, &self.device).await?)
+    }
+
+    /// Read the firmware version reported via the GFPS message stream.
+    pub async fn gfps_firmware_version(&self) -> Result<String> {
+        const TIMEOUT: Duration = Duration::from_secs(5);
+
+        tracing::debug!(address=%self.device.address(), "reading firmware version via gfps");
+        let mut stream = gfps::connect::connect(&self.session, &self.device).await?;
+
+        let version = tokio::time::timeout(TIMEOUT, gfps::connect::get_firmware_version(&mut stream)).await
+            .map_err(|_| anyhow::anyhow!("timed out waiting for gfps firmware version"))??;
+
+        Ok(version)
+    }
+
+    /// Read the battery levels reported via the GFPS message stream.
+    pub async fn gfps_battery_info(&self) -> Result<gfps::msg::BatteryLevels> {
+        const TIMEOUT: Duration = Duration::from_secs(2);
+
+        tracing::debug!(address=%self.device.address(), "reading battery levels via gfps");
+        let mut stream = gfps::connect::connect(&self.session, &self.device).await?;
+
+        let levels = tokio::time::timeout(TIMEOUT, gfps::connect::get_battery_info(&mut stream)).await
+            .map_err(|_| anyhow::anyhow!("timed out waiting for gfps battery info"))??;
+
+        Ok(levels)
+    }
+
+    /// Read the remaining battery time of the buds reported via the GFPS
+    /// message stream, in minutes.
+    pub async fn gfps_battery_time(&self) -> Result<u16> {
+        const TIMEOUT: Duration = Duration::from_secs(2);
+
+        tracing::debug!(address=%self.device.address(), "reading remaining battery time via gfps");
+        let mut stream = gfps::connect::connect(&self.session, &self.device).await?;
+
+        let minutes = tokio::time::timeout(TIMEOUT, gfps::connect::get_battery_time(&mut stream)).await
+            .map_err(|_| anyhow::anyhow!("timed out waiting for gfps battery time"))??;
+
+        Ok(minutes)
+    }
+}
+
+impl Transport for BluezTransport {
+    type Stream = Stream;
+
+    async fn open(address: Option<Address>, adapter: Option<&str>) -> Result<Self, Error> {
+        let session = Session::new().await.map_err(Error::profile)?;
+        let adapter = open_adapter(&session, adapter).await?;
+
+        let device = if let Some(address) = address {
+            tracing::debug!("using provided address: {}", address);
+            adapter.device(address).map_err(Error::profile)?
+        } else {
+            tracing::debug!("no device specified, searching for compatible one");
+            find_maestro_device(&adapter).await?
+        };
+
+        Ok(Self::new(session, device).await)
+    }
+
+    fn identity(&self) -> &DeviceIdentity {
+        &self.identity
+    }
+
+    #[tracing::instrument(level = "debug", skip(self), fields(device = %self.device.address(), mode = ?self.mode))]
+    async fn connect(&self) -> Result<Stream, Error> {
+        match self.mode {
+            ConnectMode::Profile => connect_maestro_rfcomm(&self.session, &self.device).await,
+            ConnectMode::Raw => connect_maestro_raw(&self.device).await,
+        }
+    }
+}
+
+
+/// Discover a device in pairing mode, then pair, trust, and connect it.
+///
+/// If no address is given, the first unpaired device with a matching class
+/// of device is used.
+pub async fn pair(address: Option<Address>, adapter: Option<&str>, timeout: Duration) -> Result<BluezTransport, Error> {
+    let session = Session::new().await.map_err(Error::profile)?;
+    let adapter = open_adapter(&session, adapter).await?;
+
+    adapter.set_powered(true).await.map_err(Error::profile)?;
+    adapter.set_pairable(true).await.map_err(Error::profile)?;
+
+    tracing::debug!(adapter=%adapter.name(), "discovering devices");
+    let device = tokio::time::timeout(timeout, discover_device(&adapter, address)).await
+        .map_err(|_| Error::discovery("no device in pairing mode found"))??;
+
+    if !device.is_paired().await.map_err(Error::profile)? {
+        tracing::debug!(address=%device.address(), "pairing device");
+        device.pair().await.map_err(Error::profile)?;
+    }
+
+    device.set_trusted(true).await.map_err(Error::profile)?;
+
+    if !device.is_connected().await.map_err(Error::profile)? {
+        tracing::debug!(address=%device.address(), "connecting device");
+        device.connect().await.map_err(Error::profile)?;
+    }
+
+    Ok(BluezTransport::new(session, device).await)
+}
+
+/// List the paired compatible devices known to the given adapter, or to the
+/// default one.
+pub async fn devices(adapter: Option<&str>) -> Result<Vec<KnownDevice>, Error> {
+    let session = Session::new().await.map_err(Error::profile)?;
+    let adapter = open_adapter(&session, adapter).await?;
+
+    let mut devices = Vec::new();
+    for device in maestro_devices(&adapter).await? {
+        if !device.is_paired().await.map_err(Error::profile)? {
+            continue;
+        }
+
+        devices.push(KnownDevice {
+            identity: identify(&device).await,
+            connected: device.is_connected().await.map_err(Error::profile)?,
+        });
+    }
+
+    Ok(devices)
+}
+
+/// The adapter with the given name, or the default adapter if unspecified.
+pub async fn open_adapter(session: &Session, name: Option<&str>) -> Result<Adapter, Error> {
+    let adapter = match name {
+        Some(name) => session.adapter(name).map_err(Error::profile)?,
+        None => return session.default_adapter().await.map_err(Error::profile),
+    };
+
+    // looking up an adapter by name does not check whether it exists
+    if !session.adapter_names().await.map_err(Error::profile)?.iter().any(|n| n == adapter.name()) {
+        return Err(Error::discovery(format!("adapter {} not found", adapter.name())));
+    }
+
+    Ok(adapter)
+}
+
+/// Look up alias and model of the device. Properties that cannot be read are
+/// left unknown.
+async fn identify(device: &Device) -> DeviceIdentity {
+    let mut identity = DeviceIdentity::new(device.address(), device.adapter_name());
+
+    match device.alias().await {
+        Ok(alias) => identity.alias = Some(alias),
+        Err(err) => tracing::debug!(error=?err, "failed to get device alias"),
+    }
+
+    // the alias may have been changed by the user, the name is set by the
+    // device itself
+    match device.name().await {
+        Ok(name) => identity.model = name.as_deref().and_then(maestro::models::by_name),
+        Err(err) => tracing::debug!(error=?err, "failed to get device name"),
+    }
+
+    tracing::debug!(device=%identity, model=?identity.model.map(|m| m.name), "identified device");
+    identity
+}
+
+async fn discover_device(adapter: &Adapter, address: Option<Address>) -> Result<Device, Error> {
+    let events = adapter.discover_devices().await.map_err(Error::profile)?;
+    let mut events = std::pin::pin!(events);
+
+    while let Some(event) = events.next().await {
+        let AdapterEvent::DeviceAdded(addr) = event else {
+            continue;
+        };
+
+        let dev = adapter.device(addr).map_err(Error::profile)?;
+
+        match address {
+            Some(address) if address == addr => {},
+            Some(_) => continue,
+            None if !is_pixel_buds(&dev).await? || dev.is_paired().await.map_err(Error::profile)? => continue,
+            None => {},
+        }
+
+        tracing::debug!(address=%addr, "found device");
+        return Ok(dev);
+    }
+
+    Err(Error::discovery("device discovery terminated unexpectedly"))
+}
+
+async fn is_pixel_buds(dev: &Device) -> Result<bool, Error> {
+    let class = dev.class().await.map_err(Error::profile)?.unwrap_or(0);
+    Ok(class == PIXEL_BUDS_CLASS || class == PIXEL_BUDS2_CLASS)
+}
+
+async fn is_maestro_device(dev: &Device) -> Result<bool, Error> {
+    if !is_pixel_buds(dev).await? {
+        return Ok(false);
+    }
+
+    let uuids = dev.uuids().await.map_err(Error::profile)?.unwrap_or_default();
+    Ok(uuids.contains(&maestro::UUID))
+}
+
+/// All compatible devices known to the adapter.
+async fn maestro_devices(adapter: &Adapter) -> Result<Vec<Device>, Error> {
+    let mut devices = Vec::new();
+
+    for addr in adapter.device_addresses().await.map_err(Error::profile)? {
+        let dev = adapter.device(addr).map_err(Error::profile)?;
+
+        if is_maestro_device(&dev).await? {
+            tracing::debug!(address=%addr, "found compatible device");
+            devices.push(dev);
+        }
+    }
+
+    Ok(devices)
+}
+
+async fn find_maestro_device(adapter: &Adapter) -> Result<Device, Error> {
+    match maestro_devices(adapter).await?.into_iter().next() {
+        Some(dev) => Ok(dev),
+        None => {
+            tracing::debug!("no compatible device found");
+            Err(Error::discovery("no compatible device found"))
+        },
+    }
+}
+
+async fn connect_maestro_rfcomm(session: &Session, dev: &Device) -> Result<Stream, Error> {
+    let maestro_profile = Profile {
+        uuid: maestro::UUID,
+        role: Some(Role::Client),
+        require_authentication: Some(false),
+        require_authorization: Some(false),
+        auto_connect: Some(false),
+        ..Default::default()
+    };
+
+    tracing::debug!("registering maestro profile");
+    let mut handle = session.register_profile(maestro_profile).await.map_err(Error::profile)?;
+
+    tracing::debug!("connecting to maestro profile");
+    let stream = tokio::try_join!(
+        try_connect_profile(dev),
+        handle_requests_for_profile(&mut handle, dev.address()),
+    )?.1;
+
+    Ok(stream)
+}
+
+/// Connect directly to the RFCOMM channel of the Maestro service, bypassing
+/// profile registration.
+async fn connect_maestro_raw(dev: &Device) -> Result<Stream, Error> {
+    let channel = sdp::find_rfcomm_channel(dev.address(), maestro::UUID).await
+        .map_err(Error::profile)?;
+
+    tracing::debug!(address=%dev.address(), channel, "connecting to maestro rfcomm channel");
+    let stream = Stream::connect(SocketAddr::new(dev.address(), channel)).await?;
+
+    tracing::debug!(address=%dev.address(), "maestro rfcomm channel connected");
+    Ok(stream)
+}
+
+async fn try_connect_profile(dev: &Device) -> Result<(), Error> {
+    const MAX_TRIES: u32 = 3;
+    const RETRY: Backoff = Backoff::fixed(Duration::from_secs(1), Some(MAX_TRIES + 1));
+
+    let mut delays = RETRY.delays();
+    while let Err(err) = dev.connect_profile(&maestro::UUID).await {
+        let Some(delay) = delays.next() else { return Err(Error::profile(err)) };
+
+        tracing::warn!(error=?err, "connecting to profile failed, trying again ({}/{})", delays.retries(), MAX_TRIES);
+
+        tokio::time::sleep(delay).await;
+    }
+
+    tracing::debug!(address=%dev.address(), "maestro profile connected");
+    Ok(())
+}
+
+async fn handle_requests_for_profile(handle: &mut ProfileHandle, address: Address) -> Result<Stream, Error> {
+    while let Some(req) = handle.next().await {
+        tracing::debug!(address=%req.device(), "received new profile connection request");
+
+        if req.device() == address {
+            tracing::debug!(address=%req.device(), "accepting profile connection request");
+            return req.accept().map_err(Error::profile);
+        } else {
+            req.reject(ReqError::Rejected);
+        }
+    }
+
+    Err(Error::profile("profile terminated without requests"))
+}
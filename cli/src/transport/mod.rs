@@ -0,0 +1,121 @@
+//! Platform abstraction for connecting to the device.
+//!
+//! The protocol libraries only require a byte stream implementing
+//! `AsyncRead` and `AsyncWrite`. How that stream is obtained, i.e., how the
+//! device is looked up and the RFCOMM connection to its Maestro service is
+//! established, depends on the platform's Bluetooth stack and is provided by
+//! an implementation of [`Transport`].
+
+use std::future::Future;
+
+use maestro::Error;
+use maestro::models::Model;
+
+use tokio::io::{AsyncRead, AsyncWrite};
+
+#[cfg(target_os = "linux")]
+pub mod bluez;
+
+#[cfg(target_os = "linux")]
+mod sdp;
+
+#[cfg(target_os = "linux")]
+pub use bluer::Address;
+
+/// Transport implementation for the current platform.
+#[cfg(target_os = "linux")]
+pub type Platform = bluez::BluezTransport;
+
+
+/// Identity of a device, as determined during discovery.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeviceIdentity {
+    pub address: Address,
+
+    /// Name of the device as shown to the user, if known.
+    pub alias: Option<String>,
+
+    /// Model of the device, if known.
+    pub model: Option<&'static Model>,
+
+    /// Name of the adapter the device is connected via.
+    pub adapter: String,
+}
+
+impl DeviceIdentity {
+    pub fn new(address: Address, adapter: impl Into<String>) -> Self {
+        Self { address, alias: None, model: None, adapter: adapter.into() }
+    }
+}
+
+/// Description of the device, e.g. `Kitchen Buds (Pixel Buds Pro,
+/// 24:29:34:AC:9F:D1)`.
+impl std::fmt::Display for DeviceIdentity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let model = self.model.map(|m| m.name)
+            .filter(|name| self.alias.as_deref() != Some(*name));
+
+        match (&self.alias, model) {
+            (Some(alias), Some(model)) => write!(f, "{alias} ({model}, {})", self.address),
+            (Some(alias), None) => write!(f, "{alias} ({})", self.address),
+            (None, Some(model)) => write!(f, "{model} ({})", self.address),
+            (None, None) => write!(f, "{}", self.address),
+        }
+    }
+}
+
+
+/// A compatible device known to the adapter.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KnownDevice {
+    pub identity: DeviceIdentity,
+
+    /// Whether the device is currently connected via Bluetooth.
+    pub connected: bool,
+}
+
+
+/// Set up and connect to the device.
+///
+/// Errors are reported via [`maestro::Error`], so that callers can tell, e.g.,
+/// a missing device apart from a failed profile connection.
+pub trait Transport: Sized {
+    type Stream: AsyncRead + AsyncWrite + Unpin;
+
+    /// Set up the transport for the device with the given address, or for
+    /// the first compatible device if none is specified, using the given
+    /// adapter or the default one.
+    fn open(address: Option<Address>, adapter: Option<&str>) -> impl Future<Output = Result<Self, Error>>;
+
+    /// Identity of the device.
+    fn identity(&self) -> &DeviceIdentity;
+
+    /// Address of the device.
+    fn address(&self) -> Address {
+        self.identity().address
+    }
+
+    /// Open a new connection to the Maestro service of the device.
+    fn connect(&self) -> impl Future<Output = Result<Self::Stream, Error>>;
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_identity_display() {
+        let mut identity = DeviceIdentity::new("24:29:34:AC:9F:D1".parse().unwrap(), "hci0");
+        assert_eq!(identity.to_string(), "24:29:34:AC:9F:D1");
+
+        identity.model = maestro::models::by_name("Pixel Buds Pro");
+        assert_eq!(identity.to_string(), "Pixel Buds Pro (24:29:34:AC:9F:D1)");
+
+        identity.alias = Some("Pixel Buds Pro".to_owned());
+        assert_eq!(identity.to_string(), "Pixel Buds Pro (24:29:34:AC:9F:D1)");
+
+        identity.alias = Some("Kitchen Buds".to_owned());
+        assert_eq!(identity.to_string(), "Kitchen Buds (Pixel Buds Pro, 24:29:34:AC:9F:D1)");
+    }
+}
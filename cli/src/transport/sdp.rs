@@ -0,0 +1,228 @@
+//! Minimal SDP client for looking up RFCOMM channels of remote services.
+//!
+//! Only supports what is needed to resolve the RFCOMM channel of a service by
+//! its UUID, i.e., a single `ServiceSearchAttributeRequest` for the protocol
+//! descriptor list.
+
+use anyhow::Result;
+
+use bluer::{Address, Uuid};
+use bluer::l2cap::{SeqPacket, SocketAddr};
+
+
+const SDP_PSM: u16 = 0x0001;
+
+const PDU_ERROR_RSP: u8 = 0x01;
+const PDU_SERVICE_SEARCH_ATTR_REQ: u8 = 0x06;
+const PDU_SERVICE_SEARCH_ATTR_RSP: u8 = 0x07;
+
+const ATTR_PROTOCOL_DESCRIPTOR_LIST: u16 = 0x0004;
+
+const UUID_RFCOMM: u16 = 0x0003;
+
+const MAX_ATTR_BYTES: u16 = 0xffff;
+const MAX_CONTINUATIONS: usize = 16;
+
+
+/// Look up the RFCOMM channel of the service with the given UUID on the
+/// device.
+pub async fn find_rfcomm_channel(address: Address, uuid: Uuid) -> Result<u8> {
+    tracing::debug!(%address, %uuid, "querying SDP for RFCOMM channel");
+
+    let addr = SocketAddr::new(address, bluer::AddressType::BrEdr, SDP_PSM);
+    let socket = SeqPacket::connect(addr).await?;
+
+    let mut attrs = Vec::new();
+    let mut continuation = Vec::new();
+    let mut buf = vec![0; 4096];
+
+    for tid in 0..MAX_CONTINUATIONS as u16 {
+        socket.send(&search_attr_request(tid, uuid, &continuation)).await?;
+
+        let n = socket.recv(&mut buf).await?;
+        continuation = parse_search_attr_response(tid, &buf[..n], &mut attrs)?;
+
+        if continuation.is_empty() {
+            return rfcomm_channel(&attrs)
+                .ok_or_else(|| anyhow::anyhow!("no RFCOMM channel found for service {uuid}"));
+        }
+    }
+
+    anyhow::bail!("too many SDP continuation responses")
+}
+
+fn search_attr_request(tid: u16, uuid: Uuid, continuation: &[u8]) -> Vec<u8> {
+    let mut params = Vec::new();
+
+    // service search pattern: sequence of one 128-bit UUID
+    params.extend_from_slice(&[0x35, 17, 0x1c]);
+    params.extend_from_slice(uuid.as_bytes());
+
+    params.extend_from_slice(&MAX_ATTR_BYTES.to_be_bytes());
+
+    // attribute ID list: sequence of one 16-bit attribute ID
+    params.extend_from_slice(&[0x35, 3, 0x09]);
+    params.extend_from_slice(&ATTR_PROTOCOL_DESCRIPTOR_LIST.to_be_bytes());
+
+    params.push(continuation.len() as u8);
+    params.extend_from_slice(continuation);
+
+    let mut pdu = vec![PDU_SERVICE_SEARCH_ATTR_REQ];
+    pdu.extend_from_slice(&tid.to_be_bytes());
+    pdu.extend_from_slice(&(params.len() as u16).to_be_bytes());
+    pdu.extend_from_slice(&params);
+    pdu
+}
+
+/// Parse a response PDU, appending the attribute list bytes to `attrs` and
+/// returning the continuation state.
+fn parse_search_attr_response(tid: u16, data: &[u8], attrs: &mut Vec<u8>) -> Result<Vec<u8>> {
+    let invalid = || anyhow::anyhow!("invalid SDP response");
+
+    let header = data.get(..5).ok_or_else(invalid)?;
+    let params = &data[5..];
+
+    if u16::from_be_bytes([header[1], header[2]]) != tid {
+        anyhow::bail!("unexpected SDP transaction ID");
+    }
+
+    match header[0] {
+        PDU_SERVICE_SEARCH_ATTR_RSP => {},
+        PDU_ERROR_RSP => {
+            let code = params.get(..2).map(|c| u16::from_be_bytes([c[0], c[1]]));
+            anyhow::bail!("SDP request failed with error code {:?}", code.unwrap_or(0));
+        },
+        pdu => anyhow::bail!("unexpected SDP response PDU 0x{pdu:02x}"),
+    }
+
+    let count = params.get(..2).ok_or_else(invalid)?;
+    let count = u16::from_be_bytes([count[0], count[1]]) as usize;
+
+    let lists = params.get(2..2 + count).ok_or_else(invalid)?;
+    attrs.extend_from_slice(lists);
+
+    let cont_len = *params.get(2 + count).ok_or_else(invalid)? as usize;
+    let cont = params.get(3 + count..3 + count + cont_len).ok_or_else(invalid)?;
+
+    Ok(cont.to_vec())
+}
+
+
+/// SDP data element.
+#[derive(Debug, Clone, PartialEq)]
+enum Element<'a> {
+    Uint(u64),
+    Uuid16(u16),
+    Sequence(&'a [u8]),
+    Other,
+}
+
+/// Parse a single data element, returning it and the remaining data.
+fn parse_element(data: &[u8]) -> Option<(Element<'_>, &[u8])> {
+    let (&header, data) = data.split_first()?;
+
+    let ty = header >> 3;
+    let (len, data) = match header & 0x07 {
+        0 if ty == 0 => (0, data),
+        0 => (1, data),
+        1 => (2, data),
+        2 => (4, data),
+        3 => (8, data),
+        4 => (16, data),
+        5 => (*data.first()? as usize, data.get(1..)?),
+        6 => (u16::from_be_bytes(data.get(..2)?.try_into().ok()?) as usize, data.get(2..)?),
+        _ => (u32::from_be_bytes(data.get(..4)?.try_into().ok()?) as usize, data.get(4..)?),
+    };
+
+    let value = data.get(..len)?;
+    let rest = &data[len..];
+
+    let element = match ty {
+        1 if len <= 8 => Element::Uint(value.iter().fold(0, |acc, b| (acc << 8) | u64::from(*b))),
+        3 if len == 2 => Element::Uuid16(u16::from_be_bytes([value[0], value[1]])),
+        6 | 7 => Element::Sequence(value),
+        _ => Element::Other,
+    };
+
+    Some((element, rest))
+}
+
+fn parse_sequence(data: &[u8]) -> Option<Vec<Element<'_>>> {
+    let mut items = Vec::new();
+    let mut data = data;
+
+    while !data.is_empty() {
+        let (element, rest) = parse_element(data)?;
+        items.push(element);
+        data = rest;
+    }
+
+    Some(items)
+}
+
+/// Extract the RFCOMM channel from the attribute lists of a response.
+fn rfcomm_channel(attrs: &[u8]) -> Option<u8> {
+    let (Element::Sequence(records), _) = parse_element(attrs)? else { return None };
+
+    for record in parse_sequence(records)? {
+        let Element::Sequence(record) = record else { continue };
+        let record = parse_sequence(record)?;
+
+        // attribute list: pairs of attribute ID and value
+        for pair in record.chunks(2) {
+            let [Element::Uint(id), Element::Sequence(protocols)] = pair else { continue };
+            if *id != u64::from(ATTR_PROTOCOL_DESCRIPTOR_LIST) {
+                continue;
+            }
+
+            for protocol in parse_sequence(protocols)? {
+                let Element::Sequence(protocol) = protocol else { continue };
+
+                if let [Element::Uuid16(UUID_RFCOMM), Element::Uint(channel), ..] = parse_sequence(protocol)?[..] {
+                    return u8::try_from(channel).ok();
+                }
+            }
+        }
+    }
+
+    None
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_rfcomm_channel() {
+        // one record with protocol descriptor list: L2CAP, RFCOMM channel 5
+        let attrs = [
+            0x35, 0x13,                                 // sequence of records
+            0x35, 0x11,                                 // record
+            0x09, 0x00, 0x04,                           // attribute ID 0x0004
+            0x35, 0x0c,                                 // protocol descriptor list
+            0x35, 0x03, 0x19, 0x01, 0x00,               // L2CAP
+            0x35, 0x05, 0x19, 0x00, 0x03, 0x08, 0x05,   // RFCOMM, channel 5
+        ];
+
+        assert_eq!(rfcomm_channel(&attrs), Some(5));
+        assert_eq!(rfcomm_channel(&[0x35, 0x00]), None);
+    }
+
+    #[test]
+    fn test_search_attr_response() {
+        let rsp = [
+            PDU_SERVICE_SEARCH_ATTR_RSP, 0x00, 0x01, 0x00, 0x07,
+            0x00, 0x02, 0x35, 0x00,                     // attribute lists
+            0x02, 0xaa, 0xbb,                           // continuation state
+        ];
+
+        let mut attrs = Vec::new();
+        let cont = parse_search_attr_response(1, &rsp, &mut attrs).unwrap();
+
+        assert_eq!(attrs, [0x35, 0x00]);
+        assert_eq!(cont, [0xaa, 0xbb]);
+
+        assert!(parse_search_attr_response(2, &rsp, &mut attrs).is_err());
+    }
+}
@@ -5,12 +5,11 @@
 
 use std::str::FromStr;
 
-use bluer::{Address, Session, Device};
-use bluer::rfcomm::{Profile, ReqError, Role, ProfileHandle};
+use bluer::{Address, Session};
 
 use futures::StreamExt;
 
-use gfps::msg::{Codec, DeviceEventCode, EventGroup, BatteryInfo};
+use gfps::msg::{DeviceEventCode, EventGroup, BatteryInfo};
 
 use num_enum::FromPrimitive;
 
@@ -28,27 +27,8 @@ async fn main() -> bluer::Result<()> {
     // get device
     let dev = adapter.device(addr)?;
 
-    // get RFCOMM stream
-    let stream = {
-        // register GFPS profile
-        let profile = Profile {
-            uuid: gfps::msg::UUID,
-            role: Some(Role::Client),
-            require_authentication: Some(false),
-            require_authorization: Some(false),
-            auto_connect: Some(false),
-            ..Default::default()
-        };
-
-        let mut profile_handle = session.register_profile(profile).await?;
-
-        // connect profile
-        connect_device_to_profile(&mut profile_handle, &dev).await?
-    };
-
-    // listen to event messages
-    let codec = Codec::new();
-    let mut stream = codec.wrap(stream);
+    // connect to GFPS message stream
+    let mut stream = gfps::connect::connect(&session, &dev).await?;
 
     // The battery status cannot be queried via a normal command. However, it
     // is sent right after we connect to the GFPS stream. In addition, multiple
@@ -124,32 +104,3 @@ async fn main() -> bluer::Result<()> {
 
     Ok(())
 }
-
-async fn connect_device_to_profile(profile: &mut ProfileHandle, dev: &Device)
-    -> bluer::Result<bluer::rfcomm::Stream>
-{
-    loop {
-        tokio::select! {
-            res = async {
-                let _ = dev.connect().await;
-                dev.connect_profile(&gfps::msg::UUID).await
-            } => {
-                if let Err(err) = res {
-                    println!("Connecting GFPS profile failed: {:?}", err);
-                }
-                tokio::time::sleep(std::time::Duration::from_millis(3000)).await;
-            },
-            req = profile.next() => {
-                let req = req.expect("no connection request received");
-
-                if req.device() == dev.address() {
-                    // accept our device
-                    break req.accept();
-                } else {
-                    // reject unknown devices
-                    req.reject(ReqError::Rejected);
-                }
-            },
-        }
-    }
-}
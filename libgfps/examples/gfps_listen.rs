@@ -5,13 +5,12 @@
 
 use std::str::FromStr;
 
-use bluer::{Address, Session, Device};
-use bluer::rfcomm::{Profile, ReqError, Role, ProfileHandle};
+use bluer::{Address, Session};
 
-use futures::StreamExt;
+use gfps::liveness::{Liveness, Monitor};
 
 use gfps::msg::{
-    AcknowledgementEventCode, Codec, DeviceActionEventCode, DeviceCapabilitySyncEventCode,
+    AcknowledgementEventCode, DeviceActionEventCode, DeviceCapabilitySyncEventCode,
     DeviceConfigurationEventCode, DeviceEventCode, EventGroup, Message, PlatformType,
     SassEventCode, LoggingEventCode, BluetoothEventCode, BatteryInfo,
 };
@@ -53,34 +52,15 @@ async fn main() -> bluer::Result<()> {
     }
     println!();
 
-    // try to reconnect if connection is reset
-    loop {
-        let stream = {
-            // register GFPS profile
-            println!("Registering GFPS profile...");
+    // reconnect if the connection is reset or has gone stale
+    let retry = gfps::connect::Retry::default();
 
-            let profile = Profile {
-                uuid: gfps::msg::UUID,
-                role: Some(Role::Client),
-                require_authentication: Some(false),
-                require_authorization: Some(false),
-                auto_connect: Some(false),
-                ..Default::default()
-            };
-
-            let mut profile_handle = session.register_profile(profile).await?;
-
-            // connect profile
-            println!("Connecting GFPS profile...");
-            connect_device_to_profile(&mut profile_handle, &dev).await?
-        };
+    println!("Connecting GFPS profile...");
+    gfps::connect::run_with_reconnect(&session, &dev, retry, |stream| async move {
+        let mut stream = Monitor::new(stream, Liveness::default());
 
         println!("Profile connected");
 
-        // listen to event messages
-        let codec = Codec::new();
-        let mut stream = codec.wrap(stream);
-
         println!("Listening...");
         println!();
 
@@ -89,50 +69,28 @@ async fn main() -> bluer::Result<()> {
                 Ok(msg) => {
                     print_message(&msg);
                 }
-                Err(e) if e.raw_os_error() == Some(104) => {
+                Err(e) if gfps::connect::is_connection_reset(&e) => {
                     // The Pixel Buds Pro can hand off processing between each
-                    // other. On a switch, the connection is reset. Wait a bit
-                    // and then try to reconnect.
+                    // other. On a switch, the connection is reset.
                     println!();
                     println!("Connection reset. Attempting to reconnect...");
-                    tokio::time::sleep(std::time::Duration::from_millis(500)).await;
-                    break;
+                    return Err(e);
+                }
+                Err(e) if gfps::liveness::is_timeout(&e) => {
+                    // The link may go silent without reporting an error. In
+                    // that case, the device did not answer our ping either.
+                    println!();
+                    println!("Connection stale. Attempting to reconnect...");
+                    return Err(e);
                 }
                 Err(e) => {
-                    Err(e)?;
+                    return Err(e);
                 }
             }
         }
-    }
-}
 
-async fn connect_device_to_profile(profile: &mut ProfileHandle, dev: &Device)
-    -> bluer::Result<bluer::rfcomm::Stream>
-{
-    loop {
-        tokio::select! {
-            res = async {
-                let _ = dev.connect().await;
-                dev.connect_profile(&gfps::msg::UUID).await
-            } => {
-                if let Err(err) = res {
-                    println!("Connecting GFPS profile failed: {:?}", err);
-                }
-                tokio::time::sleep(std::time::Duration::from_millis(3000)).await;
-            },
-            req = profile.next() => {
-                let req = req.expect("no connection request received");
-
-                if req.device() == dev.address() {
-                    println!("Accepting request...");
-                    break req.accept();
-                } else {
-                    println!("Rejecting unknown device {}", req.device());
-                    req.reject(ReqError::Rejected);
-                }
-            },
-        }
-    }
+        Ok(())
+    }).await
 }
 
 fn print_message(msg: &Message) {
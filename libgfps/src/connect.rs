@@ -0,0 +1,249 @@
+//! Helpers for connecting to the GFPS RFCOMM channel via BlueZ.
+//!
+//! Connecting is done by registering a client profile for the GFPS UUID and
+//! asking BlueZ to connect the device to it. The resulting connection request
+//! is then accepted for the target device and rejected for any other device.
+//!
+//! ```ignore
+//! let session = bluer::Session::new().await?;
+//! let device = session.default_adapter().await?.device(address)?;
+//!
+//! let mut stream = gfps::connect::connect(&session, &device).await?;
+//! ```
+
+use std::future::Future;
+use std::time::Duration;
+
+use bluer::{Address, Device, Session};
+use bluer::rfcomm::{Profile, ProfileHandle, ReqError, Role, Stream};
+
+use futures::{SinkExt, StreamExt};
+
+use tokio_util::codec::Framed;
+
+use crate::msg::{BatteryLevels, Codec, Message, UUID};
+
+
+/// Time to wait before reconnecting after the connection has been reset, e.g.
+/// due to the buds handing off processing between each other.
+const RECONNECT_DELAY: Duration = Duration::from_millis(500);
+
+
+/// Retry policy for connecting the GFPS profile.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Retry {
+    /// Maximum number of connection attempts, or `None` to retry until
+    /// connected.
+    pub attempts: Option<u32>,
+
+    /// Delay between connection attempts.
+    pub interval: Duration,
+}
+
+impl Default for Retry {
+    fn default() -> Self {
+        Self {
+            attempts: None,
+            interval: Duration::from_secs(3),
+        }
+    }
+}
+
+impl IntoIterator for Retry {
+    type Item = Duration;
+    type IntoIter = std::iter::RepeatN<Duration>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        let retries = self.attempts.map_or(usize::MAX, |n| n.saturating_sub(1) as usize);
+        std::iter::repeat_n(self.interval, retries)
+    }
+}
+
+
+/// The client profile used to connect to the GFPS RFCOMM channel.
+pub fn profile() -> Profile {
+    Profile {
+        uuid: UUID,
+        role: Some(Role::Client),
+        require_authentication: Some(false),
+        require_authorization: Some(false),
+        auto_connect: Some(false),
+        ..Default::default()
+    }
+}
+
+/// Register the GFPS client profile with BlueZ.
+pub async fn register_profile(session: &Session) -> bluer::Result<ProfileHandle> {
+    session.register_profile(profile()).await
+}
+
+/// Register the GFPS profile and connect the given device to it, returning
+/// the message stream.
+pub async fn connect(session: &Session, device: &Device) -> bluer::Result<Framed<Stream, Codec>> {
+    let mut profile = register_profile(session).await?;
+    let stream = connect_profile(&mut profile, device, Retry::default()).await?;
+
+    Ok(Codec::new().wrap(stream))
+}
+
+/// Connect the given device to an already registered GFPS profile.
+///
+/// Failed attempts are retried after each of the delays given by `retry`,
+/// e.g. a [`Retry`] or any other schedule like `maestro::retry::Backoff`.
+/// Connection requests of other devices received in the meantime are
+/// rejected.
+pub async fn connect_profile<D>(profile: &mut ProfileHandle, device: &Device, retry: D)
+    -> bluer::Result<Stream>
+where
+    D: IntoIterator<Item = Duration>,
+{
+    tokio::select! {
+        res = try_connect_profile(device, retry) => res,
+        res = accept(profile, device.address()) => res,
+    }
+}
+
+async fn try_connect_profile<D>(device: &Device, retry: D) -> bluer::Result<Stream>
+where
+    D: IntoIterator<Item = Duration>,
+{
+    let mut delays = retry.into_iter();
+
+    loop {
+        let _ = device.connect().await;
+
+        match device.connect_profile(&UUID).await {
+            Ok(()) => {
+                // the stream is provided via the profile connection request
+                return std::future::pending().await;
+            },
+            Err(err) => match delays.next() {
+                Some(delay) => tokio::time::sleep(delay).await,
+                None => return Err(err),
+            },
+        }
+    }
+}
+
+async fn accept(profile: &mut ProfileHandle, address: Address) -> bluer::Result<Stream> {
+    while let Some(req) = profile.next().await {
+        if req.device() == address {
+            return req.accept();
+        } else {
+            req.reject(ReqError::Rejected);
+        }
+    }
+
+    let err = std::io::Error::new(std::io::ErrorKind::ConnectionAborted, "profile terminated without requests");
+    Err(err.into())
+}
+
+
+/// Request the firmware version of the device and wait for the response.
+///
+/// Other messages received in the meantime are discarded.
+pub async fn get_firmware_version(stream: &mut Framed<Stream, Codec>) -> std::io::Result<String> {
+    stream.send(&Message::firmware_version_request()).await?;
+
+    while let Some(msg) = stream.next().await {
+        if let Some(version) = msg?.firmware_version() {
+            return Ok(version.to_owned());
+        }
+    }
+
+    let err = std::io::Error::new(std::io::ErrorKind::UnexpectedEof, "stream closed before receiving firmware version");
+    Err(err)
+}
+
+
+/// Wait for the device to report the battery levels of its components.
+///
+/// The device sends this on its own, e.g. after connecting. Other messages
+/// received in the meantime are discarded.
+pub async fn get_battery_info(stream: &mut Framed<Stream, Codec>) -> std::io::Result<BatteryLevels> {
+    while let Some(msg) = stream.next().await {
+        if let Some(levels) = msg?.battery_info() {
+            return Ok(levels);
+        }
+    }
+
+    let err = std::io::Error::new(std::io::ErrorKind::UnexpectedEof, "stream closed before receiving battery info");
+    Err(err)
+}
+
+
+/// Wait for the device to report its remaining battery time, in minutes.
+///
+/// The device sends this on its own, e.g. after connecting. Other messages
+/// received in the meantime are discarded.
+pub async fn get_battery_time(stream: &mut Framed<Stream, Codec>) -> std::io::Result<u16> {
+    while let Some(msg) = stream.next().await {
+        if let Some(minutes) = msg?.battery_time() {
+            return Ok(minutes);
+        }
+    }
+
+    let err = std::io::Error::new(std::io::ErrorKind::UnexpectedEof, "stream closed before receiving battery time");
+    Err(err)
+}
+
+
+/// Check whether the given error indicates a reset connection.
+///
+/// The Pixel Buds Pro can hand off processing between each other. On a
+/// switch, the connection is reset and needs to be re-established.
+pub fn is_connection_reset(err: &std::io::Error) -> bool {
+    err.kind() == std::io::ErrorKind::ConnectionReset
+}
+
+/// Connect to the device and run the given handler on the message stream,
+/// reconnecting whenever the connection is reset or the handler reports a
+/// liveness timeout (see [`crate::liveness`]).
+///
+/// Returns when the handler returns successfully or with an error other than
+/// a connection reset or liveness timeout.
+pub async fn run_with_reconnect<D, F, R>(session: &Session, device: &Device, retry: D, mut handler: F)
+    -> bluer::Result<()>
+where
+    D: IntoIterator<Item = Duration> + Clone,
+    F: FnMut(Framed<Stream, Codec>) -> R,
+    R: Future<Output = std::io::Result<()>>,
+{
+    loop {
+        let mut profile = register_profile(session).await?;
+        let stream = connect_profile(&mut profile, device, retry.clone()).await?;
+
+        match handler(Codec::new().wrap(stream)).await {
+            Err(err) if is_connection_reset(&err) || crate::liveness::is_timeout(&err) => {
+                tokio::time::sleep(RECONNECT_DELAY).await;
+            },
+            res => return Ok(res?),
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_retry_delays() {
+        let retry = Retry { attempts: Some(3), interval: Duration::from_secs(1) };
+        assert_eq!(retry.into_iter().collect::<Vec<_>>(), [Duration::from_secs(1); 2]);
+
+        let retry = Retry { attempts: Some(0), ..retry };
+        assert_eq!(retry.into_iter().next(), None);
+
+        assert_eq!(Retry::default().into_iter().nth(1000), Some(Duration::from_secs(3)));
+    }
+
+    #[test]
+    fn test_is_connection_reset() {
+        let err = std::io::Error::from_raw_os_error(104);
+        assert!(is_connection_reset(&err));
+
+        let err = std::io::Error::new(std::io::ErrorKind::ConnectionAborted, "aborted");
+        assert!(!is_connection_reset(&err));
+    }
+}
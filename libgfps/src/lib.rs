@@ -4,3 +4,15 @@
 //! See <https://developers.google.com/nearby/fast-pair> for the specification.
 
 pub mod msg;
+
+#[cfg(feature = "bluetooth")]
+pub mod connect;
+
+#[cfg(feature = "bluetooth")]
+pub mod liveness;
+
+#[cfg(feature = "bluetooth")]
+pub mod ring;
+
+#[cfg(feature = "bluetooth")]
+pub mod sass;
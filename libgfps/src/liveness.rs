@@ -0,0 +1,185 @@
+//! Liveness detection for GFPS message streams.
+//!
+//! A half-dead RFCOMM link may neither deliver messages nor report an error,
+//! leaving long-running listeners waiting forever. [`Monitor`] wraps a message
+//! stream, tracks when the last message has been received, optionally pings
+//! the device when the stream has been idle for a while, and fails with a
+//! [`std::io::ErrorKind::TimedOut`] error once the stream stays silent beyond
+//! the configured timeout. Use [`is_timeout`] to detect this and reconnect.
+//!
+//! ```ignore
+//! let stream = gfps::connect::connect(&session, &device).await?;
+//! let mut stream = gfps::liveness::Monitor::new(stream, Liveness::default());
+//!
+//! while let Some(msg) = stream.next().await {
+//!     match msg {
+//!         Ok(msg) => { /* ... */ },
+//!         Err(err) if gfps::liveness::is_timeout(&err) => { /* reconnect */ },
+//!         Err(err) => return Err(err),
+//!     }
+//! }
+//! ```
+
+use std::time::Duration;
+
+use futures::{Sink, SinkExt, Stream, StreamExt};
+
+use tokio::time::Instant;
+
+use crate::msg::Message;
+
+
+/// Liveness detection settings.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Liveness {
+    /// Time without any received message after which the stream is considered
+    /// dead.
+    pub timeout: Duration,
+
+    /// Time without any received message after which a ping is sent to the
+    /// device, or `None` to never ping. Should be shorter than the timeout to
+    /// give the device a chance to respond.
+    pub ping: Option<Duration>,
+}
+
+impl Liveness {
+    pub fn new(timeout: Duration) -> Self {
+        Self { timeout, ping: None }
+    }
+
+    pub fn with_ping(self, ping: Duration) -> Self {
+        Self { ping: Some(ping), ..self }
+    }
+}
+
+impl Default for Liveness {
+    fn default() -> Self {
+        Self {
+            timeout: Duration::from_secs(120),
+            ping: Some(Duration::from_secs(60)),
+        }
+    }
+}
+
+
+/// Message stream wrapper enforcing liveness.
+///
+/// Pings are sent as active-components requests, which the device answers
+/// with an active-components response without side effects.
+pub struct Monitor<S> {
+    inner: S,
+    liveness: Liveness,
+    last: Instant,
+    pinged: bool,
+}
+
+impl<S> Monitor<S>
+where
+    S: Stream<Item = std::io::Result<Message>> + for<'a> Sink<&'a Message, Error = std::io::Error> + Unpin,
+{
+    pub fn new(inner: S, liveness: Liveness) -> Self {
+        Self { inner, liveness, last: Instant::now(), pinged: false }
+    }
+
+    /// Receive the next message.
+    ///
+    /// Returns a [`std::io::ErrorKind::TimedOut`] error if no message has been
+    /// received within the configured timeout. Returns `None` if the
+    /// underlying stream has been closed.
+    pub async fn next(&mut self) -> Option<std::io::Result<Message>> {
+        loop {
+            let deadline = match self.liveness.ping {
+                Some(ping) if !self.pinged && ping < self.liveness.timeout => self.last + ping,
+                _ => self.last + self.liveness.timeout,
+            };
+
+            tokio::select! {
+                msg = self.inner.next() => {
+                    if let Some(Ok(_)) = msg {
+                        self.last = Instant::now();
+                        self.pinged = false;
+                    }
+
+                    return msg;
+                },
+                _ = tokio::time::sleep_until(deadline) => {
+                    if deadline >= self.last + self.liveness.timeout {
+                        let err = std::io::Error::new(std::io::ErrorKind::TimedOut, "no message received within liveness timeout");
+                        return Some(Err(err));
+                    }
+
+                    self.pinged = true;
+
+                    if let Err(err) = self.inner.send(&Message::active_components_request()).await {
+                        return Some(Err(err));
+                    }
+                },
+            }
+        }
+    }
+
+    /// Send a message to the device.
+    pub async fn send(&mut self, msg: &Message) -> std::io::Result<()> {
+        self.inner.send(msg).await
+    }
+}
+
+impl<S> Monitor<S> {
+    /// Time elapsed since the last message has been received, or since the
+    /// monitor has been created if no message has been received yet.
+    pub fn idle(&self) -> Duration {
+        self.last.elapsed()
+    }
+
+    pub fn get_mut(&mut self) -> &mut S {
+        &mut self.inner
+    }
+
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+}
+
+
+/// Check whether the given error indicates a liveness timeout, i.e., that the
+/// stream has gone silent and the connection should be re-established.
+pub fn is_timeout(err: &std::io::Error) -> bool {
+    err.kind() == std::io::ErrorKind::TimedOut
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use smallvec::smallvec;
+
+    use crate::msg::{Codec, DeviceEventCode, EventGroup};
+
+    #[tokio::test(start_paused = true)]
+    async fn test_ping_and_timeout() {
+        let (local, remote) = tokio::io::duplex(256);
+        let mut remote = Codec::new().wrap(remote);
+
+        let liveness = Liveness::new(Duration::from_secs(10)).with_ping(Duration::from_secs(5));
+        let mut stream = Monitor::new(Codec::new().wrap(local), liveness);
+
+        // a received message resets the idle time
+        let response = Message {
+            group: EventGroup::Device.into(),
+            code: DeviceEventCode::ActiveComponentsResponse.into(),
+            data: smallvec![0x03],
+        };
+
+        remote.send(&response).await.unwrap();
+        assert_eq!(stream.next().await.unwrap().unwrap(), response);
+
+        // silence: ping after 5s, fail after 10s
+        let start = Instant::now();
+        let err = stream.next().await.unwrap().unwrap_err();
+
+        assert!(is_timeout(&err));
+        assert_eq!(start.elapsed(), Duration::from_secs(10));
+        assert_eq!(remote.next().await.unwrap().unwrap(), Message::active_components_request());
+    }
+}
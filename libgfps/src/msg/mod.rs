@@ -7,7 +7,9 @@ use uuid::{uuid, Uuid};
 /// Defined as `df21fe2c-2515-4fdb-8886-f12c4d67927c`.
 pub const UUID: Uuid = uuid!("df21fe2c-2515-4fdb-8886-f12c4d67927c");
 
+#[cfg(feature = "codec")]
 mod codec;
+#[cfg(feature = "codec")]
 pub use codec::Codec;
 
 mod types;
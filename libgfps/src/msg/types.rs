@@ -4,7 +4,7 @@ use std::fmt::Display;
 
 use num_enum::{IntoPrimitive, FromPrimitive};
 
-use smallvec::SmallVec;
+use smallvec::{SmallVec, smallvec};
 
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -14,6 +14,183 @@ pub struct Message {
     pub data: SmallVec<[u8; 8]>,
 }
 
+impl Message {
+    /// Request for the firmware version of the device. The device responds
+    /// with a firmware version event.
+    pub fn firmware_version_request() -> Self {
+        Self {
+            group: EventGroup::Device.into(),
+            code: DeviceEventCode::FirmwareVersion.into(),
+            data: SmallVec::new(),
+        }
+    }
+
+    /// Request for the currently active components of the device. The device
+    /// responds with an active components response.
+    pub fn active_components_request() -> Self {
+        Self {
+            group: EventGroup::Device.into(),
+            code: DeviceEventCode::ActiveComponentsRequest.into(),
+            data: SmallVec::new(),
+        }
+    }
+
+    /// Request to start or stop ringing the given buds. Pixel Buds Pro ignore
+    /// ring requests with a timeout, so none is specified.
+    pub fn ring(state: RingState) -> Self {
+        Self {
+            group: EventGroup::DeviceAction.into(),
+            code: DeviceActionEventCode::Ring.into(),
+            data: smallvec![state.to_byte()],
+        }
+    }
+
+    /// Request to switch the active audio source to this device via Smart
+    /// Audio Source Switching (SASS). The device acknowledges the request and
+    /// then reports the switch via a multipoint switch event.
+    pub fn sass_switch(flags: SwitchFlags) -> Self {
+        Self {
+            group: EventGroup::SmartAudioSourceSwitching.into(),
+            code: SassEventCode::SwitchAudioSourceBetweenConnectedDevices.into(),
+            data: smallvec![flags.to_byte()],
+        }
+    }
+
+    /// Acknowledgement for this message.
+    pub fn ack(&self) -> Self {
+        Self {
+            group: EventGroup::Acknowledgement.into(),
+            code: AcknowledgementEventCode::Ack.into(),
+            data: smallvec![self.group, self.code],
+        }
+    }
+
+    /// The firmware version if this is a firmware version event.
+    pub fn firmware_version(&self) -> Option<&str> {
+        let group = EventGroup::from_primitive(self.group);
+        let code = DeviceEventCode::from_primitive(self.code);
+
+        if group != EventGroup::Device || code != DeviceEventCode::FirmwareVersion || self.data.is_empty() {
+            return None;
+        }
+
+        std::str::from_utf8(&self.data).ok()
+    }
+
+    /// The ringing state if this is a ring event.
+    pub fn ring_state(&self) -> Option<RingState> {
+        let group = EventGroup::from_primitive(self.group);
+        let code = DeviceActionEventCode::from_primitive(self.code);
+
+        if group != EventGroup::DeviceAction || code != DeviceActionEventCode::Ring {
+            return None;
+        }
+
+        self.data.first().map(|b| RingState::from_byte(*b))
+    }
+
+    /// Whether this acknowledges the message with the given group and code.
+    /// Returns `Some(true)` for an ACK, `Some(false)` for a NAK, and `None` if
+    /// this is not an acknowledgement for that message.
+    pub fn acknowledges(&self, group: u8, code: u8) -> Option<bool> {
+        if EventGroup::from_primitive(self.group) != EventGroup::Acknowledgement {
+            return None;
+        }
+
+        match AcknowledgementEventCode::from_primitive(self.code) {
+            AcknowledgementEventCode::Ack if self.data.starts_with(&[group, code]) => Some(true),
+            AcknowledgementEventCode::Nak if self.nak_reason(group, code).is_some() => Some(false),
+            AcknowledgementEventCode::Nak if self.data.starts_with(&[group, code]) => Some(false),
+            _ => None,
+        }
+    }
+
+    /// The reason given if this is a NAK for the message with the given group
+    /// and code. Returns `None` for NAKs not specifying a reason.
+    pub fn nak_reason(&self, group: u8, code: u8) -> Option<NakReason> {
+        let ack_group = EventGroup::from_primitive(self.group);
+        let ack_code = AcknowledgementEventCode::from_primitive(self.code);
+
+        if ack_group != EventGroup::Acknowledgement || ack_code != AcknowledgementEventCode::Nak {
+            return None;
+        }
+
+        match self.data[..] {
+            [reason, g, c] if g == group && c == code => Some(NakReason::from_primitive(reason)),
+            _ => None,
+        }
+    }
+
+    /// The switch event if this is a SASS multipoint switch event.
+    pub fn multipoint_switch_event(&self) -> Option<SwitchEvent> {
+        let group = EventGroup::from_primitive(self.group);
+        let code = SassEventCode::from_primitive(self.code);
+
+        if group != EventGroup::SmartAudioSourceSwitching || code != SassEventCode::NotifyMultiPointSwitchEvent {
+            return None;
+        }
+
+        match &self.data[..] {
+            [reason, target, name @ ..] => Some(SwitchEvent {
+                reason: SwitchReason::from_primitive(*reason),
+                target: SwitchTarget::from_primitive(*target),
+                device_name: String::from_utf8_lossy(name).into_owned(),
+            }),
+            _ => None,
+        }
+    }
+
+    /// The GFPS model ID if this is a model ID event.
+    pub fn model_id(&self) -> Option<u32> {
+        let group = EventGroup::from_primitive(self.group);
+        let code = DeviceEventCode::from_primitive(self.code);
+
+        if group != EventGroup::Device || code != DeviceEventCode::ModelId {
+            return None;
+        }
+
+        match self.data[..] {
+            [a, b, c] => Some(u32::from_be_bytes([0, a, b, c])),
+            _ => None,
+        }
+    }
+
+    /// The battery levels if this is a battery info event.
+    pub fn battery_info(&self) -> Option<BatteryLevels> {
+        let group = EventGroup::from_primitive(self.group);
+        let code = DeviceEventCode::from_primitive(self.code);
+
+        if group != EventGroup::Device || code != DeviceEventCode::BatteryInfo {
+            return None;
+        }
+
+        match self.data[..] {
+            [left, right, case] => Some(BatteryLevels {
+                left: BatteryInfo::from_byte(left),
+                right: BatteryInfo::from_byte(right),
+                case: BatteryInfo::from_byte(case),
+            }),
+            _ => None,
+        }
+    }
+
+    /// The remaining battery time in minutes if this is a battery time event.
+    pub fn battery_time(&self) -> Option<u16> {
+        let group = EventGroup::from_primitive(self.group);
+        let code = DeviceEventCode::from_primitive(self.code);
+
+        if group != EventGroup::Device || code != DeviceEventCode::BatteryTime {
+            return None;
+        }
+
+        match self.data[..] {
+            [minutes] => Some(minutes as u16),
+            [hi, lo] => Some(u16::from_be_bytes([hi, lo])),
+            _ => None,
+        }
+    }
+}
+
 
 #[non_exhaustive]
 #[repr(u8)]
@@ -135,6 +312,33 @@ pub enum AcknowledgementEventCode {
     Unknown(u8),
 }
 
+#[non_exhaustive]
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, IntoPrimitive, FromPrimitive)]
+pub enum NakReason {
+    NotSupported = 0x00,
+    DeviceBusy = 0x01,
+    NotAllowedInCurrentState = 0x02,
+    IncorrectMac = 0x03,
+    RedundantAction = 0x04,
+
+    #[num_enum(catch_all)]
+    Unknown(u8),
+}
+
+impl Display for NakReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            NakReason::NotSupported => write!(f, "not supported"),
+            NakReason::DeviceBusy => write!(f, "device busy"),
+            NakReason::NotAllowedInCurrentState => write!(f, "not allowed in current state"),
+            NakReason::IncorrectMac => write!(f, "incorrect message authentication code"),
+            NakReason::RedundantAction => write!(f, "redundant action"),
+            NakReason::Unknown(x) => write!(f, "unknown (0x{x:02x})"),
+        }
+    }
+}
+
 #[non_exhaustive]
 #[repr(u8)]
 #[derive(Debug, Clone, Copy, PartialEq, Eq, IntoPrimitive, FromPrimitive)]
@@ -192,3 +396,234 @@ impl Display for BatteryInfo {
         }
     }
 }
+
+
+/// Battery levels of all components, as reported by a battery info event.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct BatteryLevels {
+    pub left: BatteryInfo,
+    pub right: BatteryInfo,
+    pub case: BatteryInfo,
+}
+
+
+/// Options of a SASS switch request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct SwitchFlags {
+    /// Resume playback on this device after switching.
+    pub resume_playing: bool,
+
+    /// Reject calls on the device audio is switched away from.
+    pub reject_sco: bool,
+
+    /// Disconnect the device audio is switched away from.
+    pub disconnect_other: bool,
+}
+
+impl SwitchFlags {
+    pub fn to_byte(&self) -> u8 {
+        // bit 0 (MSB) selects this device as switch target
+        0x80 | (self.resume_playing as u8) << 6 | (self.reject_sco as u8) << 5 | (self.disconnect_other as u8) << 4
+    }
+}
+
+
+#[non_exhaustive]
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, IntoPrimitive, FromPrimitive)]
+pub enum SwitchReason {
+    Unspecified = 0x00,
+    A2dpStreaming = 0x01,
+    HfpCall = 0x02,
+
+    #[num_enum(catch_all)]
+    Unknown(u8),
+}
+
+#[non_exhaustive]
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, IntoPrimitive, FromPrimitive)]
+pub enum SwitchTarget {
+    ThisDevice = 0x01,
+    OtherDevice = 0x02,
+
+    #[num_enum(catch_all)]
+    Unknown(u8),
+}
+
+/// Switch of the active audio source, as reported by a multipoint switch
+/// event.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SwitchEvent {
+    pub reason: SwitchReason,
+
+    /// The new audio source, relative to the receiver of the event.
+    pub target: SwitchTarget,
+
+    /// Name of the new audio source.
+    pub device_name: String,
+}
+
+
+/// Ringing state of the buds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct RingState {
+    pub right: bool,
+    pub left: bool,
+}
+
+impl RingState {
+    pub const NONE: Self = Self { right: false, left: false };
+    pub const RIGHT: Self = Self { right: true, left: false };
+    pub const LEFT: Self = Self { right: false, left: true };
+    pub const BOTH: Self = Self { right: true, left: true };
+
+    pub fn from_byte(value: u8) -> Self {
+        Self {
+            right: value & 0b01 != 0,
+            left: value & 0b10 != 0,
+        }
+    }
+
+    pub fn to_byte(&self) -> u8 {
+        (self.right as u8) | ((self.left as u8) << 1)
+    }
+
+    pub fn is_ringing(&self) -> bool {
+        self.right || self.left
+    }
+}
+
+impl Display for RingState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match (self.left, self.right) {
+            (false, false) => write!(f, "none"),
+            (false, true) => write!(f, "right"),
+            (true, false) => write!(f, "left"),
+            (true, true) => write!(f, "both"),
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use smallvec::smallvec;
+
+    #[test]
+    fn test_firmware_version() {
+        let msg = Message {
+            group: EventGroup::Device.into(),
+            code: DeviceEventCode::FirmwareVersion.into(),
+            data: smallvec![0x31, 0x2e, 0x32],
+        };
+        assert_eq!(msg.firmware_version(), Some("1.2"));
+
+        // the request itself does not contain a version
+        assert_eq!(Message::firmware_version_request().firmware_version(), None);
+
+        let msg = Message {
+            group: EventGroup::Device.into(),
+            code: DeviceEventCode::ModelId.into(),
+            data: smallvec![0x31, 0x2e, 0x32],
+        };
+        assert_eq!(msg.firmware_version(), None);
+    }
+
+    #[test]
+    fn test_battery_time() {
+        let msg = Message {
+            group: EventGroup::Device.into(),
+            code: DeviceEventCode::BatteryTime.into(),
+            data: smallvec![0x01, 0x2c],
+        };
+        assert_eq!(msg.battery_time(), Some(300));
+
+        let msg = Message { data: smallvec![0x2a], ..msg };
+        assert_eq!(msg.battery_time(), Some(42));
+
+        let msg = Message { data: smallvec![], ..msg };
+        assert_eq!(msg.battery_time(), None);
+    }
+
+    #[test]
+    fn test_model_id() {
+        let msg = Message {
+            group: EventGroup::Device.into(),
+            code: DeviceEventCode::ModelId.into(),
+            data: smallvec![0x12, 0x34, 0x56],
+        };
+        assert_eq!(msg.model_id(), Some(0x123456));
+
+        let msg = Message { data: smallvec![0x12, 0x34], ..msg };
+        assert_eq!(msg.model_id(), None);
+    }
+
+    #[test]
+    fn test_battery_info() {
+        let msg = Message {
+            group: EventGroup::Device.into(),
+            code: DeviceEventCode::BatteryInfo.into(),
+            data: smallvec![0xd5, 0x55, 0x7f],
+        };
+
+        let levels = msg.battery_info().unwrap();
+        assert_eq!(levels.left, BatteryInfo::Known { is_charging: true, percent: 85 });
+        assert_eq!(levels.right, BatteryInfo::Known { is_charging: false, percent: 85 });
+        assert_eq!(levels.case, BatteryInfo::Unknown);
+
+        let msg = Message { data: smallvec![0x55], ..msg };
+        assert_eq!(msg.battery_info(), None);
+    }
+
+    #[test]
+    fn test_ring() {
+        let msg = Message::ring(RingState::BOTH);
+        assert_eq!(msg.data[..], [0x03]);
+        assert_eq!(msg.ring_state(), Some(RingState::BOTH));
+        assert_eq!(Message::ring(RingState::RIGHT).data[..], [0x01]);
+
+        let ack = msg.ack();
+        assert_eq!(ack.acknowledges(msg.group, msg.code), Some(true));
+        assert_eq!(ack.acknowledges(msg.group, 0x02), None);
+        assert_eq!(ack.ring_state(), None);
+
+        let nak = Message {
+            group: EventGroup::Acknowledgement.into(),
+            code: AcknowledgementEventCode::Nak.into(),
+            data: smallvec![msg.group, msg.code],
+        };
+        assert_eq!(nak.acknowledges(msg.group, msg.code), Some(false));
+
+        let nak = Message { data: smallvec![0x02, msg.group, msg.code], ..nak };
+        assert_eq!(nak.acknowledges(msg.group, msg.code), Some(false));
+        assert_eq!(nak.nak_reason(msg.group, msg.code), Some(NakReason::NotAllowedInCurrentState));
+
+        assert!(!RingState::from_byte(0x00).is_ringing());
+        assert_eq!(RingState::from_byte(0x02), RingState::LEFT);
+    }
+
+    #[test]
+    fn test_sass_switch() {
+        let flags = SwitchFlags { resume_playing: true, ..Default::default() };
+        let msg = Message::sass_switch(flags);
+        assert_eq!((msg.group, msg.code), (0x07, 0x30));
+        assert_eq!(msg.data[..], [0xc0]);
+        assert_eq!(Message::sass_switch(SwitchFlags::default()).data[..], [0x80]);
+
+        let event = Message {
+            group: EventGroup::SmartAudioSourceSwitching.into(),
+            code: SassEventCode::NotifyMultiPointSwitchEvent.into(),
+            data: smallvec![0x01, 0x01, b'l', b'a', b'p'],
+        };
+
+        let event = event.multipoint_switch_event().unwrap();
+        assert_eq!(event.reason, SwitchReason::A2dpStreaming);
+        assert_eq!(event.target, SwitchTarget::ThisDevice);
+        assert_eq!(event.device_name, "lap");
+
+        assert_eq!(msg.multipoint_switch_event(), None);
+    }
+}
@@ -0,0 +1,144 @@
+//! Ringing the buds to locate them.
+//!
+//! Ringing is started and stopped via [`set`], which waits for the device to
+//! acknowledge the request. Afterwards, the device reports changes of the
+//! ringing state, e.g. once a bud has been touched and stopped ringing. These
+//! updates can be received via [`next_update`].
+//!
+//! WARNING: Ringing is loud. Do not ring the buds while they are being worn.
+
+use std::time::Duration;
+
+use futures::{Sink, SinkExt, Stream, StreamExt};
+
+use crate::msg::{Message, NakReason, RingState};
+
+
+/// Time to wait for the device to acknowledge a ring request.
+const ACK_TIMEOUT: Duration = Duration::from_secs(1);
+
+
+/// Start or stop ringing the given buds, waiting for the device to
+/// acknowledge the request.
+///
+/// Fails with [`std::io::ErrorKind::Unsupported`] if the device does not
+/// support ringing, with [`std::io::ErrorKind::Other`] if it rejects the
+/// request for another reason, e.g. because it is busy, and with
+/// [`std::io::ErrorKind::TimedOut`] if it does not respond.
+/// Ring state updates received in the meantime are acknowledged and
+/// discarded.
+pub async fn set<S>(stream: &mut S, state: RingState) -> std::io::Result<()>
+where
+    S: Stream<Item = std::io::Result<Message>> + for<'a> Sink<&'a Message, Error = std::io::Error> + Unpin,
+{
+    let request = Message::ring(state);
+    stream.send(&request).await?;
+
+    let ack = async {
+        while let Some(msg) = stream.next().await {
+            let msg = msg?;
+
+            match msg.acknowledges(request.group, request.code) {
+                Some(true) => return Ok(()),
+                Some(false) => {
+                    let reason = msg.nak_reason(request.group, request.code).unwrap_or(NakReason::NotSupported);
+
+                    let kind = match reason {
+                        NakReason::NotSupported => std::io::ErrorKind::Unsupported,
+                        _ => std::io::ErrorKind::Other,
+                    };
+
+                    let err = std::io::Error::new(kind, format!("ring request rejected by device: {reason}"));
+                    return Err(err);
+                },
+                None => {},
+            }
+
+            if msg.ring_state().is_some() {
+                stream.send(&msg.ack()).await?;
+            }
+        }
+
+        let err = std::io::Error::new(std::io::ErrorKind::UnexpectedEof, "stream closed before receiving acknowledgement");
+        Err(err)
+    };
+
+    match tokio::time::timeout(ACK_TIMEOUT, ack).await {
+        Ok(result) => result,
+        Err(_) => {
+            let err = std::io::Error::new(std::io::ErrorKind::TimedOut, "timed out, ringing might be unsupported");
+            Err(err)
+        },
+    }
+}
+
+/// Wait for the next ring state update of the device and acknowledge it.
+///
+/// Other messages received in the meantime are discarded.
+pub async fn next_update<S>(stream: &mut S) -> std::io::Result<RingState>
+where
+    S: Stream<Item = std::io::Result<Message>> + for<'a> Sink<&'a Message, Error = std::io::Error> + Unpin,
+{
+    while let Some(msg) = stream.next().await {
+        let msg = msg?;
+
+        if let Some(state) = msg.ring_state() {
+            stream.send(&msg.ack()).await?;
+            return Ok(state);
+        }
+    }
+
+    let err = std::io::Error::new(std::io::ErrorKind::UnexpectedEof, "stream closed before receiving ring state");
+    Err(err)
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::msg::{AcknowledgementEventCode, Codec, EventGroup};
+
+    #[tokio::test(start_paused = true)]
+    async fn test_set() {
+        let (local, remote) = tokio::io::duplex(256);
+        let mut local = Codec::new().wrap(local);
+        let mut remote = Codec::new().wrap(remote);
+
+        let device = async {
+            let request = remote.next().await.unwrap().unwrap();
+            assert_eq!(request.ring_state(), Some(RingState::RIGHT));
+
+            remote.send(&request.ack()).await.unwrap();
+            remote.send(&Message::ring(RingState::NONE)).await.unwrap();
+
+            // the update must be acknowledged
+            let ack = remote.next().await.unwrap().unwrap();
+            assert_eq!(ack.acknowledges(request.group, request.code), Some(true));
+
+            // rejected as busy
+            let request = remote.next().await.unwrap().unwrap();
+            let nak = Message {
+                group: EventGroup::Acknowledgement.into(),
+                code: AcknowledgementEventCode::Nak.into(),
+                data: smallvec::smallvec![0x01, request.group, request.code],
+            };
+            remote.send(&nak).await.unwrap();
+        };
+
+        let host = async {
+            set(&mut local, RingState::RIGHT).await.unwrap();
+            assert_eq!(next_update(&mut local).await.unwrap(), RingState::NONE);
+
+            let err = set(&mut local, RingState::LEFT).await.unwrap_err();
+            assert_eq!(err.kind(), std::io::ErrorKind::Other);
+            assert!(err.to_string().contains("busy"), "{err}");
+
+            // no response from the device
+            let err = set(&mut local, RingState::BOTH).await.unwrap_err();
+            assert_eq!(err.kind(), std::io::ErrorKind::TimedOut);
+        };
+
+        tokio::join!(device, host);
+    }
+}
@@ -0,0 +1,206 @@
+//! Switching the active audio source via Smart Audio Source Switching (SASS).
+//!
+//! Multipoint devices play audio from one of their connected devices at a
+//! time. [`switch_to`] asks the device to make the sender of the request the
+//! active audio source and waits for the device to confirm the switch via a
+//! multipoint switch event.
+//!
+//! The specification allows devices to require switch requests to be
+//! authenticated with an account key shared during Fast Pair pairing, which
+//! is not supported here. Devices enforcing this reject the request with
+//! [`NakReason::IncorrectMac`].
+
+use std::time::Duration;
+
+use futures::{Sink, SinkExt, Stream, StreamExt};
+
+use crate::msg::{Message, NakReason, SwitchFlags, SwitchTarget};
+
+
+/// Time to wait for the device to acknowledge a switch request.
+const ACK_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Time to wait for the device to report the switch after acknowledging it.
+const SWITCH_TIMEOUT: Duration = Duration::from_secs(5);
+
+
+/// Outcome of a switch request.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SwitchResult {
+    /// Audio has been switched to this device.
+    Switched,
+
+    /// This device already is the active audio source.
+    AlreadyActive,
+
+    /// The device switched audio to another device instead, e.g. because of
+    /// an ongoing call.
+    SwitchedAway { device_name: String },
+
+    /// The device rejected the request.
+    Rejected(NakReason),
+
+    /// The device acknowledged the request, but did not report a switch.
+    Unconfirmed,
+}
+
+
+/// Switch the active audio source to this device, i.e., the sender of the
+/// request, and wait for the device to confirm the switch.
+///
+/// Fails with [`std::io::ErrorKind::TimedOut`] if the device does not respond
+/// to the request at all. Switch events received in the meantime are
+/// acknowledged, other messages are discarded.
+pub async fn switch_to<S>(stream: &mut S, flags: SwitchFlags) -> std::io::Result<SwitchResult>
+where
+    S: Stream<Item = std::io::Result<Message>> + for<'a> Sink<&'a Message, Error = std::io::Error> + Unpin,
+{
+    let request = Message::sass_switch(flags);
+    stream.send(&request).await?;
+
+    let ack = async {
+        while let Some(msg) = stream.next().await {
+            let msg = msg?;
+
+            match msg.acknowledges(request.group, request.code) {
+                Some(true) => return Ok(None),
+                Some(false) => {
+                    let reason = msg.nak_reason(request.group, request.code)
+                        .unwrap_or(NakReason::NotSupported);
+
+                    let result = match reason {
+                        NakReason::RedundantAction => SwitchResult::AlreadyActive,
+                        reason => SwitchResult::Rejected(reason),
+                    };
+
+                    return Ok(Some(result));
+                },
+                None => {},
+            }
+        }
+
+        let err = std::io::Error::new(std::io::ErrorKind::UnexpectedEof, "stream closed before receiving acknowledgement");
+        Err(err)
+    };
+
+    match tokio::time::timeout(ACK_TIMEOUT, ack).await {
+        Ok(Ok(Some(result))) => return Ok(result),
+        Ok(Ok(None)) => {},
+        Ok(Err(err)) => return Err(err),
+        Err(_) => {
+            let err = std::io::Error::new(std::io::ErrorKind::TimedOut, "timed out, audio switching might be unsupported");
+            return Err(err);
+        },
+    }
+
+    let event = async {
+        while let Some(msg) = stream.next().await {
+            let msg = msg?;
+
+            if let Some(event) = msg.multipoint_switch_event() {
+                stream.send(&msg.ack()).await?;
+                return Ok(Some(event));
+            }
+        }
+
+        Ok(None)
+    };
+
+    let result = match tokio::time::timeout(SWITCH_TIMEOUT, event).await {
+        Ok(Ok(Some(event))) => match event.target {
+            SwitchTarget::ThisDevice => SwitchResult::Switched,
+            _ => SwitchResult::SwitchedAway { device_name: event.device_name },
+        },
+        Ok(Ok(None)) | Err(_) => SwitchResult::Unconfirmed,
+        Ok(Err(err)) => return Err(err),
+    };
+
+    Ok(result)
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use smallvec::smallvec;
+
+    use crate::msg::{AcknowledgementEventCode, Codec, EventGroup, SassEventCode};
+
+    fn switch_event(target: SwitchTarget, name: &[u8]) -> Message {
+        let mut data = smallvec![0x01, target.into()];
+        data.extend_from_slice(name);
+
+        Message {
+            group: EventGroup::SmartAudioSourceSwitching.into(),
+            code: SassEventCode::NotifyMultiPointSwitchEvent.into(),
+            data,
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_switch_to() {
+        let (local, remote) = tokio::io::duplex(256);
+        let mut local = Codec::new().wrap(local);
+        let mut remote = Codec::new().wrap(remote);
+
+        let device = async {
+            // switched to this device, with unrelated messages in between
+            let request = remote.next().await.unwrap().unwrap();
+            assert_eq!(request.data[..], [0x80]);
+
+            remote.send(&request.ack()).await.unwrap();
+            remote.send(&Message::ring(Default::default())).await.unwrap();
+            remote.send(&switch_event(SwitchTarget::ThisDevice, b"laptop")).await.unwrap();
+
+            let ack = remote.next().await.unwrap().unwrap();
+            assert_eq!(ack.group, u8::from(EventGroup::Acknowledgement));
+
+            // switched to another device
+            let request = remote.next().await.unwrap().unwrap();
+            remote.send(&request.ack()).await.unwrap();
+            remote.send(&switch_event(SwitchTarget::OtherDevice, b"phone")).await.unwrap();
+            remote.next().await.unwrap().unwrap();
+
+            // rejected
+            let request = remote.next().await.unwrap().unwrap();
+            let nak = Message {
+                group: EventGroup::Acknowledgement.into(),
+                code: AcknowledgementEventCode::Nak.into(),
+                data: smallvec![0x04, request.group, request.code],
+            };
+            remote.send(&nak).await.unwrap();
+
+            let request = remote.next().await.unwrap().unwrap();
+            let nak = Message { data: smallvec![0x03, request.group, request.code], ..nak };
+            remote.send(&nak).await.unwrap();
+
+            // acknowledged, but never confirmed
+            let request = remote.next().await.unwrap().unwrap();
+            remote.send(&request.ack()).await.unwrap();
+
+            // no response at all
+            remote.next().await.unwrap().unwrap();
+            remote
+        };
+
+        let host = async {
+            let flags = SwitchFlags::default();
+
+            assert_eq!(switch_to(&mut local, flags).await.unwrap(), SwitchResult::Switched);
+
+            let result = switch_to(&mut local, flags).await.unwrap();
+            assert_eq!(result, SwitchResult::SwitchedAway { device_name: "phone".into() });
+
+            assert_eq!(switch_to(&mut local, flags).await.unwrap(), SwitchResult::AlreadyActive);
+            assert_eq!(switch_to(&mut local, flags).await.unwrap(), SwitchResult::Rejected(NakReason::IncorrectMac));
+            assert_eq!(switch_to(&mut local, flags).await.unwrap(), SwitchResult::Unconfirmed);
+
+            let err = switch_to(&mut local, flags).await.unwrap_err();
+            assert_eq!(err.kind(), std::io::ErrorKind::TimedOut);
+        };
+
+        // keep the device side open until the host is done
+        let (_remote, ()) = tokio::join!(device, host);
+    }
+}
@@ -0,0 +1,71 @@
+//! Benchmarks for client call dispatch with many pending calls.
+//!
+//! Run with `cargo bench -p maestro --features mock --bench client`.
+
+use std::time::Instant;
+
+use futures::future::join_all;
+
+use maestro::mock::Device;
+use maestro::protocol::codec::Codec;
+use maestro::protocol::types::SoftwareInfo;
+use maestro::protocol::utils;
+use maestro::pwrpc::client::{Client, UnaryRpc};
+
+
+const ROUNDS: usize = 10;
+
+
+async fn run(calls: u32) -> f64 {
+    let device = Device::new();
+    let (stream, server) = device.connect();
+    let server = tokio::task::spawn_local(server.run());
+
+    let mut client = Client::new(Codec::new().wrap(stream));
+    let channel = utils::resolve_channel(&mut client).await.unwrap();
+    let mut handle = client.handle();
+
+    let rpc: UnaryRpc<(), SoftwareInfo> = UnaryRpc::new("maestro_pw.Maestro/GetSoftwareInfo");
+
+    let start = Instant::now();
+
+    let responses: Vec<_> = (0..calls)
+        .map(|id| rpc.call(&mut handle, channel, id, ()).unwrap())
+        .collect();
+
+    let task = join_all(responses.into_iter().map(|mut rsp| async move {
+        rsp.result().await.unwrap()
+    }));
+
+    tokio::select! {
+        res = client.run() => panic!("client terminated unexpectedly: {res:?}"),
+        _ = task => {},
+    }
+
+    let elapsed = start.elapsed();
+
+    drop(client);
+    server.await.unwrap().unwrap();
+
+    elapsed.as_secs_f64()
+}
+
+fn main() {
+    let rt = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+
+    let local = tokio::task::LocalSet::new();
+
+    for calls in [1, 100, 1000, 5000] {
+        let total: f64 = (0..ROUNDS)
+            .map(|_| local.block_on(&rt, run(calls)))
+            .sum();
+
+        let us = total / ROUNDS as f64 * 1e6;
+        let per_call = us / calls as f64;
+
+        println!("client/pending-calls/{calls:<8} {us:>12.1} us/round {per_call:>10.2} us/call");
+    }
+}
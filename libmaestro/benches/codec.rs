@@ -0,0 +1,152 @@
+//! Benchmarks for the codec hot paths: HDLC framing, varint, CRC, and
+//! RpcPacket round-trips.
+//!
+//! Run with `cargo bench -p maestro --bench codec`.
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use bytes::BytesMut;
+
+use prost::Message;
+
+use tokio_util::codec::{Decoder, Encoder};
+
+use maestro::hdlc::{self, crc, varint};
+use maestro::protocol::codec::Codec;
+use maestro::pwrpc::types::{PacketType, RpcPacket};
+
+
+const TARGET: Duration = Duration::from_millis(500);
+
+
+/// Run the given function repeatedly for about `TARGET` and print the time
+/// per iteration and, if `bytes` is non-zero, the throughput.
+fn bench<F: FnMut()>(name: &str, bytes: usize, mut f: F) {
+    // warm-up and calibration
+    let mut iters: u64 = 1;
+    loop {
+        let start = Instant::now();
+        for _ in 0..iters {
+            f();
+        }
+
+        if start.elapsed() >= TARGET / 10 {
+            break;
+        }
+
+        iters *= 2;
+    }
+
+    let iters = iters * 10;
+    let start = Instant::now();
+    for _ in 0..iters {
+        f();
+    }
+    let elapsed = start.elapsed();
+
+    let ns = elapsed.as_nanos() as f64 / iters as f64;
+
+    if bytes > 0 {
+        let mbps = (bytes as f64 * iters as f64) / elapsed.as_secs_f64() / 1e6;
+        println!("{name:<32} {ns:>12.1} ns/iter {mbps:>10.1} MB/s");
+    } else {
+        println!("{name:<32} {ns:>12.1} ns/iter");
+    }
+}
+
+// Note: frames are limited by the decoder buffer size of 4096 bytes.
+fn payload(len: usize) -> Vec<u8> {
+    // include bytes that need escaping
+    (0..len).map(|i| (i * 31 % 256) as u8).collect()
+}
+
+fn packet(len: usize) -> RpcPacket {
+    RpcPacket {
+        r#type: PacketType::Request.into(),
+        channel_id: 19,
+        service_id: 0x625a7b4e,
+        method_id: 0x7ae0dcd3,
+        payload: payload(len),
+        status: 0,
+        call_id: 42,
+    }
+}
+
+
+fn bench_hdlc() {
+    for len in [16, 256, 2048] {
+        let frame = hdlc::Frame {
+            address: 0x2d,
+            control: 0x03,
+            data: payload(len).into(),
+        };
+
+        let encoded = frame.encode_bytes();
+
+        bench(&format!("hdlc/encode/{len}"), len, || {
+            let mut buf = BytesMut::with_capacity(2 * len + 16);
+            black_box(&frame).encode(&mut buf);
+            black_box(buf);
+        });
+
+        bench(&format!("hdlc/decode/{len}"), len, || {
+            let mut buf = BytesMut::from(&encoded[..]);
+            let frame = hdlc::Frame::decode(black_box(&mut buf)).unwrap();
+            black_box(frame);
+        });
+    }
+}
+
+fn bench_varint() {
+    for num in [0x7f, 0x3fff, u32::MAX] {
+        let encoded = varint::encode_vec(num);
+
+        bench(&format!("varint/encode/{num:#x}"), 0, || {
+            black_box(varint::encode_vec(black_box(num)));
+        });
+
+        bench(&format!("varint/decode/{num:#x}"), 0, || {
+            black_box(varint::decode(black_box(&encoded)).unwrap());
+        });
+    }
+}
+
+fn bench_crc() {
+    for len in [16, 4096] {
+        let data = payload(len);
+
+        bench(&format!("crc32/{len}"), len, || {
+            black_box(crc::crc32(black_box(&data)));
+        });
+    }
+}
+
+fn bench_packet() {
+    for len in [16, 256, 2048] {
+        let packet = packet(len);
+        let mut codec = Codec::new();
+
+        bench(&format!("packet/roundtrip/{len}"), len, || {
+            let mut buf = BytesMut::new();
+            codec.encode(black_box(&packet), &mut buf).unwrap();
+
+            let decoded = codec.decode(&mut buf).unwrap().unwrap();
+            black_box(decoded);
+        });
+
+        let encoded = packet.encode_to_vec();
+
+        bench(&format!("packet/protobuf-decode/{len}"), len, || {
+            black_box(RpcPacket::decode(black_box(&encoded[..])).unwrap());
+        });
+    }
+}
+
+
+fn main() {
+    bench_hdlc();
+    bench_varint();
+    bench_crc();
+    bench_packet();
+}
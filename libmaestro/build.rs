@@ -1,7 +1,13 @@
 use std::io::Result;
+use std::path::PathBuf;
 
 fn main() -> Result<()> {
-    prost_build::compile_protos(&["proto/pw.rpc.packet.proto"], &["proto/"])?;
-    prost_build::compile_protos(&["proto/maestro_pw.proto"], &["proto/"])?;
+    let out_dir = PathBuf::from(std::env::var("OUT_DIR").unwrap());
+
+    prost_build::Config::new()
+        .type_attribute(".", "#[cfg_attr(feature = \"serde\", derive(serde::Serialize, serde::Deserialize))]")
+        .file_descriptor_set_path(out_dir.join("file_descriptor_set.bin"))
+        .compile_protos(&["proto/pw.rpc.packet.proto", "proto/maestro_pw.proto"], &["proto/"])?;
+
     Ok(())
 }
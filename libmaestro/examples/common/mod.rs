@@ -10,6 +10,7 @@ use futures::StreamExt;
 use maestro::pwrpc::Error;
 use maestro::pwrpc::client::Client;
 use maestro::pwrpc::types::RpcPacket;
+use maestro::retry::Backoff;
 
 
 pub async fn run_client<S, E>(mut client: Client<S>) -> Result<()>
@@ -56,17 +57,16 @@ pub async fn connect_maestro_rfcomm(session: &Session, dev: &Device) -> Result<S
 }
 
 async fn try_connect_profile(dev: &Device) -> Result<()> {
-    const RETRY_TIMEOUT: Duration = Duration::from_secs(1);
     const MAX_TRIES: u32 = 3;
+    const RETRY: Backoff = Backoff::fixed(Duration::from_secs(1), Some(MAX_TRIES + 1));
 
-    let mut i = 0;
+    let mut delays = RETRY.delays();
     while let Err(err) = dev.connect_profile(&maestro::UUID).await {
-        if i >= MAX_TRIES { return Err(err.into()) }
-        i += 1;
+        let Some(delay) = delays.next() else { return Err(err.into()) };
 
-        tracing::warn!(error=?err, "connecting to profile failed, trying again ({}/{})", i, MAX_TRIES);
+        tracing::warn!(error=?err, "connecting to profile failed, trying again ({}/{})", delays.retries(), MAX_TRIES);
 
-        tokio::time::sleep(RETRY_TIMEOUT).await;
+        tokio::time::sleep(delay).await;
     }
 
     tracing::debug!(address=%dev.address(), "maestro profile connected");
@@ -0,0 +1,306 @@
+//! Interactive protocol exploration.
+//!
+//! Connects to the device and reads commands from stdin, sending arbitrary
+//! protobuf-encoded requests to the given service and method. All traffic is
+//! recorded to a capture file (see `maestro::capture`). Responses are decoded
+//! as the known message type if the method is known, and schema-less
+//! otherwise.
+//!
+//! Commands:
+//!   call <target> [<hex-payload>]     unary call, wait for the response
+//!   sub <target> [<hex-payload>]      server-stream call, print all items
+//!   cancel <call-id>                  cancel a server-stream call
+//!   channel [<channel>]               show or change the channel
+//!   hash <name>                       print the hash of a service or method
+//!   quit
+//!
+//! Targets are given either by name, e.g. `maestro_pw.Maestro/GetSoftwareInfo`,
+//! or by hashes, e.g. `0x7ede71ea/0x7199fa44`.
+//!
+//! Usage:
+//!   cargo run --example maestro_explore -- <bluetooth-device-address> [<capture-file>]
+
+// uses its own client loop instead of run_client()
+#[allow(dead_code)]
+mod common;
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::str::FromStr;
+
+use anyhow::{bail, Context, Result};
+use bluer::{Address, Session};
+use futures::StreamExt;
+use futures::channel::mpsc;
+
+use maestro::capture::Recorder;
+use maestro::protocol::codec::Codec;
+use maestro::protocol::types;
+use maestro::protocol::utils;
+use maestro::pwrpc::client::{Client, ClientHandle, Request};
+use maestro::pwrpc::id;
+use maestro::pwrpc::utils::EncodedMessage;
+
+
+/// First call ID to use, chosen to not collide with the IDs used by the
+/// library services.
+const FIRST_CALL_ID: u32 = 0x1000;
+
+
+/// Decoder for the response of a method.
+type Decoder = fn(&[u8]) -> Option<String>;
+
+/// Known methods and how to decode their responses.
+const METHODS: &[(&str, Decoder)] = &[
+    ("maestro_pw.Maestro/GetSoftwareInfo", decode::<types::SoftwareInfo>),
+    ("maestro_pw.Maestro/GetHardwareInfo", decode::<types::HardwareInfo>),
+    ("maestro_pw.Maestro/SubscribeRuntimeInfo", decode::<types::RuntimeInfo>),
+    ("maestro_pw.Maestro/WriteSetting", decode::<()>),
+    ("maestro_pw.Maestro/ReadSetting", decode::<types::SettingsRsp>),
+    ("maestro_pw.Maestro/SubscribeToSettingsChanges", decode::<types::SettingsRsp>),
+    ("maestro_pw.Maestro/SubscribeToOobeActions", decode::<types::OobeActionRsp>),
+    ("maestro_pw.Dosimeter/FetchDailySummaries", decode::<types::DosimeterSummary>),
+    ("maestro_pw.Dosimeter/SubscribeToLiveDb", decode::<types::DosimeterLiveDbMsg>),
+    ("maestro_pw.Multipoint/SubscribeToQuietModeStatus", decode::<types::QuietModeStatusEvent>),
+];
+
+fn decode<M: prost::Message + Default>(data: &[u8]) -> Option<String> {
+    M::decode(data).ok().map(|msg| format!("{msg:#?}"))
+}
+
+
+#[derive(Debug, Clone, Copy)]
+struct Target {
+    service: u32,
+    method: u32,
+}
+
+impl Target {
+    fn parse(target: &str) -> Result<Self> {
+        let (service, method) = target.split_once('/')
+            .context("target must be of the form <service>/<method>")?;
+
+        let hash = |s: &str| match s.strip_prefix("0x") {
+            Some(hex) => u32::from_str_radix(hex, 16).context("invalid hash"),
+            None => Ok(id::hash(s)),
+        };
+
+        Ok(Self { service: hash(service)?, method: hash(method)? })
+    }
+
+    /// Name and response decoder of the method, if known.
+    fn known(&self) -> Option<(&'static str, Decoder)> {
+        METHODS.iter()
+            .find(|(path, _)| {
+                let path = id::Path::new(*path);
+                path.service().hash() == self.service && path.method().hash() == self.method
+            })
+            .copied()
+    }
+
+    fn name(&self) -> String {
+        match self.known() {
+            Some((name, _)) => name.to_owned(),
+            None => format!("{:#010x}/{:#010x}", self.service, self.method),
+        }
+    }
+
+    /// Describe a response of this method, decoding it as well as possible.
+    fn describe(&self, data: &[u8]) -> String {
+        if let Some((_, decode)) = self.known()
+            && let Some(text) = decode(data)
+        {
+            return text;
+        }
+
+        let msg = EncodedMessage { data: data.to_vec() };
+        match msg.annotate() {
+            Some(text) if !text.is_empty() => format!("{msg:?}\n{text}"),
+            _ => format!("{msg:?}"),
+        }
+    }
+}
+
+fn parse_hex(hex: &str) -> Result<Vec<u8>> {
+    let hex: String = hex.chars().filter(|c| !c.is_whitespace()).collect();
+
+    if !hex.len().is_multiple_of(2) {
+        bail!("hex payload must have an even number of digits");
+    }
+
+    (0..hex.len()).step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i+2], 16).context("invalid hex payload"))
+        .collect()
+}
+
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() -> Result<(), anyhow::Error> {
+    tracing_subscriber::fmt::init();
+
+    // handle command line arguments
+    let addr = std::env::args().nth(1).expect("need device address as argument");
+    let addr = Address::from_str(&addr)?;
+
+    let path = std::env::args().nth(2).unwrap_or_else(|| "maestro-explore.txt".to_owned());
+
+    // set up session
+    let session = Session::new().await?;
+    let adapter = session.default_adapter().await?;
+
+    println!("Using adapter '{}'", adapter.name());
+    println!("Recording traffic to '{path}'");
+
+    // get device
+    let dev = adapter.device(addr)?;
+
+    println!("Connecting to Maestro profile");
+    let stream = common::connect_maestro_rfcomm(&session, &dev).await?;
+
+    println!("Profile connected");
+
+    // set up stream for RPC communication, recording all traffic
+    let stream = Recorder::new(stream, File::create(&path)?);
+    let stream = Codec::new().wrap(stream);
+
+    // set up RPC client
+    let mut client = Client::new(stream);
+    let handle = client.handle();
+
+    // retreive the channel numer
+    let channel = utils::resolve_channel(&mut client).await?;
+
+    println!("Channel resolved: {channel}");
+    println!();
+
+    // read commands on a separate thread, stdin is blocking
+    let (tx, rx) = mpsc::unbounded();
+    std::thread::spawn(move || {
+        for line in std::io::stdin().lines() {
+            let Ok(line) = line else { break };
+
+            if tx.unbounded_send(line).is_err() {
+                break;
+            }
+        }
+    });
+
+    tokio::select! {
+        res = client.run() => {
+            match res {
+                Ok(_) => bail!("client terminated unexpectedly without error"),
+                Err(e) => Err(e.into()),
+            }
+        },
+        res = explore(handle, channel, rx) => res,
+    }
+}
+
+async fn explore(mut handle: ClientHandle, mut channel: u32, mut lines: mpsc::UnboundedReceiver<String>) -> Result<()> {
+    let mut call_id = FIRST_CALL_ID;
+    let mut subscriptions: HashMap<u32, tokio::task::JoinHandle<()>> = HashMap::new();
+
+    while let Some(line) = lines.next().await {
+        let mut args = line.split_whitespace();
+
+        let result = match (args.next(), args.next()) {
+            (None, _) => continue,
+            (Some("quit"), _) => break,
+            (Some("hash"), Some(name)) => {
+                println!("{name}: {:#010x}", id::hash(name));
+                Ok(())
+            },
+            (Some("channel"), None) => {
+                println!("channel: {channel}");
+                Ok(())
+            },
+            (Some("channel"), Some(value)) => match value.parse() {
+                Ok(value) => {
+                    channel = value;
+                    Ok(())
+                },
+                Err(_) => Err(anyhow::anyhow!("invalid channel '{value}'")),
+            },
+            (Some("cancel"), Some(id)) => {
+                match id.parse().ok().and_then(|id: u32| subscriptions.remove(&id)) {
+                    Some(task) => {
+                        task.abort();
+                        Ok(())
+                    },
+                    None => Err(anyhow::anyhow!("no subscription with call ID '{id}'")),
+                }
+            },
+            (Some(cmd @ ("call" | "sub")), Some(target)) => {
+                let payload = parse_hex(&args.collect::<String>());
+
+                match (Target::parse(target), payload) {
+                    (Ok(target), Ok(payload)) => {
+                        call_id += 1;
+
+                        let req = Request {
+                            channel_id: channel,
+                            service_id: target.service,
+                            method_id: target.method,
+                            call_id,
+                            message: EncodedMessage { data: payload },
+                        };
+
+                        if cmd == "call" {
+                            call(&mut handle, target, req).await
+                        } else {
+                            subscribe(&mut handle, target, req).map(|task| {
+                                subscriptions.insert(call_id, task);
+                            })
+                        }
+                    },
+                    (Err(err), _) | (_, Err(err)) => Err(err),
+                }
+            },
+            _ => Err(anyhow::anyhow!("unknown command, see the documentation of this example")),
+        };
+
+        if let Err(err) = result {
+            println!("error: {err:#}");
+        }
+    }
+
+    for task in subscriptions.into_values() {
+        task.abort();
+    }
+
+    Ok(())
+}
+
+async fn call(handle: &mut ClientHandle, target: Target, req: Request<EncodedMessage>) -> Result<()> {
+    println!("[{}] calling {}", req.call_id, target.name());
+
+    let mut rsp = handle.call_unary::<_, EncodedMessage>(req)?;
+    let msg = rsp.result().await?;
+
+    println!("{}", target.describe(&msg.data));
+    Ok(())
+}
+
+fn subscribe(handle: &mut ClientHandle, target: Target, req: Request<EncodedMessage>)
+    -> Result<tokio::task::JoinHandle<()>>
+{
+    let call_id = req.call_id;
+    println!("[{call_id}] subscribing to {}", target.name());
+
+    let mut rsp = handle.call_server_stream::<_, EncodedMessage>(req)?;
+
+    let task = tokio::spawn(async move {
+        let mut stream = rsp.stream();
+
+        while let Some(item) = stream.next().await {
+            match item {
+                Ok(msg) => println!("[{call_id}] {}", target.describe(&msg.data)),
+                Err(err) => println!("[{call_id}] error: {err}"),
+            }
+        }
+
+        println!("[{call_id}] stream terminated");
+    });
+
+    Ok(task)
+}
@@ -6,6 +6,7 @@
 mod common;
 
 use std::str::FromStr;
+use std::time::Duration;
 
 use bluer::{Address, Session};
 use futures::StreamExt;
@@ -13,9 +14,14 @@ use futures::StreamExt;
 use maestro::protocol::codec::Codec;
 use maestro::protocol::utils;
 use maestro::pwrpc::client::{Client, ClientHandle};
+use maestro::retry::Backoff;
 use maestro::service::{MaestroService, DosimeterService};
 
 
+/// Delay before reconnecting after the connection has been reset.
+const RECONNECT: Backoff = Backoff::fixed(Duration::from_millis(500), None);
+
+
 #[tokio::main(flavor = "current_thread")]
 async fn main() -> Result<(), anyhow::Error> {
     tracing_subscriber::fmt::init();
@@ -53,6 +59,8 @@ async fn main() -> Result<(), anyhow::Error> {
     println!();
 
     // try to reconnect if connection is reset
+    let mut delays = RECONNECT.delays();
+
     loop {
         println!("Connecting to Maestro profile");
         let stream = common::connect_maestro_rfcomm(&session, &dev).await?;
@@ -84,14 +92,16 @@ async fn main() -> Result<(), anyhow::Error> {
                         tracing::error!("client task terminated with error");
 
                         let cause = e.root_cause();
-                        if let Some(cause) = cause.downcast_ref::<std::io::Error>() {
-                            if cause.raw_os_error() == Some(104) {
-                                // The Pixel Buds Pro can hand off processing between each
-                                // other. On a switch, the connection is reset. Wait a bit
-                                // and then try to reconnect.
+                        if let Some(cause) = cause.downcast_ref::<std::io::Error>()
+                            && cause.raw_os_error() == Some(104)
+                        {
+                            // The Pixel Buds Pro can hand off processing between each
+                            // other. On a switch, the connection is reset. Wait a bit
+                            // and then try to reconnect.
+                            if let Some(delay) = delays.next() {
                                 println!();
                                 println!("Connection reset. Attempting to reconnect...");
-                                tokio::time::sleep(std::time::Duration::from_millis(500)).await;
+                                tokio::time::sleep(delay).await;
                                 continue;
                             }
                         }
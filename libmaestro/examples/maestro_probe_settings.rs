@@ -0,0 +1,93 @@
+//! Example for probing raw setting IDs on the Pixel Buds Pro via the Maestro
+//! service, e.g. to discover settings supported by newer firmware.
+//!
+//! Usage:
+//!   cargo run --example maestro_probe_settings -- <bluetooth-device-address> [<max-id>]
+
+mod common;
+
+use std::str::FromStr;
+
+use anyhow::bail;
+use bluer::{Address, Session};
+
+use maestro::protocol::codec::Codec;
+use maestro::protocol::utils;
+use maestro::pwrpc::client::{Client, ClientHandle};
+use maestro::service::MaestroService;
+use maestro::service::settings::SettingId;
+
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() -> Result<(), anyhow::Error> {
+    tracing_subscriber::fmt::init();
+
+    // handle command line arguments
+    let addr = std::env::args().nth(1).expect("need device address as argument");
+    let addr = Address::from_str(&addr)?;
+
+    let max_id = match std::env::args().nth(2) {
+        Some(max_id) => max_id.parse()?,
+        None => 64,
+    };
+
+    // set up session
+    let session = Session::new().await?;
+    let adapter = session.default_adapter().await?;
+
+    println!("Using adapter '{}'", adapter.name());
+
+    // get device
+    let dev = adapter.device(addr)?;
+
+    println!("Connecting to Maestro profile");
+    let stream = common::connect_maestro_rfcomm(&session, &dev).await?;
+
+    println!("Profile connected");
+
+    // set up stream for RPC communication
+    let codec = Codec::new();
+    let stream = codec.wrap(stream);
+
+    // set up RPC client
+    let mut client = Client::new(stream);
+    let handle = client.handle();
+
+    // retreive the channel numer
+    let channel = utils::resolve_channel(&mut client).await?;
+
+    let exec_task = common::run_client(client);
+    let probe_task = probe_settings(handle, channel, max_id);
+
+    tokio::select! {
+        res = exec_task => {
+            match res {
+                Ok(_) => bail!("client terminated unexpectedly without error"),
+                Err(e) => Err(e),
+            }
+        },
+        res = probe_task => res,
+    }
+}
+
+async fn probe_settings(handle: ClientHandle, channel: u32, max_id: i32) -> anyhow::Result<()> {
+    let mut service = MaestroService::new(handle, channel);
+
+    println!();
+    println!("Probing setting IDs 1 to {max_id}:");
+
+    for id in 1..=max_id {
+        let name = match SettingId::from(id) {
+            SettingId::Unknown(_) => "unknown",
+            id => id.as_str(),
+        };
+
+        match service.probe_setting(id).await {
+            Ok(Some(value)) => println!("  {id:>3} ({name}): {value:?}"),
+            Ok(None) => println!("  {id:>3} ({name}): <value not decodable>"),
+            Err(err) => println!("  {id:>3} ({name}): error: {err}"),
+        }
+    }
+
+    Ok(())
+}
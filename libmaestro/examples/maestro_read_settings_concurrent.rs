@@ -0,0 +1,119 @@
+//! Example for reading all known settings concurrently via the Maestro service
+//! and comparing the latency to reading them one after the other.
+//!
+//! Usage:
+//!   cargo run --example maestro_read_settings_concurrent -- <bluetooth-device-address> [parallelism]
+
+mod common;
+
+use std::str::FromStr;
+use std::time::Instant;
+
+use anyhow::bail;
+use bluer::{Address, Session};
+
+use maestro::protocol::codec::Codec;
+use maestro::protocol::utils;
+use maestro::pwrpc::client::{Client, ClientHandle};
+use maestro::service::MaestroService;
+use maestro::service::settings::SettingId;
+
+
+const SETTINGS: [SettingId; 17] = [
+    SettingId::AutoOtaEnable,
+    SettingId::OhdEnable,
+    SettingId::OobeIsFinished,
+    SettingId::GestureEnable,
+    SettingId::DiagnosticsEnable,
+    SettingId::OobeMode,
+    SettingId::GestureControl,
+    SettingId::MultipointEnable,
+    SettingId::AncrGestureLoop,
+    SettingId::CurrentAncrState,
+    SettingId::OttsMode,
+    SettingId::VolumeEqEnable,
+    SettingId::CurrentUserEq,
+    SettingId::VolumeAsymmetry,
+    SettingId::SumToMono,
+    SettingId::VolumeExposureNotifications,
+    SettingId::SpeechDetection,
+];
+
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() -> Result<(), anyhow::Error> {
+    tracing_subscriber::fmt::init();
+
+    // handle command line arguments
+    let addr = std::env::args().nth(1).expect("need device address as argument");
+    let addr = Address::from_str(&addr)?;
+
+    let parallelism = match std::env::args().nth(2) {
+        Some(n) => n.parse()?,
+        None => 4,
+    };
+
+    // set up session
+    let session = Session::new().await?;
+    let adapter = session.default_adapter().await?;
+
+    println!("Using adapter '{}'", adapter.name());
+
+    // get device
+    let dev = adapter.device(addr)?;
+
+    println!("Connecting to Maestro profile");
+    let stream = common::connect_maestro_rfcomm(&session, &dev).await?;
+
+    println!("Profile connected");
+
+    // set up stream for RPC communication
+    let codec = Codec::new();
+    let stream = codec.wrap(stream);
+
+    // set up RPC client
+    let mut client = Client::new(stream);
+    let handle = client.handle();
+
+    // retreive the channel numer
+    let channel = utils::resolve_channel(&mut client).await?;
+
+    let exec_task = common::run_client(client);
+    let settings_task = read_settings(handle, channel, parallelism);
+
+    tokio::select! {
+        res = exec_task => {
+            match res {
+                Ok(_) => bail!("client terminated unexpectedly without error"),
+                Err(e) => Err(e),
+            }
+        },
+        res = settings_task => res,
+    }
+}
+
+async fn read_settings(handle: ClientHandle, channel: u32, parallelism: usize) -> anyhow::Result<()> {
+    let mut service = MaestroService::new(handle, channel);
+
+    let start = Instant::now();
+    service.read_settings(&SETTINGS).await;
+    let sequential = start.elapsed();
+
+    let start = Instant::now();
+    let values = service.read_settings_concurrent(&SETTINGS, parallelism).await;
+    let concurrent = start.elapsed();
+
+    println!();
+    for (id, value) in values {
+        match value {
+            Ok(value) => println!("  {id:?}: {value:?}"),
+            Err(err) => println!("  {id:?}: error: {err}"),
+        }
+    }
+
+    println!();
+    println!("Sequential:                {:>8.1} ms", sequential.as_secs_f64() * 1000.0);
+    println!("Concurrent (parallelism {parallelism}): {:>8.1} ms", concurrent.as_secs_f64() * 1000.0);
+
+    Ok(())
+}
@@ -0,0 +1,279 @@
+//! Soak test for connection stability.
+//!
+//! Keeps the runtime info and settings subscriptions open, reconnecting
+//! whenever the connection is lost, and records every connect, disconnect,
+//! handoff, and decode error with timestamps to a report file. Prints summary
+//! statistics when done, i.e., after the given number of hours or on Ctrl-C.
+//!
+//! Usage:
+//!   cargo run --example maestro_soak -- <bluetooth-device-address> [<report-file>] [<hours>]
+
+// reconnects itself instead of using run_client()
+#[allow(dead_code)]
+mod common;
+
+use std::fs::File;
+use std::io::Write;
+use std::str::FromStr;
+use std::time::{Duration, Instant, SystemTime};
+
+use anyhow::Result;
+use bluer::{Address, Device, Session};
+use futures::StreamExt;
+
+use maestro::hdlc::codec::{Stats, StatsHandle};
+use maestro::protocol::codec::Codec;
+use maestro::protocol::utils;
+use maestro::pwrpc::client::{Client, ClientHandle, Event};
+use maestro::service::MaestroService;
+
+
+/// Time to wait before reconnecting after the connection has been lost.
+const RECONNECT_DELAY: Duration = Duration::from_secs(2);
+
+
+#[derive(Debug, Default)]
+struct Summary {
+    connections: u32,
+    failed_connects: u32,
+    resets: u32,
+    disconnects: u32,
+    handoffs: u32,
+    crc_errors: u64,
+    decode_errors: u64,
+    runtime_infos: u64,
+    settings_changes: u64,
+    uptime: Duration,
+    longest: Duration,
+}
+
+struct Report {
+    file: File,
+    start: Instant,
+    summary: Summary,
+}
+
+impl Report {
+    fn new(file: File) -> Self {
+        Self { file, start: Instant::now(), summary: Summary::default() }
+    }
+
+    /// Record an event to the report file and print it.
+    fn record(&mut self, kind: &str, details: impl std::fmt::Display) {
+        let time = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap_or_default();
+        let line = format!("{:.3} +{:.3} {kind} {details}", time.as_secs_f64(), self.start.elapsed().as_secs_f64());
+
+        println!("{line}");
+
+        if let Err(err) = writeln!(self.file, "{line}") {
+            tracing::warn!(error=%err, "failed to write report");
+        }
+    }
+
+    /// Record decode errors that occurred since the given statistics.
+    fn record_stats(&mut self, last: &mut Stats, stats: &StatsHandle) {
+        let current = stats.get();
+
+        let crc_errors = current.crc_errors - last.crc_errors;
+        let errors = current.errors - last.errors;
+
+        if crc_errors > 0 || errors > 0 {
+            self.record("decode-error", format!("crc={crc_errors} other={errors}"));
+
+            self.summary.crc_errors += crc_errors;
+            self.summary.decode_errors += errors;
+        }
+
+        *last = current;
+    }
+
+    fn print_summary(&self) {
+        let s = &self.summary;
+        let total = self.start.elapsed();
+
+        let mean = match s.connections {
+            0 => Duration::ZERO,
+            n => s.uptime / n,
+        };
+
+        println!();
+        println!("Summary:");
+        println!("  duration:           {:.0} s", total.as_secs_f64());
+        println!("  connections:        {}", s.connections);
+        println!("  failed connects:    {}", s.failed_connects);
+        println!("  connection resets:  {}", s.resets);
+        println!("  other disconnects:  {}", s.disconnects);
+        println!("  handoffs:           {}", s.handoffs);
+        println!("  checksum errors:    {}", s.crc_errors);
+        println!("  decode errors:      {}", s.decode_errors);
+        println!("  runtime info items: {}", s.runtime_infos);
+        println!("  settings changes:   {}", s.settings_changes);
+        println!("  connected:          {:.1}% of the time", 100.0 * s.uptime.as_secs_f64() / total.as_secs_f64());
+        println!("  mean connection:    {:.0} s", mean.as_secs_f64());
+        println!("  longest connection: {:.0} s", s.longest.as_secs_f64());
+    }
+}
+
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() -> Result<(), anyhow::Error> {
+    tracing_subscriber::fmt::init();
+
+    // handle command line arguments
+    let addr = std::env::args().nth(1).expect("need device address as argument");
+    let addr = Address::from_str(&addr)?;
+
+    let path = std::env::args().nth(2).unwrap_or_else(|| "maestro-soak.txt".to_owned());
+
+    let duration = match std::env::args().nth(3) {
+        Some(hours) => Duration::from_secs_f64(hours.parse::<f64>()? * 3600.0),
+        None => Duration::MAX,
+    };
+
+    // set up session
+    let session = Session::new().await?;
+    let adapter = session.default_adapter().await?;
+
+    println!("Using adapter '{}'", adapter.name());
+    println!("Writing report to '{path}'");
+    println!();
+
+    let dev = adapter.device(addr)?;
+    let mut report = Report::new(File::create(&path)?);
+
+    report.record("start", format!("address={addr}"));
+
+    tokio::select! {
+        _ = soak(&session, &dev, &mut report) => {},
+        _ = tokio::time::sleep(duration) => {},
+        sig = tokio::signal::ctrl_c() => sig?,
+    }
+
+    report.record("stop", "");
+    report.print_summary();
+
+    Ok(())
+}
+
+async fn soak(session: &Session, dev: &Device, report: &mut Report) {
+    loop {
+        if let Err(err) = run_connection(session, dev, report).await {
+            tracing::debug!(error=?err, "connection terminated");
+        }
+
+        tokio::time::sleep(RECONNECT_DELAY).await;
+    }
+}
+
+async fn run_connection(session: &Session, dev: &Device, report: &mut Report) -> Result<()> {
+    let stream = match common::connect_maestro_rfcomm(session, dev).await {
+        Ok(stream) => stream,
+        Err(err) => {
+            report.record("connect-failed", format!("{err:#}"));
+            report.summary.failed_connects += 1;
+            return Err(err);
+        },
+    };
+
+    // set up stream for RPC communication
+    let codec = Codec::new();
+    let stats = codec.stats();
+    let stream = codec.wrap(stream);
+
+    // set up RPC client
+    let mut client = Client::new(stream);
+    let handle = client.handle();
+
+    let connected = Instant::now();
+    let mut last = Stats::default();
+
+    let result = match utils::resolve_channel(&mut client).await {
+        Ok(channel) => {
+            report.record("connected", format!("channel={channel}"));
+            report.summary.connections += 1;
+
+            tokio::select! {
+                res = client.run() => res.map_err(anyhow::Error::from),
+                res = run_subscriptions(handle, channel, report, &stats, &mut last) => res,
+            }
+        },
+        Err(err) => Err(err.into()),
+    };
+
+    report.record_stats(&mut last, &stats);
+
+    let uptime = connected.elapsed();
+    report.summary.uptime += uptime;
+    report.summary.longest = report.summary.longest.max(uptime);
+
+    let reset = result.as_ref().err()
+        .and_then(|e| e.root_cause().downcast_ref::<std::io::Error>())
+        .is_some_and(|e| e.raw_os_error() == Some(104));
+
+    match &result {
+        Err(_) if reset => {
+            report.record("reset", format!("uptime={:.0}s", uptime.as_secs_f64()));
+            report.summary.resets += 1;
+        },
+        Err(err) => {
+            report.record("disconnected", format!("uptime={:.0}s error={err:#}", uptime.as_secs_f64()));
+            report.summary.disconnects += 1;
+        },
+        Ok(()) => {
+            report.record("disconnected", format!("uptime={:.0}s", uptime.as_secs_f64()));
+            report.summary.disconnects += 1;
+        },
+    }
+
+    let _ = client.terminate().await;
+    result
+}
+
+/// Keep the subscriptions open, re-subscribing whenever the device moves to a
+/// new channel.
+async fn run_subscriptions(
+    mut handle: ClientHandle,
+    mut channel: u32,
+    report: &mut Report,
+    stats: &StatsHandle,
+    last: &mut Stats,
+) -> Result<()> {
+    let mut events = handle.subscribe_events()?;
+
+    loop {
+        let mut service = MaestroService::new(handle.clone(), channel);
+
+        let mut runtime = service.subscribe_to_runtime_info()?;
+        let mut runtime = runtime.stream();
+
+        let mut changes = service.subscribe_to_settings_changes()?;
+        let mut changes = changes.stream();
+
+        let mut interval = tokio::time::interval(Duration::from_secs(1));
+
+        channel = loop {
+            tokio::select! {
+                info = runtime.next() => {
+                    info.ok_or_else(|| anyhow::anyhow!("runtime info stream terminated"))??;
+                    report.summary.runtime_infos += 1;
+                },
+                rsp = changes.next() => {
+                    rsp.ok_or_else(|| anyhow::anyhow!("settings stream terminated"))??;
+                    report.summary.settings_changes += 1;
+                },
+                evt = events.next() => match evt {
+                    Some(Event::ChannelChanged { old, new }) => {
+                        report.record("handoff", format!("old={old} new={new}"));
+                        report.summary.handoffs += 1;
+                        break new;
+                    },
+                    Some(_) => {},
+                    None => anyhow::bail!("client event stream terminated"),
+                },
+                _ = interval.tick() => {
+                    report.record_stats(last, stats);
+                },
+            }
+        };
+    }
+}
@@ -0,0 +1,237 @@
+//! Capture of raw device communication.
+//!
+//! Captures record the raw bytes exchanged with the device in both
+//! directions. They are stored in a simple line-based text format, where each
+//! line contains a single chunk of data prefixed by its direction (`>` for
+//! host-to-device and `<` for device-to-host), encoded as hex. Empty lines
+//! and lines starting with `#` are ignored:
+//!
+//! ```text
+//! # pbpctrl capture
+//! < 7e2f03...7e
+//! > 7e2d03...7e
+//! ```
+//!
+//! Captures can be recorded with [`Recorder`] and replayed against a client
+//! with `mock::replay`.
+
+use std::io::Write;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+
+/// Direction of a captured chunk of data.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    /// Data sent from the host to the device.
+    Host,
+
+    /// Data sent from the device to the host.
+    Device,
+}
+
+impl Direction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Direction::Host => ">",
+            Direction::Device => "<",
+        }
+    }
+}
+
+
+/// A single chunk of captured data.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    pub direction: Direction,
+    pub data: Vec<u8>,
+}
+
+
+/// Error returned when parsing a capture fails.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    pub line: usize,
+    pub message: &'static str,
+}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "invalid capture at line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+
+/// A captured session.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Capture {
+    pub entries: Vec<Entry>,
+}
+
+impl Capture {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn parse(text: &str) -> Result<Self, ParseError> {
+        let mut entries = Vec::new();
+
+        for (i, line) in text.lines().enumerate() {
+            let line = line.trim();
+            let error = |message| ParseError { line: i + 1, message };
+
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let (direction, data) = if let Some(data) = line.strip_prefix('>') {
+                (Direction::Host, data)
+            } else if let Some(data) = line.strip_prefix('<') {
+                (Direction::Device, data)
+            } else {
+                return Err(error("expected '>' or '<'"));
+            };
+
+            let data = decode_hex(data.trim())
+                .ok_or_else(|| error("invalid hex data"))?;
+
+            entries.push(Entry { direction, data });
+        }
+
+        Ok(Self { entries })
+    }
+}
+
+impl std::fmt::Display for Capture {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for entry in &self.entries {
+            write_entry(f, entry.direction, &entry.data)?;
+        }
+
+        Ok(())
+    }
+}
+
+fn write_entry(w: &mut impl std::fmt::Write, direction: Direction, data: &[u8]) -> std::fmt::Result {
+    write!(w, "{} ", direction.as_str())?;
+
+    for byte in data {
+        write!(w, "{byte:02x}")?;
+    }
+
+    writeln!(w)
+}
+
+fn decode_hex(data: &str) -> Option<Vec<u8>> {
+    let data = data.as_bytes();
+
+    if !data.len().is_multiple_of(2) {
+        return None;
+    }
+
+    data.chunks(2)
+        .map(|c| u8::from_str_radix(std::str::from_utf8(c).ok()?, 16).ok())
+        .collect()
+}
+
+
+/// Stream wrapper recording all data passing through it.
+///
+/// Each read and write is written as separate entry to the given sink in the
+/// capture text format.
+pub struct Recorder<S, W> {
+    stream: S,
+    sink: W,
+}
+
+impl<S, W: Write> Recorder<S, W> {
+    pub fn new(stream: S, sink: W) -> Self {
+        Self { stream, sink }
+    }
+
+    pub fn into_inner(self) -> S {
+        self.stream
+    }
+
+    fn record(&mut self, direction: Direction, data: &[u8]) {
+        if data.is_empty() {
+            return;
+        }
+
+        let mut line = String::new();
+        let _ = write_entry(&mut line, direction, data);
+
+        if let Err(err) = self.sink.write_all(line.as_bytes()) {
+            tracing::warn!(error=%err, "failed to write capture");
+        }
+    }
+}
+
+impl<S: AsyncRead + Unpin, W: Write + Unpin> AsyncRead for Recorder<S, W> {
+    fn poll_read(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<std::io::Result<()>> {
+        let this = self.get_mut();
+        let start = buf.filled().len();
+
+        let res = Pin::new(&mut this.stream).poll_read(cx, buf);
+
+        if let Poll::Ready(Ok(())) = res {
+            this.record(Direction::Device, &buf.filled()[start..]);
+        }
+
+        res
+    }
+}
+
+impl<S: AsyncWrite + Unpin, W: Write + Unpin> AsyncWrite for Recorder<S, W> {
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<std::io::Result<usize>> {
+        let this = self.get_mut();
+
+        let res = Pin::new(&mut this.stream).poll_write(cx, buf);
+
+        if let Poll::Ready(Ok(n)) = res {
+            this.record(Direction::Host, &buf[..n]);
+        }
+
+        res
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        let this = self.get_mut();
+
+        if let Err(err) = this.sink.flush() {
+            tracing::warn!(error=%err, "failed to flush capture");
+        }
+
+        Pin::new(&mut this.stream).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.get_mut().stream).poll_shutdown(cx)
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let text = "# comment\n\n< 7e01\n> 7E02ff\n";
+        let capture = Capture::parse(text).unwrap();
+
+        assert_eq!(capture.entries, [
+            Entry { direction: Direction::Device, data: vec![0x7e, 0x01] },
+            Entry { direction: Direction::Host, data: vec![0x7e, 0x02, 0xff] },
+        ]);
+
+        assert_eq!(capture.to_string(), "< 7e01\n> 7e02ff\n");
+
+        assert_eq!(Capture::parse("< 7e0\n").unwrap_err().line, 1);
+        assert_eq!(Capture::parse("\n? 00\n").unwrap_err().line, 2);
+    }
+}
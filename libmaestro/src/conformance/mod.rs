@@ -0,0 +1,88 @@
+//! Conformance tests for the wire format against reference vectors.
+//!
+//! The vectors in `vectors/` are derived from the pw_hdlc and pw_rpc wire
+//! format specifications, independently of this crate's implementation. Each
+//! line contains whitespace-separated fields as described in the header of
+//! the respective file, with `-` denoting empty data.
+
+use bytes::BytesMut;
+
+use prost::Message;
+
+use crate::hdlc::{crc, varint, Frame};
+use crate::pwrpc::types::RpcPacket;
+
+
+fn vectors(text: &str) -> impl Iterator<Item = Vec<&str>> {
+    text.lines()
+        .filter(|l| !l.is_empty() && !l.starts_with('#'))
+        .map(|l| l.split_whitespace().collect())
+}
+
+fn hex(s: &str) -> Vec<u8> {
+    if s == "-" {
+        return Vec::new();
+    }
+
+    (0..s.len()).step_by(2)
+        .map(|i| u8::from_str_radix(&s[i..i+2], 16).unwrap())
+        .collect()
+}
+
+
+#[test]
+fn test_crc32() {
+    for v in vectors(include_str!("vectors/crc32.txt")) {
+        let data = hex(v[0]);
+        let expected = u32::from_str_radix(v[1], 16).unwrap();
+
+        assert_eq!(crc::crc32(&data), expected, "data: {}", v[0]);
+    }
+}
+
+#[test]
+fn test_varint() {
+    for v in vectors(include_str!("vectors/varint.txt")) {
+        let value: u32 = v[0].parse().unwrap();
+        let encoded = hex(v[1]);
+
+        assert_eq!(&varint::encode_vec(value)[..], &encoded[..], "value: {value}");
+        assert_eq!(varint::decode(&encoded).unwrap(), (value, encoded.len()), "value: {value}");
+    }
+}
+
+#[test]
+fn test_hdlc_frame() {
+    for v in vectors(include_str!("vectors/hdlc.txt")) {
+        let frame = Frame {
+            address: v[0].parse().unwrap(),
+            control: u8::from_str_radix(v[1], 16).unwrap(),
+            data: hex(v[2]).into(),
+        };
+        let encoded = hex(v[3]);
+
+        assert_eq!(&frame.encode_bytes()[..], &encoded[..], "frame: {frame:?}");
+
+        let mut buf = BytesMut::from(&encoded[..]);
+        assert_eq!(Frame::decode(&mut buf).unwrap(), Some(frame));
+    }
+}
+
+#[test]
+fn test_rpc_packet() {
+    for v in vectors(include_str!("vectors/rpc_packet.txt")) {
+        let packet = RpcPacket {
+            r#type: v[0].parse().unwrap(),
+            channel_id: v[1].parse().unwrap(),
+            service_id: u32::from_str_radix(v[2], 16).unwrap(),
+            method_id: u32::from_str_radix(v[3], 16).unwrap(),
+            call_id: v[4].parse().unwrap(),
+            status: v[5].parse().unwrap(),
+            payload: hex(v[6]),
+        };
+        let encoded = hex(v[7]);
+
+        assert_eq!(packet.encode_to_vec(), encoded, "packet: {packet:?}");
+        assert_eq!(RpcPacket::decode(&encoded[..]).unwrap(), packet);
+    }
+}
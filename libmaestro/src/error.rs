@@ -0,0 +1,126 @@
+//! Application-level error type.
+//!
+//! Covers the full path from looking up the device to talking to its
+//! services, so that applications embedding the connection logic can match on
+//! the kind of failure. Converts from and to the lower-level error types of
+//! this crate.
+
+use crate::pwrpc::{self, Status};
+
+
+type BoxError = Box<dyn std::error::Error + Send + Sync + 'static>;
+
+
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Error {
+    /// No (compatible) device could be found.
+    Discovery(String),
+
+    /// Registering or connecting the Bluetooth profile failed.
+    Profile(BoxError),
+
+    /// I/O error on the established connection.
+    Transport(std::io::Error),
+
+    /// Received data does not conform to the protocol.
+    Protocol(BoxError),
+
+    /// RPC call failed. Use [`Error::status`] to get the status code.
+    Rpc(pwrpc::Error),
+}
+
+impl Error {
+    pub fn discovery(message: impl Into<String>) -> Self {
+        Self::Discovery(message.into())
+    }
+
+    pub fn profile(error: impl Into<BoxError>) -> Self {
+        Self::Profile(error.into())
+    }
+
+    pub fn protocol(error: impl Into<BoxError>) -> Self {
+        Self::Protocol(error.into())
+    }
+
+    /// Status code of a failed RPC call, if this is [`Error::Rpc`].
+    pub fn status(&self) -> Option<Status> {
+        match self {
+            Self::Rpc(err) => Some(err.code()),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(error: std::io::Error) -> Self {
+        Self::Transport(error)
+    }
+}
+
+impl From<prost::DecodeError> for Error {
+    fn from(error: prost::DecodeError) -> Self {
+        Self::Protocol(error.into())
+    }
+}
+
+impl From<pwrpc::Error> for Error {
+    fn from(error: pwrpc::Error) -> Self {
+        Self::Rpc(error)
+    }
+}
+
+impl From<Error> for pwrpc::Error {
+    fn from(error: Error) -> Self {
+        match error {
+            Error::Discovery(message) => pwrpc::Error::not_found(message),
+            Error::Profile(err) => pwrpc::Error::extend(Status::Unavailable, "failed to connect profile", err),
+            Error::Transport(err) => err.into(),
+            Error::Protocol(err) => pwrpc::Error::extend(Status::DataLoss, "protocol violation", err),
+            Error::Rpc(err) => err,
+        }
+    }
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Discovery(message) => write!(f, "{message}"),
+            Self::Profile(err) => write!(f, "failed to connect profile: {err}"),
+            Self::Transport(err) => write!(f, "transport error: {err}"),
+            Self::Protocol(err) => write!(f, "protocol error: {err}"),
+            Self::Rpc(err) => write!(f, "rpc error: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Discovery(_) => None,
+            Self::Profile(err) | Self::Protocol(err) => Some(&**err),
+            Self::Transport(err) => Some(err),
+            Self::Rpc(err) => Some(err),
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_conversion() {
+        let err = Error::from(pwrpc::Error::unimplemented("nope"));
+        assert_eq!(err.status(), Some(Status::Unimplemented));
+
+        let err = Error::from(std::io::Error::from(std::io::ErrorKind::ConnectionReset));
+        assert!(matches!(err, Error::Transport(_)));
+        assert_eq!(err.status(), None);
+        assert_eq!(pwrpc::Error::from(err).code(), Status::Unavailable);
+
+        let err = pwrpc::Error::from(Error::discovery("no compatible device found"));
+        assert_eq!(err.code(), Status::NotFound);
+    }
+}
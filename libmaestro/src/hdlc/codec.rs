@@ -1,4 +1,6 @@
-use super::{decoder, encoder, Frame};
+use std::sync::{Arc, Mutex};
+
+use super::{decoder, encoder, Frame, FrameRef};
 
 use bytes::BytesMut;
 
@@ -25,25 +27,148 @@ impl From<decoder::Error> for DecoderError {
 }
 
 
-#[derive(Debug, Default)]
+/// Number of initially received bytes kept in [`Stats::head`].
+const STATS_HEAD_LEN: usize = 32;
+
+/// Default initial capacity of the read and write buffers of wrapped streams.
+pub const DEFAULT_BUFFER_CAPACITY: usize = 4096;
+
+
+/// Statistics of the data received by a [`Codec`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Stats {
+    /// Total number of bytes received.
+    pub bytes: u64,
+
+    /// Number of successfully decoded frames.
+    pub frames: u64,
+
+    /// Number of frames discarded due to an invalid checksum.
+    pub crc_errors: u64,
+
+    /// Number of other decoding errors.
+    pub errors: u64,
+
+    /// Size of the largest decoded frame payload.
+    pub max_frame: usize,
+
+    /// Largest amount of received data buffered at once, i.e., the
+    /// high-watermark of the read buffer.
+    pub max_buffered: usize,
+
+    /// The first bytes received, for diagnostics.
+    pub head: Vec<u8>,
+}
+
+/// Shared handle to the statistics of a [`Codec`].
+#[derive(Debug, Clone, Default)]
+pub struct StatsHandle {
+    inner: Arc<Mutex<Stats>>,
+}
+
+impl StatsHandle {
+    /// Get the current statistics.
+    pub fn get(&self) -> Stats {
+        self.inner.lock().unwrap().clone()
+    }
+}
+
+
+#[derive(Debug)]
 pub struct Codec {
     dec: decoder::Decoder,
+    stats: StatsHandle,
+    buffer_capacity: usize,
 }
 
 impl Codec {
     pub fn new() -> Self {
-        Self { dec: decoder::Decoder::new() }
+        Self::with_capacity(decoder::DEFAULT_CAPACITY)
     }
 
     pub fn with_capacity(cap: usize) -> Self {
-        Self { dec: decoder::Decoder::with_capacity(cap) }
+        Self {
+            dec: decoder::Decoder::with_capacity(cap),
+            stats: StatsHandle::default(),
+            buffer_capacity: DEFAULT_BUFFER_CAPACITY,
+        }
+    }
+
+    /// Use a frame buffer of the given capacity, limiting the size of frames
+    /// that can be decoded.
+    pub fn with_frame_capacity(self, cap: usize) -> Self {
+        Self { dec: decoder::Decoder::with_capacity(cap), ..self }
+    }
+
+    /// Use the given initial capacity for the read and write buffers of
+    /// wrapped streams.
+    pub fn with_buffer_capacity(self, cap: usize) -> Self {
+        Self { buffer_capacity: cap, ..self }
+    }
+
+    pub fn buffer_capacity(&self) -> usize {
+        self.buffer_capacity
+    }
+
+    /// Handle to the statistics of the received data, which remains valid
+    /// after wrapping the codec.
+    pub fn stats(&self) -> StatsHandle {
+        self.stats.clone()
     }
 
     pub fn wrap<T>(self, io: T) -> Framed<T, Codec>
     where
         T: AsyncRead + AsyncWrite,
     {
-        Framed::with_capacity(io, self, 4096 as _)
+        let cap = self.buffer_capacity;
+        Framed::with_capacity(io, self, cap)
+    }
+
+    /// Decode the next frame and pass it to the given function, without
+    /// allocating a buffer for its data.
+    pub fn decode_with<T>(&mut self, src: &mut BytesMut, f: impl FnOnce(FrameRef<'_>) -> T) -> Option<T> {
+        let mut stats = self.stats.inner.lock().unwrap();
+
+        let n = STATS_HEAD_LEN.saturating_sub(stats.head.len()).min(src.len());
+        stats.head.extend_from_slice(&src[..n]);
+        stats.max_buffered = stats.max_buffered.max(src.len());
+
+        // Errors only discard the offending data. Continue with the remaining
+        // data, as it may already contain a full frame and returning None here
+        // would wait for more data before trying to decode that.
+        while !src.is_empty() {
+            let len = src.len();
+            let result = self.dec.process_ref(src);
+
+            stats.bytes += (len - src.len()) as u64;
+
+            match result {
+                Ok(Some(frame)) => {
+                    tracing::trace!(address=frame.address, control=frame.control, len=frame.data.len(), "decoded frame");
+
+                    stats.frames += 1;
+                    stats.max_frame = stats.max_frame.max(frame.data.len());
+                    return Some(f(frame));
+                },
+                Ok(None) => {},
+                Err(decoder::Error::InvalidChecksum) => {
+                    tracing::warn!(error=?decoder::Error::InvalidChecksum, "error decoding data");
+                    stats.crc_errors += 1;
+                },
+                Err(e) => {
+                    tracing::warn!(error=?e, "error decoding data");
+                    stats.errors += 1;
+                },
+            }
+        }
+
+        None
+    }
+}
+
+impl Default for Codec {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
@@ -51,22 +176,117 @@ impl tokio_util::codec::Encoder<&Frame> for Codec {
     type Error = std::io::Error;
 
     fn encode(&mut self, frame: &Frame, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        tracing::trace!(address=frame.address, control=frame.control, len=frame.data.len(), "encoding frame");
         encoder::encode(dst, frame);
         Ok(())
     }
 }
 
+impl tokio_util::codec::Encoder<FrameRef<'_>> for Codec {
+    type Error = std::io::Error;
+
+    fn encode(&mut self, frame: FrameRef<'_>, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        tracing::trace!(address=frame.address, control=frame.control, len=frame.data.len(), "encoding frame");
+        encoder::encode_ref(dst, &frame);
+        Ok(())
+    }
+}
+
 impl tokio_util::codec::Decoder for Codec {
     type Item = Frame;
     type Error = std::io::Error;
 
     fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
-        match self.dec.process(src) {
-            Ok(x) => Ok(x),
-            Err(e) => {
-                tracing::warn!("error decoding data: {e:?}");
-                Ok(None)
-            },
-        }
+        Ok(self.decode_with(src, |frame| frame.to_frame()))
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use tokio_util::codec::Decoder;
+
+    #[test]
+    fn test_decode_after_garbage() {
+        let frame = Frame {
+            address: 0x010203,
+            control: 0x03,
+            data: vec![0x05, 0x06, 0x07].into(),
+        };
+
+        let mut buf = BytesMut::new();
+        encoder::encode(&mut buf, &frame);
+
+        // garbage frame followed by a valid frame in the same buffer
+        let mut data = BytesMut::from(&[0x7e, 0x12, 0x34, 0x56, 0x7e][..]);
+        data.extend_from_slice(&buf);
+
+        let mut codec = Codec::new();
+        assert_eq!(codec.decode(&mut data).unwrap(), Some(frame));
+        assert!(data.is_empty());
+    }
+
+    #[test]
+    fn test_stats() {
+        let frame = Frame {
+            address: 0x010203,
+            control: 0x03,
+            data: vec![0x05, 0x06, 0x07].into(),
+        };
+
+        let mut valid = BytesMut::new();
+        encoder::encode(&mut valid, &frame);
+
+        // corrupt the payload to cause a checksum mismatch
+        let mut corrupt = valid.clone();
+        let i = corrupt.len() - 6;
+        corrupt[i] ^= 0x01;
+
+        // both frames share the flag in between
+        let mut data = corrupt;
+        data.extend_from_slice(&valid[1..]);
+
+        let len = data.len();
+        let head = data[..STATS_HEAD_LEN.min(len)].to_vec();
+
+        let mut codec = Codec::new();
+        let stats = codec.stats();
+
+        assert_eq!(codec.decode(&mut data).unwrap(), Some(frame));
+
+        let stats = stats.get();
+        assert_eq!(stats.bytes, len as u64);
+        assert_eq!(stats.frames, 1);
+        assert_eq!(stats.crc_errors, 1);
+        assert_eq!(stats.errors, 0);
+        assert_eq!(stats.max_frame, 3);
+        assert_eq!(stats.max_buffered, len);
+        assert_eq!(stats.head, head);
+    }
+
+    #[test]
+    fn test_frame_capacity() {
+        let frame = Frame {
+            address: 0x010203,
+            control: 0x03,
+            data: vec![0x05, 0x06, 0x07].into(),
+        };
+
+        let mut valid = BytesMut::new();
+        encoder::encode(&mut valid, &frame);
+
+        // both frames share the flag in between
+        let mut data = valid.clone();
+        data.extend_from_slice(&valid[1..]);
+
+        // frames exceeding the buffer are discarded
+        let mut codec = Codec::new().with_frame_capacity(8);
+        let stats = codec.stats();
+
+        assert_eq!(codec.decode(&mut data).unwrap(), None);
+        assert_eq!(stats.get().errors, 2);
+        assert_eq!(stats.get().frames, 0);
     }
 }
@@ -3,7 +3,7 @@ use bytes::{Buf, BytesMut};
 use super::consts;
 use super::crc;
 use super::varint;
-use super::Frame;
+use super::{Frame, FrameRef};
 
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -27,11 +27,17 @@ impl From<varint::DecodeError> for Error {
 }
 
 
+/// Default capacity of the frame buffer, i.e., the maximum size of frames that
+/// can be decoded.
+pub const DEFAULT_CAPACITY: usize = 4096;
+
+
 #[derive(Debug)]
 pub struct Decoder {
     buf: Vec<u8>,
     state: (State, EscState),
     current_frame_size: usize,
+    complete: bool,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -48,7 +54,7 @@ enum EscState {
 
 impl Decoder {
     pub fn new() -> Self {
-        Self::with_capacity(4096)
+        Self::with_capacity(DEFAULT_CAPACITY)
     }
 
     pub fn with_capacity(cap: usize) -> Self {
@@ -56,10 +62,28 @@ impl Decoder {
             buf: Vec::with_capacity(cap),
             state: (State::Discard, EscState::Normal),
             current_frame_size: 0,
+            complete: false,
         }
     }
 
+    /// Capacity of the frame buffer.
+    pub fn capacity(&self) -> usize {
+        self.buf.capacity()
+    }
+
     pub fn process(&mut self, buf: &mut BytesMut) -> Result<Option<Frame>, Error> {
+        Ok(self.process_ref(buf)?.map(|frame| frame.to_frame()))
+    }
+
+    /// Like [`Self::process`], but returns the frame borrowing its data from
+    /// the internal buffer instead of allocating a new one. The buffer is
+    /// re-used for the next frame.
+    pub fn process_ref(&mut self, buf: &mut BytesMut) -> Result<Option<FrameRef<'_>>, Error> {
+        // release the buffer of the previously returned frame
+        if self.complete {
+            self.reset();
+        }
+
         if buf.is_empty() {
             return Ok(None);
         }
@@ -135,7 +159,7 @@ impl Decoder {
         }
     }
 
-    fn decode_buffered(&mut self) -> Result<Option<Frame>, Error> {
+    fn decode_buffered(&mut self) -> Result<Option<FrameRef<'_>>, Error> {
         // validate minimum frame size
         if self.buf.len() < 6 {
             self.reset();
@@ -143,6 +167,13 @@ impl Decoder {
             return Err(Error::InvalidFrame);
         }
 
+        // check for overflow, the checksum cannot be validated in this case
+        if self.current_frame_size > self.buf.len() {
+            self.reset();
+            self.state.0 = State::Frame;        // the next frame may already start
+            return Err(Error::BufferOverflow);
+        }
+
         // validate checksum
         let crc_actual = crc::crc32(&self.buf[..self.buf.len()-4]);
         let crc_expect = self.buf[self.buf.len()-4..].try_into().unwrap();
@@ -154,12 +185,6 @@ impl Decoder {
             return Err(Error::InvalidChecksum);
         }
 
-        // check for overflow
-        if self.current_frame_size > self.buf.len() {
-            self.reset();
-            return Err(Error::BufferOverflow);
-        }
-
         // decode address
         let (address, n) = varint::decode(&self.buf)?;
 
@@ -169,17 +194,16 @@ impl Decoder {
             return Err(Error::InvalidFrame);
         }
 
-        // get control byte and data
-        let control = self.buf[n];
-        let data = self.buf[n+1..self.buf.len()-4].into();
+        // get control byte and data, keep the buffer until the next call
+        self.state = (State::Discard, EscState::Normal);
+        self.complete = true;
 
-        let frame = Frame {
+        let frame = FrameRef {
             address,
-            control,
-            data,
+            control: self.buf[n],
+            data: &self.buf[n+1..self.buf.len()-4],
         };
 
-        self.reset();
         Ok(Some(frame))
     }
 
@@ -195,6 +219,7 @@ impl Decoder {
         self.buf.clear();
         self.state = (State::Discard, EscState::Normal);
         self.current_frame_size = 0;
+        self.complete = false;
     }
 }
 
@@ -1,6 +1,6 @@
 use bytes::{BufMut, BytesMut};
 
-use super::{consts, crc::Crc32, varint, Frame};
+use super::{consts, crc::Crc32, varint, Frame, FrameRef};
 
 
 struct ByteEscape<B: BufMut> {
@@ -81,6 +81,10 @@ impl Encoder<&mut BytesMut> {
 
 
 pub fn encode(buf: &mut BytesMut, frame: &Frame) {
+    encode_ref(buf, &frame.into())
+}
+
+pub fn encode_ref(buf: &mut BytesMut, frame: &FrameRef<'_>) {
     Encoder::new(buf)
         .reserve(frame.data.len() + 8)              // reserve at least data-size + min-frame-size
         .flag()                                     // flag
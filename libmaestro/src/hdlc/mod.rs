@@ -32,3 +32,37 @@ impl Frame {
         encoder::encode_bytes(self)
     }
 }
+
+
+/// Frame borrowing its data, e.g. from the internal buffer of a decoder or a
+/// re-used encoding buffer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FrameRef<'a> {
+    pub address: u32,
+    pub control: u8,
+    pub data: &'a [u8],
+}
+
+impl FrameRef<'_> {
+    pub fn to_frame(&self) -> Frame {
+        Frame {
+            address: self.address,
+            control: self.control,
+            data: self.data.into(),
+        }
+    }
+
+    pub fn encode(&self, buf: &mut BytesMut) {
+        encoder::encode_ref(buf, self)
+    }
+}
+
+impl<'a> From<&'a Frame> for FrameRef<'a> {
+    fn from(frame: &'a Frame) -> Self {
+        Self {
+            address: frame.address,
+            control: frame.control,
+            data: &frame.data,
+        }
+    }
+}
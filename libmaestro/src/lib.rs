@@ -9,7 +9,20 @@ use uuid::{uuid, Uuid};
 /// Defined as `25e97ff7-24ce-4c4c-8951-f764a708f7b5`.
 pub const UUID: Uuid = uuid!("25e97ff7-24ce-4c4c-8951-f764a708f7b5");
 
+pub mod capture;
+pub mod error;
 pub mod hdlc;
+pub mod models;
+pub mod prelude;
 pub mod protocol;
 pub mod pwrpc;
+pub mod retry;
 pub mod service;
+
+pub use error::Error;
+
+#[cfg(any(all(test, feature = "client"), feature = "mock"))]
+pub mod mock;
+
+#[cfg(test)]
+mod conformance;
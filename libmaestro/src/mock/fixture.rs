@@ -0,0 +1,49 @@
+//! Shared setup for tests running a client against the mock device.
+
+use std::future::Future;
+
+use futures::{Sink, Stream};
+
+use tokio::io::DuplexStream;
+use tokio_util::codec::Framed;
+
+use crate::protocol::codec::Codec;
+use crate::protocol::utils;
+use crate::pwrpc::Error;
+use crate::pwrpc::client::Client;
+use crate::pwrpc::types::RpcPacket;
+
+use super::Device;
+
+
+/// Client connected to the mock device.
+pub type TestClient = Client<Framed<DuplexStream, Codec>>;
+
+
+/// Connect a new client to the device and resolve its channel. The server
+/// side of the connection is run in the background.
+pub async fn connect(device: &Device) -> (TestClient, u32) {
+    let (stream, server) = device.connect();
+    tokio::spawn(server.run());
+
+    let mut client = Client::new(Codec::new().wrap(stream));
+    let channel = utils::resolve_channel(&mut client).await.unwrap();
+
+    (client, channel)
+}
+
+/// Run the client until the given task completes, panicking if the client
+/// terminates first.
+pub async fn run<S, E, F>(client: &mut Client<S>, task: F) -> F::Output
+where
+    S: Sink<RpcPacket>,
+    S: Stream<Item = Result<RpcPacket, E>> + Unpin,
+    Error: From<S::Error>,
+    Error: From<E>,
+    F: Future,
+{
+    tokio::select! {
+        res = client.run() => panic!("client terminated unexpectedly: {res:?}"),
+        output = task => output,
+    }
+}
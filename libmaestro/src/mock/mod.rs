@@ -0,0 +1,775 @@
+//! Mock device implementing the device side of the Maestro protocol.
+//!
+//! The mock communicates over an in-memory duplex stream and can be used for
+//! testing clients without hardware. It provides a fake settings store,
+//! runtime information, settings change, and gesture streams, and allows
+//! injecting faults such as connection resets, garbage data, or RPC errors.
+//!
+//! ```ignore
+//! let device = mock::Device::new();
+//! let (stream, server) = device.connect();
+//!
+//! tokio::spawn(server.run());
+//!
+//! let stream = Codec::new().wrap(stream);
+//! let mut client = Client::new(stream);
+//! // ...
+//! ```
+
+use std::sync::{Arc, Mutex};
+
+use bytes::{Buf, BytesMut};
+
+use futures::StreamExt;
+use futures::channel::mpsc;
+
+use prost::Message;
+
+use tokio::io::{AsyncWriteExt, DuplexStream};
+use tokio_util::codec::{Encoder, FramedRead};
+
+use crate::hdlc;
+use crate::protocol::addr;
+use crate::protocol::types::{
+    self, read_setting_msg, settings_rsp, write_setting_msg, BatteryInfo, DeviceBatteryInfo,
+    FirmwareInfo, FirmwareVersion, HardwareInfo, OobeAction, OobeActionRsp, PlacementInfo,
+    ReadSettingMsg, RuntimeInfo, SerialNumbers, SettingsRsp, SoftwareInfo, WriteSettingMsg,
+};
+use crate::pwrpc::Status;
+use crate::pwrpc::id::Path;
+use crate::pwrpc::types::{PacketType, RpcPacket};
+use crate::service::settings::{SettingId, SettingValue};
+
+pub mod replay;
+
+#[cfg(test)]
+pub(crate) mod fixture;
+
+
+/// Call ID used by the device for the unsolicited software info response
+/// sent after connecting, which is used for channel resolution.
+const UNSOLICITED_CALL_ID: u32 = 0xffffffff;
+
+const BUFFER_SIZE: usize = 4096;
+
+const GET_SOFTWARE_INFO: &str = "maestro_pw.Maestro/GetSoftwareInfo";
+const GET_HARDWARE_INFO: &str = "maestro_pw.Maestro/GetHardwareInfo";
+const SUBSCRIBE_RUNTIME_INFO: &str = "maestro_pw.Maestro/SubscribeRuntimeInfo";
+const WRITE_SETTING: &str = "maestro_pw.Maestro/WriteSetting";
+const READ_SETTING: &str = "maestro_pw.Maestro/ReadSetting";
+const SUBSCRIBE_SETTINGS_CHANGES: &str = "maestro_pw.Maestro/SubscribeToSettingsChanges";
+const SUBSCRIBE_OOBE_ACTIONS: &str = "maestro_pw.Maestro/SubscribeToOobeActions";
+
+
+/// Faults that can be injected into the connection.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Fault {
+    /// Close the connection.
+    Reset,
+
+    /// Send the given raw bytes.
+    Garbage(Vec<u8>),
+}
+
+
+/// State of the mock device.
+#[derive(Debug, Clone)]
+pub struct State {
+    pub software_info: SoftwareInfo,
+    pub hardware_info: HardwareInfo,
+    pub runtime_info: RuntimeInfo,
+    pub settings: Vec<SettingValue>,
+
+    /// Methods that fail with the given status when called, optionally only
+    /// for the given number of calls.
+    pub errors: Vec<(String, Status, Option<u32>)>,
+
+    /// Faults to inject when the given method is called, instead of handling
+    /// the call.
+    pub faults: Vec<(String, Fault)>,
+
+    /// Whether the device announces itself after connecting.
+    pub announce: bool,
+}
+
+impl State {
+    pub fn setting(&self, id: SettingId) -> Option<&SettingValue> {
+        self.settings.iter().find(|s| s.id() == id)
+    }
+
+    fn set_setting(&mut self, value: SettingValue) {
+        match self.settings.iter_mut().find(|s| s.id() == value.id()) {
+            Some(setting) => *setting = value,
+            None => self.settings.push(value),
+        }
+    }
+}
+
+impl Default for State {
+    fn default() -> Self {
+        let version = |v: &str| Some(FirmwareVersion {
+            unknown: String::new(),
+            version_string: v.to_owned(),
+        });
+
+        let software_info = SoftwareInfo {
+            firmware: Some(FirmwareInfo {
+                case: version("1.0.0"),
+                right: version("1.0.0"),
+                left: version("1.0.0"),
+            }),
+            ..Default::default()
+        };
+
+        let hardware_info = HardwareInfo {
+            serial_number: Some(SerialNumbers {
+                case: "case-0000".to_owned(),
+                right: "right-0000".to_owned(),
+                left: "left-0000".to_owned(),
+            }),
+            ..Default::default()
+        };
+
+        let battery = |level| Some(DeviceBatteryInfo { level, state: 1 });
+
+        let runtime_info = RuntimeInfo {
+            battery_info: Some(BatteryInfo {
+                case: battery(80),
+                left: battery(90),
+                right: battery(90),
+            }),
+            placement: Some(PlacementInfo {
+                right_bud_in_case: false,
+                left_bud_in_case: false,
+            }),
+            ..Default::default()
+        };
+
+        let settings = vec![
+            SettingValue::AutoOtaEnable(true),
+            SettingValue::OhdEnable(true),
+            SettingValue::GestureEnable(true),
+            SettingValue::MultipointEnable(true),
+            SettingValue::CurrentAncrState(crate::service::settings::AncState::Active),
+            SettingValue::VolumeEqEnable(true),
+        ];
+
+        Self {
+            software_info,
+            hardware_info,
+            runtime_info,
+            settings,
+            errors: Vec::new(),
+            faults: Vec::new(),
+            announce: true,
+        }
+    }
+}
+
+
+#[derive(Debug, Clone)]
+enum Control {
+    RuntimeInfo(RuntimeInfo),
+    SettingChanged(SettingValue),
+    Gesture(OobeAction),
+    Fault(Fault),
+    Handoff(u32),
+}
+
+
+/// Handle to the mock device.
+#[derive(Debug, Clone)]
+pub struct Device {
+    state: Arc<Mutex<State>>,
+    channel: u32,
+    control: Arc<Mutex<Vec<mpsc::UnboundedSender<Control>>>>,
+}
+
+impl Device {
+    /// Create a mock device with default state.
+    pub fn new() -> Self {
+        Self::with_state(State::default())
+    }
+
+    pub fn with_state(state: State) -> Self {
+        Self {
+            state: Arc::new(Mutex::new(state)),
+            channel: addr::channel_id(addr::Peer::MaestroA, addr::Peer::LeftBtCore).unwrap(),
+            control: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+
+    /// Channel on which the device serves requests.
+    pub fn channel(&self) -> u32 {
+        self.channel
+    }
+
+    /// Create a new connection to the device. Returns the client side of the
+    /// stream and the server, which needs to be run to handle the connection.
+    pub fn connect(&self) -> (DuplexStream, Server) {
+        let (client, server) = tokio::io::duplex(BUFFER_SIZE);
+        let (control_tx, control_rx) = mpsc::unbounded();
+
+        self.control.lock().unwrap().push(control_tx);
+
+        let server = Server {
+            stream: server,
+            state: self.state.clone(),
+            channel: self.channel,
+            control: control_rx,
+        };
+
+        (client, server)
+    }
+
+    /// Access the current device state.
+    pub fn state(&self) -> std::sync::MutexGuard<'_, State> {
+        self.state.lock().unwrap()
+    }
+
+    pub fn setting(&self, id: SettingId) -> Option<SettingValue> {
+        self.state().setting(id).cloned()
+    }
+
+    /// Change a setting on the device side, e.g. as if changed via gestures,
+    /// and notify subscribers.
+    pub fn change_setting(&self, value: SettingValue) {
+        self.state().set_setting(value.clone());
+        self.send(Control::SettingChanged(value));
+    }
+
+    /// Update the runtime information and notify subscribers.
+    pub fn update_runtime_info(&self, info: RuntimeInfo) {
+        self.state().runtime_info = info;
+        self.send(Control::RuntimeInfo(info));
+    }
+
+    /// Perform a gesture on the buds. Gestures are only reported to
+    /// subscribers while the OOBE mode setting is enabled.
+    pub fn gesture(&self, action: OobeAction) {
+        self.send(Control::Gesture(action));
+    }
+
+    /// Make the given method (e.g. `maestro_pw.Maestro/ReadSetting`) fail
+    /// with the given status.
+    pub fn fail(&self, method: impl Into<String>, status: Status) {
+        self.state().errors.push((method.into(), status, None));
+    }
+
+    /// Make the given method fail with the given status for the next `count`
+    /// calls.
+    pub fn fail_times(&self, method: impl Into<String>, status: Status, count: u32) {
+        self.state().errors.push((method.into(), status, Some(count)));
+    }
+
+    /// Inject a fault into all active connections.
+    pub fn inject(&self, fault: Fault) {
+        self.send(Control::Fault(fault));
+    }
+
+    /// Move all active connections to the given channel, as if the buds had
+    /// handed off processing. Existing subscriptions are dropped and the
+    /// device announces itself on the new channel. New connections still use
+    /// the original channel.
+    pub fn handoff(&self, channel: u32) {
+        self.send(Control::Handoff(channel));
+    }
+
+    /// Inject a fault whenever the given method is called.
+    pub fn inject_on(&self, method: impl Into<String>, fault: Fault) {
+        self.state().faults.push((method.into(), fault));
+    }
+
+    fn send(&self, msg: Control) {
+        self.control.lock().unwrap()
+            .retain(|tx| tx.unbounded_send(msg.clone()).is_ok());
+    }
+}
+
+impl Default for Device {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Subscription {
+    service_id: u32,
+    method_id: u32,
+    call_id: u32,
+}
+
+
+/// Device side of a single connection.
+pub struct Server {
+    stream: DuplexStream,
+    state: Arc<Mutex<State>>,
+    channel: u32,
+    control: mpsc::UnboundedReceiver<Control>,
+}
+
+impl Server {
+    /// Handle the connection until it is closed by either side.
+    pub async fn run(self) -> std::io::Result<()> {
+        let (rx, mut tx) = tokio::io::split(self.stream);
+        let mut rx = FramedRead::new(rx, hdlc::Codec::new());
+
+        let mut conn = Connection {
+            state: self.state,
+            channel: self.channel,
+            subscriptions: Vec::new(),
+            out: BytesMut::new(),
+        };
+
+        let mut control = self.control;
+
+        // the device announces itself via an unsolicited software info response
+        if conn.state.lock().unwrap().announce {
+            conn.announce()?;
+            conn.flush(&mut tx).await?;
+        }
+
+        loop {
+            // write pending data concurrently to reading, so that we do not
+            // block a client that is busy sending requests
+            tokio::select! {
+                n = tx.write(&conn.out), if !conn.out.is_empty() => {
+                    conn.out.advance(n?);
+                },
+                frame = rx.next() => {
+                    let Some(frame) = frame else { return Ok(()) };
+                    let frame = frame?;
+
+                    if let Some(fault) = conn.handle_frame(frame)?
+                        && conn.inject(fault, &mut tx).await?
+                    {
+                        return Ok(());
+                    }
+                },
+                msg = control.next() => {
+                    match msg {
+                        Some(Control::RuntimeInfo(info)) => conn.notify(SUBSCRIBE_RUNTIME_INFO, &info)?,
+                        Some(Control::SettingChanged(value)) => {
+                            conn.notify(SUBSCRIBE_SETTINGS_CHANGES, &settings_response(value))?
+                        },
+                        Some(Control::Gesture(action)) => {
+                            let oobe = conn.state.lock().unwrap().setting(SettingId::OobeMode).cloned();
+
+                            if oobe == Some(SettingValue::OobeMode(true)) {
+                                conn.notify(SUBSCRIBE_OOBE_ACTIONS, &OobeActionRsp { action: action.into() })?
+                            }
+                        },
+                        Some(Control::Fault(fault)) => {
+                            if conn.inject(fault, &mut tx).await? {
+                                return Ok(());
+                            }
+                        },
+                        Some(Control::Handoff(channel)) => {
+                            tracing::trace!(channel, "mock: handing off to new channel");
+
+                            conn.channel = channel;
+                            conn.subscriptions.clear();
+                            conn.announce()?;
+                        },
+                        None => return Ok(()),
+                    }
+                },
+            }
+        }
+    }
+}
+
+
+struct Connection {
+    state: Arc<Mutex<State>>,
+    channel: u32,
+    subscriptions: Vec<Subscription>,
+    out: BytesMut,
+}
+
+impl Connection {
+    /// Handle a frame received from the client. Returns any fault to inject
+    /// in response.
+    fn handle_frame(&mut self, frame: hdlc::Frame) -> std::io::Result<Option<Fault>> {
+        let packet = RpcPacket::decode(&frame.data[..])?;
+
+        if packet.channel_id != self.channel {
+            tracing::trace!(channel=packet.channel_id, "mock: ignoring packet for other channel");
+            return Ok(None);
+        }
+
+        match PacketType::try_from(packet.r#type) {
+            Ok(PacketType::Request) => self.handle_request(packet),
+            Ok(PacketType::ClientError) => {
+                // client cancelled the call
+                self.subscriptions.retain(|s| {
+                    (s.service_id, s.method_id, s.call_id) != (packet.service_id, packet.method_id, packet.call_id)
+                });
+                Ok(None)
+            },
+            _ => Ok(None),
+        }
+    }
+
+    fn handle_request(&mut self, packet: RpcPacket) -> std::io::Result<Option<Fault>> {
+        let methods = [
+            GET_SOFTWARE_INFO,
+            GET_HARDWARE_INFO,
+            SUBSCRIBE_RUNTIME_INFO,
+            WRITE_SETTING,
+            READ_SETTING,
+            SUBSCRIBE_SETTINGS_CHANGES,
+            SUBSCRIBE_OOBE_ACTIONS,
+        ];
+
+        let method = methods.into_iter().find(|m| {
+            let path = Path::new(*m);
+            path.service().hash() == packet.service_id && path.method().hash() == packet.method_id
+        });
+
+        let (service, method_id, call) = (packet.service_id, packet.method_id, packet.call_id);
+
+        let Some(method) = method else {
+            return self.send(PacketType::ServerError, service, method_id, call, &(), Status::Unimplemented)
+                .map(|_| None);
+        };
+
+        let (error, fault) = {
+            let mut state = self.state.lock().unwrap();
+
+            let error = state.errors.iter_mut()
+                .find(|(m, _, n)| m == method && *n != Some(0))
+                .map(|(_, s, n)| {
+                    if let Some(n) = n {
+                        *n -= 1;
+                    }
+                    *s
+                });
+            let fault = state.faults.iter().find(|(m, _)| m == method).map(|(_, f)| f.clone());
+
+            (error, fault)
+        };
+
+        if let Some(fault) = fault {
+            return Ok(Some(fault));
+        }
+
+        if let Some(status) = error {
+            self.send(PacketType::ServerError, service, method_id, call, &(), status)?;
+            return Ok(None);
+        }
+
+        match method {
+            GET_SOFTWARE_INFO => {
+                let info = self.state.lock().unwrap().software_info.clone();
+                self.send(PacketType::Response, service, method_id, call, &info, Status::Ok)?;
+            },
+            GET_HARDWARE_INFO => {
+                let info = self.state.lock().unwrap().hardware_info.clone();
+                self.send(PacketType::Response, service, method_id, call, &info, Status::Ok)?;
+            },
+            SUBSCRIBE_RUNTIME_INFO => {
+                self.subscriptions.push(Subscription { service_id: service, method_id, call_id: call });
+
+                let info = self.state.lock().unwrap().runtime_info;
+                self.send(PacketType::ServerStream, service, method_id, call, &info, Status::Ok)?;
+            },
+            SUBSCRIBE_SETTINGS_CHANGES | SUBSCRIBE_OOBE_ACTIONS => {
+                self.subscriptions.push(Subscription { service_id: service, method_id, call_id: call });
+            },
+            READ_SETTING => {
+                let msg = ReadSettingMsg::decode(&packet.payload[..])?;
+
+                let Some(read_setting_msg::ValueOneof::SettingsId(id)) = msg.value_oneof else {
+                    return self.send(PacketType::ServerError, service, method_id, call, &(), Status::InvalidArgument)
+                        .map(|_| None);
+                };
+
+                let value = self.state.lock().unwrap().setting(SettingId::from(id)).cloned();
+
+                match value {
+                    Some(value) => {
+                        self.send(PacketType::Response, service, method_id, call, &settings_response(value), Status::Ok)?
+                    },
+                    None => {
+                        // the device responds with status 'unknown' for unsupported settings
+                        self.send(PacketType::ServerError, service, method_id, call, &(), Status::Unknown)?
+                    },
+                }
+            },
+            WRITE_SETTING => {
+                let msg = WriteSettingMsg::decode(&packet.payload[..])?;
+
+                let value = match msg.value_oneof {
+                    Some(write_setting_msg::ValueOneof::Setting(types::SettingValue { value_oneof: Some(value) })) => value,
+                    _ => {
+                        return self.send(PacketType::ServerError, service, method_id, call, &(), Status::InvalidArgument)
+                            .map(|_| None);
+                    },
+                };
+
+                let value = SettingValue::from(value);
+                self.state.lock().unwrap().set_setting(value.clone());
+
+                self.send(PacketType::Response, service, method_id, call, &(), Status::Ok)?;
+                self.notify(SUBSCRIBE_SETTINGS_CHANGES, &settings_response(value))?;
+            },
+            _ => unreachable!(),
+        }
+
+        Ok(None)
+    }
+
+    /// Send an unsolicited software info response.
+    fn announce(&mut self) -> std::io::Result<()> {
+        let info = self.state.lock().unwrap().software_info.clone();
+        let path = Path::new(GET_SOFTWARE_INFO);
+
+        self.send(PacketType::Response, path.service().hash(), path.method().hash(), UNSOLICITED_CALL_ID, &info, Status::Ok)
+    }
+
+    /// Send a stream item to all subscribers of the given method.
+    fn notify<M: Message>(&mut self, method: &str, msg: &M) -> std::io::Result<()> {
+        let path = Path::new(method);
+        let (service_id, method_id) = (path.service().hash(), path.method().hash());
+
+        let subs: Vec<_> = self.subscriptions.iter()
+            .filter(|s| s.service_id == service_id && s.method_id == method_id)
+            .copied()
+            .collect();
+
+        for sub in subs {
+            self.send(PacketType::ServerStream, sub.service_id, sub.method_id, sub.call_id, msg, Status::Ok)?;
+        }
+
+        Ok(())
+    }
+
+    fn send<M: Message>(
+        &mut self,
+        ty: PacketType,
+        service_id: u32,
+        method_id: u32,
+        call_id: u32,
+        msg: &M,
+        status: Status,
+    ) -> std::io::Result<()> {
+        let packet = RpcPacket {
+            r#type: ty.into(),
+            channel_id: self.channel,
+            service_id,
+            method_id,
+            payload: msg.encode_to_vec(),
+            status: status as _,
+            call_id,
+        };
+
+        // device-to-host direction uses the swapped address
+        let address = addr::address_for_channel(self.channel).unwrap().swap();
+
+        let frame = hdlc::Frame {
+            address: address.value(),
+            control: 0x03,
+            data: packet.encode_to_vec().into(),
+        };
+
+        hdlc::Codec::new().encode(&frame, &mut self.out)
+    }
+
+    async fn flush<W: AsyncWriteExt + Unpin>(&mut self, tx: &mut W) -> std::io::Result<()> {
+        if !self.out.is_empty() {
+            tx.write_all(&self.out).await?;
+            self.out.clear();
+        }
+
+        Ok(())
+    }
+
+    /// Inject a fault. Returns `true` if the connection should be closed.
+    async fn inject<W: AsyncWriteExt + Unpin>(&mut self, fault: Fault, tx: &mut W) -> std::io::Result<bool> {
+        tracing::trace!(?fault, "mock: injecting fault");
+
+        match fault {
+            Fault::Reset => {
+                self.flush(tx).await?;
+                tx.shutdown().await?;
+                Ok(true)
+            },
+            Fault::Garbage(data) => {
+                self.flush(tx).await?;
+                tx.write_all(&data).await?;
+                Ok(false)
+            },
+        }
+    }
+}
+
+fn settings_response(value: SettingValue) -> SettingsRsp {
+    SettingsRsp {
+        value_oneof: Some(settings_rsp::ValueOneof::Value(types::SettingValue {
+            value_oneof: Some(value.into()),
+        })),
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::protocol::codec::Codec;
+    use crate::protocol::utils;
+    use crate::pwrpc::client::Client;
+    use crate::service::MaestroService;
+    use crate::service::settings::{self, AncState};
+
+    async fn with_client<F, Fut>(device: &Device, f: F)
+    where
+        F: FnOnce(MaestroService) -> Fut,
+        Fut: std::future::Future<Output = ()>,
+    {
+        let (stream, server) = device.connect();
+        let server = tokio::spawn(server.run());
+
+        let mut client = Client::new(Codec::new().wrap(stream));
+        let channel = utils::resolve_channel(&mut client).await.unwrap();
+        assert_eq!(channel, device.channel());
+
+        let service = MaestroService::new(client.handle(), channel);
+
+        fixture::run(&mut client, f(service)).await;
+
+        drop(client);
+        server.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_settings() {
+        let device = Device::new();
+
+        with_client(&device, |mut service| async move {
+            let info = service.get_software_info().await.unwrap();
+            assert_eq!(info.firmware.unwrap().left.unwrap().version_string, "1.0.0");
+
+            let anc = service.read_setting(settings::id::CurrentAncrState).await.unwrap();
+            assert_eq!(anc, AncState::Active);
+
+            service.write_setting(SettingValue::CurrentAncrState(AncState::Off)).await.unwrap();
+
+            let anc = service.read_setting(settings::id::CurrentAncrState).await.unwrap();
+            assert_eq!(anc, AncState::Off);
+
+            let err = service.read_setting_var(SettingId::SpeechDetection).await.unwrap_err();
+            assert_eq!(err.code(), Status::Unknown);
+        }).await;
+
+        assert_eq!(device.setting(SettingId::CurrentAncrState), Some(SettingValue::CurrentAncrState(AncState::Off)));
+    }
+
+    #[tokio::test]
+    async fn test_streams() {
+        let device = Device::new();
+
+        with_client(&device, |mut service| {
+            let device = device.clone();
+
+            async move {
+                let mut call = service.subscribe_to_settings_changes().unwrap();
+                let mut changes = call.stream();
+
+                // give the server some time to register the subscription
+                service.get_hardware_info().await.unwrap();
+
+                device.change_setting(SettingValue::GestureEnable(false));
+
+                let rsp = changes.next().await.unwrap().unwrap();
+                assert_eq!(rsp, settings_response(SettingValue::GestureEnable(false)));
+
+                let mut call = service.subscribe_to_runtime_info().unwrap();
+                let mut info = call.stream();
+
+                let initial = info.next().await.unwrap().unwrap();
+                assert_eq!(initial, device.state().runtime_info);
+
+                let update = RuntimeInfo { timestamp_ms: 42, ..initial };
+                device.update_runtime_info(update);
+
+                assert_eq!(info.next().await.unwrap().unwrap(), update);
+            }
+        }).await;
+    }
+
+    #[tokio::test]
+    async fn test_faults() {
+        let device = Device::new();
+        device.fail(GET_HARDWARE_INFO, Status::Unavailable);
+
+        with_client(&device, |mut service| {
+            let device = device.clone();
+
+            async move {
+                let err = service.get_hardware_info().await.unwrap_err();
+                assert_eq!(err.code(), Status::Unavailable);
+                assert_eq!(err.path(), Some(GET_HARDWARE_INFO));
+
+                // garbage outside of frames is skipped by the decoder
+                device.inject(Fault::Garbage(vec![0x12, 0x34, 0x56]));
+                service.get_software_info().await.unwrap();
+            }
+        }).await;
+
+        device.inject_on(GET_SOFTWARE_INFO, Fault::Reset);
+
+        let (stream, server) = device.connect();
+        let server = tokio::spawn(server.run());
+
+        let mut client = Client::new(Codec::new().wrap(stream));
+        let channel = utils::resolve_channel(&mut client).await.unwrap();
+        let mut service = MaestroService::new(client.handle(), channel);
+
+        // the reset terminates the client
+        tokio::select! {
+            _ = client.run() => {},
+            _ = service.get_software_info() => panic!("call completed despite reset"),
+        }
+
+        server.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_resolve_channel_hinted() {
+        let device = Device::new();
+        let stale = addr::channel_id(addr::Peer::MaestroB, addr::Peer::RightBtCore).unwrap();
+
+        for (hint, expected) in [(device.channel(), device.channel()), (stale, device.channel())] {
+            let (stream, server) = device.connect();
+            let server = tokio::spawn(server.run());
+
+            let mut client = Client::new(Codec::new().wrap(stream));
+            let channel = utils::resolve_channel_hinted(&mut client, hint).await.unwrap();
+            assert_eq!(channel, expected);
+
+            drop(client);
+            server.await.unwrap().unwrap();
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_resolve_channel_probing() {
+        use std::time::Duration;
+
+        let device = Device::with_state(State { announce: false, ..Default::default() });
+
+        let (stream, server) = device.connect();
+        let server = tokio::spawn(server.run());
+
+        // without announcement, the channel is found by probing
+        let mut client = Client::new(Codec::new().wrap(stream));
+        let channel = utils::resolve_channel_probing(&mut client, None, Duration::from_secs(3)).await.unwrap();
+        assert_eq!(channel, device.channel());
+
+        drop(client);
+        server.await.unwrap().unwrap();
+    }
+}
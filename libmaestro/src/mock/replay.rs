@@ -0,0 +1,235 @@
+//! Replay of captured device sessions.
+//!
+//! Replays a [`Capture`] against a client: Data sent by the device is replayed
+//! verbatim, packets sent by the host are checked against the ones in the
+//! capture. This allows turning captures of real devices (e.g., submitted for
+//! new firmware versions) into regression tests:
+//!
+//! ```ignore
+//! let capture = Capture::parse(include_str!("captures/session.txt"))?;
+//! let (stream, replay) = replay::connect(&capture)?;
+//!
+//! tokio::spawn(replay.run());
+//!
+//! let mut client = Client::new(Codec::new().wrap(stream));
+//! // ...
+//! ```
+//!
+//! Note that the client must issue the same requests in the same order as in
+//! the capture.
+
+use std::io::{Error, ErrorKind};
+
+use bytes::BytesMut;
+
+use futures::StreamExt;
+
+use prost::Message;
+
+use tokio::io::{AsyncWriteExt, DuplexStream};
+use tokio_util::codec::FramedRead;
+
+use crate::capture::{Capture, Direction};
+use crate::hdlc;
+use crate::pwrpc::types::RpcPacket;
+
+use super::BUFFER_SIZE;
+
+
+#[derive(Debug, Clone)]
+enum Step {
+    Send(Vec<u8>),
+    Expect(RpcPacket),
+}
+
+
+/// Create a connection replaying the given capture. Returns the client side
+/// of the stream and the replay, which needs to be run to handle the
+/// connection.
+pub fn connect(capture: &Capture) -> std::io::Result<(DuplexStream, Replay)> {
+    let mut steps = Vec::new();
+
+    for entry in &capture.entries {
+        match entry.direction {
+            Direction::Device => steps.push(Step::Send(entry.data.clone())),
+            Direction::Host => {
+                for packet in decode_packets(&entry.data)? {
+                    steps.push(Step::Expect(packet));
+                }
+            },
+        }
+    }
+
+    let (client, server) = tokio::io::duplex(BUFFER_SIZE);
+
+    Ok((client, Replay { stream: server, steps }))
+}
+
+fn decode_packets(data: &[u8]) -> std::io::Result<Vec<RpcPacket>> {
+    let mut buf = BytesMut::from(data);
+    let mut dec = hdlc::decoder::Decoder::new();
+    let mut packets = Vec::new();
+
+    while let Some(frame) = dec.process(&mut buf)
+        .map_err(|e| Error::new(ErrorKind::InvalidData, format!("invalid frame in capture: {e:?}")))?
+    {
+        packets.push(RpcPacket::decode(&frame.data[..])?);
+    }
+
+    Ok(packets)
+}
+
+
+/// Device side of a replayed connection.
+pub struct Replay {
+    stream: DuplexStream,
+    steps: Vec<Step>,
+}
+
+impl Replay {
+    /// Replay the capture. Returns an error if the client sends packets
+    /// differing from the capture. After the capture has been replayed, waits
+    /// for the client to close the connection.
+    pub async fn run(self) -> std::io::Result<()> {
+        let (rx, mut tx) = tokio::io::split(self.stream);
+        let mut rx = FramedRead::new(rx, hdlc::Codec::new());
+
+        for (i, step) in self.steps.into_iter().enumerate() {
+            match step {
+                Step::Send(data) => {
+                    tx.write_all(&data).await?;
+                },
+                Step::Expect(expected) => {
+                    let Some(frame) = rx.next().await else {
+                        let msg = format!("connection closed at step {i}, expected {expected:?}");
+                        return Err(Error::new(ErrorKind::UnexpectedEof, msg));
+                    };
+
+                    let packet = RpcPacket::decode(&frame?.data[..])?;
+
+                    if packet != expected {
+                        let msg = format!("unexpected packet at step {i}: expected {expected:?}, got {packet:?}");
+                        return Err(Error::new(ErrorKind::InvalidData, msg));
+                    }
+                },
+            }
+        }
+
+        match rx.next().await {
+            None => Ok(()),
+            Some(frame) => {
+                let packet = RpcPacket::decode(&frame?.data[..])?;
+                let msg = format!("unexpected packet after end of capture: {packet:?}");
+                Err(Error::new(ErrorKind::InvalidData, msg))
+            },
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::capture::Recorder;
+    use crate::mock::{Device, fixture};
+    use crate::protocol::codec::Codec;
+    use crate::protocol::types::{RuntimeInfo, SoftwareInfo};
+    use crate::protocol::utils;
+    use crate::pwrpc::client::Client;
+    use crate::service::MaestroService;
+    use crate::service::settings::{self, AncState};
+
+    use tokio::io::{AsyncRead, AsyncWrite};
+
+    async fn session<S>(stream: S) -> (SoftwareInfo, AncState, RuntimeInfo)
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
+        let mut client = Client::new(Codec::new().wrap(stream));
+        let channel = utils::resolve_channel(&mut client).await.unwrap();
+        let mut service = MaestroService::new(client.handle(), channel);
+
+        let task = async {
+            let software = service.get_software_info().await.unwrap();
+            let anc = service.read_setting(settings::id::CurrentAncrState).await.unwrap();
+
+            let mut call = service.subscribe_to_runtime_info().unwrap();
+            let runtime = call.stream().next().await.unwrap().unwrap();
+
+            call.cancel_and_wait().await.unwrap();
+
+            (software, anc, runtime)
+        };
+
+        fixture::run(&mut client, task).await
+    }
+
+    #[tokio::test]
+    async fn test_record_replay() {
+        let device = Device::new();
+
+        // record a session against the mock device
+        let mut data = Vec::new();
+        let recorded = {
+            let (stream, server) = device.connect();
+            let server = tokio::spawn(server.run());
+
+            let res = session(Recorder::new(stream, &mut data)).await;
+
+            server.await.unwrap().unwrap();
+            res
+        };
+
+        let capture = Capture::parse(std::str::from_utf8(&data).unwrap()).unwrap();
+
+        // replay it and check that we get the same values
+        let (stream, replay) = connect(&capture).unwrap();
+        let replay = tokio::spawn(replay.run());
+
+        let replayed = session(stream).await;
+
+        replay.await.unwrap().unwrap();
+        assert_eq!(recorded, replayed);
+    }
+
+    #[tokio::test]
+    async fn test_replay_mismatch() {
+        let capture = Capture::parse(include_str!("captures/session.txt")).unwrap();
+        let (stream, replay) = connect(&capture).unwrap();
+        let replay = tokio::spawn(replay.run());
+
+        let mut client = Client::new(Codec::new().wrap(stream));
+        let channel = utils::resolve_channel(&mut client).await.unwrap();
+        let mut service = MaestroService::new(client.handle(), channel);
+
+        // the capture starts with a software info request, not a hardware one
+        tokio::select! {
+            _ = client.run() => {},
+            _ = service.get_hardware_info() => panic!("call completed despite mismatch"),
+        }
+
+        let err = replay.await.unwrap().unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+    }
+
+    #[tokio::test]
+    async fn test_replay_capture() {
+        let capture = Capture::parse(include_str!("captures/session.txt")).unwrap();
+        let (stream, replay) = connect(&capture).unwrap();
+        let replay = tokio::spawn(replay.run());
+
+        let (software, anc, runtime) = session(stream).await;
+        replay.await.unwrap().unwrap();
+
+        let firmware = software.firmware.unwrap();
+        assert_eq!(firmware.left.unwrap().version_string, "1.0.0");
+        assert_eq!(firmware.right.unwrap().version_string, "1.0.0");
+
+        assert_eq!(anc, AncState::Active);
+
+        let battery = runtime.battery_info.unwrap();
+        assert_eq!(battery.case.unwrap().level, 80);
+        assert_eq!(battery.left.unwrap().level, 90);
+    }
+}
@@ -0,0 +1,169 @@
+//! Support matrix of known device models.
+//!
+//! Each [`Model`] records the services, settings, and ANC modes known to be
+//! supported by a device model, as well as its known quirks. Models are
+//! identified by their GFPS model ID, which devices report via the GFPS
+//! message stream after connecting. As a fallback, models can be matched by
+//! their Bluetooth name.
+//!
+//! Only the Pixel Buds Pro have been tested. The entries of other models are
+//! based on their advertised features and may be incomplete. Model IDs are
+//! only listed once confirmed, e.g. via the `gfps_listen` example of `gfps`.
+
+use crate::service::settings::{AncState, SettingId};
+
+
+/// A service provided via the Maestro protocol.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Service {
+    Maestro,
+    Multipoint,
+    Dosimeter,
+}
+
+
+/// Known deviations of a model from the protocol.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Quirks {
+    /// The device ignores ring requests specifying a timeout.
+    pub ignores_ring_timeout: bool,
+
+    /// The buds hand off processing between each other, which can cancel
+    /// active calls.
+    pub handoff: bool,
+}
+
+
+/// Capabilities and quirks of a device model.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Model {
+    pub name: &'static str,
+
+    /// GFPS model IDs of the model, one per color variant.
+    pub model_ids: &'static [u32],
+
+    /// Whether the entry has been verified on an actual device.
+    pub tested: bool,
+
+    pub services: &'static [Service],
+    pub settings: &'static [SettingId],
+    pub anc: &'static [AncState],
+    pub quirks: Quirks,
+}
+
+impl Model {
+    pub fn supports_service(&self, service: Service) -> bool {
+        self.services.contains(&service)
+    }
+
+    pub fn supports_setting(&self, setting: SettingId) -> bool {
+        self.settings.contains(&setting)
+    }
+
+    pub fn supports_anc(&self, state: AncState) -> bool {
+        self.anc.contains(&state)
+    }
+}
+
+
+/// Settings of the Pixel Buds Pro.
+const PRO_SETTINGS: &[SettingId] = &[
+    SettingId::AutoOtaEnable,
+    SettingId::OhdEnable,
+    SettingId::OobeIsFinished,
+    SettingId::GestureEnable,
+    SettingId::DiagnosticsEnable,
+    SettingId::OobeMode,
+    SettingId::GestureControl,
+    SettingId::MultipointEnable,
+    SettingId::AncrGestureLoop,
+    SettingId::CurrentAncrState,
+    SettingId::OttsMode,
+    SettingId::VolumeEqEnable,
+    SettingId::CurrentUserEq,
+    SettingId::VolumeAsymmetry,
+    SettingId::SumToMono,
+    SettingId::VolumeExposureNotifications,
+    SettingId::SpeechDetection,
+];
+
+const PRO_ANC: &[AncState] = &[AncState::Off, AncState::Active, AncState::Aware];
+
+
+/// All known models.
+pub const MODELS: &[Model] = &[
+    Model {
+        name: "Pixel Buds Pro",
+        model_ids: &[],
+        tested: true,
+        services: &[Service::Maestro, Service::Multipoint, Service::Dosimeter],
+        settings: PRO_SETTINGS,
+        anc: PRO_ANC,
+        quirks: Quirks { ignores_ring_timeout: true, handoff: true },
+    },
+    Model {
+        name: "Pixel Buds Pro 2",
+        model_ids: &[],
+        tested: false,
+        services: &[Service::Maestro, Service::Multipoint, Service::Dosimeter],
+        settings: PRO_SETTINGS,
+        anc: PRO_ANC,
+        quirks: Quirks { ignores_ring_timeout: true, handoff: true },
+    },
+    Model {
+        name: "Pixel Buds A-Series",
+        model_ids: &[],
+        tested: false,
+        services: &[],
+        settings: &[],
+        anc: &[],
+        quirks: Quirks { ignores_ring_timeout: false, handoff: false },
+    },
+];
+
+
+/// Look up the model with the given GFPS model ID.
+pub fn by_model_id(model_id: u32) -> Option<&'static Model> {
+    MODELS.iter().find(|m| m.model_ids.contains(&model_id))
+}
+
+/// Look up the model by the Bluetooth name of the device, e.g. `Pixel Buds
+/// Pro` or `Jane's Pixel Buds Pro 2`. The longest matching model name wins,
+/// so that the name of the Pixel Buds Pro 2 does not match the Pixel Buds Pro.
+pub fn by_name(name: &str) -> Option<&'static Model> {
+    let name = name.to_lowercase();
+
+    MODELS.iter()
+        .filter(|m| {
+            let model = m.name.to_lowercase();
+
+            // match whole words only
+            name.match_indices(&model)
+                .any(|(i, _)| !name[i + model.len()..].starts_with(char::is_alphanumeric))
+        })
+        .max_by_key(|m| m.name.len())
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_lookup() {
+        assert_eq!(by_name("Pixel Buds Pro").unwrap().name, "Pixel Buds Pro");
+        assert_eq!(by_name("Jane's Pixel Buds Pro 2").unwrap().name, "Pixel Buds Pro 2");
+        assert_eq!(by_name("pixel buds a-series").unwrap().name, "Pixel Buds A-Series");
+        assert_eq!(by_name("Pixel Buds"), None);
+        assert_eq!(by_model_id(0x000000), None);
+
+        let pro = by_name("Pixel Buds Pro").unwrap();
+        assert!(pro.supports_setting(SettingId::CurrentAncrState));
+        assert!(pro.supports_anc(AncState::Aware));
+        assert!(pro.supports_service(Service::Dosimeter));
+        assert!(pro.quirks.ignores_ring_timeout);
+
+        let a = by_name("Pixel Buds A-Series").unwrap();
+        assert!(!a.supports_service(Service::Maestro));
+    }
+}
@@ -0,0 +1,43 @@
+//! Stable high-level API.
+//!
+//! Re-exports the types needed by typical applications, i.e., the services,
+//! settings, and client events, while leaving out protocol and transport
+//! internals. Import everything via
+//!
+//! ```
+//! use maestro::prelude::*;
+//! ```
+//!
+//! The contents of each versioned module, e.g. [`v1`], are kept stable across
+//! releases: items are only ever added, never removed or renamed. Items that
+//! need to change get a new name and the old one is kept as deprecated alias
+//! for at least one release. Breaking changes go into a new versioned module
+//! instead, with `maestro::prelude` pointing to the most recent one.
+
+pub use self::v1::*;
+
+
+/// Version 1 of the prelude.
+pub mod v1 {
+    pub use crate::Error;
+
+    pub use crate::service::settings::{
+        self, AncState, AncrGestureLoop, Danger, EqBands, GestureControl, RegularActionTarget,
+        Setting, SettingId, SettingInfo, SettingValue, VolumeAsymmetry,
+    };
+
+    #[cfg(feature = "client")]
+    pub use crate::service::{
+        BatterySnapshot, BatterySubscription, DeviceSnapshot, DosimeterService, GestureInterception,
+        MaestroService, MultipointService, Retry, SnapshotTiming,
+    };
+
+    #[cfg(feature = "client")]
+    pub use crate::service::cache::SettingsCache;
+
+    #[cfg(feature = "client")]
+    pub use crate::pwrpc::client::{Client, ClientHandle, Event, EventStream};
+
+    pub use crate::protocol::codec::Codec;
+    pub use crate::pwrpc::Status;
+}
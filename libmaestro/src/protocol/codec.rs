@@ -8,25 +8,68 @@ use tokio_util::codec::{Decoder, Framed, Encoder};
 use crate::pwrpc::types::RpcPacket;
 use crate::hdlc;
 
-use super::addr;
+use super::addr::{self, Address};
+
+
+/// Control field used for outgoing frames by default.
+pub const DEFAULT_CONTROL: u8 = 0x03;
 
 
 pub struct Codec {
     hdlc: hdlc::Codec,
+    address: Option<Address>,
+    control: u8,
+
+    /// Buffer for encoding packets, re-used across frames.
+    scratch: Vec<u8>,
 }
 
 impl Codec {
     pub fn new() -> Self {
         Self {
             hdlc: hdlc::Codec::new(),
+            address: None,
+            control: DEFAULT_CONTROL,
+            scratch: Vec::new(),
         }
     }
 
+    /// Limit the size of received frames to the given capacity. See
+    /// [`hdlc::Codec::with_frame_capacity`].
+    pub fn with_frame_capacity(self, cap: usize) -> Self {
+        Self { hdlc: self.hdlc.with_frame_capacity(cap), ..self }
+    }
+
+    /// Use the given initial capacity for the read and write buffers of
+    /// wrapped streams.
+    pub fn with_buffer_capacity(self, cap: usize) -> Self {
+        Self { hdlc: self.hdlc.with_buffer_capacity(cap), ..self }
+    }
+
+    /// Send all outgoing frames to the given address instead of the one
+    /// derived from the channel ID of the packet, e.g. to direct traffic at
+    /// a specific peer.
+    pub fn with_address(self, address: Address) -> Self {
+        Self { address: Some(address), ..self }
+    }
+
+    /// Use the given control field for outgoing frames.
+    pub fn with_control(self, control: u8) -> Self {
+        Self { control, ..self }
+    }
+
+    /// Handle to the statistics of the received data, which remains valid
+    /// after wrapping the codec.
+    pub fn stats(&self) -> hdlc::codec::StatsHandle {
+        self.hdlc.stats()
+    }
+
     pub fn wrap<T>(self, io: T) -> Framed<T, Codec>
     where
         T: AsyncRead + AsyncWrite,
     {
-        Framed::with_capacity(io, self, 4096 as _)
+        let cap = self.hdlc.buffer_capacity();
+        Framed::with_capacity(io, self, cap)
     }
 }
 
@@ -41,18 +84,30 @@ impl Decoder for Codec {
     type Error = std::io::Error;
 
     fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
-        match self.hdlc.decode(src)? {
-            Some(frame) => {
-                if frame.control != 0x03 {
-                    tracing::warn!("unexpected control type: {}", frame.control);
-                    return Ok(None);
+        // Frames not carrying a valid RPC packet are skipped. Continue with
+        // the remaining data, as it may already contain a full frame and
+        // returning None here would wait for more data before trying to
+        // decode that.
+        while !src.is_empty() {
+            let packet = self.hdlc.decode_with(src, |frame| {
+                if frame.control != DEFAULT_CONTROL {
+                    tracing::warn!(address=frame.address, control=frame.control, "unexpected control type");
+                    return None;
                 }
 
-                let packet = RpcPacket::decode(&frame.data[..])?;
-                Ok(Some(packet))
+                RpcPacket::decode(frame.data).inspect_err(|e| {
+                    tracing::warn!(address=frame.address, error=%e, "failed to decode RPC packet");
+                }).ok()
+            });
+
+            match packet {
+                Some(Some(packet)) => return Ok(Some(packet)),
+                Some(None) => continue,
+                None => break,
             }
-            None => Ok(None),
         }
+
+        Ok(None)
     }
 }
 
@@ -60,15 +115,23 @@ impl Encoder<&RpcPacket> for Codec {
     type Error = std::io::Error;
 
     fn encode(&mut self, packet: &RpcPacket, dst: &mut BytesMut) -> Result<(), Self::Error> {
-        let address = addr::address_for_channel(packet.channel_id).unwrap();
+        let address = self.address
+            .or_else(|| addr::address_for_channel(packet.channel_id))
+            .ok_or_else(|| {
+                let msg = format!("no address for channel {}", packet.channel_id);
+                std::io::Error::new(std::io::ErrorKind::InvalidInput, msg)
+            })?;
 
-        let frame = hdlc::Frame {
+        self.scratch.clear();
+        packet.encode(&mut self.scratch)?;
+
+        let frame = hdlc::FrameRef {
             address: address.value(),
-            control: 0x03,
-            data: packet.encode_to_vec().into(),    // TODO: can we avoid these allocations?
+            control: self.control,
+            data: &self.scratch,
         };
 
-        self.hdlc.encode(&frame, dst)
+        self.hdlc.encode(frame, dst)
     }
 }
 
@@ -79,3 +142,64 @@ impl Encoder<RpcPacket> for Codec {
         self.encode(&packet, dst)
     }
 }
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::protocol::addr::Peer;
+
+    fn encode<T>(codec: &mut Codec, packet: T) -> hdlc::Frame
+    where
+        Codec: Encoder<T, Error = std::io::Error>,
+    {
+        let mut buf = BytesMut::new();
+        codec.encode(packet, &mut buf).unwrap();
+
+        hdlc::Frame::decode(&mut buf).unwrap().unwrap()
+    }
+
+    #[test]
+    fn test_decode_skip() {
+        let packet = RpcPacket { channel_id: 19, call_id: 4, ..Default::default() };
+        let address = addr::address_for_channel(19).unwrap();
+
+        let mut codec = Codec::new();
+        let mut buf = BytesMut::new();
+
+        // unexpected control type
+        codec.hdlc.encode(hdlc::FrameRef { address: address.value(), control: 0x13, data: &[] }, &mut buf).unwrap();
+
+        // invalid RPC packet
+        codec.hdlc.encode(hdlc::FrameRef { address: address.value(), control: DEFAULT_CONTROL, data: &[0xff] }, &mut buf).unwrap();
+
+        codec.encode(&packet, &mut buf).unwrap();
+
+        // both bad frames are skipped without waiting for more data
+        assert_eq!(codec.decode(&mut buf).unwrap(), Some(packet));
+        assert!(buf.is_empty());
+        assert_eq!(codec.decode(&mut buf).unwrap(), None);
+    }
+
+    #[test]
+    fn test_encode_address() {
+        let packet = RpcPacket { channel_id: 19, ..Default::default() };
+        let right = Address::from_peers(Peer::MaestroA, Peer::RightBtCore);
+
+        // derived from the channel by default
+        let frame = encode(&mut Codec::new(), &packet);
+        assert_eq!(frame.address, addr::address_for_channel(19).unwrap().value());
+        assert_eq!(frame.control, DEFAULT_CONTROL);
+
+        // overridden for the codec
+        let mut codec = Codec::new().with_address(right).with_control(0x13);
+        let frame = encode(&mut codec, &packet);
+        assert_eq!(frame.address, right.value());
+        assert_eq!(frame.control, 0x13);
+
+        // unknown channels without override are rejected
+        let packet = RpcPacket { channel_id: 1, ..Default::default() };
+        assert!(Codec::new().encode(packet, &mut BytesMut::new()).is_err());
+    }
+}
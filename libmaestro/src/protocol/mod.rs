@@ -1,7 +1,41 @@
 pub mod addr;
 pub mod codec;
+#[cfg(feature = "client")]
 pub mod utils;
+pub mod version;
 
+/// Message types of the Maestro protocol, generated from
+/// `proto/maestro_pw.proto`.
+///
+/// With the `serde` feature enabled, all types implement `Serialize` and
+/// `Deserialize`.
 pub mod types {
     include!(concat!(env!("OUT_DIR"), "/maestro_pw.rs"));
 }
+
+/// Protobuf definitions of the Maestro protocol messages.
+pub const PROTO: &str = include_str!("../../proto/maestro_pw.proto");
+
+/// Encoded `FileDescriptorSet` of the Maestro protocol and pw_rpc packet
+/// definitions, e.g., for decoding captures with external tools.
+pub const FILE_DESCRIPTOR_SET: &[u8] = include_bytes!(concat!(env!("OUT_DIR"), "/file_descriptor_set.bin"));
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use prost::Message;
+
+    #[test]
+    fn test_file_descriptor_set() {
+        let set = prost_types::FileDescriptorSet::decode(FILE_DESCRIPTOR_SET).unwrap();
+
+        let packages: Vec<_> = set.file.iter()
+            .filter_map(|f| f.package.as_deref())
+            .collect();
+
+        assert!(packages.contains(&"maestro_pw"));
+        assert!(packages.contains(&"pw.rpc.packet"));
+    }
+}
@@ -1,3 +1,6 @@
+use std::future::Future;
+use std::time::Duration;
+
 use crate::pwrpc::Error;
 use crate::pwrpc::client::{Client, Request, UnaryResponse, ClientHandle};
 use crate::pwrpc::id::PathRef;
@@ -8,7 +11,63 @@ use super::addr::Peer;
 use super::types::SoftwareInfo;
 
 
+/// Call ID used for the announcement received after connecting.
+const ANNOUNCE_CALL_ID: u32 = 0xffffffff;
+
+/// Call ID used for querying the channel hint.
+const HINT_CALL_ID: u32 = 0xfffffffe;
+
+/// Call ID used for probing all channels.
+const PROBE_CALL_ID: u32 = 0xfffffffd;
+
+
+#[cfg_attr(feature = "instrument", tracing::instrument(level = "debug", skip_all))]
 pub async fn resolve_channel<S, E>(client: &mut Client<S>) -> Result<u32, Error>
+where
+    S: futures::Sink<RpcPacket>,
+    S: futures::Stream<Item = Result<RpcPacket, E>> + Unpin,
+    Error: From<E>,
+    Error: From<S::Error>,
+{
+    resolve(client, None, None).await
+}
+
+/// Resolve the channel, additionally querying the given channel directly.
+///
+/// Channel resolution normally waits for the device to announce itself. If
+/// the channel is already known, e.g. from a previous connection, querying it
+/// directly may be answered sooner. A stale hint does not have any effect
+/// besides the unanswered query.
+#[cfg_attr(feature = "instrument", tracing::instrument(level = "debug", skip(client)))]
+pub async fn resolve_channel_hinted<S, E>(client: &mut Client<S>, hint: u32) -> Result<u32, Error>
+where
+    S: futures::Sink<RpcPacket>,
+    S: futures::Stream<Item = Result<RpcPacket, E>> + Unpin,
+    Error: From<E>,
+    Error: From<S::Error>,
+{
+    resolve(client, Some(hint), None).await
+}
+
+/// Resolve the channel, querying all channels directly if the device has not
+/// announced itself after the given duration.
+///
+/// Like [`resolve_channel_hinted`], the given channel hint, if any, is
+/// queried immediately.
+#[cfg_attr(feature = "instrument", tracing::instrument(level = "debug", skip(client)))]
+pub async fn resolve_channel_probing<S, E>(client: &mut Client<S>, hint: Option<u32>, probe_after: Duration)
+    -> Result<u32, Error>
+where
+    S: futures::Sink<RpcPacket>,
+    S: futures::Stream<Item = Result<RpcPacket, E>> + Unpin,
+    Error: From<E>,
+    Error: From<S::Error>,
+{
+    resolve(client, hint, Some(probe_after)).await
+}
+
+async fn resolve<S, E>(client: &mut Client<S>, hint: Option<u32>, probe_after: Option<Duration>)
+    -> Result<u32, Error>
 where
     S: futures::Sink<RpcPacket>,
     S: futures::Stream<Item = Result<RpcPacket, E>> + Unpin,
@@ -35,6 +94,38 @@ where
         try_open_channel(client.handle(), channels.5),
     );
 
+    let all = [channels.0, channels.1, channels.2, channels.3, channels.4, channels.5];
+
+    let handle = client.handle();
+    let probe = async move {
+        let hinted = async {
+            match hint {
+                Some(hint) => answered(try_call_channel(handle.clone(), hint, HINT_CALL_ID)).await,
+                None => std::future::pending().await,
+            }
+        };
+
+        let delayed = async {
+            let Some(delay) = probe_after else {
+                return std::future::pending().await;
+            };
+
+            tokio::time::sleep(delay).await;
+            tracing::debug!("no announcement received, probing all channels");
+
+            let probes = all.map(|channel| {
+                Box::pin(answered(try_call_channel(handle.clone(), channel, PROBE_CALL_ID)))
+            });
+
+            futures::future::select_all(probes).await.0
+        };
+
+        tokio::select! {
+            channel = hinted => channel,
+            channel = delayed => channel,
+        }
+    };
+
     let channel = tokio::select! {
         // Ensure that the open() calls are registered before we start running
         // the client.
@@ -46,6 +137,7 @@ where
         res = tasks.3 => { res? },
         res = tasks.4 => { res? },
         res = tasks.5 => { res? },
+        channel = probe => { channel },
         res = client.run() => { res?; return Err(Error::aborted("client terminated")) }
     };
 
@@ -53,6 +145,7 @@ where
     Ok(channel)
 }
 
+#[cfg_attr(feature = "instrument", tracing::instrument(level = "trace", skip(handle), fields(channel = channel_id)))]
 async fn try_open_channel(mut handle: ClientHandle, channel_id: u32) -> Result<u32, Error> {
     let path = PathRef::new("maestro_pw.Maestro/GetSoftwareInfo");
     let service_id = path.service().hash();
@@ -62,7 +155,7 @@ async fn try_open_channel(mut handle: ClientHandle, channel_id: u32) -> Result<u
         channel_id,
         service_id,
         method_id,
-        call_id: 0xffffffff,
+        call_id: ANNOUNCE_CALL_ID,
         message: (),
     };
 
@@ -71,3 +164,37 @@ async fn try_open_channel(mut handle: ClientHandle, channel_id: u32) -> Result<u
     rsp.result().await?;
     Ok(channel_id)
 }
+
+#[cfg_attr(feature = "instrument", tracing::instrument(level = "trace", skip(handle), fields(channel = channel_id)))]
+async fn try_call_channel(mut handle: ClientHandle, channel_id: u32, call_id: u32) -> Result<u32, Error> {
+    let path = PathRef::new("maestro_pw.Maestro/GetSoftwareInfo");
+    let service_id = path.service().hash();
+    let method_id = path.method().hash();
+
+    // The call ID must differ from the one of the announcement, as both may
+    // be pending at the same time.
+    let req = Request {
+        channel_id,
+        service_id,
+        method_id,
+        call_id,
+        message: (),
+    };
+
+    let mut rsp: UnaryResponse<SoftwareInfo> = handle.call_unary(req)?;
+
+    rsp.result().await?;
+    Ok(channel_id)
+}
+
+/// Wait for a successful query. Failed queries are not fatal, as the device
+/// may still announce itself, so they never complete.
+async fn answered(query: impl Future<Output = Result<u32, Error>>) -> u32 {
+    match query.await {
+        Ok(channel) => channel,
+        Err(err) => {
+            tracing::debug!(error=%err, "querying channel failed");
+            std::future::pending().await
+        },
+    }
+}
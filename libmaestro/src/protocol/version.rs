@@ -0,0 +1,129 @@
+//! Firmware version comparison.
+
+use std::cmp::Ordering;
+
+use super::types::{FirmwareInfo, FirmwareVersion};
+
+
+/// Numeric firmware version, e.g. `3.569.0`.
+///
+/// Versions are compared component-wise, with missing components treated as
+/// zero, i.e., `1.2` and `1.2.0` are equal.
+#[derive(Debug, Clone)]
+pub struct Version {
+    components: Vec<u32>,
+}
+
+impl Version {
+    /// Parse a version string consisting of dot-separated numbers. Returns
+    /// `None` if the string is not in this format.
+    pub fn parse(s: &str) -> Option<Self> {
+        let components = s.trim().split('.')
+            .map(|c| c.parse().ok())
+            .collect::<Option<Vec<u32>>>()?;
+
+        Some(Self { components })
+    }
+
+    pub fn components(&self) -> &[u32] {
+        &self.components
+    }
+}
+
+impl PartialEq for Version {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Version {}
+
+impl PartialOrd for Version {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Version {
+    fn cmp(&self, other: &Self) -> Ordering {
+        let len = self.components.len().max(other.components.len());
+
+        (0..len)
+            .map(|i| {
+                let a = self.components.get(i).copied().unwrap_or(0);
+                let b = other.components.get(i).copied().unwrap_or(0);
+                a.cmp(&b)
+            })
+            .find(|o| o.is_ne())
+            .unwrap_or(Ordering::Equal)
+    }
+}
+
+impl std::fmt::Display for Version {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (i, c) in self.components.iter().enumerate() {
+            if i > 0 {
+                write!(f, ".")?;
+            }
+            write!(f, "{c}")?;
+        }
+
+        Ok(())
+    }
+}
+
+
+impl FirmwareVersion {
+    /// Parsed version, if the version string is numeric.
+    pub fn version(&self) -> Option<Version> {
+        Version::parse(&self.version_string)
+    }
+
+    /// Whether both versions are the same. Falls back to comparing the
+    /// version strings if they are not numeric.
+    pub fn same_version(&self, other: &FirmwareVersion) -> bool {
+        match (self.version(), other.version()) {
+            (Some(a), Some(b)) => a == b,
+            _ => self.version_string == other.version_string,
+        }
+    }
+}
+
+impl FirmwareInfo {
+    /// Whether both buds run the same firmware version. Returns `None` if the
+    /// version of either bud is unknown.
+    pub fn buds_match(&self) -> Option<bool> {
+        let left = self.left.as_ref()?;
+        let right = self.right.as_ref()?;
+
+        Some(left.same_version(right))
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_compare() {
+        let v = |s| Version::parse(s).unwrap();
+
+        assert!(v("3.569.0") > v("3.499.1"));
+        assert!(v("1.10") > v("1.9"));
+        assert_eq!(v("1.2"), v("1.2.0"));
+        assert_eq!(v("3.569.0").to_string(), "3.569.0");
+        assert!(Version::parse("3.569-beta").is_none());
+
+        let fw = |s: &str| Some(FirmwareVersion { version_string: s.to_owned(), ..Default::default() });
+
+        let mut info = FirmwareInfo { case: fw("1.0"), left: fw("3.569.0"), right: fw("3.569") };
+        assert_eq!(info.buds_match(), Some(true));
+
+        info.right = fw("3.499.1");
+        assert_eq!(info.buds_match(), Some(false));
+
+        info.right = None;
+        assert_eq!(info.buds_match(), None);
+    }
+}
@@ -1,5 +1,10 @@
+use std::collections::{HashMap, VecDeque};
+use std::collections::hash_map::Entry;
 use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
 use std::task::Poll;
+use std::time::{Duration, Instant};
 
 use futures::{Sink, SinkExt, Stream, StreamExt};
 use futures::channel::mpsc;
@@ -7,11 +12,26 @@ use futures::stream::{SplitSink, SplitStream, FusedStream};
 
 use prost::Message;
 
-use super::id::Path;
+use tracing::Instrument;
+
+use super::id::{Path, PathRef};
 use super::status::{Status, Error};
 use super::types::{RpcType, RpcPacket, PacketType};
 
 
+/// Time to wait for a cancellation to be acknowledged by default, after which
+/// the call is completed locally. See [`UnaryResponse::cancel_and_wait`].
+pub const DEFAULT_CANCEL_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Maximum number of encoded bytes of queued requests to be sent together in
+/// a single transport write. Roughly the payload a single RFCOMM frame can
+/// carry.
+const BATCH_BUDGET: usize = 1000;
+
+/// Number of cancelled calls to remember for detecting responses that may
+/// belong to a cancelled call instead of a newer one re-using its UID.
+const CANCELLED_HISTORY: usize = 16;
+
 #[derive(Debug)]
 pub struct Client<S> {
     /// Stream for lower-level transport.
@@ -29,6 +49,38 @@ pub struct Client<S> {
 
     /// Pending RPC calls, waiting for a response.
     pending: Vec<Call>,
+
+    /// UIDs of all calls that have been requested but not completed yet.
+    /// Shared with handles to detect colliding calls.
+    active: ActiveCalls,
+
+    /// UIDs of the most recently cancelled calls, for which the peer may
+    /// still send a response.
+    cancelled: VecDeque<CallUid>,
+
+    /// Watchers receiving all packets for a specific service method.
+    watchers: Vec<Watcher>,
+
+    /// Subscribers for client events.
+    events: Vec<mpsc::UnboundedSender<Event>>,
+
+    /// Channel of the most recently received response traffic, or zero if
+    /// unknown. Shared with handles.
+    channel: Arc<AtomicU32>,
+
+    /// Counter for assigning trace IDs to calls. Shared with handles.
+    traces: Arc<AtomicU64>,
+
+    /// Counter for allocating call IDs. Shared with handles.
+    calls: Arc<AtomicU32>,
+
+    /// Timeout for unary calls in milliseconds, or zero if disabled. Shared
+    /// with handles.
+    call_timeout: Arc<AtomicU64>,
+
+    /// Encoded size of the packets fed to the transport since the last
+    /// flush.
+    unflushed: usize,
 }
 
 impl<S, E> Client<S>
@@ -48,12 +100,26 @@ where
             queue_rx,
             queue_tx,
             pending: Vec::new(),
+            active: ActiveCalls::default(),
+            cancelled: VecDeque::new(),
+            watchers: Vec::new(),
+            events: Vec::new(),
+            channel: Arc::new(AtomicU32::new(0)),
+            traces: Arc::new(AtomicU64::new(0)),
+            calls: Arc::new(AtomicU32::new(0)),
+            call_timeout: Arc::new(AtomicU64::new(0)),
+            unflushed: 0,
         }
     }
 
     pub fn handle(&self) -> ClientHandle {
         ClientHandle {
             queue_tx: self.queue_tx.clone(),
+            active: self.active.clone(),
+            channel: self.channel.clone(),
+            traces: self.traces.clone(),
+            calls: self.calls.clone(),
+            call_timeout: self.call_timeout.clone(),
         }
     }
 
@@ -63,6 +129,7 @@ where
         while let Ok(Some(request)) = self.queue_rx.try_next() {
             self.process_request(request).await?;
         }
+        self.flush().await?;
 
         loop {
             tokio::select! {
@@ -79,8 +146,18 @@ where
                     let request = request.expect("request queue closed unexpectedly");
 
                     self.process_request(request).await?;
+
+                    // Pipeline requests queued in the meantime, e.g. by
+                    // concurrent unary calls, into the same transport write.
+                    while self.unflushed < BATCH_BUDGET
+                        && let Ok(Some(request)) = self.queue_rx.try_next()
+                    {
+                        self.process_request(request).await?;
+                    }
                 },
             }
+
+            self.flush().await?;
         }
     }
 
@@ -102,16 +179,22 @@ where
         // been closed yet.
         while let Some(msg) = self.queue_rx.try_next().unwrap() {
             match msg {
-                CallRequest::New { sender, .. } => {
+                CallRequest::New { uid, trace, sender, .. } => {
                     // Drop new requests. Instead, notify caller with status 'aborted'.
+                    self.active.release(uid, trace);
+
                     let update = CallUpdate::Error { status: Status::Aborted };
                     let _ = sender.unbounded_send(update);
                     sender.close_channel();
                 },
-                CallRequest::Error { uid, code, tx } => {
+                CallRequest::Watch { .. } | CallRequest::Events { .. } => {
+                    // Drop new watchers and subscribers. This closes their
+                    // stream.
+                },
+                CallRequest::Error { uid, trace, code, tx } => {
                     // Process error requests as normal: Send error message to
                     // peer, remove and complete call.
-                    if let Some(mut call) = self.find_and_remove_call(uid) {
+                    if let Some(mut call) = self.find_and_remove_traced_call(uid, trace) {
                         call.complete_with_error(code).await;
                         if tx {
                             send.push((uid, code));
@@ -123,11 +206,17 @@ where
 
         // Cancel all pending RPCs and remove them from the list.
         for call in &mut self.pending {
+            self.active.release(call.uid, call.trace);
+
             call.complete_with_error(Status::Aborted).await;
             send.push((call.uid, Status::Cancelled));
         }
         self.pending.clear();
 
+        // Drop all watchers and event subscribers, closing their streams.
+        self.watchers.clear();
+        self.events.clear();
+
         // Define functions because async try-catch blocks aren't a thing yet...
         async fn do_send<S, E>(client: &mut Client<S>, send: Vec<(CallUid, Status)>) -> Result<(), Error>
         where
@@ -170,9 +259,15 @@ where
             packet.r#type, packet.channel_id, packet.service_id, packet.method_id, packet.call_id
         );
 
+        self.notify_watchers(&packet);
+
         let ty = packet.r#type;
         let ty = PacketType::try_from(ty);
 
+        if let Ok(PacketType::Response | PacketType::ServerStream) = ty {
+            self.track_channel(packet.channel_id);
+        }
+
         match ty {
             Ok(PacketType::Response) => {
                 self.rpc_complete(packet).await
@@ -206,22 +301,28 @@ where
 
         match call {
             Some(mut call) => {     // pending call found, complete rpc
+                let _span = call.span.clone().entered();
+                self.check_cancelled(&packet, Some(call.trace));
+
                 tracing::trace!(
-                    "completing rpc: channel_id=0x{:02x}, service_id=0x{:08x}, method_id=0x{:08x}, call_id=0x{:02x}",
-                    packet.channel_id, packet.service_id, packet.method_id, packet.call_id
+                    "completing rpc: trace_id={}, channel_id=0x{:02x}, service_id=0x{:08x}, method_id=0x{:08x}, call_id=0x{:02x}",
+                    call.trace, packet.channel_id, packet.service_id, packet.method_id, packet.call_id
                 );
 
                 if packet.status != 0 {
                     tracing::warn!(
-                        "completing rpc with non-zero status: channel_id=0x{:02x}, service_id=0x{:08x}, method_id=0x{:08x}, call_id=0x{:02x}, status={}",
-                        packet.channel_id, packet.service_id, packet.method_id, packet.call_id, packet.status
+                        "completing rpc with non-zero status: trace_id={}, channel_id=0x{:02x}, service_id=0x{:08x}, method_id=0x{:08x}, call_id=0x{:02x}, status={}",
+                        call.trace, packet.channel_id, packet.service_id, packet.method_id, packet.call_id, packet.status
                     );
                 }
 
                 let status = Status::from(packet.status);
+                self.emit_completed(&call, status);
                 call.complete(packet.payload, status).await;
             },
             None => {               // no pending call found, silently drop packet
+                self.check_cancelled(&packet, None);
+
                 tracing::debug!(
                     "received response for non-pending rpc: channel_id=0x{:02x}, service_id=0x{:08x}, method_id=0x{:08x}, call_id=0x{:02x}",
                     packet.channel_id, packet.service_id, packet.method_id, packet.call_id
@@ -236,15 +337,21 @@ where
 
         match call {
             Some(mut call) => {     // pending call found, complete rpc with error
+                let _span = call.span.clone().entered();
+                self.check_cancelled(&packet, Some(call.trace));
+
                 tracing::trace!(
-                    "completing rpc with error: channel_id=0x{:02x}, service_id=0x{:08x}, method_id=0x{:08x}, call_id=0x{:02x}, status={}",
-                    packet.channel_id, packet.service_id, packet.method_id, packet.call_id, packet.status
+                    "completing rpc with error: trace_id={}, channel_id=0x{:02x}, service_id=0x{:08x}, method_id=0x{:08x}, call_id=0x{:02x}, status={}",
+                    call.trace, packet.channel_id, packet.service_id, packet.method_id, packet.call_id, packet.status
                 );
 
                 let status = Status::from(packet.status);
+                self.emit_completed(&call, status);
                 call.complete_with_error(status).await;
             },
             None => {               // no pending call found, silently drop packet
+                self.check_cancelled(&packet, None);
+
                 tracing::debug!(
                     "received error for non-pending rpc: channel_id=0x{:02x}, service_id=0x{:08x}, method_id=0x{:08x}, call_id=0x{:02x}, status={}",
                     packet.channel_id, packet.service_id, packet.method_id, packet.call_id, packet.status
@@ -255,13 +362,19 @@ where
 
     async fn rpc_stream_push(&mut self, packet: RpcPacket) -> Result<(), Error> {
         let uid = CallUid::from_packet(&packet);
+
+        let trace = self.find_call_mut(uid).map(|call| call.trace);
+        self.check_cancelled(&packet, trace);
+
         let call = self.find_call_mut(uid);
 
         match call {
             Some(call) => {         // pending call found, forward packet to caller
+                let _span = call.span.clone().entered();
+
                 tracing::trace!(
-                    "pushing server stream packet to caller: channel_id=0x{:02x}, service_id=0x{:08x}, method_id=0x{:08x}, call_id=0x{:02x}",
-                    packet.channel_id, packet.service_id, packet.method_id, packet.call_id
+                    "pushing server stream packet to caller: trace_id={}, channel_id=0x{:02x}, service_id=0x{:08x}, method_id=0x{:08x}, call_id=0x{:02x}",
+                    call.trace, packet.channel_id, packet.service_id, packet.method_id, packet.call_id
                 );
 
                 if call.ty.has_server_stream() {    // packet was expected, forward it
@@ -273,8 +386,8 @@ where
                     let mut call = self.find_and_remove_call(uid).unwrap();
 
                     tracing::warn!(
-                        "received stream packet for non-stream rpc: channel_id=0x{:02x}, service_id=0x{:08x}, method_id=0x{:08x}, call_id=0x{:02x}",
-                        packet.channel_id, packet.service_id, packet.method_id, packet.call_id
+                        "received stream packet for non-stream rpc: trace_id={}, channel_id=0x{:02x}, service_id=0x{:08x}, method_id=0x{:08x}, call_id=0x{:02x}",
+                        call.trace, packet.channel_id, packet.service_id, packet.method_id, packet.call_id
                     );
 
                     call.complete_with_error(Status::InvalidArgument).await;
@@ -294,10 +407,73 @@ where
         Ok(())
     }
 
+    /// Check whether the packet may belong to a recently cancelled call
+    /// instead of the pending call with the given trace ID. Packets are
+    /// matched to calls by their UID only, so a late response to a cancelled
+    /// call is indistinguishable from one to a newer call re-using its UID.
+    fn check_cancelled(&mut self, packet: &RpcPacket, trace: Option<u64>) {
+        let uid = CallUid::from_packet(packet);
+
+        let Some(index) = self.cancelled.iter().position(|c| *c == uid) else {
+            return;
+        };
+
+        // Assume that at most one late packet belongs to the cancelled call.
+        self.cancelled.remove(index);
+
+        if let Some(trace) = trace {
+            tracing::warn!(
+                "received packet matching multiple calls, it may belong to a cancelled one: trace_id={}, type=0x{:02x}, channel_id=0x{:02x}, service_id=0x{:08x}, method_id=0x{:08x}, call_id=0x{:02x}",
+                trace, packet.r#type, packet.channel_id, packet.service_id, packet.method_id, packet.call_id
+            );
+        }
+    }
+
+    fn track_channel(&mut self, channel: u32) {
+        let old = self.channel.swap(channel, Ordering::Relaxed);
+
+        // The first response only establishes the channel. Any later change
+        // means that the device has moved its traffic, e.g. due to the buds
+        // handing off processing between each other.
+        if old == 0 || old == channel {
+            return;
+        }
+
+        tracing::info!(old, new=channel, "rpc channel changed");
+
+        self.emit(Event::ChannelChanged { old, new: channel });
+    }
+
+    fn emit(&mut self, event: Event) {
+        self.events.retain(|tx| tx.unbounded_send(event).is_ok());
+    }
+
+    fn emit_completed(&mut self, call: &Call, status: Status) {
+        self.emit(Event::CallCompleted {
+            trace: call.trace,
+            service: call.uid.service,
+            method: call.uid.method,
+            status,
+            duration: call.started.elapsed(),
+        });
+    }
+
+    fn notify_watchers(&mut self, packet: &RpcPacket) {
+        // Forward the packet to all matching watchers and drop the ones that
+        // have gone away.
+        self.watchers.retain(|watcher| {
+            if watcher.service != packet.service_id || watcher.method != packet.method_id {
+                return !watcher.sender.is_closed();
+            }
+
+            watcher.sender.unbounded_send(packet.clone()).is_ok()
+        });
+    }
+
     async fn process_request(&mut self, request: CallRequest) -> Result<(), Error> {
         match request {
-            CallRequest::New { ty, uid, payload, sender, tx } => {
-                let call = Call { ty, uid, sender };
+            CallRequest::New { ty, uid, trace, payload, sender, span, tx } => {
+                let call = Call { ty, uid, trace, sender, span, started: Instant::now() };
 
                 let packet = RpcPacket {
                     r#type: PacketType::Request.into(),
@@ -310,10 +486,10 @@ where
                 };
 
                 let action = if tx { "starting" } else { "opening" };
-                tracing::trace!(
-                    "{} rpc: channel_id=0x{:02x}, service_id=0x{:08x}, method_id=0x{:08x}, call_id=0x{:02x}",
-                    action, packet.channel_id, packet.service_id, packet.method_id, packet.call_id,
-                );
+                call.span.in_scope(|| tracing::trace!(
+                    "{} rpc: trace_id={}, channel_id=0x{:02x}, service_id=0x{:08x}, method_id=0x{:08x}, call_id=0x{:02x}",
+                    action, call.trace, packet.channel_id, packet.service_id, packet.method_id, packet.call_id,
+                ));
 
                 self.pending.push(call);
                 if tx {
@@ -322,19 +498,42 @@ where
 
                 Ok(())
             },
-            CallRequest::Error { uid, code, tx } => {
-                match self.find_and_remove_call(uid) {
+            CallRequest::Watch { service, method, sender } => {
+                tracing::trace!(
+                    "watching method: service_id=0x{:08x}, method_id=0x{:08x}",
+                    service, method,
+                );
+
+                self.watchers.push(Watcher { service, method, sender });
+                Ok(())
+            },
+            CallRequest::Events { sender } => {
+                self.events.push(sender);
+                Ok(())
+            },
+            CallRequest::Error { uid, trace, code, tx } => {
+                match self.find_and_remove_traced_call(uid, trace) {
                     Some(mut call) => {
+                        let _span = call.span.clone().entered();
+
                         tracing::trace!(
-                            "cancelling active rpc with code: channel_id=0x{:02x}, service_id=0x{:08x}, method_id=0x{:08x}, call_id=0x{:02x}, code={}",
-                            uid.channel, uid.service, uid.method, uid.call, code as u32,
+                            "cancelling active rpc with code: trace_id={}, channel_id=0x{:02x}, service_id=0x{:08x}, method_id=0x{:08x}, call_id=0x{:02x}, code={}",
+                            call.trace, uid.channel, uid.service, uid.method, uid.call, code as u32,
                         );
 
+                        self.emit_completed(&call, code);
                         call.complete_with_error(code).await;
                         if tx {
                             self.send_client_error(uid, code).await?;
                         }
 
+                        // The peer may still respond to the call, remember it
+                        // to detect such responses.
+                        if self.cancelled.len() >= CANCELLED_HISTORY {
+                            self.cancelled.pop_front();
+                        }
+                        self.cancelled.push_back(uid);
+
                         Ok(())
                     },
                     None => {
@@ -350,12 +549,26 @@ where
     }
 
     fn find_and_remove_call(&mut self, uid: CallUid) -> Option<Call> {
-        let index = self.pending.iter().position(|call| call.uid == uid);
+        let index = self.pending.iter().position(|call| call.uid == uid)?;
+        Some(self.remove_call(index))
+    }
 
-        match index {
-            Some(index) => Some(self.pending.remove(index)),
-            None => None,
-        }
+    /// Like [`Self::find_and_remove_call`] but only matches the call with the
+    /// given trace ID, so that late requests for a completed call do not
+    /// affect a newer one re-using its UID.
+    fn find_and_remove_traced_call(&mut self, uid: CallUid, trace: u64) -> Option<Call> {
+        let index = self.pending.iter().position(|call| call.uid == uid && call.trace == trace)?;
+        Some(self.remove_call(index))
+    }
+
+    fn remove_call(&mut self, index: usize) -> Call {
+        let call = self.pending.remove(index);
+
+        // Release the UID before the caller gets notified, so that it can be
+        // re-used as soon as the call has completed.
+        self.active.release(call.uid, call.trace);
+
+        call
     }
 
     fn find_call_mut(&mut self, uid: CallUid) -> Option<&mut Call> {
@@ -383,16 +596,45 @@ where
         self.send(error_packet).await
     }
 
+    /// Queue the given packet on the transport. Packets are only written on
+    /// the next flush.
     async fn send(&mut self, packet: RpcPacket) -> Result<(), Error> {
-        self.io_tx.send(packet).await?;
+        tracing::trace!(
+            "sending packet: type=0x{:02x}, channel_id=0x{:02x}, service_id=0x{:08x}, method_id=0x{:08x}, call_id=0x{:02x}",
+            packet.r#type, packet.channel_id, packet.service_id, packet.method_id, packet.call_id
+        );
+
+        self.unflushed += packet.encoded_len();
+        self.io_tx.feed(packet).await?;
+        Ok(())
+    }
+
+    async fn flush(&mut self) -> Result<(), Error> {
+        if self.unflushed > 0 {
+            self.unflushed = 0;
+            self.io_tx.flush().await?;
+        }
+
         Ok(())
     }
 }
 
 
+/// Handle for issuing calls on a [`Client`].
+///
+/// Handles are cheap to clone and both `Send` and `Sync`. Any number of
+/// consumers, possibly running on different tasks or threads, may issue calls
+/// concurrently via their own clone. Calls are only processed while the
+/// client itself is being run, and each call must use a unique call ID per
+/// channel and method for as long as it is active.
 #[derive(Debug, Clone)]
 pub struct ClientHandle {
     queue_tx: mpsc::UnboundedSender<CallRequest>,
+    active: ActiveCalls,
+    channel: Arc<AtomicU32>,
+    traces: Arc<AtomicU64>,
+    calls: Arc<AtomicU32>,
+    call_timeout: Arc<AtomicU64>,
 }
 
 impl ClientHandle {
@@ -442,13 +684,21 @@ impl ClientHandle {
         let payload = request.message.encode_to_vec();
         let queue_tx = self.queue_tx.clone();
 
-        let request = CallRequest::New { ty, uid, payload, sender, tx: true };
-        let handle = CallHandle { uid, queue_tx, receiver, cancel_on_drop: true };
+        let trace = self.next_trace();
+        let span = uid.span(trace);
 
-        self.queue_tx.unbounded_send(request)
-            .map_err(|_| Error::aborted("the channel has been closed, no new calls are allowed"))?;
+        let request = CallRequest::New { ty, uid, trace, payload, sender, span: span.clone(), tx: true };
+
+        self.submit(uid, trace, request)?;
+
+        let timeout = match self.call_timeout.load(Ordering::Relaxed) {
+            0 => None,
+            ms => Some(Duration::from_millis(ms)),
+        };
+
+        let active = self.active.clone();
 
-        Ok(handle)
+        Ok(CallHandle { uid, trace, queue_tx, active, receiver, cancel_on_drop: true, span, path: None, timeout })
     }
 
     pub fn open_unary<M>(&mut self, request: Request<()>) -> Result<UnaryResponse<M>, Error>
@@ -495,18 +745,113 @@ impl ClientHandle {
         let payload = Vec::new();
         let queue_tx = self.queue_tx.clone();
 
-        let request = CallRequest::New { ty, uid, payload, sender, tx: false };
-        let handle = CallHandle { uid, queue_tx, receiver, cancel_on_drop: false };
+        let trace = self.next_trace();
+        let span = uid.span(trace);
+
+        let request = CallRequest::New { ty, uid, trace, payload, sender, span: span.clone(), tx: false };
+
+        self.submit(uid, trace, request)?;
+
+        let active = self.active.clone();
+
+        Ok(CallHandle { uid, trace, queue_tx, active, receiver, cancel_on_drop: false, span, path: None, timeout: None })
+    }
+
+    /// Watch all packets received for the given service method.
+    ///
+    /// The returned stream yields every packet received for the method,
+    /// regardless of its channel, call ID, or type, and regardless of whether
+    /// there is a pending call for it. Packets are still processed as usual,
+    /// i.e., watching does not consume them. This allows discovering
+    /// unsolicited responses, e.g. for exploration or channel probing.
+    ///
+    /// Watchers registered before the client is run will receive packets
+    /// from the very start, similar to `open()` calls.
+    pub fn watch_method<'a>(&mut self, path: impl Into<PathRef<'a>>) -> Result<MethodWatch, Error> {
+        let path = path.into();
+        let (sender, receiver) = mpsc::unbounded();
+
+        let request = CallRequest::Watch {
+            service: path.service().hash(),
+            method: path.method().hash(),
+            sender,
+        };
 
         self.queue_tx.unbounded_send(request)
             .map_err(|_| Error::aborted("the channel has been closed, no new calls are allowed"))?;
 
-        Ok(handle)
+        Ok(MethodWatch { receiver })
+    }
+
+    /// The channel on which response traffic has last been received, if any.
+    ///
+    /// This changes when the device moves its traffic to a different
+    /// channel, e.g. on bud handoff.
+    pub fn active_channel(&self) -> Option<u32> {
+        match self.channel.load(Ordering::Relaxed) {
+            0 => None,
+            channel => Some(channel),
+        }
+    }
+
+    /// Subscribe to client events, e.g. channel changes. The stream ends when
+    /// the client terminates.
+    pub fn subscribe_events(&mut self) -> Result<EventStream, Error> {
+        let (sender, receiver) = mpsc::unbounded();
+
+        self.queue_tx.unbounded_send(CallRequest::Events { sender })
+            .map_err(|_| Error::aborted("the channel has been closed, no new calls are allowed"))?;
+
+        Ok(EventStream { receiver })
+    }
+
+    /// Set the time to wait for the response of unary calls issued
+    /// afterwards, shared by all handles of the client. Calls not completed in
+    /// time are cancelled and fail with [`Status::DeadlineExceeded`]. Server
+    /// streams and opened calls are not affected.
+    pub fn set_call_timeout(&self, timeout: Option<Duration>) {
+        let ms = timeout.map(|t| t.as_millis().clamp(1, u64::MAX as u128) as u64).unwrap_or(0);
+        self.call_timeout.store(ms, Ordering::Relaxed);
+    }
+
+    /// Allocate a call ID for running multiple calls of the same method
+    /// concurrently. Call IDs are handed out in increasing order starting at
+    /// one, so that they do not collide with calls using the default ID zero.
+    pub fn next_call_id(&self) -> u32 {
+        self.calls.fetch_add(1, Ordering::Relaxed).wrapping_add(1).max(1)
+    }
+
+    /// Allocate a new trace ID. Trace IDs are unique and monotonically
+    /// increasing per client, so that log lines of concurrent calls can be
+    /// correlated even if they share the same UID over time.
+    fn next_trace(&self) -> u64 {
+        self.traces.fetch_add(1, Ordering::Relaxed) + 1
+    }
+
+    fn submit(&mut self, uid: CallUid, trace: u64, request: CallRequest) -> Result<(), Error> {
+        // Responses are matched to calls by their UID only. Reject calls
+        // colliding with a pending one, as otherwise responses could complete
+        // the wrong call.
+        if !self.active.claim(uid, trace) {
+            tracing::warn!(
+                "rejecting rpc colliding with pending call: channel_id=0x{:02x}, service_id=0x{:08x}, method_id=0x{:08x}, call_id=0x{:02x}",
+                uid.channel, uid.service, uid.method, uid.call,
+            );
+
+            return Err(Error::already_exists("a call with the same channel, method, and call ID is already pending"));
+        }
+
+        if self.queue_tx.unbounded_send(request).is_err() {
+            self.active.release(uid, trace);
+            return Err(Error::aborted("the channel has been closed, no new calls are allowed"));
+        }
+
+        Ok(())
     }
 }
 
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 struct CallUid {
     channel: u32,
     service: u32,
@@ -523,6 +868,57 @@ impl CallUid {
             call: packet.call_id
         }
     }
+
+    /// Create a tracing span for this call. Spans are only created with the
+    /// `instrument` feature enabled.
+    fn span(&self, trace: u64) -> tracing::Span {
+        #[cfg(feature = "instrument")]
+        {
+            tracing::debug_span!(
+                "rpc",
+                trace_id = trace,
+                channel = self.channel,
+                service = %format_args!("0x{:08x}", self.service),
+                method = %format_args!("0x{:08x}", self.method),
+                call_id = self.call,
+                path = tracing::field::Empty,
+            )
+        }
+
+        #[cfg(not(feature = "instrument"))]
+        {
+            let _ = trace;
+            tracing::Span::none()
+        }
+    }
+}
+
+
+/// UIDs of all calls that have been requested but not completed yet, mapped
+/// to the trace ID of the call holding them.
+#[derive(Debug, Clone, Default)]
+struct ActiveCalls(Arc<Mutex<HashMap<CallUid, u64>>>);
+
+impl ActiveCalls {
+    /// Claim the UID for the given call. Fails if it is held by another call.
+    fn claim(&self, uid: CallUid, trace: u64) -> bool {
+        match self.0.lock().unwrap().entry(uid) {
+            Entry::Occupied(_) => false,
+            Entry::Vacant(entry) => {
+                entry.insert(trace);
+                true
+            },
+        }
+    }
+
+    /// Release the UID if it is still held by the given call.
+    fn release(&self, uid: CallUid, trace: u64) {
+        let mut active = self.0.lock().unwrap();
+
+        if active.get(&uid) == Some(&trace) {
+            active.remove(&uid);
+        }
+    }
 }
 
 
@@ -531,18 +927,37 @@ enum CallRequest {
     New {
         ty: RpcType,
         uid: CallUid,
+        trace: u64,
         payload: Vec<u8>,
         sender: mpsc::UnboundedSender<CallUpdate>,
+        span: tracing::Span,
         tx: bool,
     },
+    Watch {
+        service: u32,
+        method: u32,
+        sender: mpsc::UnboundedSender<RpcPacket>,
+    },
+    Events {
+        sender: mpsc::UnboundedSender<Event>,
+    },
     Error {
         uid: CallUid,
+        trace: u64,
         code: Status,
         tx: bool,
     },
 }
 
 
+#[derive(Debug)]
+struct Watcher {
+    service: u32,
+    method: u32,
+    sender: mpsc::UnboundedSender<RpcPacket>,
+}
+
+
 #[derive(Debug)]
 enum CallUpdate {
     Complete {
@@ -562,7 +977,10 @@ enum CallUpdate {
 struct Call {
     ty: RpcType,
     uid: CallUid,
+    trace: u64,
     sender: mpsc::UnboundedSender<CallUpdate>,
+    span: tracing::Span,
+    started: Instant,
 }
 
 impl Call {
@@ -590,22 +1008,22 @@ impl Call {
             match update {
                 CallUpdate::Complete { .. } => {
                     tracing::warn!(
-                        "cannot send call update, caller is gone: channel_id=0x{:02x}, service_id=0x{:08x}, method_id=0x{:08x}, call_id=0x{:02x}, update=complete",
-                        self.uid.channel, self.uid.service, self.uid.method, self.uid.call,
+                        "cannot send call update, caller is gone: trace_id={}, channel_id=0x{:02x}, service_id=0x{:08x}, method_id=0x{:08x}, call_id=0x{:02x}, update=complete",
+                        self.trace, self.uid.channel, self.uid.service, self.uid.method, self.uid.call,
                     )
                 },
                 CallUpdate::StreamItem { .. } => {
                     tracing::warn!(
-                        "cannot send call update, caller is gone: channel_id=0x{:02x}, service_id=0x{:08x}, method_id=0x{:08x}, call_id=0x{:02x}, update=stream",
-                        self.uid.channel, self.uid.service, self.uid.method, self.uid.call,
+                        "cannot send call update, caller is gone: trace_id={}, channel_id=0x{:02x}, service_id=0x{:08x}, method_id=0x{:08x}, call_id=0x{:02x}, update=stream",
+                        self.trace, self.uid.channel, self.uid.service, self.uid.method, self.uid.call,
                     )
                 },
                 CallUpdate::Error { status } => {
                     let code: u32 = status.into();
 
                     tracing::trace!(
-                        "cannot send call update, caller is gone: channel_id=0x{:02x}, service_id=0x{:08x}, method_id=0x{:08x}, call_id=0x{:02x}, update=error, error={}",
-                        self.uid.channel, self.uid.service, self.uid.method, self.uid.call, code,
+                        "cannot send call update, caller is gone: trace_id={}, channel_id=0x{:02x}, service_id=0x{:08x}, method_id=0x{:08x}, call_id=0x{:02x}, update=error, error={}",
+                        self.trace, self.uid.channel, self.uid.service, self.uid.method, self.uid.call, code,
                     )
                 },
             }
@@ -628,9 +1046,16 @@ impl Drop for Call {
 
 struct CallHandle {
     uid: CallUid,
+    trace: u64,
     queue_tx: mpsc::UnboundedSender<CallRequest>,
+    active: ActiveCalls,
     receiver: mpsc::UnboundedReceiver<CallUpdate>,
     cancel_on_drop: bool,
+    span: tracing::Span,
+    path: Option<String>,
+
+    /// Time to wait for the result of a unary call.
+    timeout: Option<Duration>,
 }
 
 impl CallHandle {
@@ -638,14 +1063,39 @@ impl CallHandle {
         self.queue_tx.is_closed()
     }
 
+    /// Error for the given status, annotated with the path of the call.
+    fn status_error(&self, status: Status) -> Error {
+        self.annotate(Error::from(status))
+    }
+
+    /// Annotate the given error with the path of the call.
+    fn annotate(&self, error: Error) -> Error {
+        match &self.path {
+            Some(path) => error.with_path(path.clone()),
+            None => error,
+        }
+    }
+
     fn error(&mut self, code: Status, tx: bool) -> bool {
-        let request = CallRequest::Error { uid: self.uid, code, tx };
+        if !self.queue_tx.is_closed() {
+            self.span.in_scope(|| tracing::trace!(
+                "requesting rpc cancellation: trace_id={}, channel_id=0x{:02x}, service_id=0x{:08x}, method_id=0x{:08x}, call_id=0x{:02x}, code={}, tx={}",
+                self.trace, self.uid.channel, self.uid.service, self.uid.method, self.uid.call, code as u32, tx,
+            ));
+        }
+
+        let request = CallRequest::Error { uid: self.uid, trace: self.trace, code, tx };
         let ok = self.queue_tx.unbounded_send(request).is_ok();
 
         // Sending an error will complete the RPC. Disconnect our queue end to
         // prevent more errors/cancel-requests to be sent.
         self.queue_tx.disconnect();
 
+        // Release the UID right away, so that it can be re-used for a new
+        // call without waiting for the client to process the request. The
+        // request is queued before any such call, so it cannot affect it.
+        self.active.release(self.uid, self.trace);
+
         ok
     }
 
@@ -661,11 +1111,29 @@ impl CallHandle {
         self.error(Status::Cancelled, true)
     }
 
-    async fn cancel_and_wait(&mut self) -> Result<(), Error> {
+    async fn cancel_and_wait(&mut self, timeout: Duration) -> Result<(), Error> {
         if !self.cancel() {
             return Ok(())
         }
 
+        match tokio::time::timeout(timeout, self.wait_cancelled()).await {
+            Ok(result) => result,
+            Err(_) => {
+                self.span.in_scope(|| tracing::debug!(
+                    "rpc cancellation not acknowledged in time, completing locally: trace_id={}, channel_id=0x{:02x}, service_id=0x{:08x}, method_id=0x{:08x}, call_id=0x{:02x}",
+                    self.trace, self.uid.channel, self.uid.service, self.uid.method, self.uid.call,
+                ));
+
+                // Consider the call complete and stop receiving updates for
+                // it. Buffered updates can still be received.
+                self.receiver.close();
+
+                Err(self.annotate(Error::cancelled("cancellation not acknowledged in time, completed locally")))
+            },
+        }
+    }
+
+    async fn wait_cancelled(&mut self) -> Result<(), Error> {
         loop {
             match self.receiver.next().await {
                 Some(CallUpdate::StreamItem { .. }) => {
@@ -678,7 +1146,7 @@ impl CallHandle {
                     return Ok(())
                 },
                 Some(CallUpdate::Error { status }) => {
-                    return Err(Error::from(status))
+                    return Err(self.status_error(status))
                 },
                 None => {
                     return Ok(())
@@ -718,20 +1186,39 @@ where
     M: Message + Default,
 {
     pub async fn result(&mut self) -> Result<M, Error> {
-        let update = match self.handle.receiver.next().await {
+        let span = self.handle.span.clone();
+        self.result_inner().instrument(span).await
+    }
+
+    async fn result_inner(&mut self) -> Result<M, Error> {
+        let update = match self.handle.timeout {
+            Some(timeout) => match tokio::time::timeout(timeout, self.handle.receiver.next()).await {
+                Ok(update) => update,
+                Err(_) => {
+                    self.handle.cancel();
+                    return Err(self.handle.annotate(Error::deadline_exceeded("no response received in time")));
+                },
+            },
+            None => self.handle.receiver.next().await,
+        };
+
+        let update = match update {
             Some(update) => update,
             None => return Err(Error::resource_exhausted("cannot fetch result() multiple times")),
         };
 
+        // The call has been completed. Disconnect our queue end so that we
+        // don't send cancel requests for it, which could otherwise affect a
+        // new call re-using the same UID.
+        self.handle.queue_tx.disconnect();
+
         let data = match update {
             CallUpdate::Complete { data, status: Status::Ok } => data,
-            CallUpdate::Complete { status, .. } => return Err(Error::from(status)),
-            CallUpdate::Error { status } => return Err(Error::from(status)),
+            CallUpdate::Complete { status, .. } => return Err(self.handle.status_error(status)),
+            CallUpdate::Error { status } => return Err(self.handle.status_error(status)),
             CallUpdate::StreamItem { .. } => unreachable!("received stream update on unary rpc"),
         };
 
-        self.handle.queue_tx.disconnect();
-
         let message = M::decode(&data[..])?;
         Ok(message)
     }
@@ -748,13 +1235,28 @@ where
         self.handle.cancel()
     }
 
+    /// Cancel the call and wait for the cancellation to be acknowledged, for
+    /// at most [`DEFAULT_CANCEL_TIMEOUT`]. See [`Self::cancel_and_wait_timeout`].
     pub async fn cancel_and_wait(&mut self) -> Result<(), Error> {
-        self.handle.cancel_and_wait().await
+        self.handle.cancel_and_wait(DEFAULT_CANCEL_TIMEOUT).await
+    }
+
+    /// Cancel the call and wait for the cancellation to be acknowledged, for
+    /// at most the given duration. If the cancellation has not been
+    /// acknowledged in time, e.g. because the client is stuck, the call is
+    /// completed locally and an error with [`Status::Cancelled`] is returned.
+    pub async fn cancel_and_wait_timeout(&mut self, timeout: Duration) -> Result<(), Error> {
+        self.handle.cancel_and_wait(timeout).await
     }
 
     pub fn is_complete(&self) -> bool {
         self.handle.is_complete()
     }
+
+    /// Trace ID of this call, as included in the log messages of the client.
+    pub fn trace_id(&self) -> u64 {
+        self.handle.trace
+    }
 }
 
 
@@ -786,13 +1288,28 @@ where
         self.handle.cancel()
     }
 
+    /// Cancel the call and wait for the cancellation to be acknowledged, for
+    /// at most [`DEFAULT_CANCEL_TIMEOUT`]. See [`Self::cancel_and_wait_timeout`].
     pub async fn cancel_and_wait(&mut self) -> Result<(), Error> {
-        self.handle.cancel_and_wait().await
+        self.handle.cancel_and_wait(DEFAULT_CANCEL_TIMEOUT).await
+    }
+
+    /// Cancel the call and wait for the cancellation to be acknowledged, for
+    /// at most the given duration. If the cancellation has not been
+    /// acknowledged in time, e.g. because the client is stuck, the call is
+    /// completed locally and an error with [`Status::Cancelled`] is returned.
+    pub async fn cancel_and_wait_timeout(&mut self, timeout: Duration) -> Result<(), Error> {
+        self.handle.cancel_and_wait(timeout).await
     }
 
     pub fn is_complete(&self) -> bool {
         self.handle.is_complete()
     }
+
+    /// Trace ID of this call, as included in the log messages of the client.
+    pub fn trace_id(&self) -> u64 {
+        self.handle.trace
+    }
 }
 
 
@@ -808,6 +1325,8 @@ where
     type Item = Result<M, Error>;
 
     fn poll_next(mut self: Pin<&mut Self>, cx: &mut std::task::Context<'_>) -> Poll<Option<Self::Item>> {
+        let _span = self.handle.span.clone().entered();
+
         let update = match Pin::new(&mut self.handle.receiver).poll_next(cx) {
             Poll::Ready(Some(update)) => update,
             Poll::Ready(None) => return Poll::Ready(None),
@@ -828,7 +1347,7 @@ where
             CallUpdate::Error { status } => {
                 self.handle.receiver.close();
                 self.handle.queue_tx.disconnect();
-                return Poll::Ready(Some(Err(Error::from(status))));
+                return Poll::Ready(Some(Err(self.handle.status_error(status))));
             },
         };
 
@@ -860,10 +1379,87 @@ where
 }
 
 
+/// Stream of packets received for a watched method.
+///
+/// See [`ClientHandle::watch_method`]. The stream ends when the client
+/// terminates.
+#[derive(Debug)]
+pub struct MethodWatch {
+    receiver: mpsc::UnboundedReceiver<RpcPacket>,
+}
+
+impl Stream for MethodWatch {
+    type Item = RpcPacket;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut std::task::Context<'_>) -> Poll<Option<Self::Item>> {
+        Pin::new(&mut self.receiver).poll_next(cx)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.receiver.size_hint()
+    }
+}
+
+impl FusedStream for MethodWatch {
+    fn is_terminated(&self) -> bool {
+        self.receiver.is_terminated()
+    }
+}
+
+
+/// Events emitted by the client.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Event {
+    /// Response traffic has been received on a new channel. Calls on the old
+    /// channel will likely not be answered any more.
+    ChannelChanged {
+        old: u32,
+        new: u32,
+    },
+
+    /// A call has been completed, either by the peer or by cancelling it
+    /// locally, e.g. after the first item of a server stream has been
+    /// received.
+    CallCompleted {
+        trace: u64,
+        service: u32,
+        method: u32,
+        status: Status,
+
+        /// Time since the call has been started.
+        duration: Duration,
+    },
+}
+
+
+/// Stream of client events.
+///
+/// See [`ClientHandle::subscribe_events`].
+#[derive(Debug)]
+pub struct EventStream {
+    receiver: mpsc::UnboundedReceiver<Event>,
+}
+
+impl Stream for EventStream {
+    type Item = Event;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut std::task::Context<'_>) -> Poll<Option<Self::Item>> {
+        Pin::new(&mut self.receiver).poll_next(cx)
+    }
+}
+
+impl FusedStream for EventStream {
+    fn is_terminated(&self) -> bool {
+        self.receiver.is_terminated()
+    }
+}
+
+
 #[derive(Debug, Clone)]
 pub struct UnaryRpc<M1, M2> {
-    marker1: std::marker::PhantomData<*const M1>,
-    marker2: std::marker::PhantomData<*const M2>,
+    marker1: std::marker::PhantomData<fn() -> M1>,
+    marker2: std::marker::PhantomData<fn() -> M2>,
     path: Path,
 }
 
@@ -891,7 +1487,11 @@ where
             message,
         };
 
-        handle.call_unary(req)
+        let mut rsp = handle.call_unary(req)?;
+        rsp.handle.span.record("path", self.path.name());
+        rsp.handle.path = Some(self.path.name().to_owned());
+
+        Ok(rsp)
     }
 
     pub fn open(&self, handle: &mut ClientHandle, channel_id: u32, call_id: u32)
@@ -905,15 +1505,19 @@ where
             message: (),
         };
 
-        handle.open_unary(req)
+        let mut rsp = handle.open_unary(req)?;
+        rsp.handle.span.record("path", self.path.name());
+        rsp.handle.path = Some(self.path.name().to_owned());
+
+        Ok(rsp)
     }
 }
 
 
 #[derive(Debug, Clone)]
 pub struct ServerStreamRpc<M1, M2> {
-    marker1: std::marker::PhantomData<*const M1>,
-    marker2: std::marker::PhantomData<*const M2>,
+    marker1: std::marker::PhantomData<fn() -> M1>,
+    marker2: std::marker::PhantomData<fn() -> M2>,
     path: Path,
 }
 
@@ -941,7 +1545,11 @@ where
             message,
         };
 
-        handle.call_server_stream(req)
+        let mut rsp = handle.call_server_stream(req)?;
+        rsp.handle.span.record("path", self.path.name());
+        rsp.handle.path = Some(self.path.name().to_owned());
+
+        Ok(rsp)
     }
 
     pub fn open(&self, handle: &mut ClientHandle, channel_id: u32, call_id: u32)
@@ -955,6 +1563,313 @@ where
             message: (),
         };
 
-        handle.open_server_stream(req)
+        let mut rsp = handle.open_server_stream(req)?;
+        rsp.handle.span.record("path", self.path.name());
+        rsp.handle.path = Some(self.path.name().to_owned());
+
+        Ok(rsp)
+    }
+}
+
+
+// Handles and responses are used across tasks, make sure they can be.
+const _: () = {
+    const fn assert_send_sync<T: Send + Sync>() {}
+    const fn assert_send<T: Send>() {}
+
+    assert_send_sync::<ClientHandle>();
+    assert_send_sync::<UnaryRpc<(), ()>>();
+    assert_send_sync::<ServerStreamRpc<(), ()>>();
+    assert_send::<UnaryResponse<()>>();
+    assert_send::<StreamResponse<()>>();
+    assert_send::<ServerStream<'static, ()>>();
+    assert_send::<MethodWatch>();
+    assert_send::<EventStream>();
+};
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::mock::{Device, fixture};
+    use crate::protocol::codec::Codec;
+    use crate::protocol::types::{RuntimeInfo, SoftwareInfo};
+    use crate::protocol::utils;
+
+    #[tokio::test]
+    async fn test_call_collision() {
+        let device = Device::new();
+
+        let (mut client, channel) = fixture::connect(&device).await;
+        let mut handle = client.handle();
+
+        let rpc: UnaryRpc<(), SoftwareInfo> = UnaryRpc::new("maestro_pw.Maestro/GetSoftwareInfo");
+
+        let task = async {
+            let mut first = rpc.call(&mut handle, channel, 7, ()).unwrap();
+
+            // a second call with the same UID must be rejected
+            let err = rpc.call(&mut handle, channel, 7, ()).err().unwrap();
+            assert_eq!(err.code(), Status::AlreadyExists);
+
+            // calls with different UIDs are fine
+            let mut other = rpc.call(&mut handle, channel, 8, ()).unwrap();
+
+            first.result().await.unwrap();
+            other.result().await.unwrap();
+
+            // the UID can be re-used once the call has completed
+            rpc.call(&mut handle, channel, 7, ()).unwrap()
+                .result().await.unwrap();
+        };
+
+        fixture::run(&mut client, task).await;
+    }
+
+    #[tokio::test]
+    async fn test_call_resubmit() {
+        let device = Device::new();
+
+        let (mut client, channel) = fixture::connect(&device).await;
+        let mut handle = client.handle();
+
+        let rpc: UnaryRpc<(), SoftwareInfo> = UnaryRpc::new("maestro_pw.Maestro/GetSoftwareInfo");
+
+        // the UID can be re-used right after cancelling or dropping a call
+        let mut first = rpc.call(&mut handle, channel, 0, ()).unwrap();
+        first.cancel();
+
+        let second = rpc.call(&mut handle, channel, 0, ()).unwrap();
+        drop(second);
+
+        let mut third = rpc.call(&mut handle, channel, 0, ()).unwrap();
+        let mut events = handle.subscribe_events().unwrap();
+
+        let task = async {
+            // wait for the response without retrieving it
+            loop {
+                match events.next().await {
+                    Some(Event::CallCompleted { trace, .. }) if trace == third.trace_id() => break,
+                    Some(_) => continue,
+                    None => panic!("event stream closed"),
+                }
+            }
+
+            // late cancellations of completed calls do not affect new ones
+            let mut fourth = rpc.call(&mut handle, channel, 0, ()).unwrap();
+            third.cancel();
+
+            assert_eq!(rpc.call(&mut handle, channel, 0, ()).err().unwrap().code(), Status::AlreadyExists);
+            fourth.result().await.unwrap();
+        };
+
+        fixture::run(&mut client, task).await;
+    }
+
+    #[tokio::test]
+    async fn test_trace_id() {
+        let device = Device::new();
+
+        let (mut client, channel) = fixture::connect(&device).await;
+        let mut handle = client.handle();
+
+        let rpc: UnaryRpc<(), SoftwareInfo> = UnaryRpc::new("maestro_pw.Maestro/GetSoftwareInfo");
+
+        let task = async {
+            let mut first = rpc.call(&mut handle, channel, 1, ()).unwrap();
+            let mut second = rpc.call(&mut handle.clone(), channel, 2, ()).unwrap();
+
+            // trace IDs are shared across handles and monotonic
+            assert!(second.trace_id() > first.trace_id());
+
+            first.result().await.unwrap();
+            second.result().await.unwrap();
+
+            // re-using a UID still gives a new trace ID
+            let third = rpc.call(&mut handle, channel, 1, ()).unwrap();
+            assert!(third.trace_id() > second.trace_id());
+        };
+
+        fixture::run(&mut client, task).await;
+    }
+
+    /// Transport recording the number of packets written per flush.
+    struct Batches<S> {
+        inner: S,
+        fed: usize,
+        flushes: Arc<Mutex<Vec<usize>>>,
+    }
+
+    impl<S: Sink<RpcPacket> + Unpin> Sink<RpcPacket> for Batches<S> {
+        type Error = S::Error;
+
+        fn poll_ready(mut self: Pin<&mut Self>, cx: &mut std::task::Context<'_>) -> Poll<Result<(), Self::Error>> {
+            Pin::new(&mut self.inner).poll_ready(cx)
+        }
+
+        fn start_send(mut self: Pin<&mut Self>, item: RpcPacket) -> Result<(), Self::Error> {
+            self.fed += 1;
+            Pin::new(&mut self.inner).start_send(item)
+        }
+
+        fn poll_flush(mut self: Pin<&mut Self>, cx: &mut std::task::Context<'_>) -> Poll<Result<(), Self::Error>> {
+            let poll = Pin::new(&mut self.inner).poll_flush(cx);
+
+            if poll.is_ready() && self.fed > 0 {
+                let fed = std::mem::take(&mut self.fed);
+                self.flushes.lock().unwrap().push(fed);
+            }
+
+            poll
+        }
+
+        fn poll_close(mut self: Pin<&mut Self>, cx: &mut std::task::Context<'_>) -> Poll<Result<(), Self::Error>> {
+            Pin::new(&mut self.inner).poll_close(cx)
+        }
+    }
+
+    impl<S: Stream + Unpin> Stream for Batches<S> {
+        type Item = S::Item;
+
+        fn poll_next(mut self: Pin<&mut Self>, cx: &mut std::task::Context<'_>) -> Poll<Option<Self::Item>> {
+            Pin::new(&mut self.inner).poll_next(cx)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_pipelining() {
+        let device = Device::new();
+
+        let (stream, server) = device.connect();
+        tokio::spawn(server.run());
+
+        let flushes = Arc::new(Mutex::new(Vec::new()));
+        let stream = Batches { inner: Codec::new().wrap(stream), fed: 0, flushes: flushes.clone() };
+
+        let mut client = Client::new(stream);
+        let mut handle = client.handle();
+        let channel = utils::resolve_channel(&mut client).await.unwrap();
+
+        let rpc: UnaryRpc<(), SoftwareInfo> = UnaryRpc::new("maestro_pw.Maestro/GetSoftwareInfo");
+
+        // queue multiple calls before the client gets to process them
+        let mut calls: Vec<_> = (1..=4)
+            .map(|id| rpc.call(&mut handle, channel, id, ()).unwrap())
+            .collect();
+
+        flushes.lock().unwrap().clear();
+
+        let task = async {
+            for call in &mut calls {
+                call.result().await.unwrap();
+            }
+        };
+
+        fixture::run(&mut client, task).await;
+
+        assert_eq!(*flushes.lock().unwrap(), [4]);
+    }
+
+    #[tokio::test]
+    async fn test_call_completed_event() {
+        let device = Device::new();
+
+        let (mut client, channel) = fixture::connect(&device).await;
+        let mut handle = client.handle();
+
+        let path = Path::new("maestro_pw.Maestro/GetSoftwareInfo");
+        let rpc: UnaryRpc<(), SoftwareInfo> = UnaryRpc::new(path.name());
+
+        let mut events = handle.subscribe_events().unwrap();
+
+        let task = async {
+            let mut call = rpc.call(&mut handle, channel, 9, ()).unwrap();
+            let trace = call.trace_id();
+            call.result().await.unwrap();
+
+            let Some(Event::CallCompleted { trace: t, service, method, status, .. }) = events.next().await else {
+                panic!("expected call completion event");
+            };
+
+            assert_eq!(t, trace);
+            assert_eq!((service, method), (path.service().hash(), path.method().hash()));
+            assert_eq!(status, Status::Ok);
+        };
+
+        fixture::run(&mut client, task).await;
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_cancel_timeout() {
+        let device = Device::new();
+
+        let (client, channel) = fixture::connect(&device).await;
+        let mut handle = client.handle();
+
+        let path = "maestro_pw.Maestro/SubscribeRuntimeInfo";
+        let rpc: ServerStreamRpc<(), RuntimeInfo> = ServerStreamRpc::new(path);
+        let mut call = rpc.call(&mut handle, channel, 5, ()).unwrap();
+
+        // the client is not running, so the cancellation is never processed
+        let err = call.cancel_and_wait_timeout(Duration::from_secs(1)).await.unwrap_err();
+        assert_eq!(err.code(), Status::Cancelled);
+        assert_eq!(err.path(), Some(path));
+
+        assert!(call.stream().next().await.is_none());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_call_timeout() {
+        let device = Device::new();
+
+        let (mut client, channel) = fixture::connect(&device).await;
+        let mut handle = client.handle();
+
+        client.handle().set_call_timeout(Some(Duration::from_secs(2)));
+
+        let path = "maestro_pw.Maestro/GetSoftwareInfo";
+        let rpc: UnaryRpc<(), SoftwareInfo> = UnaryRpc::new(path);
+        let mut call = rpc.call(&mut handle, channel, 0, ()).unwrap();
+
+        // the client is not running, so the call is never answered
+        let err = call.result().await.unwrap_err();
+        assert_eq!(err.code(), Status::DeadlineExceeded);
+        assert_eq!(err.path(), Some(path));
+        assert!(call.is_complete());
+
+        // once disabled, calls are answered as usual
+        handle.set_call_timeout(None);
+        let mut call = rpc.call(&mut handle, channel, 1, ()).unwrap();
+
+        assert!(fixture::run(&mut client, call.result()).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_watch_method() {
+        let device = Device::new();
+
+        let (mut client, channel) = fixture::connect(&device).await;
+        let mut handle = client.handle();
+
+        let path = "maestro_pw.Maestro/GetSoftwareInfo";
+        let rpc: UnaryRpc<(), SoftwareInfo> = UnaryRpc::new(path);
+
+        let mut watch = handle.watch_method(path).unwrap();
+
+        let task = async {
+            rpc.call(&mut handle, channel, 3, ()).unwrap()
+                .result().await.unwrap();
+
+            let packet = watch.next().await.unwrap();
+            assert_eq!(packet.r#type, PacketType::Response as i32);
+            assert_eq!(packet.channel_id, channel);
+            assert_eq!(packet.call_id, 3);
+        };
+
+        fixture::run(&mut client, task).await;
+
+        client.terminate().await.unwrap();
+        assert!(watch.next().await.is_none());
     }
 }
@@ -1,6 +1,24 @@
+//! Service and method identifiers.
+//!
+//! On the wire, pw_rpc identifies services and methods not by name but by a
+//! 32-bit hash of their fully-qualified names, computed via [`hash`]. A
+//! [`Path`] combines a service and method name in the form `service/method`,
+//! e.g. `maestro_pw.Maestro/GetSoftwareInfo`.
+
+/// Hash of a service or method name, as used on the wire.
 pub type Hash = u32;
 
 
+/// Compute the hash of the given service or method name.
+///
+/// This is the 65599 hash used by pw_rpc: Starting with the length of the
+/// name, each character is multiplied by increasing powers of 65599 and added
+/// up, wrapping on overflow.
+pub fn hash(name: &str) -> Hash {
+    hash::hash_65599(name)
+}
+
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Id {
     name: String,
@@ -72,6 +90,10 @@ impl<'a> From<&'a String> for IdRef<'a> {
 }
 
 
+/// Fully-qualified method name in the form `service/method`.
+///
+/// Everything up to the last `/` is the service name, everything after it the
+/// method name.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Path {
     path: String,
@@ -86,6 +108,10 @@ impl Path {
         Path { path, split }
     }
 
+    pub fn name(&self) -> &str {
+        &self.path
+    }
+
     pub fn service(&self) -> IdRef<'_> {
         IdRef::new(&self.path[..self.split])
     }
@@ -116,6 +142,7 @@ impl From<String> for Path {
 }
 
 
+/// Borrowed variant of [`Path`].
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct PathRef<'a> {
     path: &'a str,
@@ -175,6 +202,8 @@ mod test {
         assert_eq!(IdRef::new("maestro_pw.Maestro").hash(), 0x7ede71ea);
         assert_eq!(IdRef::new("GetSoftwareInfo").hash(), 0x7199fa44);
         assert_eq!(IdRef::new("SubscribeToSettingsChanges").hash(), 0x2821adf5);
+
+        assert_eq!(hash("maestro_pw.Maestro"), 0x7ede71ea);
     }
 
     #[test]
@@ -1,3 +1,4 @@
+#[cfg(feature = "client")]
 pub mod client;
 pub mod id;
 pub mod types;
@@ -86,6 +86,7 @@ pub struct Error {
     code: Status,
     message: String,
     source: Option<Box<dyn std::error::Error + Send + Sync + 'static>>,
+    path: Option<String>,
 }
 
 impl Error {
@@ -94,6 +95,7 @@ impl Error {
             code,
             message: message.into(),
             source: None,
+            path: None,
         }
     }
 
@@ -170,6 +172,7 @@ impl Error {
             code,
             message: message.into(),
             source: Some(error.into()),
+            path: None,
         }
     }
 
@@ -180,6 +183,17 @@ impl Error {
     pub fn message(&self) -> &str {
         &self.message
     }
+
+    /// Associate this error with the RPC that failed, given by its path,
+    /// e.g. `maestro_pw.Maestro/WriteSetting`.
+    pub fn with_path(self, path: impl Into<String>) -> Self {
+        Self { path: Some(path.into()), ..self }
+    }
+
+    /// Path of the RPC that failed, if known.
+    pub fn path(&self) -> Option<&str> {
+        self.path.as_deref()
+    }
 }
 
 impl From<Status> for Error {
@@ -15,6 +15,75 @@ pub struct EncodedMessage {
     pub data: Vec<u8>,
 }
 
+impl EncodedMessage {
+    /// Best-effort schema-less decoding of the message, in a format similar
+    /// to `protoscope`, e.g. `1: 42` or `2: { 1: "abc" }`.
+    ///
+    /// Length-delimited fields are shown as string if they are printable, as
+    /// nested message if they can be decoded as such, and as hex otherwise.
+    /// Returns `None` if the data is not a valid protobuf message.
+    pub fn annotate(&self) -> Option<String> {
+        let mut out = String::new();
+        annotate_fields(&self.data, 0, &mut out)?;
+        Some(out)
+    }
+}
+
+fn annotate_fields(mut data: &[u8], indent: usize, out: &mut String) -> Option<()> {
+    use std::fmt::Write;
+
+    while !data.is_empty() {
+        let key = prost::encoding::decode_varint(&mut data).ok()?;
+        let (tag, wire_type) = (key >> 3, key & 0x07);
+
+        if tag == 0 {
+            return None;
+        }
+
+        let _ = write!(out, "{:indent$}{tag}: ", "");
+
+        match wire_type {
+            0 => {
+                let value = prost::encoding::decode_varint(&mut data).ok()?;
+                let _ = writeln!(out, "{value}");
+            },
+            1 => {
+                let value = u64::from_le_bytes(data.get(..8)?.try_into().ok()?);
+                data = &data[8..];
+                let _ = writeln!(out, "{value:#018x}i64");
+            },
+            2 => {
+                let len = prost::encoding::decode_varint(&mut data).ok()? as usize;
+                let value = data.get(..len)?;
+                data = &data[len..];
+
+                let string = std::str::from_utf8(value).ok()
+                    .filter(|s| !s.is_empty() && s.chars().all(|c| !c.is_control() || c == '\n' || c == '\t'));
+
+                let mut nested = String::new();
+
+                if let Some(string) = string {
+                    let _ = writeln!(out, "{string:?}");
+                } else if !value.is_empty() && annotate_fields(value, indent + 2, &mut nested).is_some() {
+                    let _ = writeln!(out, "{{");
+                    out.push_str(&nested);
+                    let _ = writeln!(out, "{:indent$}}}", "");
+                } else {
+                    let _ = writeln!(out, "{{{value:02x?}}}");
+                }
+            },
+            5 => {
+                let value = u32::from_le_bytes(data.get(..4)?.try_into().ok()?);
+                data = &data[4..];
+                let _ = writeln!(out, "{value:#010x}i32");
+            },
+            _ => return None,
+        }
+    }
+
+    Some(())
+}
+
 impl std::fmt::Debug for EncodedMessage {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "{:02x?}", self.data)
@@ -54,3 +123,24 @@ impl prost::Message for EncodedMessage {
         self.data.clear()
     }
 }
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_annotate() {
+        // 1: 150, 2: { 1: "ab" }, 3: 0x01i32
+        let msg = EncodedMessage {
+            data: vec![0x08, 0x96, 0x01, 0x12, 0x04, 0x0a, 0x02, 0x61, 0x62, 0x1d, 0x01, 0x00, 0x00, 0x00],
+        };
+
+        let expected = "1: 150\n2: {\n  1: \"ab\"\n}\n3: 0x00000001i32\n";
+        assert_eq!(msg.annotate().unwrap(), expected);
+
+        // truncated field
+        let msg = EncodedMessage { data: vec![0x12, 0x04, 0x0a] };
+        assert_eq!(msg.annotate(), None);
+    }
+}
@@ -0,0 +1,178 @@
+//! Reconnect and retry policies.
+//!
+//! A [`Backoff`] describes the delays between attempts, e.g. of connecting to
+//! the device: starting with an initial delay, each further delay is
+//! multiplied by a constant factor up to a maximum, optionally randomized by
+//! a jitter factor so that multiple clients do not retry in lockstep. The
+//! delays are produced by iterating over the policy, which ends once the
+//! maximum number of attempts has been reached.
+//!
+//! ```
+//! use std::time::Duration;
+//! use maestro::retry::Backoff;
+//!
+//! let backoff = Backoff {
+//!     initial: Duration::from_secs(1),
+//!     max: Duration::from_secs(4),
+//!     jitter: 0.0,
+//!     attempts: Some(5),
+//!     ..Default::default()
+//! };
+//!
+//! let delays: Vec<_> = backoff.delays().map(|d| d.as_secs()).collect();
+//! assert_eq!(delays, [1, 2, 4, 4]);
+//! ```
+
+use std::hash::{BuildHasher, Hasher};
+use std::time::Duration;
+
+
+/// Policy for delays between attempts.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Backoff {
+    /// Delay before the first retry.
+    pub initial: Duration,
+
+    /// Factor applied to the delay after each retry.
+    pub multiplier: f64,
+
+    /// Upper bound of the delay, before applying jitter.
+    pub max: Duration,
+
+    /// Relative amount by which delays are randomized, e.g. `0.1` for delays
+    /// within ±10% of the nominal delay.
+    pub jitter: f64,
+
+    /// Maximum number of attempts, including the first one, or `None` to
+    /// retry indefinitely.
+    pub attempts: Option<u32>,
+}
+
+impl Backoff {
+    /// Constant delay between the given number of attempts, without jitter.
+    pub const fn fixed(delay: Duration, attempts: Option<u32>) -> Self {
+        Self {
+            initial: delay,
+            multiplier: 1.0,
+            max: delay,
+            jitter: 0.0,
+            attempts,
+        }
+    }
+
+    /// Nominal delay before the given retry, starting at zero for the first
+    /// retry, i.e., the second attempt.
+    pub fn delay(&self, retry: u32) -> Duration {
+        let factor = self.multiplier.max(1.0).powi(retry.min(i32::MAX as u32) as i32);
+        let delay = self.initial.as_secs_f64() * factor;
+
+        Duration::try_from_secs_f64(delay)
+            .unwrap_or(Duration::MAX)
+            .min(self.max.max(self.initial))
+    }
+
+    /// Delays before each retry, with jitter applied.
+    pub fn delays(&self) -> Delays {
+        Delays { policy: *self, retry: 0 }
+    }
+}
+
+impl Default for Backoff {
+    fn default() -> Self {
+        Self {
+            initial: Duration::from_millis(500),
+            multiplier: 2.0,
+            max: Duration::from_secs(30),
+            jitter: 0.1,
+            attempts: None,
+        }
+    }
+}
+
+impl IntoIterator for Backoff {
+    type Item = Duration;
+    type IntoIter = Delays;
+
+    fn into_iter(self) -> Delays {
+        self.delays()
+    }
+}
+
+
+/// Iterator over the delays of a [`Backoff`] policy.
+#[derive(Debug, Clone)]
+pub struct Delays {
+    policy: Backoff,
+    retry: u32,
+}
+
+impl Delays {
+    /// Number of delays returned so far.
+    pub fn retries(&self) -> u32 {
+        self.retry
+    }
+}
+
+impl Iterator for Delays {
+    type Item = Duration;
+
+    fn next(&mut self) -> Option<Duration> {
+        if self.policy.attempts.is_some_and(|n| self.retry + 1 >= n) {
+            return None;
+        }
+
+        let delay = self.policy.delay(self.retry);
+        self.retry = self.retry.saturating_add(1);
+
+        let jitter = self.policy.jitter.clamp(0.0, 1.0);
+        if jitter == 0.0 {
+            return Some(delay);
+        }
+
+        let factor = 1.0 + jitter * (2.0 * random() - 1.0);
+        Some(delay.mul_f64(factor))
+    }
+}
+
+
+/// Random number in `[0, 1)`, good enough for jitter.
+fn random() -> f64 {
+    let mut hasher = std::collections::hash_map::RandomState::new().build_hasher();
+    hasher.write_u64(0);
+
+    (hasher.finish() >> 11) as f64 / (1u64 << 53) as f64
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_delays() {
+        let secs = |backoff: Backoff| backoff.delays().map(|d| d.as_secs_f64()).collect::<Vec<_>>();
+
+        let backoff = Backoff {
+            initial: Duration::from_secs(1),
+            multiplier: 3.0,
+            max: Duration::from_secs(10),
+            jitter: 0.0,
+            attempts: Some(5),
+        };
+        assert_eq!(secs(backoff), [1.0, 3.0, 9.0, 10.0]);
+
+        assert_eq!(secs(Backoff::fixed(Duration::from_millis(500), Some(3))), [0.5, 0.5]);
+        assert_eq!(secs(Backoff::fixed(Duration::from_secs(1), Some(1))), [] as [f64; 0]);
+
+        let mut delays = Backoff { attempts: None, ..backoff }.delays();
+        assert_eq!(delays.nth(100), Some(Duration::from_secs(10)));
+        assert_eq!(delays.retries(), 101);
+
+        // jitter stays within bounds
+        let backoff = Backoff { jitter: 0.5, attempts: Some(100), ..backoff };
+        for (i, delay) in backoff.delays().enumerate() {
+            let nominal = backoff.delay(i as u32).as_secs_f64();
+            assert!(delay.as_secs_f64() >= nominal * 0.5 && delay.as_secs_f64() <= nominal * 1.5);
+        }
+    }
+}
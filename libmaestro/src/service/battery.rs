@@ -0,0 +1,363 @@
+//! Battery drain and charge analysis.
+//!
+//! The [`Tracker`] records the battery levels of all components over a
+//! monitoring session and derives charge and discharge rates from them. Buds
+//! draining significantly faster, or charging significantly slower, than the
+//! other one are reported as anomalies, which may indicate a failing battery.
+
+use std::time::Duration;
+
+use crate::protocol::types::{BatteryInfo, BatteryState, DeviceBatteryInfo};
+
+use super::format::Term;
+
+
+/// Minimum time a bud has to be (dis-)charging for its rate to be compared
+/// with the other bud.
+const MIN_DURATION: Duration = Duration::from_secs(30 * 60);
+
+/// Minimum ratio between the rates of both buds to report an anomaly.
+const MIN_RATIO: f32 = 1.5;
+
+/// Minimum difference between the rates of both buds to report an anomaly, in
+/// percent per hour. Avoids reporting anomalies for small absolute rates,
+/// where the resolution of the reported levels dominates.
+const MIN_DIFFERENCE: f32 = 3.0;
+
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Component {
+    Case,
+    Left,
+    Right,
+}
+
+impl Component {
+    pub const ALL: [Component; 3] = [Component::Case, Component::Left, Component::Right];
+
+    pub fn name(&self) -> &'static str {
+        self.term().english()
+    }
+
+    pub fn term(&self) -> Term {
+        match self {
+            Component::Case => Term::Case,
+            Component::Left => Term::LeftBud,
+            Component::Right => Term::RightBud,
+        }
+    }
+}
+
+
+/// Battery level of a single component at a given time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Sample {
+    /// Time since the start of the session.
+    pub time: Duration,
+    pub level: u8,
+    pub charging: bool,
+}
+
+
+/// Change of the battery level over some time.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Rate {
+    /// Change in percentage points.
+    pub change: u32,
+    pub duration: Duration,
+}
+
+impl Rate {
+    /// Change in percentage points per hour.
+    pub fn per_hour(&self) -> f32 {
+        if self.duration.is_zero() {
+            return 0.0;
+        }
+
+        self.change as f32 / self.duration.as_secs_f32() * 3600.0
+    }
+}
+
+
+/// Battery levels of a single component over time.
+///
+/// Only changes are stored as samples. The time the last level has been
+/// observed is tracked separately.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Curve {
+    samples: Vec<Sample>,
+    end: Duration,
+}
+
+impl Curve {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Samples at which the level or charging state has changed.
+    pub fn samples(&self) -> &[Sample] {
+        &self.samples
+    }
+
+    /// Time at which the component has been observed last.
+    pub fn end(&self) -> Duration {
+        self.end
+    }
+
+    pub fn push(&mut self, sample: Sample) {
+        self.end = self.end.max(sample.time);
+
+        if let Some(last) = self.samples.last()
+            && last.level == sample.level
+            && last.charging == sample.charging
+        {
+            return;
+        }
+
+        self.samples.push(sample);
+    }
+
+    /// Total level drop over all periods without charging.
+    pub fn discharge(&self) -> Rate {
+        self.rate(false)
+    }
+
+    /// Total level gain over all periods of charging.
+    pub fn charge(&self) -> Rate {
+        self.rate(true)
+    }
+
+    fn rate(&self, charging: bool) -> Rate {
+        let end = self.samples.last().map(|s| Sample { time: self.end, ..*s });
+
+        let mut rate = Rate::default();
+
+        for (a, b) in self.samples.iter().zip(self.samples.iter().skip(1).chain(end.iter())) {
+            if a.charging != charging {
+                continue;
+            }
+
+            // a change of the charging state in between is only visible with
+            // the next sample, attribute the full interval to the old state
+            let change = if charging {
+                b.level.checked_sub(a.level)
+            } else {
+                a.level.checked_sub(b.level)
+            };
+
+            // ignore intervals going in the wrong direction, e.g. levels
+            // being re-calibrated after the bud has been placed in the case
+            let Some(change) = change else { continue };
+
+            rate.change += u32::from(change);
+            rate.duration += b.time.saturating_sub(a.time);
+        }
+
+        rate
+    }
+}
+
+
+/// Per-component results of a session.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Summary {
+    pub component: Component,
+    pub first: Sample,
+    pub last: Sample,
+    pub discharge: Rate,
+    pub charge: Rate,
+}
+
+
+/// A noticeable difference between the buds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Anomaly {
+    /// The bud drains significantly faster than the other one.
+    FastDrain { component: Component, rate: Rate, other: Rate },
+
+    /// The bud charges significantly slower than the other one.
+    SlowCharge { component: Component, rate: Rate, other: Rate },
+}
+
+impl std::fmt::Display for Anomaly {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let (component, rate, other, what) = match self {
+            Anomaly::FastDrain { component, rate, other } => (component, rate, other, "drains faster"),
+            Anomaly::SlowCharge { component, rate, other } => (component, rate, other, "charges slower"),
+        };
+
+        write!(f, "{} {what} than the other bud ({:.1} %/h vs. {:.1} %/h)",
+            component.name(), rate.per_hour(), other.per_hour())
+    }
+}
+
+
+/// Results of a session.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Report {
+    /// Summaries of all components observed during the session.
+    pub components: Vec<Summary>,
+    pub anomalies: Vec<Anomaly>,
+}
+
+
+/// Records battery levels over a monitoring session.
+#[derive(Debug, Clone, Default)]
+pub struct Tracker {
+    curves: [Curve; 3],
+}
+
+impl Tracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn curve(&self, component: Component) -> &Curve {
+        &self.curves[component as usize]
+    }
+
+    /// Record the battery levels reported at the given time since the start
+    /// of the session.
+    pub fn record(&mut self, time: Duration, info: &BatteryInfo) {
+        let batteries = [info.case, info.left, info.right];
+
+        for (curve, battery) in self.curves.iter_mut().zip(batteries) {
+            if let Some(sample) = battery.as_ref().and_then(|b| sample(time, b)) {
+                curve.push(sample);
+            }
+        }
+    }
+
+    pub fn report(&self) -> Report {
+        let components = Component::ALL.into_iter()
+            .filter_map(|component| {
+                let curve = self.curve(component);
+
+                Some(Summary {
+                    component,
+                    first: *curve.samples.first()?,
+                    last: Sample { time: curve.end, ..*curve.samples.last()? },
+                    discharge: curve.discharge(),
+                    charge: curve.charge(),
+                })
+            })
+            .collect();
+
+        let left = self.curve(Component::Left);
+        let right = self.curve(Component::Right);
+        let mut anomalies = Vec::new();
+
+        if let Some((component, rate, other)) = compare(left.discharge(), right.discharge(), true) {
+            anomalies.push(Anomaly::FastDrain { component, rate, other });
+        }
+
+        if let Some((component, rate, other)) = compare(left.charge(), right.charge(), false) {
+            anomalies.push(Anomaly::SlowCharge { component, rate, other });
+        }
+
+        Report { components, anomalies }
+    }
+}
+
+fn sample(time: Duration, battery: &DeviceBatteryInfo) -> Option<Sample> {
+    let level = u8::try_from(battery.level).ok().filter(|l| *l <= 100)?;
+
+    let charging = match BatteryState::try_from(battery.state).ok()? {
+        BatteryState::BatteryCharging => true,
+        BatteryState::BatteryNotCharging => false,
+        BatteryState::Unknown => return None,
+    };
+
+    Some(Sample { time, level, charging })
+}
+
+/// Compare the rates of both buds, returning the anomalous bud, its rate, and
+/// the rate of the other bud. If `faster` is set, the faster bud is anomalous,
+/// otherwise the slower one.
+fn compare(left: Rate, right: Rate, faster: bool) -> Option<(Component, Rate, Rate)> {
+    if left.duration < MIN_DURATION || right.duration < MIN_DURATION {
+        return None;
+    }
+
+    let (fast, slow) = if left.per_hour() >= right.per_hour() {
+        ((Component::Left, left), (Component::Right, right))
+    } else {
+        ((Component::Right, right), (Component::Left, left))
+    };
+
+    let difference = fast.1.per_hour() - slow.1.per_hour();
+    if difference < MIN_DIFFERENCE || fast.1.per_hour() < slow.1.per_hour() * MIN_RATIO {
+        return None;
+    }
+
+    if faster {
+        Some((fast.0, fast.1, slow.1))
+    } else {
+        Some((slow.0, slow.1, fast.1))
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn info(left: (i32, bool), right: (i32, bool)) -> BatteryInfo {
+        let battery = |(level, charging)| Some(DeviceBatteryInfo {
+            level,
+            state: if charging { 2 } else { 1 },
+        });
+
+        BatteryInfo { case: None, left: battery(left), right: battery(right) }
+    }
+
+    #[test]
+    fn test_rates() {
+        let min = |m: u64| Duration::from_secs(m * 60);
+        let mut curve = Curve::new();
+
+        let mut push = |m, level, charging| curve.push(Sample { time: min(m), level, charging });
+        push(0, 80, false);
+        push(30, 75, false);
+        push(45, 75, false);
+        push(60, 70, true);
+        push(90, 90, true);
+        push(120, 90, true);
+
+        assert_eq!(curve.samples().len(), 4);
+        assert_eq!(curve.end(), min(120));
+
+        assert_eq!(curve.discharge(), Rate { change: 10, duration: min(60) });
+        assert_eq!(curve.charge(), Rate { change: 20, duration: min(60) });
+        assert!((curve.discharge().per_hour() - 10.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_report() {
+        let min = |m: u64| Duration::from_secs(m * 60);
+        let mut tracker = Tracker::new();
+
+        tracker.record(min(0), &info((90, false), (90, false)));
+        tracker.record(min(30), &info((80, false), (87, false)));
+        tracker.record(min(60), &info((70, false), (84, false)));
+
+        let report = tracker.report();
+
+        assert_eq!(report.components.len(), 2);
+        assert_eq!(report.components[0].component, Component::Left);
+        assert_eq!((report.components[0].first.level, report.components[0].last.level), (90, 70));
+
+        assert_eq!(report.anomalies, [Anomaly::FastDrain {
+            component: Component::Left,
+            rate: Rate { change: 20, duration: min(60) },
+            other: Rate { change: 6, duration: min(60) },
+        }]);
+
+        // similar rates are not reported
+        let mut tracker = Tracker::new();
+        tracker.record(min(0), &info((90, false), (90, false)));
+        tracker.record(min(60), &info((80, false), (82, false)));
+
+        assert!(tracker.report().anomalies.is_empty());
+    }
+}
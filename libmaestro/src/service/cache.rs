@@ -0,0 +1,186 @@
+//! Cache of the latest settings values.
+//!
+//! The [`SettingsCache`] keeps the most recent value of each setting, as read
+//! from the device or reported via the settings-change subscription, and
+//! provides a `watch` channel per setting. Consumers, e.g. GUI widgets, can
+//! bind directly to a single setting and are only woken when its value
+//! actually changes, instead of filtering the full settings-change stream.
+
+use std::sync::Mutex;
+
+use futures::StreamExt;
+
+use tokio::sync::watch;
+
+use crate::protocol::types::{settings_rsp, SettingsRsp};
+use crate::pwrpc::Error;
+
+use super::settings::{SettingId, SettingValue};
+use super::MaestroService;
+
+
+/// Cache of the latest settings values, with per-setting change
+/// notifications.
+#[derive(Debug, Default)]
+pub struct SettingsCache {
+    channels: Mutex<Vec<(SettingId, watch::Sender<Option<SettingValue>>)>>,
+}
+
+impl SettingsCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Latest known value of the given setting.
+    pub fn get(&self, id: SettingId) -> Option<SettingValue> {
+        let channels = self.channels.lock().unwrap();
+
+        channels.iter()
+            .find(|(i, _)| *i == id)
+            .and_then(|(_, tx)| tx.borrow().clone())
+    }
+
+    /// Watch the given setting.
+    ///
+    /// The receiver holds `None` until the value of the setting is known, and
+    /// is only notified when the value changes.
+    pub fn watch(&self, id: SettingId) -> watch::Receiver<Option<SettingValue>> {
+        let mut channels = self.channels.lock().unwrap();
+
+        if let Some((_, tx)) = channels.iter().find(|(i, _)| *i == id) {
+            return tx.subscribe();
+        }
+
+        let (tx, rx) = watch::channel(None);
+        channels.push((id, tx));
+        rx
+    }
+
+    /// Store the given value. Returns `true` if it differs from the previous
+    /// value, in which case watchers of the setting are notified.
+    pub fn update(&self, value: SettingValue) -> bool {
+        let mut channels = self.channels.lock().unwrap();
+        let id = value.id();
+
+        match channels.iter().find(|(i, _)| *i == id) {
+            Some((_, tx)) => tx.send_if_modified(|current| {
+                if current.as_ref() == Some(&value) {
+                    return false;
+                }
+
+                *current = Some(value);
+                true
+            }),
+            None => {
+                channels.push((id, watch::Sender::new(Some(value))));
+                true
+            },
+        }
+    }
+
+    /// Store the value contained in the given response, if any. See
+    /// [`Self::update`].
+    pub fn update_from_rsp(&self, rsp: &SettingsRsp) -> bool {
+        match &rsp.value_oneof {
+            Some(settings_rsp::ValueOneof::Value(value)) => match &value.value_oneof {
+                Some(value) => self.update((*value).into()),
+                None => false,
+            },
+            None => false,
+        }
+    }
+
+    /// Forget all values, e.g. after the connection to the device has been
+    /// lost. Watchers of known settings are notified with `None`.
+    pub fn clear(&self) {
+        let channels = self.channels.lock().unwrap();
+
+        for (_, tx) in channels.iter() {
+            tx.send_if_modified(|current| current.take().is_some());
+        }
+    }
+
+    /// Read the given settings and keep the cache up to date with changes
+    /// reported by the device, until the subscription terminates.
+    ///
+    /// Settings that cannot be read, e.g. because they are not supported by
+    /// the firmware, are skipped.
+    pub async fn follow(&self, service: &mut MaestroService, settings: &[SettingId]) -> Result<(), Error> {
+        // subscribe first so that no change between reading and subscribing
+        // gets lost
+        let mut changes = service.subscribe_to_settings_changes()?;
+
+        for (id, value) in service.read_settings(settings).await {
+            match value {
+                Ok(value) => { self.update(value); },
+                Err(err) => tracing::debug!(setting=?id, error=%err, "failed to read setting"),
+            }
+        }
+
+        let mut changes = changes.stream();
+        while let Some(rsp) = changes.next().await {
+            self.update_from_rsp(&rsp?);
+        }
+
+        Ok(())
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::mock::{Device, fixture};
+
+    #[test]
+    fn test_watch() {
+        let cache = SettingsCache::new();
+
+        let mut rx = cache.watch(SettingId::GestureEnable);
+        assert_eq!(*rx.borrow_and_update(), None);
+
+        assert!(cache.update(SettingValue::GestureEnable(true)));
+        assert!(rx.has_changed().unwrap());
+        assert_eq!(*rx.borrow_and_update(), Some(SettingValue::GestureEnable(true)));
+
+        // unchanged values and other settings do not wake watchers
+        assert!(!cache.update(SettingValue::GestureEnable(true)));
+        assert!(cache.update(SettingValue::SpeechDetection(false)));
+        assert!(!rx.has_changed().unwrap());
+
+        // new watchers start out with the current value
+        let rx2 = cache.watch(SettingId::SpeechDetection);
+        assert_eq!(*rx2.borrow(), Some(SettingValue::SpeechDetection(false)));
+        assert_eq!(cache.get(SettingId::GestureEnable), Some(SettingValue::GestureEnable(true)));
+
+        cache.clear();
+        assert!(rx.has_changed().unwrap());
+        assert_eq!(*rx.borrow(), None);
+        assert_eq!(cache.get(SettingId::SpeechDetection), None);
+    }
+
+    #[tokio::test]
+    async fn test_follow() {
+        let device = Device::new();
+
+        let (mut client, channel) = fixture::connect(&device).await;
+        let mut service = MaestroService::new(client.handle(), channel);
+
+        let cache = SettingsCache::new();
+        let mut rx = cache.watch(SettingId::GestureEnable);
+
+        let task = async {
+            rx.wait_for(|v| *v == Some(SettingValue::GestureEnable(true))).await.unwrap();
+
+            device.change_setting(SettingValue::GestureEnable(false));
+            rx.wait_for(|v| *v == Some(SettingValue::GestureEnable(false))).await.unwrap();
+        };
+
+        tokio::select! {
+            res = client.run() => panic!("client terminated unexpectedly: {res:?}"),
+            res = cache.follow(&mut service, &[SettingId::GestureEnable]) => panic!("follow terminated: {res:?}"),
+            _ = task => {},
+        }
+    }
+}
@@ -0,0 +1,222 @@
+//! Correlation of the device clock with host time.
+//!
+//! The runtime info reported by the device carries a timestamp of the device
+//! clock (`timestamp_ms`). Depending on the firmware, this clock appears to be
+//! either a wall clock, i.e., unix time with a small but consistent offset to
+//! the actual time, or a counter since the device booted. [`DeviceTime`]
+//! distinguishes both based on the value.
+//!
+//! The [`ClockTracker`] correlates timestamps received over a session with the
+//! time they have been received on the host. It estimates the offset between
+//! both clocks, the drift of the device clock, and detects the device clock
+//! jumping backwards, which indicates that the device has been rebooted.
+
+use std::time::{Duration, SystemTime};
+
+
+/// Smallest timestamp interpreted as unix time (2015-01-01). Smaller values
+/// are interpreted as time since boot.
+const WALL_CLOCK_MIN: Duration = Duration::from_secs(1_420_070_400);
+
+/// Tolerance for the device clock going backwards before it is considered to
+/// have been reset, in milliseconds.
+const RESET_TOLERANCE_MS: i64 = 1000;
+
+/// Minimum time between the first and last sample to estimate the drift.
+const MIN_DRIFT_DURATION: Duration = Duration::from_secs(10 * 60);
+
+
+/// Interpretation of a device timestamp.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeviceTime {
+    /// Time since the device booted.
+    Uptime(Duration),
+
+    /// Wall-clock time of the device.
+    Wall(SystemTime),
+}
+
+impl DeviceTime {
+    /// Interpret the given device timestamp. Returns `None` for negative
+    /// timestamps.
+    pub fn from_ms(timestamp_ms: i64) -> Option<Self> {
+        let time = Duration::from_millis(u64::try_from(timestamp_ms).ok()?);
+
+        if time < WALL_CLOCK_MIN {
+            Some(DeviceTime::Uptime(time))
+        } else {
+            Some(DeviceTime::Wall(SystemTime::UNIX_EPOCH + time))
+        }
+    }
+
+    pub fn uptime(&self) -> Option<Duration> {
+        match self {
+            DeviceTime::Uptime(uptime) => Some(*uptime),
+            DeviceTime::Wall(_) => None,
+        }
+    }
+}
+
+
+/// A device timestamp and the host time it has been received at.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Sample {
+    /// Host time, in milliseconds since the unix epoch.
+    pub host_ms: i64,
+
+    /// Device timestamp, in milliseconds.
+    pub device_ms: i64,
+}
+
+impl Sample {
+    pub fn new(host: SystemTime, device_ms: i64) -> Self {
+        let host_ms = match host.duration_since(SystemTime::UNIX_EPOCH) {
+            Ok(time) => time.as_millis() as i64,
+            Err(err) => -(err.duration().as_millis() as i64),
+        };
+
+        Self { host_ms, device_ms }
+    }
+
+    /// Offset of the host clock to the device clock, in milliseconds,
+    /// including the transmission delay.
+    pub fn offset_ms(&self) -> i64 {
+        self.host_ms - self.device_ms
+    }
+}
+
+
+/// Change of the device clock detected by the [`ClockTracker`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClockEvent {
+    /// The device clock went backwards, e.g. because the device has been
+    /// rebooted. Estimates start over with the new sample.
+    Reset { previous: Sample, current: Sample },
+}
+
+
+/// Correlates device timestamps with host time.
+#[derive(Debug, Clone, Default)]
+pub struct ClockTracker {
+    first: Option<Sample>,
+    last: Option<Sample>,
+    offset: Option<Sample>,
+    resets: u32,
+}
+
+impl ClockTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record a device timestamp received at the given host time.
+    pub fn record(&mut self, host: SystemTime, device_ms: i64) -> Option<ClockEvent> {
+        self.push(Sample::new(host, device_ms))
+    }
+
+    pub fn push(&mut self, sample: Sample) -> Option<ClockEvent> {
+        let event = match self.last {
+            Some(last) if sample.device_ms < last.device_ms - RESET_TOLERANCE_MS => {
+                self.resets += 1;
+                self.first = None;
+                self.offset = None;
+
+                Some(ClockEvent::Reset { previous: last, current: sample })
+            },
+            _ => None,
+        };
+
+        self.first.get_or_insert(sample);
+        self.last = Some(sample);
+
+        // the transmission delay only ever adds to the observed offset, so
+        // the sample with the smallest offset is the best estimate
+        if self.offset.is_none_or(|best| sample.offset_ms() < best.offset_ms()) {
+            self.offset = Some(sample);
+        }
+
+        event
+    }
+
+    /// Number of detected resets of the device clock.
+    pub fn resets(&self) -> u32 {
+        self.resets
+    }
+
+    /// Estimated offset of the host clock to the device clock, in
+    /// milliseconds, i.e., host time minus device time.
+    pub fn offset_ms(&self) -> Option<i64> {
+        self.offset.map(|s| s.offset_ms())
+    }
+
+    /// Host time at which the device clock started, i.e., the boot time for
+    /// uptime clocks.
+    pub fn origin(&self) -> Option<SystemTime> {
+        let offset = self.offset_ms()?;
+
+        if offset >= 0 {
+            Some(SystemTime::UNIX_EPOCH + Duration::from_millis(offset as u64))
+        } else {
+            SystemTime::UNIX_EPOCH.checked_sub(Duration::from_millis(offset.unsigned_abs()))
+        }
+    }
+
+    /// Estimated drift of the device clock relative to the host clock, in
+    /// parts per million. Positive values mean the device clock runs fast.
+    ///
+    /// Returns `None` until samples spanning a sufficiently long time have
+    /// been recorded.
+    pub fn drift_ppm(&self) -> Option<f64> {
+        let (first, last) = (self.first?, self.last?);
+
+        let elapsed = last.host_ms - first.host_ms;
+        if elapsed < MIN_DRIFT_DURATION.as_millis() as i64 {
+            return None;
+        }
+
+        let device = (last.device_ms - first.device_ms) as f64;
+        Some((device - elapsed as f64) / elapsed as f64 * 1e6)
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_device_time() {
+        assert_eq!(DeviceTime::from_ms(-1), None);
+        assert_eq!(DeviceTime::from_ms(42_000).unwrap().uptime(), Some(Duration::from_secs(42)));
+
+        let wall = DeviceTime::from_ms(1_700_000_000_000).unwrap();
+        assert_eq!(wall, DeviceTime::Wall(SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000)));
+        assert_eq!(wall.uptime(), None);
+    }
+
+    #[test]
+    fn test_tracker() {
+        let min = |m: i64| m * 60 * 1000;
+        let boot = 1_700_000_000_000;
+        let mut tracker = ClockTracker::new();
+
+        assert_eq!(tracker.offset_ms(), None);
+
+        // device clock runs 100 ppm fast, samples arrive with varying delay
+        assert_eq!(tracker.push(Sample { host_ms: boot + min(1) + 80, device_ms: min(1) }), None);
+        assert_eq!(tracker.push(Sample { host_ms: boot + min(2) + 20, device_ms: min(2) }), None);
+        assert_eq!(tracker.offset_ms(), Some(boot + 20));
+        assert_eq!(tracker.drift_ppm(), None);
+
+        tracker.push(Sample { host_ms: boot + min(101) + 80, device_ms: min(101) + 600 });
+        assert_eq!(tracker.offset_ms(), Some(boot - 520));
+        assert!((tracker.drift_ppm().unwrap() - 100.0).abs() < 1.0);
+
+        // clock going backwards indicates a reboot
+        let event = tracker.push(Sample { host_ms: boot + min(102), device_ms: 500 });
+        assert!(matches!(event, Some(ClockEvent::Reset { .. })));
+        assert_eq!(tracker.resets(), 1);
+        assert_eq!(tracker.offset_ms(), Some(boot + min(102) - 500));
+        assert_eq!(tracker.drift_ppm(), None);
+    }
+}
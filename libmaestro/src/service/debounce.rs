@@ -0,0 +1,135 @@
+//! Debouncing of settings changes.
+//!
+//! Settings may flap rapidly, e.g. when the buds hand off processing between
+//! each other and briefly report an intermediate state. The [`Debouncer`]
+//! holds back changes for a given duration and drops them if they are
+//! reverted in the meantime.
+
+use std::time::{Duration, Instant};
+
+use super::settings::{SettingId, SettingValue};
+
+
+#[derive(Debug, Clone)]
+struct Pending {
+    value: SettingValue,
+    since: Instant,
+}
+
+
+/// Debouncer for settings changes.
+#[derive(Debug, Clone)]
+pub struct Debouncer {
+    hold: Duration,
+    stable: Vec<SettingValue>,
+    pending: Vec<Pending>,
+}
+
+impl Debouncer {
+    /// Create a new debouncer, holding back changes for the given duration.
+    pub fn new(hold: Duration) -> Self {
+        Self {
+            hold,
+            stable: Vec::new(),
+            pending: Vec::new(),
+        }
+    }
+
+    /// Record a settings change received at the given time.
+    ///
+    /// Changes of a setting with a pending change restart the hold duration.
+    /// Changes reverting to the last emitted value are dropped.
+    pub fn push(&mut self, value: SettingValue, now: Instant) {
+        let id = value.id();
+        let stable = self.stable(id);
+
+        match self.pending.iter().position(|p| p.value.id() == id) {
+            Some(i) if stable == Some(&value) => {
+                self.pending.remove(i);
+            },
+            Some(i) => {
+                self.pending[i] = Pending { value, since: now };
+            },
+            None if stable == Some(&value) => {},
+            None => {
+                self.pending.push(Pending { value, since: now });
+            },
+        }
+    }
+
+    /// Take all changes that have been held back for at least the hold
+    /// duration.
+    pub fn poll(&mut self, now: Instant) -> Vec<SettingValue> {
+        let (ready, pending) = std::mem::take(&mut self.pending).into_iter()
+            .partition(|p: &Pending| now.saturating_duration_since(p.since) >= self.hold);
+
+        self.pending = pending;
+
+        let ready: Vec<_> = ready.into_iter().map(|p| p.value).collect();
+        for value in &ready {
+            self.set_stable(value.clone());
+        }
+
+        ready
+    }
+
+    /// The time at which the next pending change is due, if any.
+    pub fn deadline(&self) -> Option<Instant> {
+        self.pending.iter()
+            .map(|p| p.since + self.hold)
+            .min()
+    }
+
+    /// Drop all pending changes and forget the last emitted values.
+    pub fn reset(&mut self) {
+        self.stable.clear();
+        self.pending.clear();
+    }
+
+    fn stable(&self, id: SettingId) -> Option<&SettingValue> {
+        self.stable.iter().find(|v| v.id() == id)
+    }
+
+    fn set_stable(&mut self, value: SettingValue) {
+        match self.stable.iter_mut().find(|v| v.id() == value.id()) {
+            Some(stable) => *stable = value,
+            None => self.stable.push(value),
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::service::settings::AncState;
+
+    #[test]
+    fn test_debounce() {
+        let hold = Duration::from_secs(2);
+        let start = Instant::now();
+        let at = |secs| start + Duration::from_secs(secs);
+
+        let mut debouncer = Debouncer::new(hold);
+
+        // changes are held back for the hold duration
+        debouncer.push(SettingValue::CurrentAncrState(AncState::Active), at(0));
+        assert_eq!(debouncer.deadline(), Some(at(2)));
+        assert_eq!(debouncer.poll(at(1)), vec![]);
+        assert_eq!(debouncer.poll(at(2)), vec![SettingValue::CurrentAncrState(AncState::Active)]);
+        assert_eq!(debouncer.deadline(), None);
+
+        // changes reverted within the hold duration are dropped
+        debouncer.push(SettingValue::CurrentAncrState(AncState::Off), at(3));
+        debouncer.push(SettingValue::CurrentAncrState(AncState::Active), at(4));
+        assert_eq!(debouncer.deadline(), None);
+        assert_eq!(debouncer.poll(at(10)), vec![]);
+
+        // repeated changes restart the hold duration
+        debouncer.push(SettingValue::CurrentAncrState(AncState::Off), at(10));
+        debouncer.push(SettingValue::CurrentAncrState(AncState::Aware), at(11));
+        assert_eq!(debouncer.poll(at(12)), vec![]);
+        assert_eq!(debouncer.poll(at(13)), vec![SettingValue::CurrentAncrState(AncState::Aware)]);
+    }
+}
@@ -0,0 +1,254 @@
+//! Human-facing formatting of device state and settings values.
+//!
+//! All user-visible words go through a [`Locale`], which maps a [`Term`] to
+//! its text. The [`Formatter`] composes values from these terms. The `Display`
+//! implementations of the settings types use the [`English`] locale, which is
+//! also the fallback for terms a locale does not translate. Applications can
+//! provide translations by implementing [`Locale`].
+
+use std::borrow::Cow;
+
+use crate::protocol::types::{BatteryState, DeviceBatteryInfo};
+
+use super::settings::{
+    AncState, AncrGestureLoop, EqBands, GestureControl, RegularActionTarget, SettingValue,
+    VolumeAsymmetry,
+};
+
+
+/// A translatable word or phrase.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Term {
+    True,
+    False,
+    Unknown,
+
+    Case,
+    Left,
+    Right,
+    LeftBud,
+    RightBud,
+
+    InCase,
+    OutOfCase,
+
+    Charging,
+    NotCharging,
+
+    AncOff,
+    AncActive,
+    AncAware,
+
+    /// Name of a gesture action. The text of unknown actions is used as
+    /// prefix for their raw value.
+    Action(RegularActionTarget),
+}
+
+impl Term {
+    /// English text of the term.
+    pub fn english(&self) -> &'static str {
+        match self {
+            Term::True => "true",
+            Term::False => "false",
+            Term::Unknown => "unknown",
+            Term::Case => "case",
+            Term::Left => "left",
+            Term::Right => "right",
+            Term::LeftBud => "left bud",
+            Term::RightBud => "right bud",
+            Term::InCase => "in case",
+            Term::OutOfCase => "out of case",
+            Term::Charging => "charging",
+            Term::NotCharging => "not charging",
+            Term::AncOff => "off",
+            Term::AncActive => "active",
+            Term::AncAware => "aware",
+            Term::Action(RegularActionTarget::CheckNotifications) => "check-notifications",
+            Term::Action(RegularActionTarget::PreviousTrackRepeat) => "previous",
+            Term::Action(RegularActionTarget::NextTrack) => "next",
+            Term::Action(RegularActionTarget::PlayPauseTrack) => "play-pause",
+            Term::Action(RegularActionTarget::AncControl) => "anc",
+            Term::Action(RegularActionTarget::AssistantQuery) => "assistant",
+            Term::Action(RegularActionTarget::Unknown(_)) => "unknown",
+        }
+    }
+}
+
+
+/// Source of the text for each term.
+pub trait Locale {
+    /// Text of the given term, or `None` to fall back to English.
+    fn text(&self, term: Term) -> Option<Cow<'static, str>>;
+}
+
+/// The default locale.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct English;
+
+impl Locale for English {
+    fn text(&self, term: Term) -> Option<Cow<'static, str>> {
+        Some(term.english().into())
+    }
+}
+
+
+/// Formats values using the terms of a [`Locale`].
+#[derive(Clone, Copy)]
+pub struct Formatter<'a> {
+    locale: &'a dyn Locale,
+}
+
+impl Formatter<'static> {
+    pub fn english() -> Self {
+        Self { locale: &English }
+    }
+}
+
+impl<'a> Formatter<'a> {
+    pub fn new(locale: &'a dyn Locale) -> Self {
+        Self { locale }
+    }
+
+    pub fn text(&self, term: Term) -> Cow<'static, str> {
+        self.locale.text(term).unwrap_or_else(|| term.english().into())
+    }
+
+    fn unknown(&self, value: i32) -> String {
+        format!("{} ({value})", self.text(Term::Unknown))
+    }
+
+    pub fn bool(&self, value: bool) -> Cow<'static, str> {
+        self.text(if value { Term::True } else { Term::False })
+    }
+
+    pub fn anc_state(&self, state: AncState) -> String {
+        match state {
+            AncState::Off => self.text(Term::AncOff).into_owned(),
+            AncState::Active => self.text(Term::AncActive).into_owned(),
+            AncState::Aware => self.text(Term::AncAware).into_owned(),
+            AncState::Unknown(x) => self.unknown(x),
+        }
+    }
+
+    pub fn action(&self, action: RegularActionTarget) -> String {
+        match action {
+            RegularActionTarget::Unknown(x) => self.unknown(x),
+            action => self.text(Term::Action(action)).into_owned(),
+        }
+    }
+
+    pub fn gesture_control(&self, value: &GestureControl) -> String {
+        format!("{}: {}, {}: {}",
+            self.text(Term::Left), self.action(value.left),
+            self.text(Term::Right), self.action(value.right))
+    }
+
+    pub fn anc_gesture_loop(&self, value: &AncrGestureLoop) -> String {
+        let states = [(value.active, Term::AncActive), (value.off, Term::AncOff), (value.aware, Term::AncAware)];
+
+        let states: Vec<_> = states.into_iter()
+            .filter(|(enabled, _)| *enabled)
+            .map(|(_, term)| self.text(term))
+            .collect();
+
+        format!("[{}]", states.join(", "))
+    }
+
+    pub fn eq_bands(&self, value: &EqBands) -> String {
+        format!("[{:.2}, {:.2}, {:.2}, {:.2}, {:.2}]",
+            value.low_bass(), value.bass(), value.mid(), value.treble(), value.upper_treble())
+    }
+
+    pub fn volume_asymmetry(&self, value: &VolumeAsymmetry) -> String {
+        format!("{}: {}%, {}: {}%",
+            self.text(Term::Left), value.left(),
+            self.text(Term::Right), value.right())
+    }
+
+    pub fn setting_value(&self, value: &SettingValue) -> String {
+        match value {
+            SettingValue::AutoOtaEnable(x) => self.bool(*x).into_owned(),
+            SettingValue::OhdEnable(x) => self.bool(*x).into_owned(),
+            SettingValue::OobeIsFinished(x) => self.bool(*x).into_owned(),
+            SettingValue::GestureEnable(x) => self.bool(*x).into_owned(),
+            SettingValue::DiagnosticsEnable(x) => self.bool(*x).into_owned(),
+            SettingValue::OobeMode(x) => self.bool(*x).into_owned(),
+            SettingValue::GestureControl(x) => self.gesture_control(x),
+            SettingValue::MultipointEnable(x) => self.bool(*x).into_owned(),
+            SettingValue::AncrGestureLoop(x) => self.anc_gesture_loop(x),
+            SettingValue::CurrentAncrState(x) => self.anc_state(*x),
+            SettingValue::OttsMode(x) => x.to_string(),
+            SettingValue::VolumeEqEnable(x) => self.bool(*x).into_owned(),
+            SettingValue::CurrentUserEq(x) => self.eq_bands(x),
+            SettingValue::VolumeAsymmetry(x) => self.volume_asymmetry(x),
+            SettingValue::SumToMono(x) => self.bool(*x).into_owned(),
+            SettingValue::VolumeExposureNotifications(x) => self.bool(*x).into_owned(),
+            SettingValue::SpeechDetection(x) => self.bool(*x).into_owned(),
+        }
+    }
+
+    /// Battery level and charging state, e.g. `84% (charging)`.
+    pub fn battery(&self, battery: Option<&DeviceBatteryInfo>) -> String {
+        let Some(battery) = battery else {
+            return self.text(Term::Unknown).into_owned();
+        };
+
+        let state = match BatteryState::try_from(battery.state) {
+            Ok(BatteryState::BatteryCharging) => Term::Charging,
+            Ok(BatteryState::BatteryNotCharging) => Term::NotCharging,
+            _ => Term::Unknown,
+        };
+
+        format!("{}% ({})", battery.level, self.text(state))
+    }
+
+    /// Whether a bud is placed in the case.
+    pub fn placement(&self, in_case: Option<bool>) -> Cow<'static, str> {
+        match in_case {
+            Some(true) => self.text(Term::InCase),
+            Some(false) => self.text(Term::OutOfCase),
+            None => self.text(Term::Unknown),
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    struct German;
+
+    impl Locale for German {
+        fn text(&self, term: Term) -> Option<Cow<'static, str>> {
+            match term {
+                Term::Left => Some("links".into()),
+                Term::Right => Some("rechts".into()),
+                Term::AncActive => Some("aktiv".into()),
+                Term::AncAware => Some("Umgebung".into()),
+                _ => None,
+            }
+        }
+    }
+
+    #[test]
+    fn test_locale() {
+        let de = Formatter::new(&German);
+
+        assert_eq!(de.anc_state(AncState::Active), "aktiv");
+        assert_eq!(de.anc_state(AncState::Off), "off");
+        assert_eq!(de.anc_state(AncState::Unknown(7)), "unknown (7)");
+
+        let value = VolumeAsymmetry::from_normalized(0);
+        assert_eq!(de.volume_asymmetry(&value), "links: 100%, rechts: 100%");
+
+        let value = AncrGestureLoop { active: true, off: false, aware: true };
+        assert_eq!(de.anc_gesture_loop(&value), "[aktiv, Umgebung]");
+        assert_eq!(Formatter::english().anc_gesture_loop(&value), "[active, aware]");
+
+        let battery = DeviceBatteryInfo { level: 84, state: 2 };
+        assert_eq!(Formatter::english().battery(Some(&battery)), "84% (charging)");
+        assert_eq!(de.battery(None), "unknown");
+    }
+}
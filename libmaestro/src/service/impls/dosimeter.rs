@@ -9,6 +9,7 @@ use crate::pwrpc::Error;
 pub struct DosimeterService {
     client: ClientHandle,
     channel_id: u32,
+    follow_channel: bool,
 
     rpc_fetch_daily_summaries: UnaryRpc<(), DosimeterSummary>,
     rpc_sub_live_db: ServerStreamRpc<(), DosimeterLiveDbMsg>,
@@ -19,18 +20,37 @@ impl DosimeterService {
         Self {
             client,
             channel_id,
+            follow_channel: false,
 
             rpc_fetch_daily_summaries: UnaryRpc::new("maestro_pw.Dosimeter/FetchDailySummaries"),
             rpc_sub_live_db: ServerStreamRpc::new("maestro_pw.Dosimeter/SubscribeToLiveDb"),
         }
     }
 
+    /// Send requests on the channel with the most recent response traffic
+    /// instead of the channel given on construction. This keeps the service
+    /// working when the device moves to a different channel, e.g. on bud
+    /// handoff. Existing subscriptions are not moved and need to be renewed.
+    pub fn follow_channel(&mut self, follow: bool) {
+        self.follow_channel = follow;
+    }
+
+    /// The channel on which requests are sent.
+    pub fn channel(&self) -> u32 {
+        match self.follow_channel {
+            true => self.client.active_channel().unwrap_or(self.channel_id),
+            false => self.channel_id,
+        }
+    }
+
     pub async fn fetch_daily_summaries(&mut self) -> Result<DosimeterSummary, Error> {
-        self.rpc_fetch_daily_summaries.call(&mut self.client, self.channel_id, 0, ())?
+        let channel = self.channel();
+        self.rpc_fetch_daily_summaries.call(&mut self.client, channel, 0, ())?
             .result().await
     }
 
     pub fn subscribe_to_live_db(&mut self) -> Result<StreamResponse<DosimeterLiveDbMsg>, Error> {
-        self.rpc_sub_live_db.call(&mut self.client, self.channel_id, 0, ())
+        let channel = self.channel();
+        self.rpc_sub_live_db.call(&mut self.client, channel, 0, ())
     }
 }
@@ -1,16 +1,197 @@
+use std::future::Future;
+use std::time::{Duration, Instant};
+
+use futures::{Stream, StreamExt};
+
 use crate::protocol::types::{
-    self, read_setting_msg, settings_rsp, write_setting_msg, HardwareInfo, OobeActionRsp,
-    ReadSettingMsg, RuntimeInfo, SettingsRsp, SoftwareInfo, WriteSettingMsg,
+    self, read_setting_msg, settings_rsp, write_setting_msg, DeviceBatteryInfo, HardwareInfo,
+    OobeAction, OobeActionRsp, ReadSettingMsg, RuntimeInfo, SettingsRsp, SoftwareInfo, WriteSettingMsg,
 };
 use crate::pwrpc::client::{ClientHandle, ServerStreamRpc, StreamResponse, UnaryRpc};
-use crate::pwrpc::Error;
+use crate::pwrpc::{Error, Status};
 use crate::service::settings::{Setting, SettingId, SettingValue};
 
 
+/// Maximum number of settings read concurrently for a snapshot.
+const SNAPSHOT_PARALLELISM: usize = 4;
+
+
+/// Retry policy for idempotent requests.
+///
+/// Right after connecting, the device may fail requests with `Unavailable`
+/// or `FailedPrecondition` while the buds are still finishing their handoff.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Retry {
+    /// Maximum number of attempts, including the first one.
+    pub attempts: u32,
+
+    /// Delay before the first retry, doubled for each subsequent one.
+    pub backoff: Duration,
+}
+
+impl Retry {
+    fn is_transient(error: &Error) -> bool {
+        matches!(error.code(), Status::Unavailable | Status::FailedPrecondition)
+    }
+}
+
+impl Default for Retry {
+    fn default() -> Self {
+        Self {
+            attempts: 3,
+            backoff: Duration::from_millis(250),
+        }
+    }
+}
+
+
+/// Battery levels and charging states of all components.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+#[non_exhaustive]
+pub struct BatterySnapshot {
+    pub case: Option<DeviceBatteryInfo>,
+    pub left: Option<DeviceBatteryInfo>,
+    pub right: Option<DeviceBatteryInfo>,
+}
+
+impl From<&RuntimeInfo> for BatterySnapshot {
+    fn from(info: &RuntimeInfo) -> Self {
+        let battery = info.battery_info.unwrap_or_default();
+
+        Self {
+            case: battery.case,
+            left: battery.left,
+            right: battery.right,
+        }
+    }
+}
+
+
+/// State of the device gathered via [`MaestroService::snapshot`].
+#[derive(Debug)]
+#[non_exhaustive]
+pub struct DeviceSnapshot {
+    pub software_info: SoftwareInfo,
+    pub hardware_info: HardwareInfo,
+    pub runtime_info: RuntimeInfo,
+
+    /// Values of the requested settings, or the errors returned for them,
+    /// e.g. if not supported by the firmware.
+    pub settings: Vec<(SettingId, Result<SettingValue, Error>)>,
+
+    pub timing: SnapshotTiming,
+}
+
+/// Time taken until the individual parts of a [`DeviceSnapshot`] were
+/// available, measured from the start of the snapshot.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct SnapshotTiming {
+    pub software_info: Duration,
+    pub hardware_info: Duration,
+    pub runtime_info: Duration,
+    pub settings: Duration,
+    pub total: Duration,
+}
+
+
+/// Subscription to battery changes.
+///
+/// Derived from the runtime info subscription, but only yields a new
+/// snapshot when a battery level or charging state has changed.
+pub struct BatterySubscription {
+    inner: StreamResponse<RuntimeInfo>,
+    last: Option<BatterySnapshot>,
+}
+
+impl BatterySubscription {
+    pub fn stream(&mut self) -> impl Stream<Item = Result<BatterySnapshot, Error>> + '_ {
+        let last = &mut self.last;
+
+        self.inner.stream().filter_map(move |item| {
+            let item = match item {
+                Ok(info) => {
+                    let snapshot = BatterySnapshot::from(&info);
+
+                    if *last == Some(snapshot) {
+                        None
+                    } else {
+                        *last = Some(snapshot);
+                        Some(Ok(snapshot))
+                    }
+                },
+                Err(err) => Some(Err(err)),
+            };
+
+            std::future::ready(item)
+        })
+    }
+
+    pub fn cancel(&mut self) -> bool {
+        self.inner.cancel()
+    }
+
+    pub async fn cancel_and_wait(&mut self) -> Result<(), Error> {
+        self.inner.cancel_and_wait().await
+    }
+
+    pub async fn cancel_and_wait_timeout(&mut self, timeout: Duration) -> Result<(), Error> {
+        self.inner.cancel_and_wait_timeout(timeout).await
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.inner.is_complete()
+    }
+
+    /// Trace ID of the underlying call. See [`StreamResponse::trace_id`].
+    pub fn trace_id(&self) -> u64 {
+        self.inner.trace_id()
+    }
+}
+
+
+/// Interception of touch gestures.
+///
+/// While the OOBE mode setting is enabled, the buds report gestures instead
+/// of performing the configured actions for them, which allows remapping them.
+/// See [`MaestroService::intercept_gestures`].
+pub struct GestureInterception {
+    inner: StreamResponse<OobeActionRsp>,
+}
+
+impl GestureInterception {
+    pub fn stream(&mut self) -> impl Stream<Item = Result<OobeAction, Error>> + '_ {
+        self.inner.stream().map(|item| item.map(|rsp| rsp.action()))
+    }
+
+    pub fn cancel(&mut self) -> bool {
+        self.inner.cancel()
+    }
+
+    pub async fn cancel_and_wait(&mut self) -> Result<(), Error> {
+        self.inner.cancel_and_wait().await
+    }
+
+    pub async fn cancel_and_wait_timeout(&mut self, timeout: Duration) -> Result<(), Error> {
+        self.inner.cancel_and_wait_timeout(timeout).await
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.inner.is_complete()
+    }
+
+    /// Trace ID of the underlying call. See [`StreamResponse::trace_id`].
+    pub fn trace_id(&self) -> u64 {
+        self.inner.trace_id()
+    }
+}
+
+
 #[derive(Debug, Clone)]
 pub struct MaestroService {
     client: ClientHandle,
     channel_id: u32,
+    follow_channel: bool,
 
     rpc_get_software_info: UnaryRpc<(), SoftwareInfo>,
     rpc_get_hardware_info: UnaryRpc<(), HardwareInfo>,
@@ -28,6 +209,7 @@ impl MaestroService {
         Self {
             client,
             channel_id,
+            follow_channel: false,
 
             rpc_get_software_info: UnaryRpc::new("maestro_pw.Maestro/GetSoftwareInfo"),
             rpc_get_hardware_info: UnaryRpc::new("maestro_pw.Maestro/GetHardwareInfo"),
@@ -41,22 +223,49 @@ impl MaestroService {
         }
     }
 
+    /// Send requests on the channel with the most recent response traffic
+    /// instead of the channel given on construction. This keeps the service
+    /// working when the device moves to a different channel, e.g. on bud
+    /// handoff. Existing subscriptions are not moved and need to be renewed.
+    pub fn follow_channel(&mut self, follow: bool) {
+        self.follow_channel = follow;
+    }
+
+    /// The channel on which requests are sent.
+    pub fn channel(&self) -> u32 {
+        match self.follow_channel {
+            true => self.client.active_channel().unwrap_or(self.channel_id),
+            false => self.channel_id,
+        }
+    }
+
     pub async fn get_software_info(&mut self) -> Result<SoftwareInfo, Error> {
-        self.rpc_get_software_info.call(&mut self.client, self.channel_id, 0, ())?
+        let channel = self.channel();
+        self.rpc_get_software_info.call(&mut self.client, channel, 0, ())?
             .result().await
     }
 
     pub async fn get_hardware_info(&mut self) -> Result<HardwareInfo, Error> {
-        self.rpc_get_hardware_info.call(&mut self.client, self.channel_id, 0, ())?
+        let channel = self.channel();
+        self.rpc_get_hardware_info.call(&mut self.client, channel, 0, ())?
             .result().await
     }
 
     pub fn subscribe_to_runtime_info(&mut self) -> Result<StreamResponse<RuntimeInfo>, Error> {
-        self.rpc_sub_runtime_info.call(&mut self.client, self.channel_id, 0, ())
+        let channel = self.channel();
+        self.rpc_sub_runtime_info.call(&mut self.client, channel, 0, ())
+    }
+
+    /// Subscribe to battery changes. Unlike the runtime info subscription,
+    /// this only yields when a level or charging state changes.
+    pub fn subscribe_to_battery(&mut self) -> Result<BatterySubscription, Error> {
+        let inner = self.subscribe_to_runtime_info()?;
+        Ok(BatterySubscription { inner, last: None })
     }
 
     pub async fn write_setting_raw(&mut self, setting: WriteSettingMsg) -> Result<(), Error> {
-        self.rpc_write_setting.call(&mut self.client, self.channel_id, 0, setting)?
+        let channel = self.channel();
+        self.rpc_write_setting.call(&mut self.client, channel, 0, setting)?
             .result().await
     }
 
@@ -73,15 +282,24 @@ impl MaestroService {
     }
 
     pub async fn read_setting_raw(&mut self, setting: ReadSettingMsg) -> Result<SettingsRsp, Error> {
-        self.rpc_read_setting.call(&mut self.client, self.channel_id, 0, setting)?
+        self.read_setting_raw_call(setting, 0).await
+    }
+
+    async fn read_setting_raw_call(&mut self, setting: ReadSettingMsg, call_id: u32) -> Result<SettingsRsp, Error> {
+        let channel = self.channel();
+        self.rpc_read_setting.call(&mut self.client, channel, call_id, setting)?
             .result().await
     }
 
     pub async fn read_setting_var(&mut self, setting: SettingId) -> Result<SettingValue, Error> {
+        self.read_setting_var_call(setting, 0).await
+    }
+
+    async fn read_setting_var_call(&mut self, setting: SettingId, call_id: u32) -> Result<SettingValue, Error> {
         let setting = read_setting_msg::ValueOneof::SettingsId(setting.into());
         let setting = ReadSettingMsg { value_oneof: Some(setting) };
 
-        let value = self.read_setting_raw(setting).await?;
+        let value = self.read_setting_raw_call(setting, call_id).await?;
 
         let value = value.value_oneof
             .ok_or_else(|| Error::invalid_argument("did not receive any settings value"))?;
@@ -94,6 +312,64 @@ impl MaestroService {
         Ok(value.into())
     }
 
+    /// Probe a setting by its raw ID, e.g. to discover settings not yet known
+    /// to this library.
+    ///
+    /// Returns `Ok(None)` if the device responded with a value of a type that
+    /// cannot be decoded. Settings not supported by the firmware result in an
+    /// error.
+    pub async fn probe_setting(&mut self, id: i32) -> Result<Option<SettingValue>, Error> {
+        let setting = read_setting_msg::ValueOneof::SettingsId(id);
+        let setting = ReadSettingMsg { value_oneof: Some(setting) };
+
+        let value = self.read_setting_raw(setting).await?;
+
+        let value = value.value_oneof
+            .and_then(|settings_rsp::ValueOneof::Value(value)| value.value_oneof)
+            .map(SettingValue::from);
+
+        Ok(value)
+    }
+
+    /// Read multiple settings, returning the result for each of them.
+    ///
+    /// The `ReadSetting` RPC only accepts a single setting ID per request and
+    /// no bulk variant is known for any firmware so far. Settings are
+    /// therefore read one after the other, as concurrent calls to the same
+    /// method need distinct call IDs, see
+    /// [`read_settings_concurrent`](Self::read_settings_concurrent).
+    pub async fn read_settings(&mut self, settings: &[SettingId]) -> Vec<(SettingId, Result<SettingValue, Error>)> {
+        let mut values = Vec::with_capacity(settings.len());
+
+        for id in settings {
+            values.push((*id, self.read_setting_var(*id).await));
+        }
+
+        values
+    }
+
+    /// Read multiple settings with up to `parallelism` requests in flight at
+    /// once, returning the result for each of them in the given order.
+    ///
+    /// Each request uses its own call ID allocated via
+    /// [`ClientHandle::next_call_id`], so that responses are matched to the
+    /// correct setting. A parallelism of one reads settings sequentially.
+    pub async fn read_settings_concurrent(&mut self, settings: &[SettingId], parallelism: usize)
+        -> Vec<(SettingId, Result<SettingValue, Error>)>
+    {
+        let reads = settings.iter().map(|id| {
+            let mut service = self.clone();
+            let call_id = self.client.next_call_id();
+
+            async move { (*id, service.read_setting_var_call(*id, call_id).await) }
+        });
+
+        futures::stream::iter(reads)
+            .buffered(parallelism.max(1))
+            .collect()
+            .await
+    }
+
     pub async fn read_setting<T>(&mut self, setting: T) -> Result<T::Type, Error>
     where
         T: Setting,
@@ -104,14 +380,326 @@ impl MaestroService {
             .ok_or_else(|| Error::invalid_argument("failed to decode settings value"))
     }
 
+    /// Read a setting, retrying on transient errors according to the given
+    /// policy.
+    pub async fn read_setting_with_retry<T>(&mut self, setting: T, retry: Retry) -> Result<T::Type, Error>
+    where
+        T: Setting,
+    {
+        let id = setting.id();
+        let mut backoff = retry.backoff;
+        let mut attempt = 1;
+
+        let value = loop {
+            match self.read_setting_var(id).await {
+                Ok(value) => break value,
+                Err(err) if attempt < retry.attempts && Retry::is_transient(&err) => {
+                    tracing::debug!(setting=%id, attempt, error=%err, "reading setting failed, retrying");
+
+                    tokio::time::sleep(backoff).await;
+                    backoff *= 2;
+                    attempt += 1;
+                },
+                Err(err) => return Err(err),
+            }
+        };
+
+        T::from_var(value)
+            .ok_or_else(|| Error::invalid_argument("failed to decode settings value"))
+    }
+
     pub fn subscribe_to_settings_changes(&mut self) -> Result<StreamResponse<SettingsRsp>, Error> {
-        self.rpc_sub_settings_changes.call(&mut self.client, self.channel_id, 0, ())
+        let channel = self.channel();
+        self.rpc_sub_settings_changes.call(&mut self.client, channel, 0, ())
     }
 
     pub fn subscribe_to_oobe_actions(&mut self) -> Result<StreamResponse<OobeActionRsp>, Error> {
-        self.rpc_sub_oobe_actions.call(&mut self.client, self.channel_id, 0, ())
+        let channel = self.channel();
+        self.rpc_sub_oobe_actions.call(&mut self.client, channel, 0, ())
+    }
+
+    /// Intercept touch gestures by enabling the OOBE mode.
+    ///
+    /// The buds stop performing the configured gesture actions until
+    /// [`release_gestures`](Self::release_gestures) is called. Note that the
+    /// OOBE mode persists on the device, e.g. if the connection is lost before
+    /// releasing the gestures.
+    pub async fn intercept_gestures(&mut self) -> Result<GestureInterception, Error> {
+        // subscribe first so that we do not miss any gestures
+        let inner = self.subscribe_to_oobe_actions()?;
+        self.write_setting(SettingValue::OobeMode(true)).await?;
+
+        Ok(GestureInterception { inner })
+    }
+
+    /// Restore normal gesture handling by disabling the OOBE mode.
+    pub async fn release_gestures(&mut self) -> Result<(), Error> {
+        self.write_setting(SettingValue::OobeMode(false)).await
+    }
+
+    /// Gather software info, hardware info, the current runtime info, and
+    /// the given settings concurrently.
+    ///
+    /// Settings are read via
+    /// [`read_settings_concurrent`](Self::read_settings_concurrent) with up to
+    /// four requests in flight, in parallel to the other requests.
+    pub async fn snapshot(&mut self, settings: &[SettingId]) -> Result<DeviceSnapshot, Error> {
+        let start = Instant::now();
+
+        let mut software = self.clone();
+        let mut hardware = self.clone();
+        let mut runtime = self.clone();
+        let mut setting = self.clone();
+
+        let runtime_info = async {
+            let mut call = runtime.subscribe_to_runtime_info()?;
+
+            let info = call.stream().next().await
+                .ok_or_else(|| Error::aborted("runtime info stream terminated without item"))??;
+
+            call.cancel();
+            Ok::<_, Error>(info)
+        };
+
+        let settings = setting.read_settings_concurrent(settings, SNAPSHOT_PARALLELISM);
+
+        let (software_info, hardware_info, runtime_info, settings) = futures::join!(
+            timed(start, software.get_software_info()),
+            timed(start, hardware.get_hardware_info()),
+            timed(start, runtime_info),
+            timed(start, settings),
+        );
+
+        let timing = SnapshotTiming {
+            software_info: software_info.1,
+            hardware_info: hardware_info.1,
+            runtime_info: runtime_info.1,
+            settings: settings.1,
+            total: start.elapsed(),
+        };
+
+        Ok(DeviceSnapshot {
+            software_info: software_info.0?,
+            hardware_info: hardware_info.0?,
+            runtime_info: runtime_info.0?,
+            settings: settings.0,
+            timing,
+        })
     }
 
     // TODO:
     // - SetWallClock
 }
+
+/// Run the given future, additionally returning the time elapsed since the
+/// given start once it completes.
+async fn timed<F: Future>(start: Instant, future: F) -> (F::Output, Duration) {
+    let output = future.await;
+    (output, start.elapsed())
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::mock::{Device, fixture};
+    use crate::protocol::addr::{self, Peer};
+    use crate::pwrpc::client::Event;
+    use crate::service::settings::{self, AncState};
+
+    const READ_SETTING: &str = "maestro_pw.Maestro/ReadSetting";
+
+    async fn read_anc(device: &Device, retry: Retry) -> Result<AncState, Error> {
+        let (mut client, channel) = fixture::connect(device).await;
+        let mut service = MaestroService::new(client.handle(), channel);
+
+        fixture::run(&mut client, service.read_setting_with_retry(settings::id::CurrentAncrState, retry)).await
+    }
+
+    #[tokio::test]
+    async fn test_subscribe_to_battery() {
+        let device = Device::new();
+
+        let (mut client, channel) = fixture::connect(&device).await;
+        let mut service = MaestroService::new(client.handle(), channel);
+
+        let task = async {
+            let mut call = service.subscribe_to_battery().unwrap();
+            let mut battery = call.stream();
+
+            let initial = device.state().runtime_info;
+            assert_eq!(battery.next().await.unwrap().unwrap(), BatterySnapshot::from(&initial));
+
+            // updates not affecting the battery are filtered out
+            device.update_runtime_info(RuntimeInfo { timestamp_ms: 42, ..initial });
+
+            let mut info = initial;
+            let mut bat = info.battery_info.unwrap();
+            bat.left = Some(DeviceBatteryInfo { level: 89, ..bat.left.unwrap() });
+            info.battery_info = Some(bat);
+            device.update_runtime_info(info);
+
+            let snapshot = battery.next().await.unwrap().unwrap();
+            assert_eq!(snapshot.left.unwrap().level, 89);
+            assert_eq!(snapshot.right, initial.battery_info.unwrap().right);
+        };
+
+        fixture::run(&mut client, task).await;
+    }
+
+    #[tokio::test]
+    async fn test_snapshot() {
+        let device = Device::new();
+
+        let (mut client, channel) = fixture::connect(&device).await;
+        let mut service = MaestroService::new(client.handle(), channel);
+
+        let task = async {
+            let settings = [SettingId::GestureEnable, SettingId::SpeechDetection];
+            let snapshot = service.snapshot(&settings).await.unwrap();
+
+            assert_eq!(snapshot.software_info, device.state().software_info);
+            assert_eq!(snapshot.hardware_info, device.state().hardware_info);
+
+            assert_eq!(snapshot.settings.len(), 2);
+            assert_eq!(snapshot.settings[0].1.as_ref().unwrap(), &SettingValue::GestureEnable(true));
+            assert!(snapshot.settings[1].1.is_err());
+
+            assert!(snapshot.timing.total >= snapshot.timing.settings);
+        };
+
+        fixture::run(&mut client, task).await;
+    }
+
+    #[tokio::test]
+    async fn test_read_settings_concurrent() {
+        let device = Device::new();
+
+        let (mut client, channel) = fixture::connect(&device).await;
+        let mut service = MaestroService::new(client.handle(), channel);
+
+        let task = async {
+            let settings = [
+                SettingId::GestureEnable,
+                SettingId::SpeechDetection,
+                SettingId::CurrentAncrState,
+                SettingId::MultipointEnable,
+                SettingId::GestureEnable,
+            ];
+
+            for parallelism in [0, 1, 3, 10] {
+                let values = service.read_settings_concurrent(&settings, parallelism).await;
+                let expected = service.read_settings(&settings).await;
+
+                let ids: Vec<_> = values.iter().map(|(id, _)| *id).collect();
+                assert_eq!(ids, settings);
+
+                for ((_, value), (_, expected)) in values.iter().zip(&expected) {
+                    assert_eq!(value.as_ref().ok(), expected.as_ref().ok());
+                }
+
+                assert!(values[1].1.is_err());
+            }
+        };
+
+        fixture::run(&mut client, task).await;
+    }
+
+    #[tokio::test]
+    async fn test_probe_setting() {
+        let device = Device::new();
+
+        let (mut client, channel) = fixture::connect(&device).await;
+        let mut service = MaestroService::new(client.handle(), channel);
+
+        let task = async {
+            let value = service.probe_setting(SettingId::GestureEnable.into()).await.unwrap();
+            assert_eq!(value, Some(SettingValue::GestureEnable(true)));
+
+            assert!(service.probe_setting(20).await.is_err());
+        };
+
+        fixture::run(&mut client, task).await;
+    }
+
+    #[tokio::test]
+    async fn test_intercept_gestures() {
+        let device = Device::new();
+
+        let (mut client, channel) = fixture::connect(&device).await;
+        let mut service = MaestroService::new(client.handle(), channel);
+
+        let task = async {
+            let mut interception = service.intercept_gestures().await.unwrap();
+            assert_eq!(device.setting(SettingId::OobeMode), Some(SettingValue::OobeMode(true)));
+
+            let mut gestures = interception.stream();
+
+            device.gesture(OobeAction::DoubleTap);
+            assert_eq!(gestures.next().await.unwrap().unwrap(), OobeAction::DoubleTap);
+
+            drop(gestures);
+            drop(interception);
+
+            service.release_gestures().await.unwrap();
+            assert_eq!(device.setting(SettingId::OobeMode), Some(SettingValue::OobeMode(false)));
+        };
+
+        fixture::run(&mut client, task).await;
+    }
+
+    #[tokio::test]
+    async fn test_follow_channel() {
+        let device = Device::new();
+
+        let (mut client, channel) = fixture::connect(&device).await;
+        let mut handle = client.handle();
+
+        let mut service = MaestroService::new(handle.clone(), channel);
+        service.follow_channel(true);
+
+        let mut events = handle.subscribe_events().unwrap();
+
+        let task = async {
+            service.get_software_info().await.unwrap();
+
+            let new = addr::channel_id(Peer::MaestroB, Peer::RightBtCore).unwrap();
+            device.handoff(new);
+
+            let event = loop {
+                match events.next().await {
+                    Some(Event::CallCompleted { .. }) => continue,
+                    event => break event,
+                }
+            };
+
+            assert_eq!(event, Some(Event::ChannelChanged { old: channel, new }));
+            assert_eq!(service.channel(), new);
+
+            service.get_software_info().await.unwrap();
+        };
+
+        fixture::run(&mut client, task).await;
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_read_setting_with_retry() {
+        let retry = Retry::default();
+
+        let device = Device::new();
+        device.fail_times(READ_SETTING, Status::Unavailable, 2);
+        assert_eq!(read_anc(&device, retry).await.unwrap(), AncState::Active);
+
+        let device = Device::new();
+        device.fail_times(READ_SETTING, Status::FailedPrecondition, 3);
+        let err = read_anc(&device, retry).await.unwrap_err();
+        assert_eq!(err.code(), Status::FailedPrecondition);
+
+        // non-transient errors are not retried
+        let device = Device::new();
+        device.fail_times(READ_SETTING, Status::NotFound, 1);
+        let err = read_anc(&device, retry).await.unwrap_err();
+        assert_eq!(err.code(), Status::NotFound);
+    }
+}
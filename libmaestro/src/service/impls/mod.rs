@@ -2,7 +2,23 @@ mod dosimeter;
 pub use self::dosimeter::DosimeterService;
 
 mod maestro;
-pub use self::maestro::MaestroService;
+pub use self::maestro::{
+    BatterySnapshot, BatterySubscription, DeviceSnapshot, GestureInterception, MaestroService, Retry,
+    SnapshotTiming,
+};
 
 mod multipoint;
 pub use self::multipoint::MultipointService;
+
+
+// Services are moved into and shared between tasks, make sure they can be.
+const _: () = {
+    const fn assert_send_sync<T: Send + Sync>() {}
+    const fn assert_send<T: Send>() {}
+
+    assert_send_sync::<MaestroService>();
+    assert_send_sync::<MultipointService>();
+    assert_send_sync::<DosimeterService>();
+    assert_send::<BatterySubscription>();
+    assert_send::<GestureInterception>();
+};
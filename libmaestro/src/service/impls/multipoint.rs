@@ -7,6 +7,7 @@ use crate::pwrpc::Error;
 pub struct MultipointService {
     client: ClientHandle,
     channel_id: u32,
+    follow_channel: bool,
 
     rpc_sub_quiet_mode_status: ServerStreamRpc<(), QuietModeStatusEvent>,
 }
@@ -16,13 +17,31 @@ impl MultipointService {
         Self {
             client,
             channel_id,
+            follow_channel: false,
 
             rpc_sub_quiet_mode_status: ServerStreamRpc::new("maestro_pw.Multipoint/SubscribeToQuietModeStatus"),
         }
     }
 
+    /// Send requests on the channel with the most recent response traffic
+    /// instead of the channel given on construction. This keeps the service
+    /// working when the device moves to a different channel, e.g. on bud
+    /// handoff. Existing subscriptions are not moved and need to be renewed.
+    pub fn follow_channel(&mut self, follow: bool) {
+        self.follow_channel = follow;
+    }
+
+    /// The channel on which requests are sent.
+    pub fn channel(&self) -> u32 {
+        match self.follow_channel {
+            true => self.client.active_channel().unwrap_or(self.channel_id),
+            false => self.channel_id,
+        }
+    }
+
     pub fn subscribe_to_quiet_mode_status(&mut self) -> Result<StreamResponse<QuietModeStatusEvent>, Error> {
-        self.rpc_sub_quiet_mode_status.call(&mut self.client, self.channel_id, 0, ())
+        let channel = self.channel();
+        self.rpc_sub_quiet_mode_status.call(&mut self.client, channel, 0, ())
     }
 
     // TODO:
@@ -1,4 +1,16 @@
+pub mod battery;
+pub mod clock;
+pub mod debounce;
+pub mod format;
 pub mod settings;
 
+#[cfg(feature = "client")]
+pub mod cache;
+
+#[cfg(feature = "client")]
 mod impls;
-pub use impls::{MaestroService, MultipointService, DosimeterService};
+#[cfg(feature = "client")]
+pub use impls::{
+    MaestroService, MultipointService, DosimeterService, Retry, BatterySnapshot, BatterySubscription,
+    DeviceSnapshot, GestureInterception, SnapshotTiming,
+};
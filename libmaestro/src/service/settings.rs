@@ -2,9 +2,12 @@ use num_enum::{IntoPrimitive, FromPrimitive};
 
 use crate::protocol::types;
 
+use super::format;
+
 
 #[repr(i32)]
 #[derive(Debug, Clone, Copy, PartialEq, Eq, IntoPrimitive, FromPrimitive)]
+#[non_exhaustive]
 pub enum SettingId {
     AutoOtaEnable = 1,
     OhdEnable = 2,
@@ -32,8 +35,146 @@ pub enum SettingId {
     Unknown(i32),
 }
 
+impl SettingId {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SettingId::AutoOtaEnable => "auto-ota-enable",
+            SettingId::OhdEnable => "ohd-enable",
+            SettingId::OobeIsFinished => "oobe-is-finished",
+            SettingId::GestureEnable => "gesture-enable",
+            SettingId::DiagnosticsEnable => "diagnostics-enable",
+            SettingId::OobeMode => "oobe-mode",
+            SettingId::GestureControl => "gesture-control",
+            SettingId::AncAccessibilityMode => "anc-accessibility-mode",
+            SettingId::AncrStateOneBud => "ancr-state-one-bud",
+            SettingId::AncrStateTwoBuds => "ancr-state-two-buds",
+            SettingId::MultipointEnable => "multipoint-enable",
+            SettingId::AncrGestureLoop => "ancr-gesture-loop",
+            SettingId::CurrentAncrState => "current-ancr-state",
+            SettingId::OttsMode => "otts-mode",
+            SettingId::VolumeEqEnable => "volume-eq-enable",
+            SettingId::CurrentUserEq => "current-user-eq",
+            SettingId::VolumeAsymmetry => "volume-asymmetry",
+            SettingId::LastSavedUserEq => "last-saved-user-eq",
+            SettingId::SumToMono => "sum-to-mono",
+            SettingId::VolumeExposureNotifications => "volume-exposure-notifications",
+            SettingId::SpeechDetection => "speech-detection",
+            SettingId::Unknown(_) => "unknown",
+        }
+    }
+}
+
+impl std::fmt::Display for SettingId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SettingId::Unknown(x) => write!(f, "unknown ({x})"),
+            _ => write!(f, "{}", self.as_str()),
+        }
+    }
+}
+
+
+/// How risky it is to change a setting.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Danger {
+    /// Regular user-facing setting.
+    Safe,
+
+    /// Setting that changes device behavior in non-obvious ways, e.g.
+    /// firmware updates or diagnostics.
+    Caution,
+
+    /// Setting used internally by the device or companion app. Changing it
+    /// may require re-pairing or resetting the device.
+    Dangerous,
+}
+
+impl Danger {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Danger::Safe => "safe",
+            Danger::Caution => "caution",
+            Danger::Dangerous => "dangerous",
+        }
+    }
+}
+
+impl std::fmt::Display for Danger {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+
+/// Metadata describing a setting, e.g. for documentation or user interfaces.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SettingInfo {
+    /// Short human-readable description of the setting.
+    pub description: &'static str,
+
+    /// Minimum firmware version known to support the setting, if known.
+    pub min_firmware: Option<&'static str>,
+
+    /// How risky it is to change the setting.
+    pub danger: Danger,
+
+    /// Unit of the setting value, if any.
+    pub unit: Option<&'static str>,
+
+    /// Whether writing the setting is known to desync the buds if they run
+    /// different firmware versions.
+    pub requires_matching_firmware: bool,
+}
+
+impl SettingInfo {
+    const fn new(description: &'static str, danger: Danger) -> Self {
+        Self { description, min_firmware: None, danger, unit: None, requires_matching_firmware: false }
+    }
+
+    const fn with_unit(self, unit: &'static str) -> Self {
+        Self { unit: Some(unit), ..self }
+    }
+
+    const fn with_matching_firmware(self) -> Self {
+        Self { requires_matching_firmware: true, ..self }
+    }
+}
+
+impl SettingId {
+    /// Metadata describing this setting.
+    pub fn info(&self) -> SettingInfo {
+        use Danger::*;
+
+        match self {
+            SettingId::AutoOtaEnable => SettingInfo::new("Automatically download and install firmware updates", Caution),
+            SettingId::OhdEnable => SettingInfo::new("On-head detection, pauses media when the buds are taken out", Safe),
+            SettingId::OobeIsFinished => SettingInfo::new("Whether the out-of-box setup has been completed", Dangerous),
+            SettingId::GestureEnable => SettingInfo::new("Enable touch gestures", Safe),
+            SettingId::DiagnosticsEnable => SettingInfo::new("Collect and report diagnostics data", Caution),
+            SettingId::OobeMode => SettingInfo::new("Out-of-box setup mode", Dangerous),
+            SettingId::GestureControl => SettingInfo::new("Action performed when holding the left or right bud", Safe).with_matching_firmware(),
+            SettingId::AncAccessibilityMode => SettingInfo::new("ANC accessibility mode", Safe),
+            SettingId::AncrStateOneBud => SettingInfo::new("ANC state used when only one bud is worn", Safe),
+            SettingId::AncrStateTwoBuds => SettingInfo::new("ANC state used when both buds are worn", Safe),
+            SettingId::MultipointEnable => SettingInfo::new("Connect to multiple audio sources simultaneously", Safe),
+            SettingId::AncrGestureLoop => SettingInfo::new("ANC modes cycled through via the hold gesture", Safe).with_matching_firmware(),
+            SettingId::CurrentAncrState => SettingInfo::new("Active noise cancelling mode (off, active, aware)", Safe),
+            SettingId::OttsMode => SettingInfo::new("OTTS mode (purpose unknown)", Dangerous),
+            SettingId::VolumeEqEnable => SettingInfo::new("Adjust equalizer dynamically with volume", Safe),
+            SettingId::CurrentUserEq => SettingInfo::new("Current equalizer band gains (-6 to +6)", Safe).with_unit("dB"),
+            SettingId::VolumeAsymmetry => SettingInfo::new("Volume balance between left (-100) and right (+100) bud", Safe),
+            SettingId::LastSavedUserEq => SettingInfo::new("Last saved equalizer band gains (-6 to +6)", Safe).with_unit("dB"),
+            SettingId::SumToMono => SettingInfo::new("Mix stereo audio to mono", Safe),
+            SettingId::VolumeExposureNotifications => SettingInfo::new("Notify about high volume exposure", Safe),
+            SettingId::SpeechDetection => SettingInfo::new("Switch to conversation mode when speech is detected", Safe),
+            SettingId::Unknown(_) => SettingInfo::new("Unknown setting", Dangerous),
+        }
+    }
+}
+
 
 #[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
 pub enum SettingValue {
     AutoOtaEnable(bool),
     OhdEnable(bool),
@@ -78,6 +219,12 @@ impl SettingValue {
     }
 }
 
+impl std::fmt::Display for SettingValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&format::Formatter::english().setting_value(self))
+    }
+}
+
 impl From<types::setting_value::ValueOneof> for SettingValue {
     fn from(value: crate::protocol::types::setting_value::ValueOneof) -> Self {
         use types::setting_value::ValueOneof;
@@ -189,13 +336,14 @@ impl Default for GestureControl {
 
 impl std::fmt::Display for GestureControl {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "left: {}, right: {}", self.left, self.right)
+        f.write_str(&format::Formatter::english().gesture_control(self))
     }
 }
 
 
 #[repr(i32)]
 #[derive(Debug, Clone, Copy, PartialEq, Eq, IntoPrimitive, FromPrimitive)]
+#[non_exhaustive]
 pub enum RegularActionTarget {
     CheckNotifications = 1,
     PreviousTrackRepeat = 2,
@@ -224,15 +372,7 @@ impl RegularActionTarget {
 
 impl std::fmt::Display for RegularActionTarget {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            RegularActionTarget::CheckNotifications => write!(f, "check-notifications"),
-            RegularActionTarget::PreviousTrackRepeat => write!(f, "previous"),
-            RegularActionTarget::NextTrack => write!(f, "next"),
-            RegularActionTarget::PlayPauseTrack => write!(f, "play-pause"),
-            RegularActionTarget::AncControl => write!(f, "anc"),
-            RegularActionTarget::AssistantQuery => write!(f, "assistant"),
-            RegularActionTarget::Unknown(x) => write!(f, "unknown ({x})"),
-        }
+        f.write_str(&format::Formatter::english().action(*self))
     }
 }
 
@@ -269,39 +409,14 @@ impl From<AncrGestureLoop> for types::AncrGestureLoop {
 
 impl std::fmt::Display for AncrGestureLoop {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let mut n = 0;
-
-        write!(f, "[")?;
-
-        if self.active {
-            write!(f, "active")?;
-            n += 1;
-        }
-
-        if self.off {
-            if n > 0 {
-                write!(f, ", ")?;
-            }
-
-            write!(f, "off")?;
-            n += 1;
-        }
-
-        if self.aware {
-            if n > 0 {
-                write!(f, ", ")?;
-            }
-
-            write!(f, "aware")?;
-        }
-
-        write!(f, "]")
+        f.write_str(&format::Formatter::english().anc_gesture_loop(self))
     }
 }
 
 
 #[repr(i32)]
 #[derive(Debug, Clone, Copy, PartialEq, Eq, IntoPrimitive, FromPrimitive)]
+#[non_exhaustive]
 pub enum AncState {
     Off = 1,
     Active = 2,
@@ -332,12 +447,7 @@ impl Default for AncState {
 
 impl std::fmt::Display for AncState {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            AncState::Off => write!(f, "off"),
-            AncState::Active => write!(f, "active"),
-            AncState::Aware => write!(f, "aware"),
-            AncState::Unknown(x) => write!(f, "unknown ({x})"),
-        }
+        f.write_str(&format::Formatter::english().anc_state(*self))
     }
 }
 
@@ -444,10 +554,7 @@ impl From<EqBands> for types::EqBands {
 
 impl std::fmt::Display for EqBands {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(
-            f, "[{:.2}, {:.2}, {:.2}, {:.2}, {:.2}]",
-            self.low_bass, self.bass, self.mid, self.treble, self.upper_treble,
-        )
+        f.write_str(&format::Formatter::english().eq_bands(self))
     }
 }
 
@@ -483,9 +590,31 @@ impl VolumeAsymmetry {
         }
     }
 
+    /// Create the asymmetry from per-bud volume levels in percent, keeping the
+    /// louder bud at full volume.
+    ///
+    /// The device only supports attenuating one side, so only the difference
+    /// between both levels is retained.
+    pub fn from_levels(left: u32, right: u32) -> Self {
+        let left = left.min(100) as i32;
+        let right = right.min(100) as i32;
+
+        Self::from_normalized(right - left)
+    }
+
     pub fn value(&self) -> i32 {
         self.value
     }
+
+    /// Volume level of the left bud in percent.
+    pub fn left(&self) -> u32 {
+        (100 - self.value).min(100) as u32
+    }
+
+    /// Volume level of the right bud in percent.
+    pub fn right(&self) -> u32 {
+        (100 + self.value).min(100) as u32
+    }
 }
 
 impl std::fmt::Debug for VolumeAsymmetry {
@@ -496,10 +625,7 @@ impl std::fmt::Debug for VolumeAsymmetry {
 
 impl std::fmt::Display for VolumeAsymmetry {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let left = (100 - self.value).min(100);
-        let right = (100 + self.value).min(100);
-
-        write!(f, "left: {left}%, right: {right}%")
+        f.write_str(&format::Formatter::english().volume_asymmetry(self))
     }
 }
 
@@ -812,4 +938,38 @@ mod test {
             assert_eq!(VolumeAsymmetry::from_raw(i).raw(), i)
         }
     }
+
+    #[test]
+    fn test_volume_asymmetry_levels() {
+        let value = VolumeAsymmetry::from_levels(80, 100);
+        assert_eq!(value.value(), 20);
+        assert_eq!((value.left(), value.right()), (80, 100));
+
+        let value = VolumeAsymmetry::from_levels(100, 70);
+        assert_eq!(value.value(), -30);
+        assert_eq!((value.left(), value.right()), (100, 70));
+
+        // only the difference is retained
+        let value = VolumeAsymmetry::from_levels(50, 60);
+        assert_eq!((value.left(), value.right()), (90, 100));
+
+        assert_eq!(VolumeAsymmetry::from_levels(0, 150).value(), 100);
+    }
+
+    #[test]
+    fn test_setting_info() {
+        for raw in 1..=22 {
+            let id = SettingId::from(raw);
+
+            if let SettingId::Unknown(_) = id {
+                continue;
+            }
+
+            assert!(!id.info().description.is_empty(), "missing description for {id}");
+        }
+
+        assert_eq!(SettingId::CurrentUserEq.info().unit, Some("dB"));
+        assert_eq!(SettingId::OobeMode.info().danger, Danger::Dangerous);
+        assert_eq!(SettingId::Unknown(42).info().danger, Danger::Dangerous);
+    }
 }